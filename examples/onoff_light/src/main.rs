@@ -20,6 +20,9 @@ fn main() {
         pid: 0x8002,
         hw_ver: 2,
         sw_ver: 1,
+        vendor_name: "Test Vendor".to_owned(),
+        product_name: "OnOff Light".to_owned(),
+        ..Default::default()
     };
     let dev_att = Box::new(dev_att::HardCodedDevAtt::new());
 
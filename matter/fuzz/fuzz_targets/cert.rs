@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use matter::cert::Cert;
+
+fuzz_target!(|data: &[u8]| {
+    // The input is tried both as a Matter certificate and as an X.509 one
+    if let Ok(cert) = Cert::new(data).or_else(|_| Cert::from_x509(data)) {
+        let mut buf = [0u8; 1024];
+        let _ = cert.as_tlv(&mut buf);
+        let _ = cert.as_asn1(&mut buf);
+        let _ = cert.as_x509(&mut buf);
+        let _ = cert.get_node_id();
+        let _ = cert.get_fabric_id();
+        let _ = cert.get_subject_key_id();
+        let _ = cert.is_authority(&cert);
+        let _ = cert.verify_chain_start().finalise();
+        let _ = cert.to_string();
+    }
+});
@@ -0,0 +1,35 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use matter::interaction_model::messages::msg::{InvReq, ReadReq, ReportDataMsg, WriteReq};
+use matter::tlv::{self, FromTLV};
+
+fuzz_target!(|data: &[u8]| {
+    // The lenient parser, to also reach the message parsers with TLV that the strict one rejects
+    let root = match tlv::get_root_node_struct(data) {
+        Ok(root) => root,
+        Err(_) => return,
+    };
+    if let Ok(req) = InvReq::from_tlv(&root) {
+        for cmd in req.inv_requests.iter().flat_map(|a| a.iter()) {
+            let _ = cmd.path.path.leaf;
+        }
+    }
+    if let Ok(req) = ReadReq::from_tlv(&root) {
+        for path in req.attr_requests.iter().flat_map(|a| a.iter()) {
+            let _ = path.to_gp();
+        }
+        for filter in req.dataver_filters.iter().flat_map(|a| a.iter()) {
+            let _ = filter.data_ver;
+        }
+    }
+    if let Ok(req) = WriteReq::from_tlv(&root) {
+        for data in req.write_requests.iter() {
+            let _ = data.data;
+        }
+    }
+    if let Ok(report) = ReportDataMsg::from_tlv(&root) {
+        for resp in report.attr_reports.iter().flat_map(|a| a.iter()) {
+            let _ = resp;
+        }
+    }
+});
@@ -0,0 +1,24 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use matter::transport::{plain_hdr::PlainHdr, proto_hdr::ProtoHdr};
+use matter::utils::parsebuf::ParseBuf;
+
+const KEY: [u8; 16] = [
+    0x44, 0xd4, 0x3c, 0x91, 0xd2, 0x27, 0xf3, 0xba, 0x08, 0x24, 0xc5, 0xd8, 0x7c, 0xb8, 0x1b, 0x33,
+];
+
+fn decode(data: &[u8], dec_key: Option<&[u8]>) {
+    let mut buf = data.to_vec();
+    let len = buf.len();
+    let mut parsebuf = ParseBuf::new(&mut buf, len);
+    let mut plain_hdr = PlainHdr::default();
+    if plain_hdr.decode(&mut parsebuf).is_ok() {
+        let mut proto_hdr = ProtoHdr::default();
+        let _ = proto_hdr.decrypt_and_decode(&plain_hdr, &mut parsebuf, 0, dec_key);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode(data, None);
+    decode(data, Some(&KEY));
+});
@@ -0,0 +1,33 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use matter::tlv::{self, TLVElement, TLVList, TLVValue};
+
+fn walk(element: &TLVElement, depth: usize) {
+    let _ = element.to_string();
+    // The lenient parser doesn't limit the nesting, this mustn't overflow the stack itself
+    if depth > tlv::TLV_MAX_DEPTH {
+        return;
+    }
+    if let Some(iter) = element.enter() {
+        for e in iter {
+            walk(&e, depth + 1);
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    for e in TLVList::new(data).iter() {
+        let _ = e.to_string();
+    }
+    if let Ok(root) = tlv::get_root_node(data) {
+        walk(&root, 0);
+        let _ = root.find_tag(1);
+    }
+    let _ = tlv::get_root_node_strict(data);
+    if let Ok(value) = TLVValue::from_slice(data) {
+        let _ = value.to_string();
+        if let Ok(json) = value.to_json() {
+            let _ = TLVValue::from_json(&json);
+        }
+    }
+});
@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 use crate::{
-    data_model::objects::{Access, Privilege},
+    data_model::objects::{Access, GlobalElements, Privilege},
     error::Error,
     fabric,
     interaction_model::messages::GenericPath,
@@ -150,8 +150,7 @@ pub struct AclEntry {
     auth_mode: AuthMode,
     subjects: Subjects,
     targets: Targets,
-    // TODO: Instead of the direct value, we should consider GlobalElements::FabricIndex
-    #[tagval(0xFE)]
+    #[tagval(GlobalElements::FabricIndex)]
     pub fab_idx: Option<u8>,
 }
 
@@ -244,36 +243,52 @@ impl AclEntry {
     }
 }
 
-const MAX_ACL_ENTRIES: usize = ENTRIES_PER_FABRIC * fabric::MAX_SUPPORTED_FABRICS;
-type AclEntries = [Option<AclEntry>; MAX_ACL_ENTRIES];
-
-#[derive(ToTLV, FromTLV, Debug)]
+#[derive(Debug)]
 struct AclMgrInner {
-    entries: AclEntries,
+    // Fixed size, with room for entries_per_fabric entries for each of the supported fabrics
+    entries: Vec<Option<AclEntry>>,
 }
 
 const ACL_KV_ENTRY: &str = "acl";
-const ACL_KV_MAX_SIZE: usize = 300;
+// The largest encoding of an entry, with all its subjects and targets
+const ACL_KV_ENTRY_MAX_SIZE: usize = 120;
 impl AclMgrInner {
+    fn new(entries_per_fabric: usize) -> Self {
+        Self {
+            entries: vec![None; entries_per_fabric * fabric::MAX_SUPPORTED_FABRICS],
+        }
+    }
+
     pub fn store(&self, psm: &MutexGuard<Psm>) -> Result<(), Error> {
-        let mut acl_tlvs = [0u8; ACL_KV_MAX_SIZE];
-        let mut wb = WriteBuf::new(&mut acl_tlvs, ACL_KV_MAX_SIZE);
+        // Room for every entry, and the array that holds them
+        let max_size = self.entries.len() * ACL_KV_ENTRY_MAX_SIZE + 2;
+        let mut acl_tlvs = vec![0u8; max_size];
+        let mut wb = WriteBuf::new(&mut acl_tlvs, max_size);
         let mut tw = TLVWriter::new(&mut wb);
-        self.entries.to_tlv(&mut tw, TagType::Anonymous)?;
+        tw.start_array(TagType::Anonymous)?;
+        for entry in self.entries.iter().flatten() {
+            entry.to_tlv(&mut tw, TagType::Anonymous)?;
+        }
+        tw.end_container()?;
         psm.set_kv_slice(ACL_KV_ENTRY, wb.as_slice())
     }
 
-    pub fn load(psm: &MutexGuard<Psm>) -> Result<Self, Error> {
+    pub fn load(psm: &MutexGuard<Psm>, entries_per_fabric: usize) -> Result<Self, Error> {
         let mut acl_tlvs = Vec::new();
         psm.get_kv_slice(ACL_KV_ENTRY, &mut acl_tlvs)?;
         let root = TLVList::new(&acl_tlvs)
             .iter()
             .next()
             .ok_or(Error::Invalid)?;
+        root.confirm_array()?;
 
-        Ok(Self {
-            entries: AclEntries::from_tlv(&root)?,
-        })
+        let mut inner = Self::new(entries_per_fabric);
+        if let Some(tlv_iter) = root.enter() {
+            for (slot, element) in inner.entries.iter_mut().zip(tlv_iter) {
+                *slot = Some(AclEntry::from_tlv(&element)?);
+            }
+        }
+        Ok(inner)
     }
 
     /// Traverse fabric specific entries to find the index
@@ -305,47 +320,44 @@ pub struct AclMgr {
     // The Option<> is solely because test execution is faster
     // Doing this here adds the least overhead during ACL verification
     psm: Option<Arc<Mutex<Psm>>>,
+    entries_per_fabric: usize,
 }
 
 impl AclMgr {
-    pub fn new() -> Result<Self, Error> {
-        AclMgr::new_with(true)
+    /// Create an ACL Manager that persists the entries in the given store, if any
+    pub fn new_with(psm: Option<Arc<Mutex<Psm>>>) -> Result<Self, Error> {
+        AclMgr::new_with_limit(psm, ENTRIES_PER_FABRIC)
     }
 
-    pub fn new_with(psm_support: bool) -> Result<Self, Error> {
-        const INIT: Option<AclEntry> = None;
-        let mut psm = None;
-
-        let inner = if !psm_support {
-            AclMgrInner {
-                entries: [INIT; MAX_ACL_ENTRIES],
-            }
-        } else {
-            let psm_handle = Psm::get()?;
-            let inner = {
+    /// Create an ACL Manager, with room for the given number of entries for each fabric
+    pub fn new_with_limit(
+        psm: Option<Arc<Mutex<Psm>>>,
+        entries_per_fabric: usize,
+    ) -> Result<Self, Error> {
+        let inner = match &psm {
+            None => AclMgrInner::new(entries_per_fabric),
+            Some(psm_handle) => {
                 let psm_lock = psm_handle.lock().unwrap();
-                AclMgrInner::load(&psm_lock)
-            };
-
-            psm = Some(psm_handle);
-            inner.unwrap_or({
                 // Error loading from PSM
-                AclMgrInner {
-                    entries: [INIT; MAX_ACL_ENTRIES],
-                }
-            })
+                AclMgrInner::load(&psm_lock, entries_per_fabric)
+                    .unwrap_or_else(|_| AclMgrInner::new(entries_per_fabric))
+            }
         };
         Ok(Self {
             inner: RwLock::new(inner),
             psm,
+            entries_per_fabric,
         })
     }
 
+    /// The number of entries that each fabric can have
+    pub fn entries_per_fabric(&self) -> usize {
+        self.entries_per_fabric
+    }
+
     pub fn erase_all(&self) {
         let mut inner = self.inner.write().unwrap();
-        for i in 0..MAX_ACL_ENTRIES {
-            inner.entries[i] = None;
-        }
+        inner.entries.iter_mut().for_each(|e| *e = None);
         if let Some(psm) = self.psm.as_ref() {
             let psm = psm.lock().unwrap();
             let _ = inner.store(&psm).map_err(|e| {
@@ -362,7 +374,7 @@ impl AclMgr {
             .flatten()
             .filter(|a| a.fab_idx == entry.fab_idx)
             .count();
-        if cnt >= ENTRIES_PER_FABRIC {
+        if cnt >= self.entries_per_fabric {
             return Err(Error::NoSpace);
         }
         let index = inner
@@ -410,12 +422,9 @@ impl AclMgr {
     pub fn delete_for_fabric(&self, fab_idx: u8) -> Result<(), Error> {
         let mut inner = self.inner.write().unwrap();
 
-        for i in 0..MAX_ACL_ENTRIES {
-            if inner.entries[i]
-                .filter(|e| e.fab_idx == Some(fab_idx))
-                .is_some()
-            {
-                inner.entries[i] = None;
+        for entry in inner.entries.iter_mut() {
+            if entry.filter(|e| e.fab_idx == Some(fab_idx)).is_some() {
+                *entry = None;
             }
         }
 
@@ -473,15 +482,21 @@ impl std::fmt::Display for AclMgr {
 mod tests {
     use crate::{
         data_model::objects::{Access, Privilege},
+        error::Error,
+        fabric,
         interaction_model::messages::GenericPath,
+        sys::Psm,
     };
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
 
-    use super::{AccessReq, Accessor, AclEntry, AclMgr, AuthMode, Target};
+    use super::{
+        AccessReq, Accessor, AclEntry, AclMgr, AuthMode, Target, SUBJECTS_PER_ENTRY,
+        TARGETS_PER_ENTRY,
+    };
 
     #[test]
     fn test_basic_empty_subject_target() {
-        let am = Arc::new(AclMgr::new_with(false).unwrap());
+        let am = Arc::new(AclMgr::new_with(None).unwrap());
         am.erase_all();
         let accessor = Accessor::new(2, 112233, AuthMode::Case, am.clone());
         let path = GenericPath::new(Some(1), Some(1234), None);
@@ -509,7 +524,7 @@ mod tests {
 
     #[test]
     fn test_subject() {
-        let am = Arc::new(AclMgr::new_with(false).unwrap());
+        let am = Arc::new(AclMgr::new_with(None).unwrap());
         am.erase_all();
         let accessor = Accessor::new(2, 112233, AuthMode::Case, am.clone());
         let path = GenericPath::new(Some(1), Some(1234), None);
@@ -531,7 +546,7 @@ mod tests {
 
     #[test]
     fn test_target() {
-        let am = Arc::new(AclMgr::new_with(false).unwrap());
+        let am = Arc::new(AclMgr::new_with(None).unwrap());
         am.erase_all();
         let accessor = Accessor::new(2, 112233, AuthMode::Case, am.clone());
         let path = GenericPath::new(Some(1), Some(1234), None);
@@ -592,7 +607,7 @@ mod tests {
 
     #[test]
     fn test_privilege() {
-        let am = Arc::new(AclMgr::new_with(false).unwrap());
+        let am = Arc::new(AclMgr::new_with(None).unwrap());
         am.erase_all();
         let accessor = Accessor::new(2, 112233, AuthMode::Case, am.clone());
         let path = GenericPath::new(Some(1), Some(1234), None);
@@ -629,4 +644,68 @@ mod tests {
         req.set_target_perms(Access::RWVA);
         assert_eq!(req.allow(), true);
     }
+
+    #[test]
+    fn test_entries_per_fabric() {
+        let am = Arc::new(AclMgr::new_with_limit(None, 2).unwrap());
+        assert_eq!(am.entries_per_fabric(), 2);
+        for _ in 0..2 {
+            am.add(AclEntry::new(1, Privilege::VIEW, AuthMode::Case))
+                .unwrap();
+        }
+        assert_eq!(
+            am.add(AclEntry::new(1, Privilege::VIEW, AuthMode::Case)),
+            Err(Error::NoSpace)
+        );
+
+        // The limit applies to each fabric separately
+        am.add(AclEntry::new(2, Privilege::VIEW, AuthMode::Case))
+            .unwrap();
+        am.delete(0, 1).unwrap();
+        am.add(AclEntry::new(1, Privilege::VIEW, AuthMode::Case))
+            .unwrap();
+    }
+
+    // An entry with all its subjects and targets, in their largest encoding
+    fn full_entry(fab_idx: u8) -> AclEntry {
+        let mut entry = AclEntry::new(fab_idx, Privilege::ADMIN, AuthMode::Case);
+        for i in 0..SUBJECTS_PER_ENTRY {
+            entry.add_subject(u64::MAX - i as u64).unwrap();
+        }
+        for i in 0..TARGETS_PER_ENTRY {
+            entry
+                .add_target(Target {
+                    cluster: Some(u32::MAX - i as u32),
+                    endpoint: Some(u16::MAX - i as u16),
+                    device_type: Some(u32::MAX - i as u32),
+                })
+                .unwrap();
+        }
+        entry
+    }
+
+    #[test]
+    fn test_store_entries_per_fabric() {
+        let dir = std::env::temp_dir().join("matter_acl_entries_per_fabric");
+        let _ = std::fs::remove_dir_all(&dir);
+        let psm = Arc::new(Mutex::new(Psm::new_at(&dir).unwrap()));
+
+        // Fill all the slots, of all the fabrics
+        let am = AclMgr::new_with_limit(Some(psm.clone()), 8).unwrap();
+        for fab_idx in 1..=fabric::MAX_SUPPORTED_FABRICS as u8 {
+            for _ in 0..8 {
+                am.add(full_entry(fab_idx)).unwrap();
+            }
+        }
+
+        // All the entries are loaded back
+        let am = AclMgr::new_with_limit(Some(psm), 8).unwrap();
+        let mut count = 0;
+        am.for_each_acl(|e| {
+            assert_eq!(*e, full_entry(e.fab_idx.unwrap()));
+            count += 1;
+        })
+        .unwrap();
+        assert_eq!(count, 8 * fabric::MAX_SUPPORTED_FABRICS);
+    }
 }
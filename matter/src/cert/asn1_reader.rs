@@ -0,0 +1,317 @@
+use super::X509_NO_EXPIRY;
+use crate::error::Error;
+use chrono::NaiveDate;
+use log::error;
+use std::convert::TryFrom;
+
+pub const TAG_BOOL: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BITSTR: u8 = 0x03;
+pub const TAG_OSTR: u8 = 0x04;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8STR: u8 = 0x0c;
+pub const TAG_SEQ: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+pub const TAG_UTCTIME: u8 = 0x17;
+pub const TAG_GENERALIZEDTIME: u8 = 0x18;
+
+/// A reader of the DER encoding, the reverse of the [ASN1Writer](super::asn1_writer::ASN1Writer)
+///
+/// Each read consumes an element of the expected tag, and returns its contents. The contents
+/// of a constructed element are read with a nested reader.
+#[derive(Debug, Clone)]
+pub struct ASN1Reader<'a> {
+    buf: &'a [u8],
+    // The current read offset in the buffer
+    offset: usize,
+}
+
+impl<'a> ASN1Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset >= self.buf.len()
+    }
+
+    /// The tag of the next element, if there is one
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.buf.get(self.offset).copied()
+    }
+
+    /// The slice of the buffer that was read so far
+    pub fn read_slice(&self) -> &'a [u8] {
+        &self.buf[..self.offset]
+    }
+
+    /// Check that all the elements were read
+    pub fn finish(&self) -> Result<(), Error> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            error!("Unexpected element with tag {:#x?}", self.peek_tag());
+            Err(Error::InvalidData)
+        }
+    }
+
+    /// The contents of the next element, which must have this tag
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        match self.peek_tag() {
+            Some(t) if t == tag => (),
+            t => {
+                error!("Expected tag {:#x}, found {:#x?}", tag, t);
+                return Err(Error::InvalidData);
+            }
+        }
+        let (len, len_bytes) = self.read_len(self.offset + 1)?;
+        let start = self.offset + 1 + len_bytes;
+        let contents = start
+            .checked_add(len)
+            .and_then(|end| self.buf.get(start..end))
+            .ok_or(Error::TruncatedPacket)?;
+        self.offset = start + len;
+        Ok(contents)
+    }
+
+    // The length at the offset, and the number of bytes it is encoded with
+    fn read_len(&self, offset: usize) -> Result<(usize, usize), Error> {
+        let first = *self.buf.get(offset).ok_or(Error::TruncatedPacket)?;
+        if first & 0x80 == 0 {
+            return Ok((first as usize, 1));
+        }
+        // The indefinite form (0x80) isn't DER, and the lengths are less than 64K as in the writer
+        let bytes_of_len = (first & 0x7f) as usize;
+        if bytes_of_len == 0 || bytes_of_len > 2 {
+            error!("Unsupported length encoding {:#x}", first);
+            return Err(Error::InvalidData);
+        }
+        let len_buf = self
+            .buf
+            .get((offset + 1)..(offset + 1 + bytes_of_len))
+            .ok_or(Error::TruncatedPacket)?;
+        let len = len_buf.iter().fold(0, |len, b| (len << 8) | *b as usize);
+        // DER requires the shortest length encoding
+        if len < 128 || (bytes_of_len == 2 && len < 256) {
+            error!("The length {} isn't minimally encoded", len);
+            return Err(Error::InvalidData);
+        }
+        Ok((len, 1 + bytes_of_len))
+    }
+
+    /// A reader of the elements in the next constructed element with this tag
+    pub fn enter(&mut self, tag: u8) -> Result<ASN1Reader<'a>, Error> {
+        Ok(ASN1Reader::new(self.read(tag)?))
+    }
+
+    /// A reader of the elements in the next sequence
+    pub fn seq(&mut self) -> Result<ASN1Reader<'a>, Error> {
+        self.enter(TAG_SEQ)
+    }
+
+    /// A reader of the elements in the next set
+    pub fn set(&mut self) -> Result<ASN1Reader<'a>, Error> {
+        self.enter(TAG_SET)
+    }
+
+    /// The bytes of the next integer, as they are encoded
+    pub fn integer(&mut self) -> Result<&'a [u8], Error> {
+        let i = self.read(TAG_INTEGER)?;
+        // DER integers are minimal: 0x00 only precedes a byte with its top bit set, and 0xff
+        // one without
+        match i {
+            [] => Err(Error::InvalidData),
+            [0x00, b, ..] if b & 0x80 == 0 => Err(Error::InvalidData),
+            [0xff, b, ..] if b & 0x80 != 0 => Err(Error::InvalidData),
+            _ => Ok(i),
+        }
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        // DER only allows 0xff as true
+        match self.read(TAG_BOOL)? {
+            [0x00] => Ok(false),
+            [0xff] => Ok(true),
+            b => {
+                error!("Invalid boolean {:x?}", b);
+                Err(Error::InvalidData)
+            }
+        }
+    }
+
+    pub fn oid(&mut self) -> Result<&'a [u8], Error> {
+        self.read(TAG_OID)
+    }
+
+    pub fn ostr(&mut self) -> Result<&'a [u8], Error> {
+        self.read(TAG_OSTR)
+    }
+
+    pub fn utf8str(&mut self) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.read(TAG_UTF8STR)?).map_err(|_| Error::InvalidData)
+    }
+
+    /// The bytes of the next bit string, and the number of unused bits in its last byte
+    pub fn bitstr(&mut self) -> Result<(&'a [u8], u8), Error> {
+        match self.read(TAG_BITSTR)? {
+            [unused, s @ ..] if *unused < 8 && (*unused == 0 || !s.is_empty()) => Ok((s, *unused)),
+            _ => Err(Error::InvalidData),
+        }
+    }
+
+    /// The next element with this context-specific tag, primitive or constructed
+    pub fn ctx(&mut self, id: u8) -> Result<&'a [u8], Error> {
+        self.read(0x80 | id)
+    }
+
+    pub fn start_ctx(&mut self, id: u8) -> Result<ASN1Reader<'a>, Error> {
+        self.enter(0xA0 | id)
+    }
+
+    /// The next UTCTime or GeneralizedTime, in seconds since the Matter epoch
+    pub fn time(&mut self) -> Result<u32, Error> {
+        let (s, year_len) = match self.peek_tag() {
+            Some(TAG_UTCTIME) => (self.read(TAG_UTCTIME)?, 2),
+            _ => (self.read(TAG_GENERALIZEDTIME)?, 4),
+        };
+        // DER times are in UTC, with seconds: YYMMDDHHMMSSZ or YYYYMMDDHHMMSSZ
+        let digits = match s.split_last() {
+            Some((b'Z', digits))
+                if digits.len() == year_len + 10 && digits.iter().all(u8::is_ascii_digit) =>
+            {
+                digits
+            }
+            _ => {
+                error!("Invalid time {:x?}", s);
+                return Err(Error::InvalidData);
+            }
+        };
+        let num = |range: std::ops::Range<usize>| {
+            digits[range]
+                .iter()
+                .fold(0u32, |n, d| n * 10 + (d - b'0') as u32)
+        };
+        let mut year = num(0..year_len) as i32;
+        if year_len == 2 {
+            // As per RFC 5280, the years of a UTCTime are between 1950 and 2049
+            year += if year < 50 { 2000 } else { 1900 };
+        }
+        let y = year_len;
+        let time = NaiveDate::from_ymd_opt(year, num(y..y + 2), num(y + 2..y + 4))
+            .and_then(|d| d.and_hms_opt(num(y + 4..y + 6), num(y + 6..y + 8), num(y + 8..y + 10)))
+            .ok_or_else(|| {
+                error!("Invalid time {:x?}", s);
+                Error::InvalidData
+            })?;
+        let matter_epoch = NaiveDate::from_ymd_opt(2000, 1, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .ok_or(Error::Invalid)?;
+        let secs = time.signed_duration_since(matter_epoch).num_seconds();
+        u32::try_from(secs).map_err(|_| {
+            error!("The time {:x?} doesn't fit in the Matter epoch", s);
+            Error::Invalid
+        })
+    }
+
+    /// The next time, as the end of a validity period, which is 0 for no well-defined expiration
+    pub fn expiry(&mut self) -> Result<u32, Error> {
+        let mut r = self.clone();
+        if r.read(TAG_GENERALIZEDTIME) == Ok(X509_NO_EXPIRY.as_bytes()) {
+            *self = r;
+            return Ok(0);
+        }
+        self.time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ASN1Reader;
+    use crate::error::Error;
+
+    #[test]
+    fn test_read_len() {
+        let mut buf = vec![0x04, 0x81, 0x80];
+        buf.extend_from_slice(&[0xaa; 0x80]);
+        buf.extend_from_slice(&[0x04, 0x82, 0x01, 0x00]);
+        buf.extend_from_slice(&[0xbb; 0x100]);
+        let mut r = ASN1Reader::new(&buf);
+        assert_eq!(r.ostr(), Ok(&[0xaa; 0x80][..]));
+        assert_eq!(r.ostr(), Ok(&[0xbb; 0x100][..]));
+        assert!(r.is_empty());
+
+        // Not the shortest length, the indefinite length, and past the end
+        assert_eq!(
+            ASN1Reader::new(&[0x04, 0x81, 0x01, 0xaa]).ostr(),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            ASN1Reader::new(&[0x30, 0x80, 0x00, 0x00]).seq().map(|_| ()),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            ASN1Reader::new(&[0x04, 0x03, 0xaa]).ostr(),
+            Err(Error::TruncatedPacket)
+        );
+    }
+
+    #[test]
+    fn test_read_elements() {
+        let buf = [
+            0x30, 0x0c, 0x02, 0x02, 0x00, 0x80, 0x01, 0x01, 0xff, 0x03, 0x03, 0x01, 0x02, 0x02,
+            0x06, 0x01, 0x2a,
+        ];
+        let mut r = ASN1Reader::new(&buf);
+        let mut seq = r.seq().unwrap();
+        assert_eq!(seq.integer(), Ok(&[0x00, 0x80][..]));
+        assert_eq!(seq.bool(), Ok(true));
+        assert_eq!(seq.bitstr(), Ok((&[0x02, 0x02][..], 1)));
+        seq.finish().unwrap();
+        assert_eq!(r.read_slice().len(), 14);
+        assert_eq!(r.oid(), Ok(&[0x2a][..]));
+        r.finish().unwrap();
+
+        // Integers that aren't minimal, and a boolean that isn't DER
+        assert_eq!(
+            ASN1Reader::new(&[0x02, 0x02, 0x00, 0x7f]).integer(),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            ASN1Reader::new(&[0x01, 0x01, 0x01]).bool(),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            ASN1Reader::new(&[0x02, 0x01, 0x01]).oid(),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_read_time() {
+        let mut r = ASN1Reader::new(b"\x17\x0d000101000140Z\x18\x0f20680119031407Z");
+        assert_eq!(r.time(), Ok(100));
+        assert_eq!(r.time(), Ok(0x7fffffff));
+
+        // No well-defined expiration, only as the end of a validity period
+        let mut r = ASN1Reader::new(b"\x18\x0f99991231235959Z\x18\x0f99991231235959Z");
+        assert_eq!(r.expiry(), Ok(0));
+        assert_eq!(r.time(), Err(Error::Invalid));
+        let mut r = ASN1Reader::new(b"\x18\x0f20680119031407Z\x17\x0d000101000140Z");
+        assert_eq!(r.expiry(), Ok(0x7fffffff));
+        assert_eq!(r.expiry(), Ok(100));
+
+        // Before the Matter epoch, an invalid date, and a local time
+        assert_eq!(
+            ASN1Reader::new(b"\x17\x0d991231235959Z").time(),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            ASN1Reader::new(b"\x17\x0d210230000000Z").time(),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            ASN1Reader::new(b"\x17\x0d2101010000000").time(),
+            Err(Error::InvalidData)
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use super::{CertConsumer, MAX_DEPTH};
+use super::{CertConsumer, MAX_DEPTH, X509_NO_EXPIRY};
 use crate::error::Error;
-use chrono::{TimeZone, Utc};
+use chrono::{Datelike, TimeZone, Utc};
 
 #[derive(Debug)]
 pub struct ASN1Writer<'a> {
@@ -171,17 +171,19 @@ impl<'a> CertConsumer for ASN1Writer<'a> {
         // Note: ASN1 has 3 string, this is BIT String
 
         // Strip off the end zeroes
-        let mut last_byte = s.len() - 1;
+        let mut len = s.len();
         let mut num_of_zero = 0;
         if truncate {
-            while s[last_byte] == 0 {
-                last_byte -= 1;
+            while len > 0 && s[len - 1] == 0 {
+                len -= 1;
             }
             // For the last valid byte, identifying the number of last bits
             // that are 0s
-            num_of_zero = s[last_byte].trailing_zeros() as u8;
+            if len > 0 {
+                num_of_zero = s[len - 1].trailing_zeros() as u8;
+            }
         }
-        let s = &s[..(last_byte + 1)];
+        let s = &s[..len];
         self.append_tlv(0x03, s.len() + 1, |t| {
             t.buf[t.offset] = num_of_zero;
             let end_offset = t.offset + 1 + s.len();
@@ -242,7 +244,17 @@ impl<'a> CertConsumer for ASN1Writer<'a> {
         matter_epoch += epoch as i64;
 
         let dt = Utc.timestamp(matter_epoch, 0);
-        let time_str = format!("{}Z", dt.format("%y%m%d%H%M%S"));
-        self.write_str(0x17, time_str.as_bytes())
+        if dt.year() >= 2050 {
+            // As per RFC 5280, UTCTime only has the years until 2049, GeneralizedTime is used after
+            let time_str = format!("{}Z", dt.format("%Y%m%d%H%M%S"));
+            self.write_str(0x18, time_str.as_bytes())
+        } else {
+            let time_str = format!("{}Z", dt.format("%y%m%d%H%M%S"));
+            self.write_str(0x17, time_str.as_bytes())
+        }
+    }
+
+    fn no_expiry(&mut self, _tag: &str) -> Result<(), Error> {
+        self.write_str(0x18, X509_NO_EXPIRY.as_bytes())
     }
 }
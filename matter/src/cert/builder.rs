@@ -0,0 +1,244 @@
+use log::error;
+use rand::Rng;
+
+use super::{
+    BasicConstraints, Cert, DistNames, DnTags, EcCurveIdValue, Extensions, PubKeyAlgoValue,
+    SignAlgoValue, KEY_ID_LEN, KEY_USAGE_CRL_SIGN, KEY_USAGE_DIGITAL_SIGN, KEY_USAGE_KEY_CERT_SIGN,
+    MAX_ASN1_CERT_SIZE, MAX_SERIAL_NUM_LEN,
+};
+use crate::{
+    crypto::{
+        CryptoKeyPair, KeyPair, Sha256, EC_POINT_LEN_BYTES, EC_SIGNATURE_LEN_BYTES,
+        SHA256_HASH_LEN_BYTES,
+    },
+    error::Error,
+};
+
+const SERIAL_NUM_LEN: usize = 8;
+
+const EXT_KEY_USAGE_SERVER_AUTH: u8 = 1;
+const EXT_KEY_USAGE_CLIENT_AUTH: u8 = 2;
+
+#[derive(PartialEq)]
+enum CertKind {
+    Rcac,
+    Icac,
+    Noc,
+}
+
+/// Issues a Matter certificate: a root CA (RCAC), an intermediate CA (ICAC), or a node operational
+/// certificate (NOC)
+///
+/// The subject DN of the certificate is set by the constructor, and the extensions are those
+/// that the Matter specification requires for its kind. The issuer is the certificate of the
+/// signing key, for all but a root CA, which is self-signed:
+///
+/// ```ignore
+/// let rcac = CertBuilder::rcac(1).fabric_id(1).pubkey(&root_pubkey).sign(&root_key)?;
+/// let noc = CertBuilder::noc(0x1234, 1)
+///     .issuer(&rcac)
+///     .pubkey(&node_pubkey)
+///     .sign(&root_key)?;
+/// ```
+///
+/// The certificate can then be written in its Matter TLV form with [Cert::as_tlv], and in its
+/// X.509 DER form with [Cert::as_x509].
+pub struct CertBuilder {
+    kind: CertKind,
+    cert: Cert,
+    issuer: Option<(DistNames, Vec<u8>)>,
+}
+
+impl CertBuilder {
+    fn new(kind: CertKind, subject: Vec<(u8, u64)>) -> Self {
+        let (basic_const, key_usage, ext_key_usage) = match kind {
+            CertKind::Rcac | CertKind::Icac => (
+                BasicConstraints {
+                    is_ca: true,
+                    path: None,
+                },
+                KEY_USAGE_KEY_CERT_SIGN | KEY_USAGE_CRL_SIGN,
+                None,
+            ),
+            CertKind::Noc => (
+                BasicConstraints {
+                    is_ca: false,
+                    path: None,
+                },
+                KEY_USAGE_DIGITAL_SIGN,
+                Some(vec![EXT_KEY_USAGE_CLIENT_AUTH, EXT_KEY_USAGE_SERVER_AUTH].into()),
+            ),
+        };
+
+        // A random positive integer, that doesn't start with a 0 byte
+        let mut serial_no = vec![0; SERIAL_NUM_LEN];
+        rand::thread_rng().fill(serial_no.as_mut_slice());
+        serial_no[0] = (serial_no[0] & 0x7f) | 0x01;
+
+        Self {
+            kind,
+            cert: Cert {
+                serial_no,
+                sign_algo: SignAlgoValue::ECDSAWithSHA256 as u8,
+                subject: DistNames { dn: subject },
+                pubkey_algo: PubKeyAlgoValue::EcPubKey as u8,
+                ec_curve_id: EcCurveIdValue::Prime256V1 as u8,
+                extensions: Extensions {
+                    basic_const: Some(basic_const),
+                    key_usage: Some(key_usage),
+                    ext_key_usage,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            issuer: None,
+        }
+    }
+
+    /// A root CA certificate, with this root CA ID
+    pub fn rcac(rcac_id: u64) -> Self {
+        Self::new(CertKind::Rcac, vec![(DnTags::RootCaId as u8, rcac_id)])
+    }
+
+    /// An intermediate CA certificate, with this ICA ID
+    pub fn icac(icac_id: u64) -> Self {
+        Self::new(CertKind::Icac, vec![(DnTags::IcaId as u8, icac_id)])
+    }
+
+    /// A node operational certificate, for this node in this fabric
+    pub fn noc(node_id: u64, fabric_id: u64) -> Self {
+        Self::new(
+            CertKind::Noc,
+            vec![
+                (DnTags::NodeId as u8, node_id),
+                (DnTags::FabricId as u8, fabric_id),
+            ],
+        )
+    }
+
+    /// The fabric ID in the subject of a CA certificate, which then only issues for this fabric
+    pub fn fabric_id(mut self, fabric_id: u64) -> Self {
+        self.cert
+            .subject
+            .dn
+            .push((DnTags::FabricId as u8, fabric_id));
+        self
+    }
+
+    /// A CASE Authenticated Tag in the subject of a NOC, up to 3 of them
+    ///
+    /// The upper 16 bits are the tag identifier, and the lower 16 bits its version.
+    pub fn cat(mut self, cat: u32) -> Self {
+        self.cert
+            .subject
+            .dn
+            .push((DnTags::NocCat as u8, cat as u64));
+        self
+    }
+
+    /// The serial number, a random one by default
+    pub fn serial_no(mut self, serial_no: &[u8]) -> Self {
+        self.cert.serial_no = serial_no.to_vec();
+        self
+    }
+
+    /// The validity period, in seconds since the Matter epoch (2000-01-01 00:00:00 UTC)
+    pub fn validity(mut self, not_before: u32, not_after: u32) -> Self {
+        self.cert.not_before = not_before;
+        self.cert.not_after = not_after;
+        self
+    }
+
+    /// The uncompressed P-256 public key of the subject
+    pub fn pubkey(mut self, pubkey: &[u8]) -> Self {
+        self.cert.pubkey = pubkey.to_vec();
+        self
+    }
+
+    /// The subject key ID, the first 160 bits of the SHA-256 of the public key by default
+    pub fn subject_key_id(mut self, key_id: &[u8]) -> Self {
+        self.cert.extensions.subj_key_id = Some(key_id.to_vec());
+        self
+    }
+
+    /// The certificate of the CA that signs this one
+    ///
+    /// This sets the issuer DN, and the authority key ID. It is required for all but root CA
+    /// certificates.
+    pub fn issuer(mut self, issuer: &Cert) -> Self {
+        let key_id = issuer.get_subject_key_id().unwrap_or_default().to_vec();
+        self.issuer = Some((
+            DistNames {
+                dn: issuer.subject.dn.clone(),
+            },
+            key_id,
+        ));
+        self
+    }
+
+    /// Sign the certificate with the key of the issuer, or its own key for a root CA
+    pub fn sign(mut self, key: &KeyPair) -> Result<Cert, Error> {
+        self.validate()?;
+
+        let subj_key_id = match self.cert.extensions.subj_key_id.take() {
+            Some(key_id) => key_id,
+            None => key_id(&self.cert.pubkey)?,
+        };
+        let (issuer, auth_key_id) = match self.issuer.take() {
+            Some(issuer) => issuer,
+            None => (
+                DistNames {
+                    dn: self.cert.subject.dn.clone(),
+                },
+                subj_key_id.clone(),
+            ),
+        };
+        self.cert.issuer = issuer;
+        self.cert.extensions.subj_key_id = Some(subj_key_id);
+        self.cert.extensions.auth_key_id = Some(auth_key_id);
+        self.cert.validate()?;
+
+        // The signature is over the DER encoding of the certificate without it
+        let mut asn1 = [0u8; MAX_ASN1_CERT_SIZE];
+        let len = self.cert.as_asn1(&mut asn1)?;
+        let mut signature = [0u8; EC_SIGNATURE_LEN_BYTES];
+        let len = key.sign_msg(&asn1[..len], &mut signature)?;
+        self.cert.signature = signature[..len].to_vec();
+        Ok(self.cert)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.cert.pubkey.len() != EC_POINT_LEN_BYTES {
+            error!("The public key of the certificate isn't set");
+            return Err(Error::Invalid);
+        }
+        if self.issuer.is_none() && self.kind != CertKind::Rcac {
+            error!("Only a root CA certificate has no issuer");
+            return Err(Error::Invalid);
+        }
+        if let Some((_, key_id)) = &self.issuer {
+            if key_id.is_empty() {
+                error!("The issuer has no subject key ID");
+                return Err(Error::Invalid);
+            }
+        }
+        if self.cert.serial_no.is_empty() || self.cert.serial_no.len() > MAX_SERIAL_NUM_LEN {
+            error!("The serial number has 1 to {} bytes", MAX_SERIAL_NUM_LEN);
+            return Err(Error::Invalid);
+        }
+        if self.cert.not_after != 0 && self.cert.not_after < self.cert.not_before {
+            error!("The certificate expires before it is valid");
+            return Err(Error::Invalid);
+        }
+        Ok(())
+    }
+}
+
+/// The key ID of a public key, the first 160 bits of its SHA-256 (as in RFC 7093)
+fn key_id(pubkey: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut hash = [0u8; SHA256_HASH_LEN_BYTES];
+    let mut h = Sha256::new()?;
+    h.update(pubkey)?;
+    h.finish(&mut hash)?;
+    Ok(hash[..KEY_ID_LEN].to_vec())
+}
@@ -1,7 +1,9 @@
 use std::fmt;
 
 use crate::{
-    crypto::{CryptoKeyPair, KeyPair},
+    crypto::{
+        CryptoKeyPair, KeyPair, BIGNUM_LEN_BYTES, EC_POINT_LEN_BYTES, EC_SIGNATURE_LEN_BYTES,
+    },
     error::Error,
     tlv::{self, FromTLV, TLVArrayOwned, TLVElement, TLVWriter, TagType, ToTLV},
     utils::writebuf::WriteBuf,
@@ -9,7 +11,7 @@ use crate::{
 use log::error;
 use num_derive::FromPrimitive;
 
-use self::{asn1_writer::ASN1Writer, printer::CertPrinter};
+use self::{asn1_reader::ASN1Reader, asn1_writer::ASN1Writer, printer::CertPrinter};
 
 // As per https://datatracker.ietf.org/doc/html/rfc5280
 
@@ -118,31 +120,45 @@ fn encode_key_usage(key_usage: u16, w: &mut dyn CertConsumer) -> Result<(), Erro
     Ok(())
 }
 
+fn decode_key_usage(r: &mut ASN1Reader) -> Result<u16, Error> {
+    let (s, _) = r.bitstr()?;
+    if s.len() > 2 {
+        error!("Invalid key usage {:x?}", s);
+        return Err(Error::Invalid);
+    }
+    Ok(s.iter()
+        .rev()
+        .fold(0, |key_usage, b| (key_usage << 8) | reverse_byte(*b) as u16))
+}
+
+const OID_SERVER_AUTH: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+const OID_CLIENT_AUTH: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+const OID_CODE_SIGN: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+const OID_EMAIL_PROT: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04];
+const OID_TIMESTAMP: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+const OID_OCSP_SIGN: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
+
+// The extended key usages, indexed by their value in the Matter certificate
+const EXT_KEY_USAGE_ENCODING: [(&str, [u8; 8]); 7] = [
+    ("", [0; 8]),
+    ("ServerAuth", OID_SERVER_AUTH),
+    ("ClientAuth", OID_CLIENT_AUTH),
+    ("CodeSign", OID_CODE_SIGN),
+    ("EmailProtection", OID_EMAIL_PROT),
+    ("Timestamp", OID_TIMESTAMP),
+    ("OCSPSign", OID_OCSP_SIGN),
+];
+
 fn encode_extended_key_usage(
     list: &TLVArrayOwned<u8>,
     w: &mut dyn CertConsumer,
 ) -> Result<(), Error> {
-    const OID_SERVER_AUTH: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
-    const OID_CLIENT_AUTH: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
-    const OID_CODE_SIGN: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
-    const OID_EMAIL_PROT: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04];
-    const OID_TIMESTAMP: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
-    const OID_OCSP_SIGN: [u8; 8] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
-    let encoding = [
-        ("", &[0; 8]),
-        ("ServerAuth", &OID_SERVER_AUTH),
-        ("ClientAuth", &OID_CLIENT_AUTH),
-        ("CodeSign", &OID_CODE_SIGN),
-        ("EmailProtection", &OID_EMAIL_PROT),
-        ("Timestamp", &OID_TIMESTAMP),
-        ("OCSPSign", &OID_OCSP_SIGN),
-    ];
-
     w.start_seq("")?;
     for t in list.iter() {
         let t = *t as usize;
-        if t > 0 && t <= encoding.len() {
-            w.oid(encoding[t].0, encoding[t].1)?;
+        if t > 0 && t < EXT_KEY_USAGE_ENCODING.len() {
+            let (name, oid) = &EXT_KEY_USAGE_ENCODING[t];
+            w.oid(name, oid)?;
         } else {
             error!("Skipping encoding key usage out of bounds");
         }
@@ -151,6 +167,24 @@ fn encode_extended_key_usage(
     Ok(())
 }
 
+fn decode_extended_key_usage(r: &mut ASN1Reader) -> Result<TLVArrayOwned<u8>, Error> {
+    let mut seq = r.seq()?;
+    let mut list = Vec::new();
+    while !seq.is_empty() {
+        let oid = seq.oid()?;
+        let t = EXT_KEY_USAGE_ENCODING
+            .iter()
+            .skip(1)
+            .position(|(_, o)| o == oid)
+            .ok_or_else(|| {
+                error!("Unsupported extended key usage {:x?}", oid);
+                Error::Invalid
+            })?;
+        list.push(t as u8 + 1);
+    }
+    Ok(list.into())
+}
+
 #[derive(FromTLV, ToTLV, Default)]
 #[tlvargs(start = 1)]
 struct BasicConstraints {
@@ -165,11 +199,36 @@ impl BasicConstraints {
             // Encode CA only if true
             w.bool("CA:", true)?
         }
-        if self.path.is_some() {
-            error!("Path Len is not yet implemented");
+        if let Some(path) = self.path {
+            // A positive integer, with a leading 0 if its top bit is set
+            if path & 0x80 != 0 {
+                w.integer("Path Len Constraint:", &[0, path])?
+            } else {
+                w.integer("Path Len Constraint:", &[path])?
+            }
         }
         w.end_seq()
     }
+
+    fn decode(r: &mut ASN1Reader) -> Result<Self, Error> {
+        let mut seq = r.seq()?;
+        let mut b = Self::default();
+        if seq.peek_tag() == Some(asn1_reader::TAG_BOOL) {
+            b.is_ca = seq.bool()?;
+        }
+        if !seq.is_empty() {
+            b.path = match seq.integer()? {
+                [path] if path & 0x80 == 0 => Some(*path),
+                [0, path] => Some(*path),
+                path => {
+                    error!("Invalid path length {:x?}", path);
+                    return Err(Error::Invalid);
+                }
+            };
+        }
+        seq.finish()?;
+        Ok(b)
+    }
 }
 
 fn encode_extension_start(
@@ -202,14 +261,14 @@ struct Extensions {
     future_extensions: Option<Vec<u8>>,
 }
 
+const OID_BASIC_CONSTRAINTS: [u8; 3] = [0x55, 0x1D, 0x13];
+const OID_KEY_USAGE: [u8; 3] = [0x55, 0x1D, 0x0F];
+const OID_EXT_KEY_USAGE: [u8; 3] = [0x55, 0x1D, 0x25];
+const OID_SUBJ_KEY_IDENTIFIER: [u8; 3] = [0x55, 0x1D, 0x0E];
+const OID_AUTH_KEY_ID: [u8; 3] = [0x55, 0x1D, 0x23];
+
 impl Extensions {
     fn encode(&self, w: &mut dyn CertConsumer) -> Result<(), Error> {
-        const OID_BASIC_CONSTRAINTS: [u8; 3] = [0x55, 0x1D, 0x13];
-        const OID_KEY_USAGE: [u8; 3] = [0x55, 0x1D, 0x0F];
-        const OID_EXT_KEY_USAGE: [u8; 3] = [0x55, 0x1D, 0x25];
-        const OID_SUBJ_KEY_IDENTIFIER: [u8; 3] = [0x55, 0x1D, 0x0E];
-        const OID_AUTH_KEY_ID: [u8; 3] = [0x55, 0x1D, 0x23];
-
         w.start_ctx("X509v3 extensions:", 3)?;
         w.start_seq("")?;
         if let Some(t) = &self.basic_const {
@@ -246,7 +305,45 @@ impl Extensions {
         w.end_ctx()?;
         Ok(())
     }
+
+    fn decode(r: &mut ASN1Reader) -> Result<Self, Error> {
+        let mut ctx = r.start_ctx(3)?;
+        let mut seq = ctx.seq()?;
+        ctx.finish()?;
+
+        let mut e = Self::default();
+        while !seq.is_empty() {
+            let mut ext = seq.seq()?;
+            let oid = ext.oid()?;
+            // Whether the extension is critical is checked when it is encoded back
+            if ext.peek_tag() == Some(asn1_reader::TAG_BOOL) {
+                ext.bool()?;
+            }
+            let mut value = ASN1Reader::new(ext.ostr()?);
+            ext.finish()?;
+
+            if oid == OID_BASIC_CONSTRAINTS {
+                e.basic_const = Some(BasicConstraints::decode(&mut value)?);
+            } else if oid == OID_KEY_USAGE {
+                e.key_usage = Some(decode_key_usage(&mut value)?);
+            } else if oid == OID_EXT_KEY_USAGE {
+                e.ext_key_usage = Some(decode_extended_key_usage(&mut value)?);
+            } else if oid == OID_SUBJ_KEY_IDENTIFIER {
+                e.subj_key_id = Some(value.ostr()?.to_vec());
+            } else if oid == OID_AUTH_KEY_ID {
+                let mut auth_key_id = value.seq()?;
+                e.auth_key_id = Some(auth_key_id.ctx(0)?.to_vec());
+                auth_key_id.finish()?;
+            } else {
+                error!("Extension not yet supported: {:x?}", oid);
+                return Err(Error::Invalid);
+            }
+            value.finish()?;
+        }
+        Ok(e)
+    }
 }
+
 const MAX_DN_ENTRIES: usize = 5;
 
 #[derive(FromPrimitive, Copy, Clone)]
@@ -305,29 +402,34 @@ impl ToTLV for DistNames {
     }
 }
 
+const OID_MATTER_NODE_ID: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x01];
+const OID_MATTER_FW_SIGN_ID: [u8; 10] =
+    [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x02];
+const OID_MATTER_ICA_ID: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x03];
+const OID_MATTER_ROOT_CA_ID: [u8; 10] =
+    [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x04];
+const OID_MATTER_FABRIC_ID: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x05];
+const OID_MATTER_NOC_CAT_ID: [u8; 10] =
+    [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x06];
+
+// The Matter DNs, indexed by their tag from DnTags::NodeId
+const DN_ENCODING: [(&str, [u8; 10]); 6] = [
+    ("Chip Node Id:", OID_MATTER_NODE_ID),
+    ("Chip Firmware Signing Id:", OID_MATTER_FW_SIGN_ID),
+    ("Chip ICA Id:", OID_MATTER_ICA_ID),
+    ("Chip Root CA Id:", OID_MATTER_ROOT_CA_ID),
+    ("Chip Fabric Id:", OID_MATTER_FABRIC_ID),
+    ("Chip NOC CAT Id:", OID_MATTER_NOC_CAT_ID),
+];
+
+// The operational node IDs, the others are reserved for groups, CASE Authenticated Tags, etc.
+const MIN_OPERATIONAL_NODE_ID: u64 = 0x0000_0000_0000_0001;
+const MAX_OPERATIONAL_NODE_ID: u64 = 0xFFFF_FFEF_FFFF_FFFF;
+
+const MAX_CATS: usize = 3;
+
 impl DistNames {
     fn encode(&self, tag: &str, w: &mut dyn CertConsumer) -> Result<(), Error> {
-        const OID_MATTER_NODE_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x01];
-        const OID_MATTER_FW_SIGN_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x02];
-        const OID_MATTER_ICA_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x03];
-        const OID_MATTER_ROOT_CA_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x04];
-        const OID_MATTER_FABRIC_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x05];
-        const OID_MATTER_NOC_CAT_ID: [u8; 10] =
-            [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x01, 0x06];
-
-        let dn_encoding = [
-            ("Chip Node Id:", &OID_MATTER_NODE_ID),
-            ("Chip Firmware Signing Id:", &OID_MATTER_FW_SIGN_ID),
-            ("Chip ICA Id:", &OID_MATTER_ICA_ID),
-            ("Chip Root CA Id:", &OID_MATTER_ROOT_CA_ID),
-            ("Chip Fabric Id:", &OID_MATTER_FABRIC_ID),
-        ];
-
         w.start_seq(tag)?;
         for (id, value) in &self.dn {
             if let Ok(tag) = num::FromPrimitive::from_u8(*id).ok_or(Error::InvalidData) {
@@ -335,15 +437,15 @@ impl DistNames {
                     DnTags::NocCat => {
                         w.start_set("")?;
                         w.start_seq("")?;
-                        w.oid("Chip NOC CAT Id:", &OID_MATTER_NOC_CAT_ID)?;
+                        w.oid(DN_ENCODING[5].0, &OID_MATTER_NOC_CAT_ID)?;
                         w.utf8str("", format!("{:08X}", value).as_str())?;
                         w.end_seq()?;
                         w.end_set()?;
                     }
                     _ => {
                         let index: usize = (*id as usize) - (DnTags::NodeId as usize);
-                        let this = &dn_encoding[index];
-                        encode_u64_dn(*value, this.0, this.1, w)?;
+                        let this = &DN_ENCODING[index];
+                        encode_u64_dn(*value, this.0, &this.1, w)?;
                     }
                 }
             } else {
@@ -353,6 +455,108 @@ impl DistNames {
         w.end_seq()?;
         Ok(())
     }
+
+    fn decode(r: &mut ASN1Reader) -> Result<Self, Error> {
+        let mut seq = r.seq()?;
+        let mut d = Self {
+            dn: Vec::with_capacity(MAX_DN_ENTRIES),
+        };
+        while !seq.is_empty() {
+            // Each RDN has a single attribute
+            let mut set = seq.set()?;
+            let mut attr = set.seq()?;
+            set.finish()?;
+            let oid = attr.oid()?;
+            let value = attr.utf8str()?;
+            attr.finish()?;
+
+            let index = DN_ENCODING
+                .iter()
+                .position(|(_, o)| o == oid)
+                .ok_or_else(|| {
+                    error!("Non Matter DNs are not yet supported {:x?}", oid);
+                    Error::Invalid
+                })?;
+            let id = DnTags::NodeId as u8 + index as u8;
+            let digits = if id == DnTags::NocCat as u8 { 8 } else { 16 };
+            let value = match u64::from_str_radix(value, 16) {
+                Ok(v) if value.len() == digits => v,
+                _ => {
+                    error!(
+                        "Invalid value {} for the DN {}",
+                        value, DN_ENCODING[index].0
+                    );
+                    return Err(Error::Invalid);
+                }
+            };
+            d.dn.push((id, value));
+        }
+        Ok(d)
+    }
+
+    fn count(&self, match_id: DnTags) -> usize {
+        self.dn
+            .iter()
+            .filter(|(id, _)| *id == match_id as u8)
+            .count()
+    }
+
+    /// Check the constraints of the Matter specification on the subject of a certificate
+    fn validate_subject(&self) -> Result<(), Error> {
+        let ids =
+            self.count(DnTags::NodeId) + self.count(DnTags::IcaId) + self.count(DnTags::RootCaId);
+        if ids != 1 {
+            error!(
+                "The subject has {} node, ICA or root CA IDs, instead of 1",
+                ids
+            );
+            return Err(Error::Invalid);
+        }
+        if self.count(DnTags::FabricId) > 1 {
+            error!("The subject has more than one fabric ID");
+            return Err(Error::Invalid);
+        }
+        if self.u64(DnTags::FabricId) == Some(0) {
+            error!("The fabric ID 0 is invalid");
+            return Err(Error::Invalid);
+        }
+        if let Some(node_id) = self.u64(DnTags::NodeId) {
+            if !(MIN_OPERATIONAL_NODE_ID..=MAX_OPERATIONAL_NODE_ID).contains(&node_id) {
+                error!("{:#x} isn't an operational node ID", node_id);
+                return Err(Error::Invalid);
+            }
+            if self.u64(DnTags::FabricId).is_none() {
+                error!("The subject of a NOC has a fabric ID");
+                return Err(Error::Invalid);
+            }
+        }
+
+        let cats: Vec<u64> = self
+            .dn
+            .iter()
+            .filter(|(id, _)| *id == DnTags::NocCat as u8)
+            .map(|(_, cat)| *cat)
+            .collect();
+        if !cats.is_empty() && self.u64(DnTags::NodeId).is_none() {
+            error!("Only a NOC has CASE Authenticated Tags");
+            return Err(Error::Invalid);
+        }
+        if cats.len() > MAX_CATS {
+            error!("A NOC has at most {} CASE Authenticated Tags", MAX_CATS);
+            return Err(Error::Invalid);
+        }
+        for (i, cat) in cats.iter().enumerate() {
+            if cat & 0xffff == 0 {
+                error!("The CASE Authenticated Tag {:#x} has no version", cat);
+                return Err(Error::Invalid);
+            }
+            if cats[..i].iter().any(|c| c >> 16 == cat >> 16) {
+                error!("The CASE Authenticated Tag {:#x} is repeated", cat);
+                return Err(Error::Invalid);
+            }
+        }
+        Ok(())
+    }
 }
 
 fn encode_u64_dn(
@@ -389,8 +593,143 @@ pub struct Cert {
 // rules in terms of sequence may get complicated. Need to look into this
 impl Cert {
     pub fn new(cert_bin: &[u8]) -> Result<Self, Error> {
-        let root = tlv::get_root_node(cert_bin)?;
-        Cert::from_tlv(&root)
+        let root = tlv::TLVValidator::new_with(tlv::TLV_MAX_DEPTH, true).root(cert_bin)?;
+        let cert = Cert::from_tlv(&root)?;
+        // The signature is r and s, of a fixed size
+        if cert.signature.len() != EC_SIGNATURE_LEN_BYTES {
+            error!("Invalid signature length {}", cert.signature.len());
+            return Err(Error::Invalid);
+        }
+        Ok(cert)
+    }
+
+    /// Convert an X.509 certificate in DER to a Matter certificate
+    ///
+    /// Besides the constraints of the Matter specification on its subject and extensions, the
+    /// certificate must be one that the Matter form represents: [Cert::as_x509] has to encode it
+    /// back byte for byte, as its signature is over that encoding.
+    pub fn from_x509(der: &[u8]) -> Result<Self, Error> {
+        let mut r = ASN1Reader::new(der);
+        let mut x509 = r.seq()?;
+        r.finish()?;
+
+        let mut tbs = x509.seq()?;
+        let tbs_der = x509.read_slice();
+
+        let mut version = tbs.start_ctx(0)?;
+        if version.integer()? != [2] {
+            error!("Only X.509 v3 certificates are supported");
+            return Err(Error::Invalid);
+        }
+        version.finish()?;
+
+        let serial_no = tbs.integer()?.to_vec();
+        if serial_no.len() > MAX_SERIAL_NUM_LEN {
+            error!(
+                "The serial number has more than {} bytes",
+                MAX_SERIAL_NUM_LEN
+            );
+            return Err(Error::Invalid);
+        }
+        let sign_algo = decode_sign_algo(&mut tbs)?;
+        let issuer = DistNames::decode(&mut tbs)?;
+
+        let mut validity = tbs.seq()?;
+        let not_before = validity.time()?;
+        let not_after = validity.expiry()?;
+        validity.finish()?;
+
+        let subject = DistNames::decode(&mut tbs)?;
+
+        let mut pubkey_info = tbs.seq()?;
+        let mut algo = pubkey_info.seq()?;
+        if algo.oid()? != OID_PUB_KEY_ECPUBKEY || algo.oid()? != OID_EC_TYPE_PRIME256V1 {
+            error!("Only EC public keys on the P-256 curve are supported");
+            return Err(Error::Invalid);
+        }
+        algo.finish()?;
+        let pubkey = match pubkey_info.bitstr()? {
+            (pubkey, 0) if pubkey.len() == EC_POINT_LEN_BYTES && pubkey[0] == 0x04 => pubkey,
+            _ => {
+                error!("Only uncompressed public keys are supported");
+                return Err(Error::Invalid);
+            }
+        };
+        pubkey_info.finish()?;
+
+        let extensions = Extensions::decode(&mut tbs)?;
+        tbs.finish()?;
+
+        if decode_sign_algo(&mut x509)? != sign_algo {
+            error!("The signature algorithms of the certificate differ");
+            return Err(Error::Invalid);
+        }
+        let signature = match x509.bitstr()? {
+            (signature, 0) => decode_signature(signature)?,
+            _ => return Err(Error::InvalidData),
+        };
+        x509.finish()?;
+
+        let cert = Self {
+            serial_no,
+            sign_algo,
+            issuer,
+            not_before,
+            not_after,
+            subject,
+            pubkey_algo: PubKeyAlgoValue::EcPubKey as u8,
+            ec_curve_id: EcCurveIdValue::Prime256V1 as u8,
+            pubkey: pubkey.to_vec(),
+            extensions,
+            signature,
+        };
+        cert.validate()?;
+
+        let mut asn1 = [0u8; MAX_ASN1_CERT_SIZE];
+        let len = cert.as_asn1(&mut asn1)?;
+        if &asn1[..len] != tbs_der {
+            error!("The certificate isn't encoded as a Matter certificate would be");
+            return Err(Error::Invalid);
+        }
+        Ok(cert)
+    }
+
+    /// Check the constraints of the Matter specification on the certificate
+    fn validate(&self) -> Result<(), Error> {
+        self.subject.validate_subject()?;
+        if self.issuer.count(DnTags::RootCaId) + self.issuer.count(DnTags::IcaId) != 1 {
+            error!("The issuer has no root CA or ICA ID");
+            return Err(Error::Invalid);
+        }
+
+        let e = &self.extensions;
+        let (basic_const, key_usage) = match (&e.basic_const, e.key_usage) {
+            (Some(basic_const), Some(key_usage)) => (basic_const, key_usage),
+            _ => {
+                error!("The basic constraints and the key usage extensions are required");
+                return Err(Error::Invalid);
+            }
+        };
+        for key_id in [&e.subj_key_id, &e.auth_key_id] {
+            if key_id.as_ref().map(|k| k.len()) != Some(KEY_ID_LEN) {
+                error!(
+                    "The subject and authority key IDs of {} bytes are required",
+                    KEY_ID_LEN
+                );
+                return Err(Error::Invalid);
+            }
+        }
+
+        if self.subject.u64(DnTags::NodeId).is_some() {
+            if basic_const.is_ca || key_usage & KEY_USAGE_DIGITAL_SIGN == 0 {
+                error!("A NOC isn't a CA, and has the digital signature key usage");
+                return Err(Error::Invalid);
+            }
+        } else if !basic_const.is_ca || key_usage & KEY_USAGE_KEY_CERT_SIGN == 0 {
+            error!("A CA certificate is a CA, and has the certificate signing key usage");
+            return Err(Error::Invalid);
+        }
+        Ok(())
     }
 
     pub fn get_node_id(&self) -> Result<u64, Error> {
@@ -439,6 +778,29 @@ impl Cert {
         Ok(w.as_slice().len())
     }
 
+    /// The X.509 DER form of the certificate, with its signature
+    pub fn as_x509(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut w = ASN1Writer::new(buf);
+        w.start_seq("")?;
+        self.encode(&mut w)?;
+
+        w.start_seq("")?;
+        let oid = match get_sign_algo(self.sign_algo).ok_or(Error::Invalid)? {
+            SignAlgoValue::ECDSAWithSHA256 => OID_ECDSA_WITH_SHA256,
+        };
+        w.oid("", &oid)?;
+        w.end_seq()?;
+
+        // The signature is r and s, that X.509 encodes as a sequence of two integers
+        let mut sig_buf = [0u8; MAX_ASN1_SIGNATURE_SIZE];
+        let mut sig = ASN1Writer::new(&mut sig_buf);
+        encode_signature(self.get_signature(), &mut sig)?;
+        w.bitstr("", false, sig.as_slice())?;
+
+        w.end_seq()?;
+        Ok(w.as_slice().len())
+    }
+
     pub fn verify_chain_start(&self) -> CertVerifier {
         CertVerifier::new(self)
     }
@@ -463,7 +825,11 @@ impl Cert {
 
         w.start_seq("Validity:")?;
         w.utctime("Not Before:", self.not_before)?;
-        w.utctime("Not After:", self.not_after)?;
+        if self.not_after == 0 {
+            w.no_expiry("Not After:")?;
+        } else {
+            w.utctime("Not After:", self.not_after)?;
+        }
         w.end_seq()?;
 
         self.subject.encode("Subject:", w)?;
@@ -491,6 +857,63 @@ impl Cert {
     }
 }
 
+fn decode_sign_algo(r: &mut ASN1Reader) -> Result<u8, Error> {
+    let mut seq = r.seq()?;
+    let oid = seq.oid()?;
+    seq.finish()?;
+    if oid == OID_ECDSA_WITH_SHA256 {
+        Ok(SignAlgoValue::ECDSAWithSHA256 as u8)
+    } else {
+        error!("Only ECDSA with SHA256 signatures are supported {:x?}", oid);
+        Err(Error::Invalid)
+    }
+}
+
+fn decode_signature(der: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut r = ASN1Reader::new(der);
+    let mut seq = r.seq()?;
+    r.finish()?;
+    let mut signature = vec![0u8; EC_SIGNATURE_LEN_BYTES];
+    for int in signature.chunks_mut(BIGNUM_LEN_BYTES) {
+        let mut value = seq.integer()?;
+        if value.first() == Some(&0) {
+            value = &value[1..];
+        }
+        if value.len() > BIGNUM_LEN_BYTES {
+            error!("Invalid signature");
+            return Err(Error::InvalidSignature);
+        }
+        int[(BIGNUM_LEN_BYTES - value.len())..].copy_from_slice(value);
+    }
+    seq.finish()?;
+    Ok(signature)
+}
+
+fn encode_signature(signature: &[u8], w: &mut dyn CertConsumer) -> Result<(), Error> {
+    if signature.len() != EC_SIGNATURE_LEN_BYTES {
+        error!("Invalid signature length {}", signature.len());
+        return Err(Error::InvalidSignature);
+    }
+    w.start_seq("")?;
+    for int in signature.chunks(BIGNUM_LEN_BYTES) {
+        // The integers are minimal and positive: without leading zeroes, unless the top bit is set
+        let start = int
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(BIGNUM_LEN_BYTES - 1);
+        let mut buf = [0u8; BIGNUM_LEN_BYTES + 1];
+        let len = BIGNUM_LEN_BYTES - start;
+        let int = if int[start] & 0x80 != 0 {
+            buf[1..=len].copy_from_slice(&int[start..]);
+            &buf[..=len]
+        } else {
+            &int[start..]
+        };
+        w.integer("", int)?;
+    }
+    w.end_seq()
+}
+
 impl fmt::Display for Cert {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut printer = CertPrinter::new(f);
@@ -556,17 +979,30 @@ pub trait CertConsumer {
     fn end_ctx(&mut self) -> Result<(), Error>;
     fn oid(&mut self, tag: &str, oid: &[u8]) -> Result<(), Error>;
     fn utctime(&mut self, tag: &str, epoch: u32) -> Result<(), Error>;
+    // The not after of a certificate without a well-defined expiration
+    fn no_expiry(&mut self, tag: &str) -> Result<(), Error>;
 }
 
+// The X.509 time of no well-defined expiration, a Matter not after of 0
+const X509_NO_EXPIRY: &str = "99991231235959Z";
+
 const MAX_DEPTH: usize = 10;
 const MAX_ASN1_CERT_SIZE: usize = 800;
+const MAX_ASN1_SIGNATURE_SIZE: usize = 80;
+const MAX_SERIAL_NUM_LEN: usize = 20;
+const KEY_ID_LEN: usize = 20;
 
+mod asn1_reader;
 mod asn1_writer;
+mod builder;
 mod printer;
 
+pub use builder::CertBuilder;
+
 #[cfg(test)]
 mod tests {
-    use crate::cert::Cert;
+    use crate::cert::{encode_signature, ASN1Writer, Cert, CertBuilder};
+    use crate::crypto::{CryptoKeyPair, KeyPair, EC_POINT_LEN_BYTES, EC_SIGNATURE_LEN_BYTES};
     use crate::error::Error;
     use crate::tlv::{self, FromTLV, TLVWriter, TagType, ToTLV};
     use crate::utils::writebuf::WriteBuf;
@@ -630,6 +1066,372 @@ mod tests {
         assert_eq!(Err(Error::InvalidSignature), a.add_cert(&icac).map(|_| ()));
     }
 
+    #[test]
+    fn test_invalid_signature_len() {
+        // The signature is the last member, before the end of the certificate
+        let input = test_vectors::RCA1_SUCCESS;
+        let sig_start = input.len() - 1 - EC_SIGNATURE_LEN_BYTES;
+        assert_eq!(input[sig_start - 3..sig_start], [0x30, 0x0b, 0x40]);
+        for len in [0, 55, 63, 65] {
+            let mut cert = input[..sig_start - 1].to_vec();
+            cert.push(len as u8);
+            cert.extend(input[sig_start..].iter().cycle().take(len));
+            cert.push(0x18);
+            assert_eq!(Cert::new(&cert).map(|_| ()), Err(Error::Invalid));
+        }
+
+        // A certificate that wasn't decoded isn't verified either
+        let mut rca = Cert::new(&input).unwrap();
+        rca.signature.truncate(55);
+        assert_eq!(
+            rca.verify_chain_start().finalise(),
+            Err(Error::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_asn1_encode_no_key_usage() {
+        // A key usage of 0 is an empty bit string
+        let mut input = test_vectors::RCA1_SUCCESS;
+        let key_usage = [0x37, 0xa, 0x35, 0x1, 0x29, 0x1, 0x18, 0x24, 0x2];
+        let pos = input
+            .windows(key_usage.len())
+            .position(|w| w == key_usage)
+            .unwrap()
+            + key_usage.len();
+        input[pos] = 0;
+
+        let mut asn1_buf = [0u8; 1000];
+        let c = Cert::new(&input).unwrap();
+        let len = c.as_asn1(&mut asn1_buf).unwrap();
+        let key_usage = [
+            0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x03, 0x03, 0x01, 0x00,
+        ];
+        assert!(asn1_buf[..len]
+            .windows(key_usage.len())
+            .any(|w| w == key_usage));
+    }
+
+    fn pubkey(key: &KeyPair) -> Vec<u8> {
+        let mut pubkey = [0u8; EC_POINT_LEN_BYTES];
+        let len = key.get_public_key(&mut pubkey).unwrap();
+        pubkey[..len].to_vec()
+    }
+
+    #[test]
+    fn test_cert_builder_chain() {
+        let root_key = KeyPair::new().unwrap();
+        let ica_key = KeyPair::new().unwrap();
+        let node_key = KeyPair::new().unwrap();
+
+        let rcac = CertBuilder::rcac(1)
+            .fabric_id(0xFAB)
+            .validity(0, 0x7fffffff)
+            .pubkey(&pubkey(&root_key))
+            .sign(&root_key)
+            .unwrap();
+        let icac = CertBuilder::icac(2)
+            .fabric_id(0xFAB)
+            .issuer(&rcac)
+            .pubkey(&pubkey(&ica_key))
+            .sign(&root_key)
+            .unwrap();
+        let noc = CertBuilder::noc(0x1234, 0xFAB)
+            .cat(0x0001_0001)
+            .serial_no(&[0x12, 0x34])
+            .issuer(&icac)
+            .pubkey(&pubkey(&node_key))
+            .sign(&ica_key)
+            .unwrap();
+
+        // The certificates go through their TLV form unchanged
+        let mut buf = [0u8; 1000];
+        let len = noc.as_tlv(&mut buf).unwrap();
+        let noc = Cert::new(&buf[..len]).unwrap();
+        assert_eq!(noc.get_node_id(), Ok(0x1234));
+        assert_eq!(noc.get_fabric_id(), Ok(0xFAB));
+        assert_eq!(noc.get_pubkey(), pubkey(&node_key).as_slice());
+        assert_eq!(noc.get_subject_key_id().unwrap().len(), 20);
+        let len = rcac.as_tlv(&mut buf).unwrap();
+        let rcac = Cert::new(&buf[..len]).unwrap();
+
+        noc.verify_chain_start()
+            .add_cert(&icac)
+            .unwrap()
+            .add_cert(&rcac)
+            .unwrap()
+            .finalise()
+            .unwrap();
+
+        // The NOC has the extensions of the test vectors' NOC
+        let mut asn1 = [0u8; 1000];
+        let len = noc.as_asn1(&mut asn1).unwrap();
+        let extensions = [
+            0xa3, 0x81, 0x83, 0x30, 0x81, 0x80, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01,
+            0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01,
+            0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07, 0x80, 0x30, 0x20, 0x06, 0x03, 0x55, 0x1d,
+            0x25, 0x01, 0x01, 0xff, 0x04, 0x16, 0x30, 0x14, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05,
+            0x05, 0x07, 0x03, 0x02, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01,
+        ];
+        assert!(asn1[..len]
+            .windows(extensions.len())
+            .any(|w| w == extensions));
+    }
+
+    #[test]
+    fn test_cert_builder_invalid() {
+        let key = KeyPair::new().unwrap();
+        let rcac = CertBuilder::rcac(1)
+            .pubkey(&pubkey(&key))
+            .sign(&key)
+            .unwrap();
+
+        // No public key
+        assert_eq!(
+            CertBuilder::rcac(1).sign(&key).map(|_| ()),
+            Err(Error::Invalid)
+        );
+        // No issuer
+        assert_eq!(
+            CertBuilder::noc(1, 1)
+                .pubkey(&pubkey(&key))
+                .sign(&key)
+                .map(|_| ()),
+            Err(Error::Invalid)
+        );
+
+        let invalid = [
+            CertBuilder::noc(0xFFFF_FFFD_0001_0001, 1),
+            CertBuilder::noc(0, 1),
+            CertBuilder::noc(1, 0),
+            CertBuilder::noc(1, 1).cat(0x0001_0000),
+            CertBuilder::noc(1, 1).cat(0x0001_0001).cat(0x0001_0002),
+            CertBuilder::noc(1, 1)
+                .cat(1)
+                .cat(0x10001)
+                .cat(0x20001)
+                .cat(0x30001),
+            CertBuilder::icac(1).cat(1),
+            CertBuilder::noc(1, 1).validity(10, 5),
+        ];
+        for builder in invalid {
+            let cert = builder.issuer(&rcac).pubkey(&pubkey(&key)).sign(&key);
+            assert_eq!(cert.map(|_| ()), Err(Error::Invalid));
+        }
+    }
+
+    #[test]
+    fn test_cert_builder_no_expiry() {
+        // A not after of 0, the default, is the X.509 time of no well-defined expiration
+        let key = KeyPair::new().unwrap();
+        let rcac = CertBuilder::rcac(1)
+            .pubkey(&pubkey(&key))
+            .sign(&key)
+            .unwrap();
+        let mut validity = vec![0x30, 0x20, 0x17, 0x0d];
+        validity.extend_from_slice(b"000101000000Z");
+        validity.extend_from_slice(&[0x18, 0x0f]);
+        validity.extend_from_slice(b"99991231235959Z");
+        let mut asn1 = [0u8; 1000];
+        let len = rcac.as_asn1(&mut asn1).unwrap();
+        assert!(asn1[..len].windows(validity.len()).any(|w| w == validity));
+        assert!(rcac.to_string().contains("Not After: No expiry"));
+
+        // The signature is over that time
+        rcac.verify_chain_start().finalise().unwrap();
+    }
+
+    #[test]
+    fn test_x509_encode() {
+        let c = Cert::new(&test_vectors::NOC1_SUCCESS).unwrap();
+        let mut tbs = [0u8; 1000];
+        let tbs_len = c.as_asn1(&mut tbs).unwrap();
+        let tbs = &tbs[..tbs_len];
+
+        let mut x509 = [0u8; 1000];
+        let len = c.as_x509(&mut x509).unwrap();
+        let x509 = &x509[..len];
+
+        // A sequence of the certificate without its signature, the signature algorithm, and
+        // the signature, as a sequence of r and s
+        assert_eq!(
+            &x509[..4],
+            &[0x30, 0x82, ((len - 4) >> 8) as u8, (len - 4) as u8]
+        );
+        assert_eq!(&x509[4..(4 + tbs_len)], tbs);
+        let signature = c.get_signature();
+        let mut expected = vec![
+            0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x47,
+            0x00, 0x30, 0x44, 0x02, 0x20,
+        ];
+        expected.extend_from_slice(&signature[..32]);
+        expected.extend_from_slice(&[0x02, 0x20]);
+        expected.extend_from_slice(&signature[32..]);
+        assert_eq!(&x509[(4 + tbs_len)..], expected.as_slice());
+    }
+
+    #[test]
+    fn test_x509_encode_signature() {
+        // The integers are minimal: r has a 0 before its top bit, s has no leading zeroes
+        let mut signature = [0x11u8; 64];
+        signature[0] = 0x80;
+        signature[32] = 0;
+        signature[33] = 0;
+        signature[34] = 0x7f;
+        let mut buf = [0u8; 100];
+        let mut w = ASN1Writer::new(&mut buf);
+        encode_signature(&signature, &mut w).unwrap();
+        let mut expected = vec![0x30, 0x43, 0x02, 0x21, 0x00];
+        expected.extend_from_slice(&signature[..32]);
+        expected.extend_from_slice(&[0x02, 0x1e]);
+        expected.extend_from_slice(&signature[34..]);
+        assert_eq!(w.as_slice(), expected.as_slice());
+
+        assert_eq!(
+            encode_signature(&signature[..63], &mut w),
+            Err(Error::InvalidSignature)
+        );
+    }
+
+    fn x509(c: &Cert) -> Vec<u8> {
+        let mut buf = [0u8; 1000];
+        let len = c.as_x509(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    fn replace(buf: &mut Vec<u8>, from: &[u8], to: &[u8]) {
+        let pos = buf.windows(from.len()).position(|w| w == from).unwrap();
+        buf.splice(pos..(pos + from.len()), to.iter().copied());
+    }
+
+    #[test]
+    fn test_x509_decode_round_trip() {
+        let test_input: [&[u8]; 5] = [
+            &test_vectors::NOC1_SUCCESS,
+            &test_vectors::ICAC1_SUCCESS,
+            &test_vectors::RCA1_SUCCESS,
+            &test_vectors::ASN1_INPUT1,
+            &test_vectors::ASN1_INPUT2,
+        ];
+
+        for input in test_input.iter() {
+            let der = x509(&Cert::new(input).unwrap());
+            let cert = Cert::from_x509(&der).unwrap();
+            let mut buf = [0u8; 1000];
+            let len = cert.as_tlv(&mut buf).unwrap();
+            assert_eq!(*input, &buf[..len]);
+            assert_eq!(x509(&cert), der);
+        }
+
+        // The converted certificates are still signed by their issuers
+        let noc = Cert::from_x509(&x509(&Cert::new(&test_vectors::NOC1_SUCCESS).unwrap()));
+        let icac = Cert::from_x509(&x509(&Cert::new(&test_vectors::ICAC1_SUCCESS).unwrap()));
+        let rca = Cert::from_x509(&x509(&Cert::new(&test_vectors::RCA1_SUCCESS).unwrap()));
+        noc.unwrap()
+            .verify_chain_start()
+            .add_cert(&icac.unwrap())
+            .unwrap()
+            .add_cert(&rca.unwrap())
+            .unwrap()
+            .finalise()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_x509_decode_extensions() {
+        // A path length, and a validity after 2049
+        let key = KeyPair::new().unwrap();
+        let mut c = CertBuilder::rcac(1)
+            .validity(0, 0x7fffffff)
+            .pubkey(&pubkey(&key))
+            .sign(&key)
+            .unwrap();
+        c.extensions.basic_const.as_mut().unwrap().path = Some(0x80);
+        let der = x509(&c);
+        let decoded = Cert::from_x509(&der).unwrap();
+        assert_eq!(decoded.extensions.basic_const.unwrap().path, Some(0x80));
+        assert_eq!(decoded.not_after, 0x7fffffff);
+        assert_eq!(decoded.extensions.key_usage, Some(0x60));
+    }
+
+    #[test]
+    fn test_x509_no_expiry() {
+        let key = KeyPair::new().unwrap();
+        let rcac = CertBuilder::rcac(1)
+            .validity(100, 0)
+            .pubkey(&pubkey(&key))
+            .sign(&key)
+            .unwrap();
+        let der = x509(&rcac);
+        let decoded = Cert::from_x509(&der).unwrap();
+        assert_eq!((decoded.not_before, decoded.not_after), (100, 0));
+        assert_eq!(x509(&decoded), der);
+        decoded.verify_chain_start().finalise().unwrap();
+    }
+
+    #[test]
+    fn test_x509_decode_invalid() {
+        let der = x509(&Cert::new(&test_vectors::NOC1_SUCCESS).unwrap());
+
+        // Trailing data
+        let mut input = der.clone();
+        input.push(0);
+        assert_eq!(Cert::from_x509(&input).map(|_| ()), Err(Error::InvalidData));
+
+        // Truncated
+        assert_eq!(
+            Cert::from_x509(&der[..(der.len() - 1)]).map(|_| ()),
+            Err(Error::TruncatedPacket)
+        );
+
+        // A DN that isn't a Matter one
+        let mut input = der.clone();
+        replace(
+            &mut input,
+            &[0x82, 0xa2, 0x7c, 0x01, 0x01],
+            &[0x82, 0xa2, 0x7c, 0x01, 0x07],
+        );
+        assert_eq!(Cert::from_x509(&input).map(|_| ()), Err(Error::Invalid));
+
+        // A DN in lowercase doesn't encode back to the same certificate
+        let mut input = der.clone();
+        replace(&mut input, b"BC5C02", b"bc5c02");
+        assert_eq!(Cert::from_x509(&input).map(|_| ()), Err(Error::Invalid));
+
+        // Another signature algorithm (ECDSA with SHA384)
+        let mut input = der.clone();
+        let sign_algo = [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x47];
+        replace(
+            &mut input,
+            &sign_algo,
+            &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03, 0x03, 0x47],
+        );
+        assert_eq!(Cert::from_x509(&input).map(|_| ()), Err(Error::Invalid));
+
+        // A NOC that is a CA, and without a subject key ID
+        let mut c = Cert::new(&test_vectors::NOC1_SUCCESS).unwrap();
+        c.extensions.basic_const.as_mut().unwrap().is_ca = true;
+        assert_eq!(Cert::from_x509(&x509(&c)).map(|_| ()), Err(Error::Invalid));
+        let mut c = Cert::new(&test_vectors::NOC1_SUCCESS).unwrap();
+        c.extensions.subj_key_id = None;
+        assert_eq!(Cert::from_x509(&x509(&c)).map(|_| ()), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn test_asn1_encode_generalized_time() {
+        // After 2049, the validity is a GeneralizedTime
+        let key = KeyPair::new().unwrap();
+        let c = CertBuilder::rcac(1)
+            .validity(0, 0x7fffffff)
+            .pubkey(&pubkey(&key))
+            .sign(&key)
+            .unwrap();
+        let mut asn1 = [0u8; 1000];
+        let len = c.as_asn1(&mut asn1).unwrap();
+        let validity = b"\x30\x20\x17\x0d000101000000Z\x18\x0f20680119031407Z";
+        assert!(asn1[..len].windows(validity.len()).any(|w| w == validity));
+    }
+
     #[test]
     fn test_tlv_conversions() {
         let test_input: [&[u8]; 3] = [
@@ -112,4 +112,8 @@ impl<'a, 'b> CertConsumer for CertPrinter<'a, 'b> {
         );
         Ok(())
     }
+    fn no_expiry(&mut self, tag: &str) -> Result<(), Error> {
+        let _ = writeln!(self.f, "{} {} No expiry", SPACE[self.level], tag);
+        Ok(())
+    }
 }
@@ -0,0 +1,148 @@
+use smol::{future, Timer};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// Completes once a [Clock] has reached a given time
+pub type Sleep<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// The source of time for the timeouts of the stack
+pub trait Clock: Send + Sync {
+    /// The time elapsed since an arbitrary, fixed, instant
+    ///
+    /// This must never go backwards.
+    fn now(&self) -> Duration;
+
+    /// Wait until [now](Clock::now) has reached `at`
+    fn sleep_until(&self, at: Duration) -> Sleep<'_>;
+}
+
+/// The monotonic clock of the system
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&self, at: Duration) -> Sleep<'_> {
+        let deadline = self.start + at;
+        Box::pin(async move {
+            Timer::at(deadline).await;
+        })
+    }
+}
+
+#[derive(Default)]
+struct ManualInner {
+    now: Duration,
+    // The sleepers to wake up once the clock moves
+    wakers: Vec<Waker>,
+}
+
+/// A clock that only moves when it is told to, for testing the timeouts without sleeping
+#[derive(Default)]
+pub struct ManualClock {
+    inner: Mutex<ManualInner>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward by the given duration
+    ///
+    /// This wakes up whatever is sleeping on the clock, so that it notices.
+    pub fn advance(&self, by: Duration) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.now += by;
+            inner.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.inner.lock().map(|inner| inner.now).unwrap_or_default()
+    }
+
+    fn sleep_until(&self, at: Duration) -> Sleep<'_> {
+        Box::pin(future::poll_fn(move |cx| {
+            let mut inner = match self.inner.lock() {
+                Ok(inner) => inner,
+                // Nobody can move the clock anymore
+                Err(_) => return Poll::Pending,
+            };
+            if inner.now >= at {
+                return Poll::Ready(());
+            }
+            if !inner.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                inner.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ManualClock, SystemClock};
+    use smol::future;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_clocks() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.advance(Duration::from_millis(1500));
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(clock.now(), Duration::from_secs(2));
+
+        let clock = SystemClock::new();
+        let before = clock.now();
+        assert!(clock.now() >= before);
+    }
+
+    #[test]
+    fn test_sleep() {
+        let clock = ManualClock::new();
+        let at = Duration::from_secs(10);
+        let start = Instant::now();
+        let ((), ()) = smol::block_on(future::zip(clock.sleep_until(at), async {
+            // The sleeper only wakes up once the clock reaches the time
+            clock.advance(Duration::from_secs(5));
+            future::yield_now().await;
+            clock.advance(Duration::from_secs(5));
+        }));
+        assert_eq!(clock.now(), at);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // A time that has already passed doesn't wait
+        smol::block_on(clock.sleep_until(Duration::from_secs(1)));
+
+        let clock = SystemClock::new();
+        let at = Duration::from_millis(20);
+        smol::block_on(clock.sleep_until(at));
+        assert!(clock.now() >= at);
+    }
+}
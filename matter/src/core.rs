@@ -1,17 +1,40 @@
 use crate::{
     acl::AclMgr,
+    clock::{Clock, SystemClock},
     data_model::{
-        cluster_basic_information::BasicInfoConfig, core::DataModel,
+        cluster_basic_information::BasicInfoConfig,
+        core::DataModel,
+        device_types::{device_type_add_root_node, validate_node},
         sdm::dev_att::DevAttDataFetcher,
     },
     error::*,
     fabric::FabricMgr,
     interaction_model::InteractionModel,
-    mdns::Mdns,
-    secure_channel::core::SecureChannel,
-    transport,
+    limits::Limits,
+    mdns::{Mdns, MdnsBackend},
+    secure_channel::{
+        core::SecureChannel,
+        pake::{PBKDF_MAX_ITERATIONS, PBKDF_MIN_ITERATIONS},
+    },
+    sys::{Psm, PSM_DIR, SPAKE2_ITERATION_COUNT},
+    transport::{
+        self,
+        network::NetworkInterface,
+        proto_demux::HandleProto,
+        queue::{Msg, WorkQ},
+        tcp::TcpListener,
+        udp::{UdpListener, MATTER_PORT},
+    },
+};
+use log::error;
+use smol::future;
+use std::{
+    future::Future,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use std::sync::Arc;
 
 #[derive(Default)]
 /// Device Commissioning Data
@@ -30,6 +53,7 @@ pub struct Matter {
     transport_mgr: transport::mgr::Mgr,
     data_model: DataModel,
     fabric_mgr: Arc<FabricMgr>,
+    acl_mgr: Arc<AclMgr>,
 }
 
 impl Matter {
@@ -44,28 +68,22 @@ impl Matter {
         dev_att: Box<dyn DevAttDataFetcher>,
         dev_comm: CommissioningData,
     ) -> Result<Box<Matter>, Error> {
-        let mdns = Mdns::get()?;
-        mdns.set_values(dev_det.vid, dev_det.pid, dev_comm.discriminator);
-
-        let fabric_mgr = Arc::new(FabricMgr::new()?);
-        let acl_mgr = Arc::new(AclMgr::new()?);
-        let open_comm_window = fabric_mgr.is_empty();
-        let data_model = DataModel::new(dev_det, dev_att, fabric_mgr.clone(), acl_mgr)?;
-        let mut matter = Box::new(Matter {
-            transport_mgr: transport::mgr::Mgr::new()?,
-            data_model,
-            fabric_mgr,
-        });
-        let interaction_model =
-            Box::new(InteractionModel::new(Box::new(matter.data_model.clone())));
-        matter.transport_mgr.register_protocol(interaction_model)?;
-        let mut secure_channel = Box::new(SecureChannel::new(matter.fabric_mgr.clone()));
-        if open_comm_window {
-            secure_channel.open_comm_window(&dev_comm.salt, dev_comm.passwd)?;
-        }
+        Matter::new_with(dev_det, dev_att, dev_comm, Limits::default())
+    }
 
-        matter.transport_mgr.register_protocol(secure_channel)?;
-        Ok(matter)
+    /// Creates a new Matter object, with the given capacities for its resources
+    ///
+    /// See [Matter::new] for the other parameters. Returns [Error::Invalid] if the limits aren't
+    /// usable.
+    pub fn new_with(
+        dev_det: BasicInfoConfig,
+        dev_att: Box<dyn DevAttDataFetcher>,
+        dev_comm: CommissioningData,
+        limits: Limits,
+    ) -> Result<Box<Matter>, Error> {
+        MatterBuilder::new(dev_det, dev_att, dev_comm)
+            .limits(limits)
+            .build()
     }
 
     /// Returns an Arc to [DataModel]
@@ -77,13 +95,311 @@ impl Matter {
         self.data_model.clone()
     }
 
+    /// Returns an Arc to the [FabricMgr]
+    pub fn get_fabric_mgr(&self) -> Arc<FabricMgr> {
+        self.fabric_mgr.clone()
+    }
+
+    /// Returns an Arc to the [AclMgr]
+    pub fn get_acl_mgr(&self) -> Arc<AclMgr> {
+        self.acl_mgr.clone()
+    }
+
+    /// Returns a [MatterHandle], to control the daemon from other threads
+    pub fn handle(&self) -> MatterHandle {
+        MatterHandle {
+            work_q: self.transport_mgr.work_q(),
+        }
+    }
+
     /// Starts the Matter daemon
     ///
-    /// This call does NOT return
+    /// This call only returns once the daemon is stopped with [MatterHandle::shutdown]
     ///
     /// This call starts the Matter daemon that starts communication with other Matter
-    /// devices on the network.
+    /// devices on the network. Any endpoints that don't conform to their device types are
+    /// reported before that.
     pub fn start_daemon(&mut self) -> Result<(), Error> {
-        self.transport_mgr.start()
+        self.run_until(future::pending())
+    }
+
+    /// Runs the Matter daemon until the `stop` future completes
+    ///
+    /// See [Matter::start_daemon]. The daemon can also be stopped earlier with
+    /// [MatterHandle::shutdown].
+    pub fn run_until<F: Future<Output = ()>>(&mut self, stop: F) -> Result<(), Error> {
+        future::block_on(self.run(stop))
+    }
+
+    /// Runs the Matter daemon until the `stop` future completes, without blocking the thread
+    ///
+    /// This is the asynchronous version of [Matter::run_until], it doesn't depend on any
+    /// particular executor. The returned future isn't Send, so it has to run on a thread-local
+    /// executor, or a task that isn't moved between threads.
+    pub async fn run<F: Future<Output = ()>>(&mut self, stop: F) -> Result<(), Error> {
+        {
+            let node = self.data_model.node.read()?;
+            for issue in validate_node(&node) {
+                error!("Device type conformance: {}", issue);
+            }
+        }
+        self.transport_mgr.run(stop).await
+    }
+}
+
+/// A handle to a running [Matter] daemon
+///
+/// The handle can be cloned and sent to other threads. Any commands that are sent before the
+/// daemon is started are handled once it starts.
+#[derive(Clone)]
+pub struct MatterHandle {
+    work_q: WorkQ,
+}
+
+impl MatterHandle {
+    /// Stops the daemon, after the event that it is currently handling
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.work_q.sync_send(Msg::Shutdown)
+    }
+
+    /// The asynchronous version of [MatterHandle::shutdown]
+    pub async fn shutdown_async(&self) -> Result<(), Error> {
+        self.work_q.send(Msg::Shutdown).await
+    }
+
+    /// Runs `callback` in the context of the daemon, once `delay` has expired
+    pub fn schedule<F: FnOnce() + Send + 'static>(
+        &self,
+        delay: Duration,
+        callback: F,
+    ) -> Result<(), Error> {
+        self.work_q.sync_send(Msg::Timer(delay, Box::new(callback)))
+    }
+
+    /// The asynchronous version of [MatterHandle::schedule]
+    pub async fn schedule_async<F: FnOnce() + Send + 'static>(
+        &self,
+        delay: Duration,
+        callback: F,
+    ) -> Result<(), Error> {
+        self.work_q
+            .send(Msg::Timer(delay, Box::new(callback)))
+            .await
+    }
+}
+
+/// Configures and creates a [Matter] object
+///
+/// Anything that isn't configured keeps the same default as [Matter::new]. This allows, for
+/// instance, running several devices on the same host by giving each its own port and storage
+/// location.
+pub struct MatterBuilder {
+    dev_det: BasicInfoConfig,
+    dev_att: Box<dyn DevAttDataFetcher>,
+    dev_comm: CommissioningData,
+    limits: Limits,
+    bind_addr: IpAddr,
+    port: u16,
+    storage_dir: Option<PathBuf>,
+    mdns_backend: Option<Box<dyn MdnsBackend>>,
+    network: Option<Box<dyn NetworkInterface>>,
+    tcp: bool,
+    clock: Arc<dyn Clock>,
+    pbkdf_iterations: u32,
+    root_node_defaults: bool,
+    protocols: Vec<Box<dyn HandleProto>>,
+}
+
+impl MatterBuilder {
+    /// See [Matter::new] for the parameters
+    pub fn new(
+        dev_det: BasicInfoConfig,
+        dev_att: Box<dyn DevAttDataFetcher>,
+        dev_comm: CommissioningData,
+    ) -> Self {
+        Self {
+            dev_det,
+            dev_att,
+            dev_comm,
+            limits: Limits::default(),
+            bind_addr: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            port: MATTER_PORT,
+            storage_dir: None,
+            mdns_backend: None,
+            network: None,
+            tcp: false,
+            clock: Arc::new(SystemClock::new()),
+            pbkdf_iterations: SPAKE2_ITERATION_COUNT,
+            root_node_defaults: true,
+            protocols: Vec::new(),
+        }
+    }
+
+    /// The UDP port to listen on, and to advertise over mDNS
+    ///
+    /// This is also the TCP port, if [TCP](Self::tcp) is enabled
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// The address of the interface to listen on, all interfaces by default
+    pub fn bind_addr(mut self, bind_addr: IpAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// The directory where the fabrics, ACLs and cluster data are stored, [PSM_DIR] by default
+    pub fn storage_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.storage_dir = Some(dir.into());
+        self
+    }
+
+    /// The backend that publishes the mDNS services, instead of the one of the platform
+    pub fn mdns_backend(mut self, backend: Box<dyn MdnsBackend>) -> Self {
+        self.mdns_backend = Some(backend);
+        self
+    }
+
+    /// The network interface to use, instead of listening on UDP
+    ///
+    /// The port is then only used for the mDNS services. This is typically used with a
+    /// [LoopbackNetwork](crate::transport::loopback::LoopbackNetwork) in tests.
+    pub fn network(mut self, network: Box<dyn NetworkInterface>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Also accept Matter messages over TCP, on the same address and port as UDP
+    ///
+    /// TCP support is then advertised over mDNS, so that the peers can use it for the messages
+    /// that are too large for UDP.
+    pub fn tcp(mut self) -> Self {
+        self.tcp = true;
+        self
+    }
+
+    /// The clock that the timeouts of the stack follow, instead of the system's
+    ///
+    /// The daemon still sleeps in real time, so with a
+    /// [ManualClock](crate::clock::ManualClock) the timers only expire when the daemon is woken
+    /// up after the clock has been advanced.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The PBKDF2 iteration count used for commissioning
+    ///
+    /// This has to be between [PBKDF_MIN_ITERATIONS] and [PBKDF_MAX_ITERATIONS]
+    pub fn pbkdf_iterations(mut self, iterations: u32) -> Self {
+        self.pbkdf_iterations = iterations;
+        self
+    }
+
+    /// The capacities of the various resources
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Don't add the root endpoint and its clusters to the Data Model
+    ///
+    /// The application is then responsible for adding the root endpoint, typically with
+    /// [device_type_add_root_node](crate::data_model::device_types::device_type_add_root_node)
+    /// using [Matter::get_fabric_mgr] and [Matter::get_acl_mgr].
+    pub fn skip_root_node_defaults(mut self) -> Self {
+        self.root_node_defaults = false;
+        self
+    }
+
+    /// Handle another protocol, besides the Interaction Model and Secure Channel
+    pub fn protocol(mut self, handler: Box<dyn HandleProto>) -> Self {
+        self.protocols.push(handler);
+        self
+    }
+
+    /// Creates the [Matter] object
+    ///
+    /// Returns [Error::Invalid] if the limits or the PBKDF2 iteration count aren't usable, or if
+    /// two protocol handlers have the same protocol ID.
+    pub fn build(self) -> Result<Box<Matter>, Error> {
+        let limits = self.limits;
+        limits.validate()?;
+        if !(PBKDF_MIN_ITERATIONS..=PBKDF_MAX_ITERATIONS).contains(&self.pbkdf_iterations) {
+            return Err(Error::Invalid);
+        }
+
+        let storage_dir = self.storage_dir.unwrap_or_else(|| PathBuf::from(PSM_DIR));
+        let psm = Arc::new(Mutex::new(Psm::new_at(storage_dir)?));
+
+        let mdns = Arc::new(Mdns::new());
+        mdns.set_values(
+            self.dev_det.vid,
+            self.dev_det.pid,
+            self.dev_comm.discriminator,
+        );
+        mdns.set_port(self.port);
+        mdns.set_tcp(self.tcp);
+        if let Some(backend) = self.mdns_backend {
+            mdns.set_backend(backend);
+        }
+
+        let fabric_mgr = Arc::new(FabricMgr::new(psm.clone(), mdns.clone())?);
+        let acl_mgr = Arc::new(AclMgr::new_with_limit(
+            Some(psm.clone()),
+            limits.acl_entries_per_fabric,
+        )?);
+        let open_comm_window = fabric_mgr.is_empty();
+        let data_model = DataModel::new_empty(acl_mgr.clone(), Some(psm), &limits)?;
+        if self.root_node_defaults {
+            let mut node = data_model.node.write()?;
+            device_type_add_root_node(
+                &mut node,
+                self.dev_det,
+                self.dev_att,
+                fabric_mgr.clone(),
+                acl_mgr.clone(),
+            )?;
+        }
+
+        let network = match self.network {
+            Some(network) => network,
+            None => {
+                let addr = SocketAddr::new(self.bind_addr, self.port);
+                Box::new(UdpListener::new_with(addr)?)
+            }
+        };
+        let mut transport_mgr =
+            transport::mgr::Mgr::new_with_network(&limits, network, self.clock.clone())?;
+        if self.tcp {
+            let addr = SocketAddr::new(self.bind_addr, self.port);
+            transport_mgr.add_network_interface(Box::new(TcpListener::new_with(addr)?))?;
+        }
+        let mut matter = Box::new(Matter {
+            transport_mgr,
+            data_model,
+            fabric_mgr,
+            acl_mgr,
+        });
+        let interaction_model =
+            Box::new(InteractionModel::new(Box::new(matter.data_model.clone())));
+        matter.transport_mgr.register_protocol(interaction_model)?;
+        let mut secure_channel = Box::new(SecureChannel::new_with(
+            matter.fabric_mgr.clone(),
+            mdns,
+            matter.transport_mgr.work_q(),
+            self.pbkdf_iterations,
+            self.clock,
+        ));
+        if open_comm_window {
+            secure_channel.open_comm_window(&self.dev_comm.salt, self.dev_comm.passwd)?;
+        }
+
+        matter.transport_mgr.register_protocol(secure_channel)?;
+        for handler in self.protocols {
+            matter.transport_mgr.register_protocol(handler)?;
+        }
+        Ok(matter)
     }
 }
@@ -154,6 +154,11 @@ impl CryptoKeyPair for KeyPair {
     }
 
     fn verify_msg(&self, msg: &[u8], signature: &[u8]) -> Result<(), Error> {
+        if signature.len() != super::EC_SIGNATURE_LEN_BYTES {
+            error!("Invalid signature length {}", signature.len());
+            return Err(Error::InvalidSignature);
+        }
+
         // mbedtls requires a 'mut' key. Instead of making a change in our Trait,
         // we just clone the key this way
         let tmp_key = self.key.ec_public()?;
@@ -233,48 +238,13 @@ fn convert_asn1_sign_to_r_s(signature: &mut [u8]) -> Result<usize, Error> {
     if signature[0] == 0x30 {
         // Type 0x30 ASN1 Sequence
         // Length: Skip
-        let mut offset: usize = 2;
-
-        // Type 0x2 is Integer (first integer is r)
-        if signature[offset] != 2 {
-            return Err(Error::Invalid);
-        }
-        offset += 1;
+        let offset: usize = 2;
 
-        // Length
-        let len = signature[offset];
-        offset += 1;
-        // XXX Once, I have seen a crash in this conversion, need to dig
-        if len < 32 {
-            error!(
-                "Cannot deal with this: this will crash: the slice is: {:x?}",
-                signature
-            );
-        }
-
-        // Sometimes length is more than 32 with a 0 prefix-padded, skip over that
-        offset += (len - 32) as usize;
-
-        // Extract the 32 bytes of 'r'
+        // The first integer is r, the second is s
         let mut r = [0_u8; super::BIGNUM_LEN_BYTES];
-        r.copy_from_slice(&signature[offset..(offset + 32)]);
-        offset += 32;
-
-        // Type 0x2 is Integer (this integer is s)
-        if signature[offset] != 2 {
-            return Err(Error::Invalid);
-        }
-        offset += 1;
-
-        // Length
-        let len = signature[offset];
-        offset += 1;
-        // Sometimes length is more than 32 with a 0 prefix-padded, skip over that
-        offset += (len - 32) as usize;
-
-        // Extract the 32 bytes of 's'
+        let offset = read_asn1_integer(signature, offset, &mut r)?;
         let mut s = [0_u8; super::BIGNUM_LEN_BYTES];
-        s.copy_from_slice(&signature[offset..(offset + 32)]);
+        read_asn1_integer(signature, offset, &mut s)?;
 
         signature[0..32].copy_from_slice(&r);
         signature[32..64].copy_from_slice(&s);
@@ -285,6 +255,29 @@ fn convert_asn1_sign_to_r_s(signature: &mut [u8]) -> Result<usize, Error> {
     }
 }
 
+// Reads the ASN1 integer at the offset into the bignum, and returns the offset after it
+fn read_asn1_integer(signature: &[u8], offset: usize, out: &mut [u8]) -> Result<usize, Error> {
+    // Type 0x2 is Integer
+    if signature.get(offset) != Some(&2) {
+        return Err(Error::Invalid);
+    }
+    let len = *signature.get(offset + 1).ok_or(Error::Invalid)? as usize;
+    let start = offset + 2;
+    let mut value = signature.get(start..(start + len)).ok_or(Error::Invalid)?;
+
+    // The integer is prefixed with a 0 when its top bit is set, and it is shorter than the
+    // bignum when it starts with zeroes
+    while value.len() > out.len() && value[0] == 0 {
+        value = &value[1..];
+    }
+    if value.len() > out.len() {
+        return Err(Error::Invalid);
+    }
+    let pad = out.len() - value.len();
+    out[pad..].copy_from_slice(value);
+    Ok(start + len)
+}
+
 pub fn pbkdf2_hmac(pass: &[u8], iter: usize, salt: &[u8], key: &mut [u8]) -> Result<(), Error> {
     mbedtls::hash::pbkdf2_hmac(Type::Sha256, pass, salt, iter as u32, key)
         .map_err(|_e| Error::TLSStack)
@@ -169,14 +169,20 @@ impl CryptoKeyPair for KeyPair {
         safemem::write_bytes(signature, 0);
 
         let sig = EcdsaSig::sign(&msg, self.private_key()?)?;
+        // r and s are shorter than 32 bytes when they start with zeroes
         let r = sig.r().to_vec();
-        signature[0..r.len()].copy_from_slice(r.as_slice());
+        signature[(32 - r.len())..32].copy_from_slice(r.as_slice());
         let s = sig.s().to_vec();
-        signature[32..(32 + s.len())].copy_from_slice(s.as_slice());
+        signature[(64 - s.len())..64].copy_from_slice(s.as_slice());
         Ok(64)
     }
 
     fn verify_msg(&self, msg: &[u8], signature: &[u8]) -> Result<(), Error> {
+        if signature.len() != super::EC_SIGNATURE_LEN_BYTES {
+            error!("Invalid signature length {}", signature.len());
+            return Err(Error::InvalidSignature);
+        }
+
         // First get the SHA256 of the message
         let mut h = Hasher::new(MessageDigest::sha256())?;
         h.update(msg)?;
@@ -1,19 +1,58 @@
 use super::objects::*;
-use crate::error::*;
+use crate::{error::*, interaction_model::core::IMStatusCode, tlv::TLVElement};
+use log::error;
+use num_derive::FromPrimitive;
 
 pub const ID: u32 = 0x0028;
+
+const DATA_MODEL_REVISION: u16 = 1;
+const MAX_NODE_LABEL_LEN: usize = 32;
+const LOCATION_LEN: usize = 2;
+// The ISO 3166-1 code for an unknown location
+const DEFAULT_LOCATION: &str = "XX";
+
+#[derive(FromPrimitive)]
 enum Attributes {
+    DataModelRevision = 0,
+    VendorName = 1,
     VendorId = 2,
+    ProductName = 3,
     ProductId = 4,
+    NodeLabel = 5,
+    Location = 6,
     HwVer = 7,
+    HwVerString = 8,
     SwVer = 9,
+    SwVerString = 0x0a,
 }
 
+/// The basic information about the device
+///
+/// The version strings are optional: when left empty, the version numbers are reported as
+/// strings instead.
+#[derive(Default)]
 pub struct BasicInfoConfig {
     pub vid: u16,
     pub pid: u16,
     pub hw_ver: u16,
     pub sw_ver: u32,
+    pub vendor_name: String,
+    pub product_name: String,
+    pub hw_ver_str: String,
+    pub sw_ver_str: String,
+}
+
+fn attr_data_model_rev_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::DataModelRevision as u16,
+        AttrValue::Uint16(DATA_MODEL_REVISION),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
+
+fn attr_string_new(attr: Attributes) -> Result<Attribute, Error> {
+    Attribute::new(attr as u16, AttrValue::Custom, Access::RV, Quality::FIXED)
 }
 
 fn attr_vid_new(vid: u16) -> Result<Attribute, Error> {
@@ -34,6 +73,26 @@ fn attr_pid_new(pid: u16) -> Result<Attribute, Error> {
     )
 }
 
+fn attr_node_label_new() -> Result<Attribute, Error> {
+    Ok(Attribute::new(
+        Attributes::NodeLabel as u16,
+        AttrValue::Custom,
+        Access::RWVM,
+        Quality::NONE,
+    )?
+    .with_constraint(Constraint::MaxStrLen(MAX_NODE_LABEL_LEN)))
+}
+
+fn attr_location_new() -> Result<Attribute, Error> {
+    Ok(Attribute::new(
+        Attributes::Location as u16,
+        AttrValue::Custom,
+        Access::RWVA,
+        Quality::NONE,
+    )?
+    .with_constraint(Constraint::MaxStrLen(LOCATION_LEN)))
+}
+
 fn attr_hw_ver_new(hw_ver: u16) -> Result<Attribute, Error> {
     Attribute::new(
         Attributes::HwVer as u16,
@@ -52,28 +111,86 @@ fn attr_sw_ver_new(sw_ver: u32) -> Result<Attribute, Error> {
     )
 }
 
+#[derive(ClusterType)]
+#[cluster(id = "ID", read = "read_custom", write = "write_custom")]
 pub struct BasicInfoCluster {
+    cfg: BasicInfoConfig,
+    node_label: String,
+    location: String,
     base: Cluster,
 }
 
 impl BasicInfoCluster {
-    pub fn new(cfg: BasicInfoConfig) -> Result<Box<Self>, Error> {
-        let mut cluster = Box::new(BasicInfoCluster {
-            base: Cluster::new(ID)?,
-        });
-        cluster.base.add_attribute(attr_vid_new(cfg.vid)?)?;
-        cluster.base.add_attribute(attr_pid_new(cfg.pid)?)?;
-        cluster.base.add_attribute(attr_hw_ver_new(cfg.hw_ver)?)?;
-        cluster.base.add_attribute(attr_sw_ver_new(cfg.sw_ver)?)?;
-        Ok(cluster)
+    pub fn new(mut cfg: BasicInfoConfig) -> Result<Box<Self>, Error> {
+        if cfg.hw_ver_str.is_empty() {
+            cfg.hw_ver_str = cfg.hw_ver.to_string();
+        }
+        if cfg.sw_ver_str.is_empty() {
+            cfg.sw_ver_str = cfg.sw_ver.to_string();
+        }
+
+        let mut base = Self::new_base()?;
+        base.add_attribute(attr_data_model_rev_new()?)?;
+        base.add_attribute(attr_string_new(Attributes::VendorName)?)?;
+        base.add_attribute(attr_vid_new(cfg.vid)?)?;
+        base.add_attribute(attr_string_new(Attributes::ProductName)?)?;
+        base.add_attribute(attr_pid_new(cfg.pid)?)?;
+        base.add_attribute(attr_node_label_new()?)?;
+        base.add_attribute(attr_location_new()?)?;
+        base.add_attribute(attr_hw_ver_new(cfg.hw_ver)?)?;
+        base.add_attribute(attr_string_new(Attributes::HwVerString)?)?;
+        base.add_attribute(attr_sw_ver_new(cfg.sw_ver)?)?;
+        base.add_attribute(attr_string_new(Attributes::SwVerString)?)?;
+        Ok(Box::new(Self {
+            cfg,
+            node_label: String::new(),
+            location: DEFAULT_LOCATION.to_owned(),
+            base,
+        }))
+    }
+
+    fn get_string(&self, attr_id: u16) -> Option<&str> {
+        let s = match num::FromPrimitive::from_u16(attr_id)? {
+            Attributes::VendorName => &self.cfg.vendor_name,
+            Attributes::ProductName => &self.cfg.product_name,
+            Attributes::NodeLabel => &self.node_label,
+            Attributes::Location => &self.location,
+            Attributes::HwVerString => &self.cfg.hw_ver_str,
+            Attributes::SwVerString => &self.cfg.sw_ver_str,
+            _ => return None,
+        };
+        Some(s)
     }
-}
 
-impl ClusterType for BasicInfoCluster {
-    fn base(&self) -> &Cluster {
-        &self.base
+    fn read_custom(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
+        if let Some(s) = self.get_string(attr.attr_id) {
+            encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                let _ = tw.utf8(tag, s.as_bytes());
+            }))
+        } else {
+            error!("Attribute not supported: this shouldn't happen");
+        }
     }
-    fn base_mut(&mut self) -> &mut Cluster {
-        &mut self.base
+
+    fn write_custom(&mut self, attr: &AttrDetails, data: &TLVElement) -> Result<(), IMStatusCode> {
+        let attr_id = num::FromPrimitive::from_u16(attr.attr_id);
+        match attr_id {
+            Some(Attributes::NodeLabel) | Some(Attributes::Location) => {
+                let value = data.slice().map_err(|_| IMStatusCode::InvalidDataType)?;
+                let value =
+                    std::str::from_utf8(value).map_err(|_| IMStatusCode::ConstraintError)?;
+                if let Some(Attributes::Location) = attr_id {
+                    if value.len() != LOCATION_LEN {
+                        return Err(IMStatusCode::ConstraintError);
+                    }
+                    self.location = value.to_owned();
+                } else {
+                    self.node_label = value.to_owned();
+                }
+                self.base.cluster_changed();
+                Ok(())
+            }
+            _ => self.base.write_attribute_from_tlv(attr.attr_id, data),
+        }
     }
 }
@@ -0,0 +1,136 @@
+use super::objects::*;
+use crate::{error::*, interaction_model::core::IMStatusCode, tlv::TLVElement};
+use log::error;
+use num_derive::FromPrimitive;
+
+pub const ID: u32 = 0x0039;
+
+const MAX_NODE_LABEL_LEN: usize = 32;
+
+#[derive(FromPrimitive)]
+pub enum Attributes {
+    VendorName = 0x01,
+    VendorId = 0x02,
+    ProductName = 0x03,
+    NodeLabel = 0x05,
+    SerialNumber = 0x0F,
+    Reachable = 0x11,
+    UniqueId = 0x12,
+}
+
+/// The information about a bridged device
+///
+/// Only the attributes that have a value here are part of the cluster, with the exception of
+/// Reachable which is mandatory. Reachable can be updated later by writing the attribute through
+/// the cluster's base.
+#[derive(Default)]
+pub struct BridgedDeviceInfo {
+    pub vendor_name: Option<String>,
+    pub vendor_id: Option<u16>,
+    pub product_name: Option<String>,
+    pub node_label: Option<String>,
+    pub serial_number: Option<String>,
+    pub unique_id: Option<String>,
+    pub reachable: bool,
+}
+
+fn attr_string_new(attr: Attributes) -> Result<Attribute, Error> {
+    Attribute::new(attr as u16, AttrValue::Custom, Access::RV, Quality::FIXED)
+}
+
+fn attr_vid_new(vid: u16) -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::VendorId as u16,
+        AttrValue::Uint16(vid),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
+
+fn attr_node_label_new() -> Result<Attribute, Error> {
+    Ok(Attribute::new(
+        Attributes::NodeLabel as u16,
+        AttrValue::Custom,
+        Access::RWVM,
+        Quality::NONE,
+    )?
+    .with_constraint(Constraint::MaxStrLen(MAX_NODE_LABEL_LEN)))
+}
+
+fn attr_reachable_new(reachable: bool) -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::Reachable as u16,
+        AttrValue::Bool(reachable),
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+#[derive(ClusterType)]
+#[cluster(id = "ID", read = "read_custom", write = "write_custom")]
+pub struct BridgedDeviceBasicInfoCluster {
+    info: BridgedDeviceInfo,
+    base: Cluster,
+}
+
+impl BridgedDeviceBasicInfoCluster {
+    pub fn new(info: BridgedDeviceInfo) -> Result<Box<Self>, Error> {
+        let mut base = Self::new_base()?;
+        if info.vendor_name.is_some() {
+            base.add_attribute(attr_string_new(Attributes::VendorName)?)?;
+        }
+        if let Some(vid) = info.vendor_id {
+            base.add_attribute(attr_vid_new(vid)?)?;
+        }
+        if info.product_name.is_some() {
+            base.add_attribute(attr_string_new(Attributes::ProductName)?)?;
+        }
+        if info.node_label.is_some() {
+            base.add_attribute(attr_node_label_new()?)?;
+        }
+        if info.serial_number.is_some() {
+            base.add_attribute(attr_string_new(Attributes::SerialNumber)?)?;
+        }
+        base.add_attribute(attr_reachable_new(info.reachable)?)?;
+        if info.unique_id.is_some() {
+            base.add_attribute(attr_string_new(Attributes::UniqueId)?)?;
+        }
+        Ok(Box::new(Self { info, base }))
+    }
+
+    fn get_string(&self, attr_id: u16) -> Option<&str> {
+        let s = match num::FromPrimitive::from_u16(attr_id)? {
+            Attributes::VendorName => &self.info.vendor_name,
+            Attributes::ProductName => &self.info.product_name,
+            Attributes::NodeLabel => &self.info.node_label,
+            Attributes::SerialNumber => &self.info.serial_number,
+            Attributes::UniqueId => &self.info.unique_id,
+            _ => return None,
+        };
+        s.as_deref()
+    }
+
+    fn read_custom(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
+        if let Some(s) = self.get_string(attr.attr_id) {
+            encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                let _ = tw.utf8(tag, s.as_bytes());
+            }))
+        } else {
+            error!("Attribute not supported: this shouldn't happen");
+        }
+    }
+
+    fn write_custom(&mut self, attr: &AttrDetails, data: &TLVElement) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr.attr_id) {
+            Some(Attributes::NodeLabel) => {
+                let label = data.slice().map_err(|_| IMStatusCode::InvalidDataType)?;
+                let label =
+                    std::str::from_utf8(label).map_err(|_| IMStatusCode::ConstraintError)?;
+                self.info.node_label = Some(label.to_owned());
+                self.base.cluster_changed();
+                Ok(())
+            }
+            _ => self.base.write_attribute_from_tlv(attr.attr_id, data),
+        }
+    }
+}
@@ -5,107 +5,83 @@ use crate::{
     interaction_model::{command::CommandReq, core::IMStatusCode},
 };
 use log::info;
-use num_derive::FromPrimitive;
 
 pub const ID: u32 = 0x0006;
 
+#[derive(ClusterAttributes)]
 pub enum Attributes {
+    #[attribute(
+        default = "AttrValue::Bool(false)",
+        access = "RV",
+        quality = "PERSISTENT"
+    )]
     OnOff = 0x0,
 }
 
-#[derive(FromPrimitive)]
 pub enum Commands {
     Off = 0x0,
     On = 0x01,
     Toggle = 0x02,
 }
 
-fn attr_on_off_new() -> Result<Attribute, Error> {
-    // OnOff, Value: false
-    Attribute::new(
-        Attributes::OnOff as u16,
-        AttrValue::Bool(false),
-        Access::RV,
-        Quality::PERSISTENT,
-    )
-}
-
+#[derive(ClusterType)]
+#[cluster(id = "ID", attributes = "Attributes", commands)]
 pub struct OnOffCluster {
     base: Cluster,
 }
 
 impl OnOffCluster {
     pub fn new() -> Result<Box<Self>, Error> {
-        let mut cluster = Box::new(OnOffCluster {
-            base: Cluster::new(ID)?,
-        });
-        cluster.base.add_attribute(attr_on_off_new()?)?;
-        Ok(cluster)
+        Ok(Box::new(OnOffCluster {
+            base: Self::new_base()?,
+        }))
     }
-}
 
-impl ClusterType for OnOffCluster {
-    fn base(&self) -> &Cluster {
-        &self.base
+    fn set_on_off(&mut self, on: bool) -> Result<(), IMStatusCode> {
+        let value = self
+            .base
+            .read_attribute_raw(Attributes::OnOff as u16)
+            .unwrap();
+        if AttrValue::Bool(on) != *value {
+            self.base
+                .write_attribute_raw(Attributes::OnOff as u16, AttrValue::Bool(on))
+                .map_err(|_| IMStatusCode::Failure)?;
+        }
+        Ok(())
     }
-    fn base_mut(&mut self) -> &mut Cluster {
-        &mut self.base
+}
+
+#[cluster_commands]
+impl OnOffCluster {
+    #[command(id = "Commands::Off")]
+    fn handle_off(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("Off");
+        self.set_on_off(false)?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
     }
 
-    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        let cmd = cmd_req
-            .cmd
-            .path
-            .leaf
-            .map(num::FromPrimitive::from_u32)
-            .ok_or(IMStatusCode::UnsupportedCommand)?
-            .ok_or(IMStatusCode::UnsupportedCommand)?;
-        match cmd {
-            Commands::Off => {
-                cmd_enter!("Off");
-                let value = self
-                    .base
-                    .read_attribute_raw(Attributes::OnOff as u16)
-                    .unwrap();
-                if AttrValue::Bool(true) == *value {
-                    self.base
-                        .write_attribute_raw(Attributes::OnOff as u16, AttrValue::Bool(false))
-                        .map_err(|_| IMStatusCode::Failure)?;
-                }
-                cmd_req.trans.complete();
-                Err(IMStatusCode::Sucess)
-            }
-            Commands::On => {
-                cmd_enter!("On");
-                let value = self
-                    .base
-                    .read_attribute_raw(Attributes::OnOff as u16)
-                    .unwrap();
-                if AttrValue::Bool(false) == *value {
-                    self.base
-                        .write_attribute_raw(Attributes::OnOff as u16, AttrValue::Bool(true))
-                        .map_err(|_| IMStatusCode::Failure)?;
-                }
+    #[command(id = "Commands::On")]
+    fn handle_on(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("On");
+        self.set_on_off(true)?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
 
-                cmd_req.trans.complete();
-                Err(IMStatusCode::Sucess)
-            }
-            Commands::Toggle => {
-                cmd_enter!("Toggle");
-                let value = match self
-                    .base
-                    .read_attribute_raw(Attributes::OnOff as u16)
-                    .unwrap()
-                {
-                    &AttrValue::Bool(v) => v,
-                    _ => false,
-                };
-                self.base
-                    .write_attribute_raw(Attributes::OnOff as u16, AttrValue::Bool(!value))
-                    .map_err(|_| IMStatusCode::Failure)?;
-                cmd_req.trans.complete();
-                Err(IMStatusCode::Sucess)
-            }
-        }
+    #[command(id = "Commands::Toggle")]
+    fn handle_toggle(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("Toggle");
+        let value = match self
+            .base
+            .read_attribute_raw(Attributes::OnOff as u16)
+            .unwrap()
+        {
+            &AttrValue::Bool(v) => v,
+            _ => false,
+        };
+        self.set_on_off(!value)?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
     }
 }
@@ -19,11 +19,14 @@ use crate::{
         },
         InteractionConsumer, Transaction,
     },
+    limits::Limits,
+    sys::Psm,
     tlv::{TLVArray, TLVWriter, TagType, ToTLV},
     transport::session::{Session, SessionMode},
 };
+use async_channel::Receiver;
 use log::{error, info};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Clone)]
 pub struct DataModel {
@@ -32,24 +35,57 @@ pub struct DataModel {
 }
 
 impl DataModel {
-    pub fn new(
+    /// Create a Data Model with the root endpoint
+    ///
+    /// The clusters are persisted in the given store, if any
+    pub fn new_with(
         dev_details: BasicInfoConfig,
         dev_att: Box<dyn DevAttDataFetcher>,
         fabric_mgr: Arc<FabricMgr>,
         acl_mgr: Arc<AclMgr>,
+        psm: Option<Arc<Mutex<Psm>>>,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        let dm = DataModel::new_empty(acl_mgr.clone(), psm, limits)?;
+        {
+            let mut node = dm.node.write()?;
+            device_type_add_root_node(&mut node, dev_details, dev_att, fabric_mgr, acl_mgr)?;
+        }
+        Ok(dm)
+    }
+
+    /// Create a Data Model without any endpoints, not even the root endpoint
+    ///
+    /// The clusters are persisted in the given store, if any
+    pub fn new_empty(
+        acl_mgr: Arc<AclMgr>,
+        psm: Option<Arc<Mutex<Psm>>>,
+        limits: &Limits,
     ) -> Result<Self, Error> {
         let dm = DataModel {
             node: Arc::new(RwLock::new(Node::new()?)),
-            acl_mgr: acl_mgr.clone(),
+            acl_mgr,
         };
         {
             let mut node = dm.node.write()?;
             node.set_changes_cb(Box::new(dm.clone()));
-            device_type_add_root_node(&mut node, dev_details, dev_att, fabric_mgr, acl_mgr)?;
+            node.set_max_endpoints(limits.max_endpoints);
+            node.set_clusters_per_endpoint(limits.clusters_per_endpoint);
+            if let Some(psm) = psm {
+                node.enable_persistence(psm)?;
+            }
         }
         Ok(dm)
     }
 
+    /// Watch the changes to the attributes, as they are written or updated by the clusters
+    ///
+    /// The changes are received on the returned channel, which can be awaited on from any
+    /// executor.
+    pub fn watch(&self) -> Result<Receiver<AttrChange>, Error> {
+        Ok(self.node.read()?.watch())
+    }
+
     pub fn read_attribute_raw(
         &self,
         endpoint: u16,
@@ -1,20 +1,381 @@
+use super::cluster_basic_information;
 use super::cluster_basic_information::BasicInfoCluster;
 use super::cluster_basic_information::BasicInfoConfig;
-use super::cluster_on_off::OnOffCluster;
+use super::cluster_bridged_device_basic_information::BridgedDeviceInfo;
+use super::cluster_bridged_device_basic_information::{self, BridgedDeviceBasicInfoCluster};
+use super::cluster_on_off::{self, OnOffCluster};
 use super::objects::*;
 use super::sdm::dev_att::DevAttDataFetcher;
-use super::sdm::general_commissioning::GenCommCluster;
-use super::sdm::noc::NocCluster;
-use super::sdm::nw_commissioning::NwCommCluster;
-use super::system_model::access_control::AccessControlCluster;
+use super::sdm::general_commissioning::{self, GenCommCluster};
+use super::sdm::noc::{self, NocCluster};
+use super::sdm::nw_commissioning::{self, NwCommCluster};
+use super::system_model::access_control::{self, AccessControlCluster};
+use super::system_model::descriptor;
 use crate::acl::AclMgr;
 use crate::error::*;
 use crate::fabric::FabricMgr;
+use crate::interaction_model::messages::GenericPath;
+use std::fmt;
 use std::sync::Arc;
 use std::sync::RwLockWriteGuard;
 
 type WriteNode<'a> = RwLockWriteGuard<'a, Box<Node>>;
 
+pub const DEV_TYPE_ROOT_NODE: DeviceType = DeviceType {
+    dtype: 0x0016,
+    drev: 1,
+};
+
+pub const DEV_TYPE_ON_OFF_LIGHT: DeviceType = DeviceType {
+    dtype: 0x0100,
+    drev: 2,
+};
+
+pub const DEV_TYPE_AGGREGATOR: DeviceType = DeviceType {
+    dtype: 0x000E,
+    drev: 1,
+};
+
+pub const DEV_TYPE_BRIDGED_NODE: DeviceType = DeviceType {
+    dtype: 0x0013,
+    drev: 1,
+};
+
+/// The requirements that a device type places on one of the server clusters of its endpoint
+pub struct ClusterReq {
+    pub id: u32,
+    /// The mandatory attributes, other than the global ones
+    pub attributes: &'static [u16],
+    /// The mandatory client to server commands
+    pub commands: &'static [u32],
+    /// The feature bits that must be set in the FeatureMap
+    pub features: u32,
+    /// Creates the cluster, for the clusters that don't need any configuration
+    pub new: Option<fn() -> Result<Box<dyn ClusterType>, Error>>,
+}
+
+impl ClusterReq {
+    /// Only require the cluster, with none of its optional elements
+    pub const fn new(id: u32) -> Self {
+        Self {
+            id,
+            attributes: &[],
+            commands: &[],
+            features: 0,
+            new: None,
+        }
+    }
+}
+
+/// The definition of a device type
+pub struct DeviceTypeDef {
+    pub device_type: DeviceType,
+    pub name: &'static str,
+    pub server_clusters: &'static [ClusterReq],
+    pub client_clusters: &'static [u32],
+}
+
+// The Descriptor cluster is added to every endpoint by the Data Model itself
+const DESCRIPTOR_REQ: ClusterReq = ClusterReq {
+    attributes: &[
+        descriptor::Attributes::DeviceTypeList as u16,
+        descriptor::Attributes::ServerList as u16,
+        descriptor::Attributes::ClientList as u16,
+        descriptor::Attributes::PartsList as u16,
+    ],
+    ..ClusterReq::new(descriptor::ID)
+};
+
+fn new_nw_comm() -> Result<Box<dyn ClusterType>, Error> {
+    Ok(NwCommCluster::new()?)
+}
+
+fn new_on_off() -> Result<Box<dyn ClusterType>, Error> {
+    Ok(OnOffCluster::new()?)
+}
+
+/// The device types known to this implementation
+///
+/// Only the clusters that this implementation has are required, so that a device built from it
+/// conforms. Per the specification, a Root Node also has the Group Key Management,
+/// Administrator Commissioning and General Diagnostics clusters, and an On/Off Light the
+/// Identify, Groups and Scenes clusters.
+pub static DEVICE_TYPES: [DeviceTypeDef; 4] = [
+    DeviceTypeDef {
+        device_type: DEV_TYPE_ROOT_NODE,
+        name: "Root Node",
+        server_clusters: &[
+            DESCRIPTOR_REQ,
+            ClusterReq {
+                // DataModelRevision up to SoftwareVersionString
+                attributes: &[
+                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A,
+                ],
+                ..ClusterReq::new(cluster_basic_information::ID)
+            },
+            ClusterReq::new(access_control::ID),
+            ClusterReq {
+                attributes: &[
+                    general_commissioning::Attributes::BreadCrumb as u16,
+                    general_commissioning::Attributes::BasicCommissioningInfo as u16,
+                    general_commissioning::Attributes::RegConfig as u16,
+                    general_commissioning::Attributes::LocationCapability as u16,
+                ],
+                commands: &[
+                    general_commissioning::Commands::ArmFailsafe as u32,
+                    general_commissioning::Commands::SetRegulatoryConfig as u32,
+                    general_commissioning::Commands::CommissioningComplete as u32,
+                ],
+                ..ClusterReq::new(general_commissioning::ID)
+            },
+            ClusterReq {
+                new: Some(new_nw_comm),
+                ..ClusterReq::new(nw_commissioning::ID)
+            },
+            ClusterReq {
+                commands: &[
+                    noc::Commands::AttReq as u32,
+                    noc::Commands::CertChainReq as u32,
+                    noc::Commands::CSRReq as u32,
+                    noc::Commands::AddNOC as u32,
+                    noc::Commands::AddTrustedRootCert as u32,
+                ],
+                ..ClusterReq::new(noc::ID)
+            },
+        ],
+        client_clusters: &[],
+    },
+    DeviceTypeDef {
+        device_type: DEV_TYPE_ON_OFF_LIGHT,
+        name: "On/Off Light",
+        server_clusters: &[
+            DESCRIPTOR_REQ,
+            ClusterReq {
+                attributes: &[cluster_on_off::Attributes::OnOff as u16],
+                commands: &[
+                    cluster_on_off::Commands::Off as u32,
+                    cluster_on_off::Commands::On as u32,
+                    cluster_on_off::Commands::Toggle as u32,
+                ],
+                new: Some(new_on_off),
+                ..ClusterReq::new(cluster_on_off::ID)
+            },
+        ],
+        client_clusters: &[],
+    },
+    DeviceTypeDef {
+        device_type: DEV_TYPE_AGGREGATOR,
+        name: "Aggregator",
+        server_clusters: &[DESCRIPTOR_REQ],
+        client_clusters: &[],
+    },
+    DeviceTypeDef {
+        device_type: DEV_TYPE_BRIDGED_NODE,
+        name: "Bridged Node",
+        server_clusters: &[
+            DESCRIPTOR_REQ,
+            ClusterReq {
+                attributes: &[
+                    cluster_bridged_device_basic_information::Attributes::Reachable as u16,
+                ],
+                ..ClusterReq::new(cluster_bridged_device_basic_information::ID)
+            },
+        ],
+        client_clusters: &[],
+    },
+];
+
+/// Get the definition of one of the built-in device types
+///
+/// Use Node::register_device_type() for the device types that aren't built-in
+pub fn get_device_type_def(dtype: u32) -> Option<&'static DeviceTypeDef> {
+    DEVICE_TYPES.iter().find(|d| d.device_type.dtype == dtype)
+}
+
+/// Add a device type to an endpoint
+///
+/// This adds the device type's client clusters, and those of its mandatory server clusters that
+/// don't need any configuration. The remaining mandatory clusters have to be added by the caller,
+/// validate_node() reports the ones that are still missing.
+pub fn device_type_add(
+    node: &mut WriteNode,
+    endpoint: u16,
+    device_type: DeviceType,
+) -> Result<(), Error> {
+    let def = node
+        .get_device_type_def(device_type.dtype)
+        .ok_or(Error::Invalid)?;
+    let e = node.get_endpoint_mut(endpoint)?;
+    e.add_device_type(device_type)?;
+    for c in def.client_clusters {
+        e.add_client_cluster(*c)?;
+    }
+    for c in def.server_clusters {
+        if let Some(new) = c.new {
+            if node.get_cluster(endpoint, c.id).is_err() {
+                node.add_cluster(endpoint as u32, new()?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A way in which an endpoint doesn't conform to one of its device types
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nonconformance {
+    MissingServerCluster {
+        endpoint: u16,
+        dtype: u32,
+        cluster: u32,
+    },
+    MissingClientCluster {
+        endpoint: u16,
+        dtype: u32,
+        cluster: u32,
+    },
+    MissingAttribute {
+        endpoint: u16,
+        cluster: u32,
+        attr: u16,
+    },
+    MissingCommand {
+        endpoint: u16,
+        cluster: u32,
+        cmd: u32,
+    },
+    MissingFeatures {
+        endpoint: u16,
+        cluster: u32,
+        features: u32,
+    },
+}
+
+impl fmt::Display for Nonconformance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Nonconformance::MissingServerCluster {
+                endpoint,
+                dtype,
+                cluster,
+            } => write!(
+                f,
+                "endpoint {}: device type {:#x} requires server cluster {:#x}",
+                endpoint, dtype, cluster
+            ),
+            Nonconformance::MissingClientCluster {
+                endpoint,
+                dtype,
+                cluster,
+            } => write!(
+                f,
+                "endpoint {}: device type {:#x} requires client cluster {:#x}",
+                endpoint, dtype, cluster
+            ),
+            Nonconformance::MissingAttribute {
+                endpoint,
+                cluster,
+                attr,
+            } => write!(
+                f,
+                "endpoint {}: cluster {:#x} is missing attribute {:#x}",
+                endpoint, cluster, attr
+            ),
+            Nonconformance::MissingCommand {
+                endpoint,
+                cluster,
+                cmd,
+            } => write!(
+                f,
+                "endpoint {}: cluster {:#x} is missing command {:#x}",
+                endpoint, cluster, cmd
+            ),
+            Nonconformance::MissingFeatures {
+                endpoint,
+                cluster,
+                features,
+            } => write!(
+                f,
+                "endpoint {}: cluster {:#x} is missing features {:#x}",
+                endpoint, cluster, features
+            ),
+        }
+    }
+}
+
+fn validate_endpoint(node: &Node, e: &Endpoint, issues: &mut Vec<Nonconformance>) {
+    let endpoint = e.id();
+    // Device types that we don't know of can't be validated
+    let defs = e
+        .device_types()
+        .iter()
+        .filter_map(|d| node.get_device_type_def(d.dtype));
+    for def in defs {
+        let dtype = def.device_type.dtype;
+        for req in def.server_clusters {
+            let cluster = match e.get_cluster(req.id) {
+                Ok(c) => c.base(),
+                Err(_) => {
+                    issues.push(Nonconformance::MissingServerCluster {
+                        endpoint,
+                        dtype,
+                        cluster: req.id,
+                    });
+                    continue;
+                }
+            };
+            for attr in req
+                .attributes
+                .iter()
+                .filter(|a| !cluster.has_attribute(**a))
+            {
+                issues.push(Nonconformance::MissingAttribute {
+                    endpoint,
+                    cluster: req.id,
+                    attr: *attr,
+                });
+            }
+            let accepted = cluster.get_accepted_commands();
+            for cmd in req.commands.iter().filter(|c| !accepted.contains(c)) {
+                issues.push(Nonconformance::MissingCommand {
+                    endpoint,
+                    cluster: req.id,
+                    cmd: *cmd,
+                });
+            }
+            let missing = req.features & !cluster.get_feature_map().unwrap_or(0);
+            if missing != 0 {
+                issues.push(Nonconformance::MissingFeatures {
+                    endpoint,
+                    cluster: req.id,
+                    features: missing,
+                });
+            }
+        }
+        for c in def.client_clusters {
+            if !e.client_clusters().contains(c) {
+                issues.push(Nonconformance::MissingClientCluster {
+                    endpoint,
+                    dtype,
+                    cluster: *c,
+                });
+            }
+        }
+    }
+}
+
+/// Check all the endpoints of the node against the definitions of their device types
+///
+/// Returns the list of everything that is missing, which is empty if the node conforms
+pub fn validate_node(node: &Node) -> Vec<Nonconformance> {
+    let mut issues = Vec::new();
+    let path = GenericPath::new(None, None, None);
+    let _ = node.for_each_endpoint(&path, |_, e| {
+        validate_endpoint(node, e, &mut issues);
+        Ok(())
+    });
+    issues
+}
+
 pub fn device_type_add_root_node(
     node: &mut WriteNode,
     dev_info: BasicInfoConfig,
@@ -39,11 +400,33 @@ pub fn device_type_add_root_node(
         NocCluster::new(dev_att, fabric_mgr, acl_mgr.clone(), failsafe)?,
     )?;
     node.add_cluster(0, AccessControlCluster::new(acl_mgr)?)?;
+    device_type_add(node, 0, DEV_TYPE_ROOT_NODE)?;
     Ok(endpoint)
 }
 
 pub fn device_type_add_on_off_light(node: &mut WriteNode) -> Result<u32, Error> {
     let endpoint = node.add_endpoint()?;
-    node.add_cluster(endpoint, OnOffCluster::new()?)?;
+    device_type_add(node, endpoint as u16, DEV_TYPE_ON_OFF_LIGHT)?;
+    Ok(endpoint)
+}
+
+pub fn device_type_add_aggregator(node: &mut WriteNode) -> Result<u32, Error> {
+    let endpoint = node.add_endpoint()?;
+    device_type_add(node, endpoint as u16, DEV_TYPE_AGGREGATOR)?;
+    Ok(endpoint)
+}
+
+/// Add a bridged device as a part of the aggregator endpoint
+///
+/// The device types and clusters for the device's functionality have to be added to the
+/// returned endpoint
+pub fn device_type_add_bridged_node(
+    node: &mut WriteNode,
+    aggregator: u16,
+    info: BridgedDeviceInfo,
+) -> Result<u32, Error> {
+    let endpoint = node.add_endpoint_with_parent(aggregator)?;
+    device_type_add(node, endpoint as u16, DEV_TYPE_BRIDGED_NODE)?;
+    node.add_cluster(endpoint, BridgedDeviceBasicInfoCluster::new(info)?)?;
     Ok(endpoint)
 }
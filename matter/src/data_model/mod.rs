@@ -3,6 +3,7 @@ pub mod device_types;
 pub mod objects;
 
 pub mod cluster_basic_information;
+pub mod cluster_bridged_device_basic_information;
 pub mod cluster_on_off;
 pub mod cluster_template;
 pub mod sdm;
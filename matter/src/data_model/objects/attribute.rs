@@ -1,8 +1,9 @@
 use super::{GlobalElements, Privilege};
 use crate::{
     error::*,
+    interaction_model::core::IMStatusCode,
     // TODO: This layer shouldn't really depend on the TLV layer, should create an abstraction layer
-    tlv::{TLVElement, TLVWriter, TagType, ToTLV},
+    tlv::{ElementType, TLVElement, TLVWriter, TagType, ToTLV},
 };
 use bitflags::bitflags;
 use log::error;
@@ -61,8 +62,8 @@ bitflags! {
         const NONE = 0x00;
         const SCENE = 0x01;
         const PERSISTENT = 0x02;
-        const FIXED = 0x03;
         const NULLABLE = 0x04;
+        const FIXED = 0x08;
     }
 }
 
@@ -131,12 +132,92 @@ impl AttrValue {
     }
 }
 
+/// Constraints on the values that may be written to an attribute
+///
+/// These are validated against the incoming TLV before a write from the Interaction Model
+/// is handed over to the cluster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint {
+    /// The integer value must lie within [min, max], both inclusive
+    Range(i64, i64),
+    /// The integer value must lie within [min, max], both inclusive, for the unsigned values
+    /// beyond what Range can express
+    UnsignedRange(u64, u64),
+    /// The value must be one of the listed enumeration values
+    Enum(&'static [u64]),
+    /// The octet/character string must not be longer than these many bytes
+    MaxStrLen(usize),
+    /// The list must not have more than these many entries
+    ///
+    /// Appending an entry is checked against the length that [ClusterType::list_len] reports
+    ///
+    /// [ClusterType::list_len]: super::ClusterType::list_len
+    MaxListLen(usize),
+}
+
+impl Constraint {
+    // Wide enough for both the signed and the unsigned values
+    fn int_value(data: &TLVElement) -> Result<i128, IMStatusCode> {
+        match data.get_element_type() {
+            ElementType::S8(v) => Ok(v.into()),
+            ElementType::S16(v) => Ok(v.into()),
+            ElementType::S32(v) => Ok(v.into()),
+            ElementType::S64(v) => Ok(v.into()),
+            ElementType::U8(v) => Ok(v.into()),
+            ElementType::U16(v) => Ok(v.into()),
+            ElementType::U32(v) => Ok(v.into()),
+            ElementType::U64(v) => Ok(v.into()),
+            _ => Err(IMStatusCode::InvalidDataType),
+        }
+    }
+
+    fn check_range<T: Into<i128>>(data: &TLVElement, min: T, max: T) -> Result<(), IMStatusCode> {
+        let v = Constraint::int_value(data)?;
+        if v < min.into() || v > max.into() {
+            return Err(IMStatusCode::ConstraintError);
+        }
+        Ok(())
+    }
+
+    /// Check if the value in the TLV satisfies this constraint
+    pub fn check(&self, data: &TLVElement) -> Result<(), IMStatusCode> {
+        match *self {
+            Constraint::Range(min, max) => Constraint::check_range(data, min, max)?,
+            Constraint::UnsignedRange(min, max) => Constraint::check_range(data, min, max)?,
+            Constraint::Enum(allowed) => {
+                let v = data.u64().map_err(|_| IMStatusCode::InvalidDataType)?;
+                if !allowed.contains(&v) {
+                    return Err(IMStatusCode::InvalidValue);
+                }
+            }
+            Constraint::MaxStrLen(max) => {
+                let s = data.slice().map_err(|_| IMStatusCode::InvalidDataType)?;
+                if s.len() > max {
+                    return Err(IMStatusCode::ConstraintError);
+                }
+            }
+            Constraint::MaxListLen(max) => {
+                let iter = data
+                    .confirm_array()
+                    .ok()
+                    .and_then(|a| a.enter())
+                    .ok_or(IMStatusCode::InvalidDataType)?;
+                if iter.count() > max {
+                    return Err(IMStatusCode::ConstraintError);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct Attribute {
     pub(super) id: u16,
     pub(super) value: AttrValue,
     pub(super) quality: Quality,
     pub(super) access: Access,
+    pub(super) constraint: Option<Constraint>,
 }
 
 impl Default for Attribute {
@@ -146,6 +227,7 @@ impl Default for Attribute {
             value: AttrValue::Bool(true),
             quality: Default::default(),
             access: Default::default(),
+            constraint: None,
         }
     }
 }
@@ -162,9 +244,27 @@ impl Attribute {
             value,
             access,
             quality,
+            constraint: None,
         })
     }
 
+    /// Attach a constraint that all the writes to this attribute must satisfy
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = Some(constraint);
+        self
+    }
+
+    /// Validate the incoming data for a write against the attribute's constraint, if any
+    pub fn validate(&self, data: &TLVElement) -> Result<(), IMStatusCode> {
+        if self.quality.contains(Quality::NULLABLE) && data.null().is_ok() {
+            return Ok(());
+        }
+        match &self.constraint {
+            Some(c) => c.check(data),
+            None => Ok(()),
+        }
+    }
+
     pub fn set_value(&mut self, value: AttrValue) -> Result<(), Error> {
         if !self.quality.contains(Quality::FIXED) {
             self.value = value;
@@ -174,6 +274,10 @@ impl Attribute {
         }
     }
 
+    pub fn is_persistent(&self) -> bool {
+        self.quality.contains(Quality::PERSISTENT) && self.value != AttrValue::Custom
+    }
+
     pub fn is_system_attr(attr_id: u16) -> bool {
         attr_id >= (GlobalElements::ServerGenCmd as u16)
     }
@@ -187,8 +291,12 @@ impl std::fmt::Display for Attribute {
 
 #[cfg(test)]
 mod tests {
-    use super::Access;
-    use crate::data_model::objects::Privilege;
+    use super::{Access, AttrValue, Attribute, Constraint, Quality};
+    use crate::{
+        data_model::objects::Privilege,
+        interaction_model::core::IMStatusCode,
+        tlv::{get_root_node, ElementType, TLVElement, TagType},
+    };
 
     #[test]
     fn test_read() {
@@ -254,4 +362,87 @@ mod tests {
         assert_eq!(c.is_ok(Access::WRITE, Privilege::MANAGE), true);
         assert_eq!(c.is_ok(Access::WRITE, Privilege::ADMIN), true);
     }
+
+    #[test]
+    fn test_constraint_range() {
+        let c = Constraint::Range(-5, 100);
+        let v = |e| TLVElement::new(TagType::Anonymous, e);
+        assert_eq!(c.check(&v(ElementType::U8(0))), Ok(()));
+        assert_eq!(c.check(&v(ElementType::U8(100))), Ok(()));
+        assert_eq!(c.check(&v(ElementType::S8(-5))), Ok(()));
+        assert_eq!(
+            c.check(&v(ElementType::U8(101))),
+            Err(IMStatusCode::ConstraintError)
+        );
+        assert_eq!(
+            c.check(&v(ElementType::S16(-6))),
+            Err(IMStatusCode::ConstraintError)
+        );
+        assert_eq!(
+            c.check(&v(ElementType::U64(u64::MAX))),
+            Err(IMStatusCode::ConstraintError)
+        );
+        assert_eq!(
+            c.check(&v(ElementType::True)),
+            Err(IMStatusCode::InvalidDataType)
+        );
+
+        let c = Constraint::UnsignedRange(1 << 63, u64::MAX);
+        assert_eq!(c.check(&v(ElementType::U64(u64::MAX))), Ok(()));
+        assert_eq!(c.check(&v(ElementType::U64(1 << 63))), Ok(()));
+        assert_eq!(
+            c.check(&v(ElementType::U64((1 << 63) - 1))),
+            Err(IMStatusCode::ConstraintError)
+        );
+        assert_eq!(
+            c.check(&v(ElementType::S64(-1))),
+            Err(IMStatusCode::ConstraintError)
+        );
+    }
+
+    #[test]
+    fn test_constraint_enum() {
+        let c = Constraint::Enum(&[0, 1, 4]);
+        let v = |e| TLVElement::new(TagType::Anonymous, e);
+        assert_eq!(c.check(&v(ElementType::U8(1))), Ok(()));
+        assert_eq!(c.check(&v(ElementType::U16(4))), Ok(()));
+        assert_eq!(
+            c.check(&v(ElementType::U8(2))),
+            Err(IMStatusCode::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_constraint_lengths() {
+        let c = Constraint::MaxStrLen(3);
+        let v = |e| TLVElement::new(TagType::Anonymous, e);
+        assert_eq!(c.check(&v(ElementType::Utf8l(b"abc"))), Ok(()));
+        assert_eq!(
+            c.check(&v(ElementType::Str8l(b"abcd"))),
+            Err(IMStatusCode::ConstraintError)
+        );
+
+        // Anonymous array with 3 u8 entries
+        let b = [0x16, 0x04, 0x01, 0x04, 0x02, 0x04, 0x03, 0x18];
+        let array = get_root_node(&b).unwrap();
+        assert_eq!(Constraint::MaxListLen(3).check(&array), Ok(()));
+        assert_eq!(
+            Constraint::MaxListLen(2).check(&array),
+            Err(IMStatusCode::ConstraintError)
+        );
+    }
+
+    #[test]
+    fn test_validate_nullable() {
+        let null = TLVElement::new(TagType::Anonymous, ElementType::Null);
+        let a = Attribute::new(1, AttrValue::Uint8(0), Access::RWVA, Quality::NULLABLE)
+            .unwrap()
+            .with_constraint(Constraint::Range(0, 10));
+        assert_eq!(a.validate(&null), Ok(()));
+
+        let a = Attribute::new(1, AttrValue::Uint8(0), Access::RWVA, Quality::NONE)
+            .unwrap()
+            .with_constraint(Constraint::Range(0, 10));
+        assert_eq!(a.validate(&null), Err(IMStatusCode::InvalidDataType));
+    }
 }
@@ -1,28 +1,53 @@
 use crate::{
     acl::AccessReq,
-    data_model::objects::{Access, AttrValue, Attribute, EncodeValue, Quality},
+    data_model::objects::{Access, AttrValue, Attribute, Constraint, EncodeValue, Quality},
     error::*,
     interaction_model::{command::CommandReq, core::IMStatusCode},
+    sys::Psm,
     // TODO: This layer shouldn't really depend on the TLV layer, should create an abstraction layer
-    tlv::{Nullable, TLVElement, TLVWriter, TagType},
+    tlv::{get_root_node, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
 };
-use log::error;
+use log::{error, info};
 use num_derive::FromPrimitive;
 use rand::Rng;
-use std::fmt::{self, Debug};
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, Mutex},
+};
 
-use super::Encoder;
+use super::{AttrChange, Encoder, Watchers};
 
-pub const ATTRS_PER_CLUSTER: usize = 8;
+pub const ATTRS_PER_CLUSTER: usize = 16;
 pub const CMDS_PER_CLUSTER: usize = 8;
 
+// The largest TLV encoding of a persisted AttrValue (control + u64)
+const MAX_PERSISTED_ATTR_LEN: usize = 9;
+
+// The number of data versions that are reserved in the store at a time. The store is only written
+// once every so many changes, and after a reboot the cluster starts from the end of the reservation,
+// past any data version that it may have used before.
+const DATAVER_RESERVATION: u32 = 1000;
+
+macro_rules! attr_key {
+    ($endpoint:expr, $cluster:expr, $attr:expr) => {
+        &format!("dm{}_{:x}_{:x}", $endpoint, $cluster, $attr)
+    };
+}
+
+macro_rules! dataver_key {
+    ($endpoint:expr, $cluster:expr) => {
+        &format!("dm{}_{:x}_dv", $endpoint, $cluster)
+    };
+}
+
 #[derive(FromPrimitive, Debug)]
 pub enum GlobalElements {
     _ClusterRevision = 0xFFFD,
     FeatureMap = 0xFFFC,
     AttributeList = 0xFFFB,
     _EventList = 0xFFFA,
-    _ClientGenCmd = 0xFFF9,
+    AcceptedCmdList = 0xFFF9,
     ServerGenCmd = 0xFFF8,
     FabricIndex = 0xFE,
 }
@@ -65,22 +90,42 @@ pub trait ClusterType {
     ) -> Result<(), IMStatusCode> {
         self.base_mut().write_attribute_from_tlv(attr.attr_id, data)
     }
+
+    /// The number of entries in a custom list attribute
+    ///
+    /// If this is defined, appending an entry to a list attribute that has a
+    /// Constraint::MaxListLen is checked against it. None skips that check.
+    fn list_len(&self, _attr_id: u16) -> Option<usize> {
+        None
+    }
 }
 
 pub struct Cluster {
     pub(super) id: u32,
     attributes: Vec<Attribute>,
     feature_map: Option<u32>,
+    accepted_cmds: &'static [u32],
     data_ver: u32,
+    // The data version up to which the versions are reserved in the store
+    dataver_reserved: u32,
+    // The endpoint this cluster lives on and the store for its persistent attributes
+    psm: Option<(u16, Arc<Mutex<Psm>>)>,
+    // The endpoint this cluster lives on and the watchers of its changes
+    watchers: Option<(u16, Arc<Watchers>)>,
 }
 
 impl Cluster {
     pub fn new(id: u32) -> Result<Cluster, Error> {
+        let data_ver = rand::thread_rng().gen_range(0..0xFFFFFFFF);
         let mut c = Cluster {
             id,
             attributes: Vec::with_capacity(ATTRS_PER_CLUSTER),
             feature_map: None,
-            data_ver: rand::thread_rng().gen_range(0..0xFFFFFFFF),
+            accepted_cmds: &[],
+            data_ver,
+            dataver_reserved: data_ver,
+            psm: None,
+            watchers: None,
         };
         c.add_default_attributes()?;
         Ok(c)
@@ -94,6 +139,115 @@ impl Cluster {
         self.data_ver
     }
 
+    /// Enable persistence for this cluster
+    ///
+    /// Any stored values of the attributes with Quality::PERSISTENT, and the cluster's data version,
+    /// are loaded from the store. Subsequent changes to the attributes are written back to the store.
+    /// The data version is only written once every DATAVER_RESERVATION changes, and resumes from
+    /// the end of the last reservation, so that it never repeats a version used before a reboot.
+    pub fn enable_persistence(&mut self, endpoint: u16, psm: Arc<Mutex<Psm>>) -> Result<(), Error> {
+        {
+            let psm = psm.lock()?;
+            let mut data_ver = 0;
+            if psm
+                .get_kv_u64(dataver_key!(endpoint, self.id), &mut data_ver)
+                .is_ok()
+            {
+                self.data_ver = data_ver as u32;
+            }
+            self.dataver_reserved = self.data_ver.wrapping_add(DATAVER_RESERVATION);
+            psm.set_kv_u64(
+                dataver_key!(endpoint, self.id),
+                self.dataver_reserved as u64,
+            )?;
+
+            for a in self.attributes.iter_mut() {
+                if !a.is_persistent() {
+                    continue;
+                }
+                let mut buf = Vec::new();
+                if psm
+                    .get_kv_slice(attr_key!(endpoint, self.id, a.id), &mut buf)
+                    .is_err()
+                {
+                    // Nothing stored yet, keep the default value
+                    continue;
+                }
+                let mut value = a.value;
+                let loaded = get_root_node(&buf)
+                    .and_then(|root| value.update_from_tlv(&root))
+                    .and_then(|_| a.set_value(value));
+                match loaded {
+                    Ok(()) => info!("Loaded persistent attribute {}: {:?}", a.id, value),
+                    // A corrupted value shouldn't keep the device from starting
+                    Err(e) => error!(
+                        "Ignoring the stored value {:x?} of attribute {}: {:?}",
+                        buf, a.id, e
+                    ),
+                }
+            }
+        }
+        self.psm = Some((endpoint, psm));
+        Ok(())
+    }
+
+    /// Notify the given watchers of the changes to this cluster
+    pub fn set_watchers(&mut self, endpoint: u16, watchers: Arc<Watchers>) {
+        self.watchers = Some((endpoint, watchers));
+    }
+
+    fn notify(&self, attr: Option<u16>) {
+        if let Some((endpoint, watchers)) = &self.watchers {
+            watchers.notify(AttrChange {
+                endpoint: *endpoint,
+                cluster: self.id,
+                attr,
+            });
+        }
+    }
+
+    fn store_attribute(&self, attr_id: u16) -> Result<(), Error> {
+        if let Some((endpoint, psm)) = &self.psm {
+            let a = self.get_attribute(attr_id)?;
+            if a.is_persistent() {
+                let mut buf = [0u8; MAX_PERSISTED_ATTR_LEN];
+                let mut wb = WriteBuf::new(&mut buf, MAX_PERSISTED_ATTR_LEN);
+                let mut tw = TLVWriter::new(&mut wb);
+                a.value.to_tlv(&mut tw, TagType::Anonymous)?;
+                psm.lock()?
+                    .set_kv_slice(attr_key!(endpoint, self.id, attr_id), wb.as_borrow_slice())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reserve_dataver(&mut self) -> Result<(), Error> {
+        self.dataver_reserved = self.data_ver.wrapping_add(DATAVER_RESERVATION);
+        if let Some((endpoint, psm)) = &self.psm {
+            psm.lock()?.set_kv_u64(
+                dataver_key!(endpoint, self.id),
+                self.dataver_reserved as u64,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Remove the stored values of this cluster's persistent attributes and data version
+    pub fn remove_persisted(&self) -> Result<(), Error> {
+        if let Some((endpoint, psm)) = &self.psm {
+            let psm = psm.lock()?;
+            for a in self.attributes.iter().filter(|a| a.is_persistent()) {
+                psm.rm(attr_key!(endpoint, self.id, a.id))?;
+            }
+            psm.rm(dataver_key!(endpoint, self.id))?;
+        }
+        Ok(())
+    }
+
+    pub fn get_feature_map(&self) -> Option<u32> {
+        self.feature_map
+    }
+
     pub fn set_feature_map(&mut self, map: u32) -> Result<(), Error> {
         if self.feature_map.is_none() {
             self.add_attribute(Attribute::new(
@@ -110,6 +264,27 @@ impl Cluster {
         Ok(())
     }
 
+    /// Set the commands that are reported in the AcceptedCommandList
+    pub fn set_accepted_commands(&mut self, cmds: &'static [u32]) -> Result<(), Error> {
+        if self
+            .get_attribute_index(GlobalElements::AcceptedCmdList as u16)
+            .is_none()
+        {
+            self.add_attribute(Attribute::new(
+                GlobalElements::AcceptedCmdList as u16,
+                AttrValue::Custom,
+                Access::RV,
+                Quality::NONE,
+            )?)?;
+        }
+        self.accepted_cmds = cmds;
+        Ok(())
+    }
+
+    pub fn get_accepted_commands(&self) -> &[u32] {
+        self.accepted_cmds
+    }
+
     fn add_default_attributes(&mut self) -> Result<(), Error> {
         self.add_attribute(Attribute::new(
             GlobalElements::AttributeList as u16,
@@ -132,6 +307,10 @@ impl Cluster {
         self.attributes.iter().position(|c| c.id == attr_id)
     }
 
+    pub fn has_attribute(&self, attr_id: u16) -> bool {
+        self.get_attribute_index(attr_id).is_some()
+    }
+
     fn get_attribute(&self, attr_id: u16) -> Result<&Attribute, Error> {
         let index = self
             .get_attribute_index(attr_id)
@@ -215,6 +394,16 @@ impl Cluster {
                     }));
                     return;
                 }
+                GlobalElements::AcceptedCmdList => {
+                    encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                        let _ = tw.start_array(tag);
+                        for cmd in self.accepted_cmds {
+                            let _ = tw.u32(TagType::Anonymous, *cmd);
+                        }
+                        let _ = tw.end_container();
+                    }));
+                    return;
+                }
                 GlobalElements::FeatureMap => {
                     let val = if let Some(m) = self.feature_map { m } else { 0 };
                     encoder.encode(EncodeValue::Value(&val));
@@ -257,6 +446,20 @@ impl Cluster {
             return Err(IMStatusCode::UnsupportedAccess);
         }
 
+        match (attr.list_index, a.constraint) {
+            // Writes to a specific list index carry a single entry, rather than the attribute value
+            (Some(Nullable::NotNull(_)), _) => (),
+            // A single entry, that is appended to the list
+            (_, Some(Constraint::MaxListLen(max)))
+                if data.confirm_array().is_err() && data.null().is_err() =>
+            {
+                if matches!(c.list_len(attr.attr_id), Some(len) if len >= max) {
+                    return Err(IMStatusCode::ConstraintError);
+                }
+            }
+            _ => a.validate(data)?,
+        }
+
         c.write_attribute(attr, data)
     }
 
@@ -272,11 +475,9 @@ impl Cluster {
                 .update_from_tlv(data)
                 .map_err(|_| IMStatusCode::Failure)?;
             a.set_value(value)
-                .map(|_| {
-                    self.cluster_changed();
-                    ()
-                })
-                .map_err(|_| IMStatusCode::UnsupportedWrite)
+                .map_err(|_| IMStatusCode::UnsupportedWrite)?;
+            self.attribute_changed(attr_id);
+            Ok(())
         } else {
             Err(IMStatusCode::UnsupportedAttribute)
         }
@@ -284,17 +485,41 @@ impl Cluster {
 
     pub fn write_attribute_raw(&mut self, attr_id: u16, value: AttrValue) -> Result<(), Error> {
         let a = self.get_attribute_mut(attr_id)?;
-        a.set_value(value).map(|_| {
-            self.cluster_changed();
-            ()
-        })
+        a.set_value(value)?;
+        self.attribute_changed(attr_id);
+        Ok(())
+    }
+
+    fn attribute_changed(&mut self, attr_id: u16) {
+        self.bump_dataver();
+        self.notify(Some(attr_id));
+        let _ = self.store_attribute(attr_id).map_err(|e| {
+            error!(
+                "Error storing attribute {} of cluster {}: {:?}",
+                attr_id, self.id, e
+            );
+        });
     }
 
     /// This method must be called for any changes to the data model
     ///     Currently this only increments the data version, but we can reuse the same
     ///     for raising events too
     pub fn cluster_changed(&mut self) {
+        self.bump_dataver();
+        self.notify(None);
+    }
+
+    fn bump_dataver(&mut self) {
         self.data_ver = self.data_ver.wrapping_add(1);
+        if self.data_ver != self.dataver_reserved {
+            return;
+        }
+        let _ = self.reserve_dataver().map_err(|e| {
+            error!(
+                "Error storing data version for cluster {}: {:?}",
+                self.id, e
+            );
+        });
     }
 }
 
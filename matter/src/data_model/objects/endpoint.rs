@@ -1,22 +1,95 @@
-use crate::{data_model::objects::ClusterType, error::*, interaction_model::core::IMStatusCode};
+use crate::{
+    data_model::objects::ClusterType,
+    error::*,
+    interaction_model::core::IMStatusCode,
+    tlv::{TLVWriter, TagType, ToTLV},
+};
 
 use std::fmt;
 
+/// The default for the maximum number of server clusters on an endpoint
 pub const CLUSTERS_PER_ENDPT: usize = 7;
+pub const DEVICE_TYPES_PER_ENDPT: usize = 3;
+
+/// A device type, along with the revision of its definition, that an endpoint conforms to
+#[derive(ToTLV, Debug, Copy, Clone, PartialEq)]
+pub struct DeviceType {
+    pub dtype: u32,
+    pub drev: u16,
+}
 
 pub struct Endpoint {
+    id: u16,
+    // The endpoint whose PartsList this endpoint is part of, None for the root endpoint
+    parent: Option<u16>,
+    device_types: Vec<DeviceType>,
     clusters: Vec<Box<dyn ClusterType>>,
+    max_clusters: usize,
+    // The IDs of the clusters that this endpoint is a client of
+    client_clusters: Vec<u32>,
 }
 
 impl Endpoint {
-    pub fn new() -> Result<Box<Endpoint>, Error> {
+    pub fn new(id: u16, parent: Option<u16>) -> Result<Box<Endpoint>, Error> {
+        Endpoint::new_with(id, parent, CLUSTERS_PER_ENDPT)
+    }
+
+    pub fn new_with(
+        id: u16,
+        parent: Option<u16>,
+        max_clusters: usize,
+    ) -> Result<Box<Endpoint>, Error> {
         Ok(Box::new(Endpoint {
-            clusters: Vec::with_capacity(CLUSTERS_PER_ENDPT),
+            id,
+            parent,
+            device_types: Vec::with_capacity(DEVICE_TYPES_PER_ENDPT),
+            clusters: Vec::with_capacity(max_clusters),
+            max_clusters,
+            client_clusters: Vec::with_capacity(CLUSTERS_PER_ENDPT),
         }))
     }
 
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn parent(&self) -> Option<u16> {
+        self.parent
+    }
+
+    pub fn add_device_type(&mut self, device_type: DeviceType) -> Result<(), Error> {
+        if self.device_types.contains(&device_type) {
+            Ok(())
+        } else if self.device_types.len() < self.device_types.capacity() {
+            self.device_types.push(device_type);
+            Ok(())
+        } else {
+            Err(Error::NoSpace)
+        }
+    }
+
+    pub fn device_types(&self) -> &[DeviceType] {
+        &self.device_types
+    }
+
+    /// Declare that this endpoint hosts the client side of a cluster
+    pub fn add_client_cluster(&mut self, cluster_id: u32) -> Result<(), Error> {
+        if self.client_clusters.contains(&cluster_id) {
+            Ok(())
+        } else if self.client_clusters.len() < self.client_clusters.capacity() {
+            self.client_clusters.push(cluster_id);
+            Ok(())
+        } else {
+            Err(Error::NoSpace)
+        }
+    }
+
+    pub fn client_clusters(&self) -> &[u32] {
+        &self.client_clusters
+    }
+
     pub fn add_cluster(&mut self, cluster: Box<dyn ClusterType>) -> Result<(), Error> {
-        if self.clusters.len() < self.clusters.capacity() {
+        if self.clusters.len() < self.max_clusters {
             self.clusters.push(cluster);
             Ok(())
         } else {
@@ -77,7 +150,13 @@ impl Endpoint {
 
 impl std::fmt::Display for Endpoint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "clusters:[")?;
+        write!(f, "device types:[")?;
+        let mut comma = "";
+        for d in self.device_types.iter() {
+            write!(f, "{} {{ {:#x} rev {} }}", comma, d.dtype, d.drev)?;
+            comma = ", ";
+        }
+        write!(f, "], clusters:[")?;
         let mut comma = "";
         for element in self.clusters.iter() {
             write!(f, "{} {{ {} }}", comma, element.base())?;
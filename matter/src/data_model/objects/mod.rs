@@ -15,3 +15,8 @@ pub use privilege::*;
 
 mod encoder;
 pub use encoder::*;
+
+mod watch;
+pub use watch::*;
+
+pub use matter_macro_derive::{cluster_commands, ClusterAttributes, ClusterType};
@@ -1,30 +1,64 @@
+use async_channel::Receiver;
+
 use crate::{
-    data_model::objects::{ClusterType, Endpoint},
+    data_model::{
+        device_types::{DeviceTypeDef, DEVICE_TYPES},
+        objects::{AttrChange, ClusterType, Endpoint, Watchers, CLUSTERS_PER_ENDPT},
+        system_model::descriptor,
+    },
     error::*,
     interaction_model::{core::IMStatusCode, messages::GenericPath},
+    sys::Psm,
     // TODO: This layer shouldn't really depend on the TLV layer, should create an abstraction layer
 };
-use std::fmt;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 pub trait ChangeConsumer {
     fn endpoint_added(&self, id: u16, endpoint: &mut Endpoint) -> Result<(), Error>;
 }
 
+/// The default for the maximum number of endpoints that can be present at the same time
 pub const ENDPTS_PER_ACC: usize = 3;
 
-#[derive(Default)]
+// Endpoint IDs are allocated incrementally, and only reused after wrapping around
+const MAX_ENDPOINT_ID: u16 = 0xFFFE;
+
 pub struct Node {
-    endpoints: [Option<Box<Endpoint>>; ENDPTS_PER_ACC],
+    // Sorted by the endpoint ID
+    endpoints: Vec<Endpoint>,
+    max_endpoints: usize,
+    clusters_per_endpoint: usize,
+    next_endpoint_id: u16,
     changes_cb: Option<Box<dyn ChangeConsumer>>,
+    psm: Option<Arc<Mutex<Psm>>>,
+    watchers: Arc<Watchers>,
+    // The device types that can be added to the endpoints, and validated
+    device_type_defs: Vec<&'static DeviceTypeDef>,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            max_endpoints: ENDPTS_PER_ACC,
+            clusters_per_endpoint: CLUSTERS_PER_ENDPT,
+            next_endpoint_id: 0,
+            changes_cb: None,
+            psm: None,
+            watchers: Arc::new(Watchers::new()),
+            device_type_defs: DEVICE_TYPES.iter().collect(),
+        }
+    }
 }
 
 impl std::fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "node:")?;
-        for (i, element) in self.endpoints.iter().enumerate() {
-            if let Some(e) = element {
-                writeln!(f, "endpoint {}: {}", i, e)?;
-            }
+        for e in self.endpoints.iter() {
+            writeln!(f, "endpoint {}: {}", e.id(), e)?;
         }
         write!(f, "")
     }
@@ -40,40 +74,206 @@ impl Node {
         self.changes_cb = Some(consumer);
     }
 
-    pub fn add_endpoint(&mut self) -> Result<u32, Error> {
-        let index = self
-            .endpoints
+    /// Set the maximum number of endpoints that can be present at the same time
+    pub fn set_max_endpoints(&mut self, max_endpoints: usize) {
+        self.max_endpoints = max_endpoints;
+    }
+
+    /// Set the maximum number of server clusters on the endpoints that are added from now on
+    pub fn set_clusters_per_endpoint(&mut self, clusters_per_endpoint: usize) {
+        self.clusters_per_endpoint = clusters_per_endpoint;
+    }
+
+    /// Register the definition of a device type, in addition to the built-in ones
+    ///
+    /// This replaces any definition that was registered for the same device type ID
+    pub fn register_device_type(&mut self, def: &'static DeviceTypeDef) {
+        let dtype = def.device_type.dtype;
+        self.device_type_defs
+            .retain(|d| d.device_type.dtype != dtype);
+        self.device_type_defs.push(def);
+    }
+
+    /// Get the definition of a device type, from those registered with this node
+    pub fn get_device_type_def(&self, dtype: u32) -> Option<&'static DeviceTypeDef> {
+        self.device_type_defs
             .iter()
-            .position(|x| x.is_none())
-            .ok_or(Error::NoSpace)?;
-        let mut endpoint = Endpoint::new()?;
-        if let Some(cb) = &self.changes_cb {
-            cb.endpoint_added(index as u16, &mut endpoint)?;
+            .find(|d| d.device_type.dtype == dtype)
+            .copied()
+    }
+
+    /// Persist the clusters' persistent attributes and data versions in this store
+    ///
+    /// This applies to the clusters that are already part of the node, as well as those added later
+    pub fn enable_persistence(&mut self, psm: Arc<Mutex<Psm>>) -> Result<(), Error> {
+        for endpoint in self.endpoints.iter_mut() {
+            Node::endpoint_enable_persistence(endpoint, &psm)?;
         }
-        self.endpoints[index] = Some(endpoint);
-        Ok(index as u32)
+        self.psm = Some(psm);
+        Ok(())
     }
 
-    pub fn get_endpoint(&self, endpoint_id: u16) -> Result<&Endpoint, Error> {
-        if (endpoint_id as usize) < ENDPTS_PER_ACC {
-            let endpoint = self.endpoints[endpoint_id as usize]
-                .as_ref()
-                .ok_or(Error::EndpointNotFound)?;
-            Ok(endpoint)
-        } else {
-            Err(Error::EndpointNotFound)
+    /// Watch the changes to the attributes of all the clusters, see [Watchers::watch]
+    pub fn watch(&self) -> Receiver<AttrChange> {
+        self.watchers.watch()
+    }
+
+    fn endpoint_set_watchers(
+        endpoint: &mut Endpoint,
+        watchers: &Arc<Watchers>,
+    ) -> Result<(), Error> {
+        let id = endpoint.id();
+        let (clusters, _) = endpoint
+            .get_wildcard_clusters_mut(None)
+            .map_err(|_| Error::ClusterNotFound)?;
+        for c in clusters.iter_mut() {
+            c.base_mut().set_watchers(id, watchers.clone());
         }
+        Ok(())
     }
 
-    pub fn get_endpoint_mut(&mut self, endpoint_id: u16) -> Result<&mut Endpoint, Error> {
-        if (endpoint_id as usize) < ENDPTS_PER_ACC {
-            let endpoint = self.endpoints[endpoint_id as usize]
-                .as_mut()
-                .ok_or(Error::EndpointNotFound)?;
-            Ok(endpoint)
+    fn endpoint_enable_persistence(
+        endpoint: &mut Endpoint,
+        psm: &Arc<Mutex<Psm>>,
+    ) -> Result<(), Error> {
+        let id = endpoint.id();
+        let (clusters, _) = endpoint
+            .get_wildcard_clusters_mut(None)
+            .map_err(|_| Error::ClusterNotFound)?;
+        for c in clusters.iter_mut() {
+            c.base_mut().enable_persistence(id, psm.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Add an endpoint that is part of the root endpoint
+    ///
+    /// The first endpoint that is added is the root endpoint itself
+    pub fn add_endpoint(&mut self) -> Result<u32, Error> {
+        let parent = if self.endpoints.is_empty() {
+            None
         } else {
-            Err(Error::EndpointNotFound)
+            Some(0)
+        };
+        self.add_endpoint_to(parent)
+    }
+
+    /// Add an endpoint that is part of the given endpoint, for example, a bridged device that is
+    /// part of an aggregator
+    pub fn add_endpoint_with_parent(&mut self, parent: u16) -> Result<u32, Error> {
+        self.get_endpoint(parent)?;
+        self.add_endpoint_to(Some(parent))
+    }
+
+    fn add_endpoint_to(&mut self, parent: Option<u16>) -> Result<u32, Error> {
+        if self.endpoints.len() >= self.max_endpoints {
+            return Err(Error::NoSpace);
         }
+        let id = self.alloc_endpoint_id();
+        let mut endpoint = *Endpoint::new_with(id, parent, self.clusters_per_endpoint)?;
+        if let Some(cb) = &self.changes_cb {
+            cb.endpoint_added(id, &mut endpoint)?;
+        }
+        if let Some(psm) = &self.psm {
+            Node::endpoint_enable_persistence(&mut endpoint, psm)?;
+        }
+        Node::endpoint_set_watchers(&mut endpoint, &self.watchers)?;
+        let index = self.get_endpoint_index(id).unwrap_err();
+        self.endpoints.insert(index, endpoint);
+        self.parts_changed(parent);
+        Ok(id as u32)
+    }
+
+    fn alloc_endpoint_id(&mut self) -> u16 {
+        // There is always a free ID, since the number of endpoints is limited by max_endpoints
+        loop {
+            let id = self.next_endpoint_id;
+            self.next_endpoint_id = if id >= MAX_ENDPOINT_ID { 1 } else { id + 1 };
+            if self.get_endpoint_index(id).is_err() {
+                return id;
+            }
+        }
+    }
+
+    /// Remove an endpoint, along with all its clusters
+    ///
+    /// The root endpoint can't be removed, and neither can endpoints that still have other
+    /// endpoints as their parts.
+    pub fn remove_endpoint(&mut self, endpoint_id: u16) -> Result<(), Error> {
+        let index = self
+            .get_endpoint_index(endpoint_id)
+            .map_err(|_| Error::EndpointNotFound)?;
+        if endpoint_id == 0
+            || self
+                .endpoints
+                .iter()
+                .any(|e| e.parent() == Some(endpoint_id))
+        {
+            return Err(Error::Invalid);
+        }
+        // The endpoint ID may be reused once the IDs wrap around, so forget the stored values
+        let (clusters, _) = self.endpoints[index]
+            .get_wildcard_clusters(None)
+            .map_err(|_| Error::ClusterNotFound)?;
+        for c in clusters.iter() {
+            c.base().remove_persisted()?;
+        }
+        let endpoint = self.endpoints.remove(index);
+        self.parts_changed(endpoint.parent());
+        Ok(())
+    }
+
+    /// The PartsList of the given endpoint changed, this also changes the PartsList of all
+    /// its ancestors. Their Descriptor clusters get a new data version.
+    fn parts_changed(&mut self, mut endpoint_id: Option<u16>) {
+        while let Some(id) = endpoint_id {
+            endpoint_id = match self.get_endpoint_mut(id) {
+                Ok(e) => {
+                    if let Ok(d) = e.get_cluster_mut(descriptor::ID) {
+                        d.base_mut().cluster_changed();
+                    }
+                    e.parent()
+                }
+                Err(_) => None,
+            }
+        }
+    }
+
+    /// Run a closure for all the endpoints that are part of the given endpoint, including the
+    /// parts of those endpoints
+    pub fn for_each_part<T>(&self, endpoint_id: u16, mut f: T)
+    where
+        T: FnMut(u16),
+    {
+        for e in self.endpoints.iter() {
+            let mut parent = e.parent();
+            while let Some(p) = parent {
+                if p == endpoint_id {
+                    f(e.id());
+                    break;
+                }
+                parent = self.get_endpoint(p).ok().and_then(|p| p.parent());
+            }
+        }
+    }
+
+    fn get_endpoint_index(&self, endpoint_id: u16) -> Result<usize, usize> {
+        self.endpoints
+            .binary_search_by_key(&endpoint_id, |e| e.id())
+    }
+
+    pub fn get_endpoint(&self, endpoint_id: u16) -> Result<&Endpoint, Error> {
+        let index = self
+            .get_endpoint_index(endpoint_id)
+            .map_err(|_| Error::EndpointNotFound)?;
+        Ok(&self.endpoints[index])
+    }
+
+    pub fn get_endpoint_mut(&mut self, endpoint_id: u16) -> Result<&mut Endpoint, Error> {
+        let index = self
+            .get_endpoint_index(endpoint_id)
+            .map_err(|_| Error::EndpointNotFound)?;
+        Ok(&mut self.endpoints[index])
     }
 
     pub fn get_cluster_mut(&mut self, e: u16, c: u32) -> Result<&mut dyn ClusterType, Error> {
@@ -87,49 +287,47 @@ impl Node {
     pub fn add_cluster(
         &mut self,
         endpoint_id: u32,
-        cluster: Box<dyn ClusterType>,
+        mut cluster: Box<dyn ClusterType>,
     ) -> Result<(), Error> {
-        let endpoint_id = endpoint_id as usize;
-        if endpoint_id < ENDPTS_PER_ACC {
-            self.endpoints[endpoint_id]
-                .as_mut()
-                .ok_or(Error::NoEndpoint)?
-                .add_cluster(cluster)
-        } else {
-            Err(Error::Invalid)
+        if let Some(psm) = &self.psm {
+            cluster
+                .base_mut()
+                .enable_persistence(endpoint_id as u16, psm.clone())?;
         }
+        cluster
+            .base_mut()
+            .set_watchers(endpoint_id as u16, self.watchers.clone());
+        self.get_endpoint_mut(endpoint_id as u16)
+            .map_err(|_| Error::NoEndpoint)?
+            .add_cluster(cluster)
     }
 
     // Returns a slice of endpoints, with either a single endpoint or all (wildcard)
     pub fn get_wildcard_endpoints(
         &self,
         endpoint: Option<u16>,
-    ) -> Result<(&[Option<Box<Endpoint>>], usize, bool), IMStatusCode> {
+    ) -> Result<(&[Endpoint], bool), IMStatusCode> {
         if let Some(e) = endpoint {
-            let e = e as usize;
-            if self.endpoints.len() <= e || self.endpoints[e].is_none() {
-                Err(IMStatusCode::UnsupportedEndpoint)
-            } else {
-                Ok((&self.endpoints[e..e + 1], e, false))
-            }
+            let i = self
+                .get_endpoint_index(e)
+                .map_err(|_| IMStatusCode::UnsupportedEndpoint)?;
+            Ok((&self.endpoints[i..i + 1], false))
         } else {
-            Ok((&self.endpoints[..], 0, true))
+            Ok((&self.endpoints[..], true))
         }
     }
 
     pub fn get_wildcard_endpoints_mut(
         &mut self,
         endpoint: Option<u16>,
-    ) -> Result<(&mut [Option<Box<Endpoint>>], usize, bool), IMStatusCode> {
+    ) -> Result<(&mut [Endpoint], bool), IMStatusCode> {
         if let Some(e) = endpoint {
-            let e = e as usize;
-            if self.endpoints.len() <= e || self.endpoints[e].is_none() {
-                Err(IMStatusCode::UnsupportedEndpoint)
-            } else {
-                Ok((&mut self.endpoints[e..e + 1], e, false))
-            }
+            let i = self
+                .get_endpoint_index(e)
+                .map_err(|_| IMStatusCode::UnsupportedEndpoint)?;
+            Ok((&mut self.endpoints[i..i + 1], false))
         } else {
-            Ok((&mut self.endpoints[..], 0, true))
+            Ok((&mut self.endpoints[..], true))
         }
     }
 
@@ -147,14 +345,10 @@ impl Node {
         T: FnMut(&GenericPath, &Endpoint) -> Result<(), IMStatusCode>,
     {
         let mut current_path = *path;
-        let (endpoints, mut endpoint_id, wildcard) = self.get_wildcard_endpoints(path.endpoint)?;
+        let (endpoints, wildcard) = self.get_wildcard_endpoints(path.endpoint)?;
         for e in endpoints.iter() {
-            if let Some(e) = e {
-                current_path.endpoint = Some(endpoint_id as u16);
-                f(&current_path, e.as_ref())
-                    .or_else(|e| if !wildcard { Err(e) } else { Ok(()) })?;
-            }
-            endpoint_id += 1;
+            current_path.endpoint = Some(e.id());
+            f(&current_path, e).or_else(|e| if !wildcard { Err(e) } else { Ok(()) })?;
         }
         Ok(())
     }
@@ -177,15 +371,10 @@ impl Node {
         T: FnMut(&GenericPath, &mut Endpoint) -> Result<(), IMStatusCode>,
     {
         let mut current_path = *path;
-        let (endpoints, mut endpoint_id, wildcard) =
-            self.get_wildcard_endpoints_mut(path.endpoint)?;
+        let (endpoints, wildcard) = self.get_wildcard_endpoints_mut(path.endpoint)?;
         for e in endpoints.iter_mut() {
-            if let Some(e) = e {
-                current_path.endpoint = Some(endpoint_id as u16);
-                f(&current_path, e.as_mut())
-                    .or_else(|e| if !wildcard { Err(e) } else { Ok(()) })?;
-            }
-            endpoint_id += 1;
+            current_path.endpoint = Some(e.id());
+            f(&current_path, e).or_else(|e| if !wildcard { Err(e) } else { Ok(()) })?;
         }
         Ok(())
     }
@@ -0,0 +1,70 @@
+use async_channel::{unbounded, Receiver, Sender};
+use std::sync::Mutex;
+
+/// A change in the Data Model
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AttrChange {
+    pub endpoint: u16,
+    pub cluster: u32,
+    /// The attribute that changed, or None if the cluster only reported that some of its data
+    /// changed
+    pub attr: Option<u16>,
+}
+
+/// The watchers of the changes in the Data Model
+#[derive(Default)]
+pub struct Watchers {
+    senders: Mutex<Vec<Sender<AttrChange>>>,
+}
+
+impl Watchers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a watcher, the changes are received on the returned channel
+    ///
+    /// The watcher is removed once the channel is dropped
+    pub fn watch(&self) -> Receiver<AttrChange> {
+        let (tx, rx) = unbounded();
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.push(tx);
+        }
+        rx
+    }
+
+    pub fn notify(&self, change: AttrChange) {
+        if let Ok(mut senders) = self.senders.lock() {
+            // The channels are unbounded, so this only fails for the closed ones
+            senders.retain(|tx| tx.try_send(change).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttrChange, Watchers};
+
+    #[test]
+    fn test_watchers() {
+        let watchers = Watchers::new();
+        let rx_1 = watchers.watch();
+        let rx_2 = watchers.watch();
+        let change = AttrChange {
+            endpoint: 1,
+            cluster: 6,
+            attr: Some(0),
+        };
+
+        watchers.notify(change);
+        assert_eq!(rx_1.try_recv(), Ok(change));
+        assert_eq!(rx_2.try_recv(), Ok(change));
+
+        // Dropped watchers are removed
+        drop(rx_1);
+        watchers.notify(change);
+        assert_eq!(watchers.senders.lock().unwrap().len(), 1);
+        assert_eq!(rx_2.try_recv(), Ok(change));
+        assert!(rx_2.try_recv().is_err());
+    }
+}
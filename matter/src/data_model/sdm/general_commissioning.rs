@@ -3,7 +3,7 @@ use crate::data_model::objects::*;
 use crate::data_model::sdm::failsafe::FailSafe;
 use crate::interaction_model::core::IMStatusCode;
 use crate::interaction_model::messages::ib;
-use crate::tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV};
+use crate::tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV, UtfStr};
 use crate::{error::*, interaction_model::command::CommandReq};
 use log::{error, info};
 use num_derive::FromPrimitive;
@@ -20,15 +20,22 @@ enum CommissioningError {
 
 pub const ID: u32 = 0x0030;
 
-#[derive(FromPrimitive)]
+// TODO: Arch-Specific, the values of RegConfig and LocationCapability
+#[derive(FromPrimitive, ClusterAttributes)]
 pub enum Attributes {
+    #[attribute(default = "AttrValue::Uint64(0)", access = "READ | WRITE | NEED_ADMIN")]
     BreadCrumb = 0,
+    #[attribute(quality = "FIXED")]
     BasicCommissioningInfo = 1,
+    #[attribute(default = "AttrValue::Uint8(RegLocationType::IndoorOutdoor as u8)")]
     RegConfig = 2,
+    #[attribute(
+        default = "AttrValue::Uint8(RegLocationType::IndoorOutdoor as u8)",
+        quality = "FIXED"
+    )]
     LocationCapability = 3,
 }
 
-#[derive(FromPrimitive)]
 pub enum Commands {
     ArmFailsafe = 0x00,
     ArmFailsafeResp = 0x01,
@@ -44,63 +51,45 @@ pub enum RegLocationType {
     IndoorOutdoor = 2,
 }
 
-fn attr_bread_crumb_new(bread_crumb: u64) -> Result<Attribute, Error> {
-    Attribute::new(
-        Attributes::BreadCrumb as u16,
-        AttrValue::Uint64(bread_crumb),
-        Access::READ | Access::WRITE | Access::NEED_ADMIN,
-        Quality::NONE,
-    )
-}
-
-fn attr_reg_config_new(reg_config: RegLocationType) -> Result<Attribute, Error> {
-    Attribute::new(
-        Attributes::RegConfig as u16,
-        AttrValue::Uint8(reg_config as u8),
-        Access::RV,
-        Quality::NONE,
-    )
-}
-
-fn attr_location_capability_new(reg_config: RegLocationType) -> Result<Attribute, Error> {
-    Attribute::new(
-        Attributes::LocationCapability as u16,
-        AttrValue::Uint8(reg_config as u8),
-        Access::RV,
-        Quality::FIXED,
-    )
-}
-
-fn attr_comm_info_new() -> Result<Attribute, Error> {
-    Attribute::new(
-        Attributes::BasicCommissioningInfo as u16,
-        AttrValue::Custom,
-        Access::RV,
-        Quality::FIXED,
-    )
-}
-
 #[derive(FromTLV, ToTLV)]
 struct FailSafeParams {
     expiry_len: u8,
     bread_crumb: u8,
 }
 
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct SetRegConfigParams<'a> {
+    new_reg_config: u8,
+    country_code: UtfStr<'a>,
+    bread_crumb: u64,
+}
+
+#[derive(ClusterType)]
+#[cluster(id = "ID", attributes = "Attributes", commands, read = "read_custom")]
 pub struct GenCommCluster {
     expiry_len: u16,
     failsafe: Arc<FailSafe>,
     base: Cluster,
 }
 
-impl ClusterType for GenCommCluster {
-    fn base(&self) -> &Cluster {
-        &self.base
+impl GenCommCluster {
+    pub fn new() -> Result<Box<Self>, Error> {
+        let failsafe = Arc::new(FailSafe::new());
+
+        Ok(Box::new(GenCommCluster {
+            // TODO: Arch-Specific
+            expiry_len: 120,
+            failsafe,
+            base: Self::new_base()?,
+        }))
     }
-    fn base_mut(&mut self) -> &mut Cluster {
-        &mut self.base
+
+    pub fn failsafe(&self) -> Arc<FailSafe> {
+        self.failsafe.clone()
     }
 
-    fn read_custom_attribute(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
+    fn read_custom(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
         match num::FromPrimitive::from_u16(attr.attr_id) {
             Some(Attributes::BasicCommissioningInfo) => {
                 encoder.encode(EncodeValue::Closure(&|tag, tw| {
@@ -114,56 +103,18 @@ impl ClusterType for GenCommCluster {
             }
         }
     }
-
-    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        let cmd = cmd_req
-            .cmd
-            .path
-            .leaf
-            .map(num::FromPrimitive::from_u32)
-            .ok_or(IMStatusCode::UnsupportedCommand)?
-            .ok_or(IMStatusCode::UnsupportedCommand)?;
-        match cmd {
-            Commands::ArmFailsafe => self.handle_command_armfailsafe(cmd_req),
-            Commands::SetRegulatoryConfig => self.handle_command_setregulatoryconfig(cmd_req),
-            Commands::CommissioningComplete => self.handle_command_commissioningcomplete(cmd_req),
-            _ => Err(IMStatusCode::UnsupportedCommand),
-        }
-    }
 }
 
+#[cluster_commands]
 impl GenCommCluster {
-    pub fn new() -> Result<Box<Self>, Error> {
-        let failsafe = Arc::new(FailSafe::new());
-
-        let mut c = Box::new(GenCommCluster {
-            // TODO: Arch-Specific
-            expiry_len: 120,
-            failsafe,
-            base: Cluster::new(ID)?,
-        });
-        c.base.add_attribute(attr_bread_crumb_new(0)?)?;
-        // TODO: Arch-Specific
-        c.base
-            .add_attribute(attr_reg_config_new(RegLocationType::IndoorOutdoor)?)?;
-        // TODO: Arch-Specific
-        c.base.add_attribute(attr_location_capability_new(
-            RegLocationType::IndoorOutdoor,
-        )?)?;
-        c.base.add_attribute(attr_comm_info_new()?)?;
-
-        Ok(c)
-    }
-
-    pub fn failsafe(&self) -> Arc<FailSafe> {
-        self.failsafe.clone()
-    }
-
-    fn handle_command_armfailsafe(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+    #[command(id = "Commands::ArmFailsafe", response = "Commands::ArmFailsafeResp")]
+    fn handle_command_armfailsafe(
+        &mut self,
+        p: FailSafeParams,
+        cmd_req: &mut CommandReq,
+    ) -> Result<CommonResponse, IMStatusCode> {
         cmd_enter!("ARM Fail Safe");
 
-        let p = FailSafeParams::from_tlv(&cmd_req.data)?;
-
         if self
             .failsafe
             .arm(p.expiry_len, cmd_req.trans.session.get_session_mode())
@@ -172,53 +123,42 @@ impl GenCommCluster {
             return Err(IMStatusCode::Busy);
         }
 
-        let cmd_data = CommonResponse {
+        Ok(CommonResponse {
             error_code: CommissioningError::Ok as u8,
             debug_txt: "".to_owned(),
-        };
-        let resp = ib::InvResp::cmd_new(
-            0,
-            ID,
-            Commands::ArmFailsafeResp as u16,
-            EncodeValue::Value(&cmd_data),
-        );
-        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-        cmd_req.trans.complete();
-        Ok(())
+        })
     }
 
+    #[command(
+        id = "Commands::SetRegulatoryConfig",
+        response = "Commands::SetRegulatoryConfigResp"
+    )]
     fn handle_command_setregulatoryconfig(
         &mut self,
-        cmd_req: &mut CommandReq,
-    ) -> Result<(), IMStatusCode> {
+        p: SetRegConfigParams,
+    ) -> Result<CommonResponse, IMStatusCode> {
         cmd_enter!("Set Regulatory Config");
-        let country_code = cmd_req
-            .data
-            .find_tag(1)
-            .map_err(|_| IMStatusCode::InvalidCommand)?
-            .slice()
-            .map_err(|_| IMStatusCode::InvalidCommand)?;
-        info!("Received country code: {:?}", country_code);
+        info!(
+            "Received reg config: {}, country code: {}, breadcrumb: {}",
+            p.new_reg_config,
+            String::from_utf8_lossy(p.country_code.0),
+            p.bread_crumb
+        );
 
-        let cmd_data = CommonResponse {
+        Ok(CommonResponse {
             error_code: 0,
             debug_txt: "".to_owned(),
-        };
-        let resp = ib::InvResp::cmd_new(
-            0,
-            ID,
-            Commands::SetRegulatoryConfigResp as u16,
-            EncodeValue::Value(&cmd_data),
-        );
-        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-        cmd_req.trans.complete();
-        Ok(())
+        })
     }
 
+    #[command(
+        id = "Commands::CommissioningComplete",
+        response = "Commands::CommissioningCompleteResp"
+    )]
     fn handle_command_commissioningcomplete(
         &mut self,
         cmd_req: &mut CommandReq,
-    ) -> Result<(), IMStatusCode> {
+    ) -> Result<CommonResponse, IMStatusCode> {
         cmd_enter!("Commissioning Complete");
         let mut status: u8 = CommissioningError::Ok as u8;
 
@@ -237,19 +177,10 @@ impl GenCommCluster {
             status = CommissioningError::ErrInvalidAuth as u8;
         }
 
-        let cmd_data = CommonResponse {
+        Ok(CommonResponse {
             error_code: status,
             debug_txt: "".to_owned(),
-        };
-        let resp = ib::InvResp::cmd_new(
-            0,
-            ID,
-            Commands::CommissioningCompleteResp as u16,
-            EncodeValue::Value(&cmd_data),
-        );
-        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-        cmd_req.trans.complete();
-        Ok(())
+        })
     }
 }
 
@@ -258,3 +189,36 @@ struct CommonResponse {
     error_code: u8,
     debug_txt: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SetRegConfigParams;
+    use crate::{
+        tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType, UtfStr},
+        utils::writebuf::WriteBuf,
+    };
+
+    #[test]
+    /// The country code is a UTF-8 string, as a controller sends it
+    fn test_set_reg_config_params() {
+        let mut buf = [0u8; 32];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_struct(TagType::Anonymous).unwrap();
+        tw.u8(TagType::Context(0), 2).unwrap();
+        tw.utf8(TagType::Context(1), b"US").unwrap();
+        tw.u64(TagType::Context(2), 1).unwrap();
+        tw.end_container().unwrap();
+        assert_eq!(
+            wb.as_borrow_slice(),
+            [0x15, 0x24, 0x00, 0x02, 0x2c, 0x01, 0x02, 0x55, 0x53, 0x24, 0x02, 0x01, 0x18]
+        );
+
+        let root = get_root_node_struct(wb.as_borrow_slice()).unwrap();
+        let p = SetRegConfigParams::from_tlv(&root).unwrap();
+        assert_eq!(p.new_reg_config, 2);
+        assert_eq!(p.country_code, UtfStr(b"US"));
+        assert_eq!(p.bread_crumb, 1);
+    }
+}
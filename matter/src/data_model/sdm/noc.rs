@@ -83,18 +83,29 @@ impl NocData {
 }
 
 impl NocCluster {
+    /// The commands accepted by this cluster
+    pub const ACCEPTED_COMMANDS: &'static [u32] = &[
+        Commands::AttReq as u32,
+        Commands::CertChainReq as u32,
+        Commands::CSRReq as u32,
+        Commands::AddNOC as u32,
+        Commands::AddTrustedRootCert as u32,
+    ];
+
     pub fn new(
         dev_att: Box<dyn DevAttDataFetcher>,
         fabric_mgr: Arc<FabricMgr>,
         acl_mgr: Arc<AclMgr>,
         failsafe: Arc<FailSafe>,
     ) -> Result<Box<Self>, Error> {
+        let mut base = Cluster::new(ID)?;
+        base.set_accepted_commands(Self::ACCEPTED_COMMANDS)?;
         Ok(Box::new(Self {
             dev_att,
             fabric_mgr,
             acl_mgr,
             failsafe,
-            base: Cluster::new(ID)?,
+            base,
         }))
     }
 
@@ -28,6 +28,7 @@ pub struct AccessControlCluster {
 
 impl AccessControlCluster {
     pub fn new(acl_mgr: Arc<AclMgr>) -> Result<Box<Self>, Error> {
+        let entries_per_fabric = acl_mgr.entries_per_fabric();
         let mut c = Box::new(AccessControlCluster {
             base: Cluster::new(ID)?,
             acl_mgr,
@@ -36,7 +37,8 @@ impl AccessControlCluster {
         c.base.add_attribute(attr_extension_new()?)?;
         c.base.add_attribute(attr_subjects_per_entry_new()?)?;
         c.base.add_attribute(attr_targets_per_entry_new()?)?;
-        c.base.add_attribute(attr_entries_per_fabric_new()?)?;
+        c.base
+            .add_attribute(attr_entries_per_fabric_new(entries_per_fabric)?)?;
         Ok(c)
     }
 
@@ -163,10 +165,10 @@ fn attr_targets_per_entry_new() -> Result<Attribute, Error> {
     )
 }
 
-fn attr_entries_per_fabric_new() -> Result<Attribute, Error> {
+fn attr_entries_per_fabric_new(entries_per_fabric: usize) -> Result<Attribute, Error> {
     Attribute::new(
         Attributes::EntriesPerFabric as u16,
-        AttrValue::Uint16(acl::ENTRIES_PER_FABRIC as u16),
+        AttrValue::Uint16(entries_per_fabric as u16),
         Access::RV,
         Quality::FIXED,
     )
@@ -197,7 +199,7 @@ mod tests {
         let mut writebuf = WriteBuf::new(&mut buf, buf_len);
         let mut tw = TLVWriter::new(&mut writebuf);
 
-        let acl_mgr = Arc::new(AclMgr::new_with(false).unwrap());
+        let acl_mgr = Arc::new(AclMgr::new_with(None).unwrap());
         let mut acl = AccessControlCluster::new(acl_mgr.clone()).unwrap();
 
         let new = AclEntry::new(2, Privilege::VIEW, AuthMode::Case);
@@ -226,7 +228,7 @@ mod tests {
         let mut tw = TLVWriter::new(&mut writebuf);
 
         // Add 3 ACLs, belonging to fabric index 2, 1 and 2, in that order
-        let acl_mgr = Arc::new(AclMgr::new_with(false).unwrap());
+        let acl_mgr = Arc::new(AclMgr::new_with(None).unwrap());
         let mut verifier = [
             AclEntry::new(2, Privilege::VIEW, AuthMode::Case),
             AclEntry::new(1, Privilege::VIEW, AuthMode::Case),
@@ -261,7 +263,7 @@ mod tests {
     /// - The listindex used for delete should be relative to the current fabric
     fn acl_cluster_delete() {
         // Add 3 ACLs, belonging to fabric index 2, 1 and 2, in that order
-        let acl_mgr = Arc::new(AclMgr::new_with(false).unwrap());
+        let acl_mgr = Arc::new(AclMgr::new_with(None).unwrap());
         let input = [
             AclEntry::new(2, Privilege::VIEW, AuthMode::Case),
             AclEntry::new(1, Privilege::VIEW, AuthMode::Case),
@@ -297,7 +299,7 @@ mod tests {
         let mut writebuf = WriteBuf::new(&mut buf, buf_len);
 
         // Add 3 ACLs, belonging to fabric index 2, 1 and 2, in that order
-        let acl_mgr = Arc::new(AclMgr::new_with(false).unwrap());
+        let acl_mgr = Arc::new(AclMgr::new_with(None).unwrap());
         let input = [
             AclEntry::new(2, Privilege::VIEW, AuthMode::Case),
             AclEntry::new(1, Privilege::VIEW, AuthMode::Case),
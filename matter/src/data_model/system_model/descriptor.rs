@@ -4,13 +4,13 @@ use crate::data_model::core::DataModel;
 use crate::data_model::objects::*;
 use crate::error::*;
 use crate::interaction_model::messages::GenericPath;
-use crate::tlv::{TLVWriter, TagType};
+use crate::tlv::{TLVWriter, TagType, ToTLV};
 use log::error;
 
 pub const ID: u32 = 0x001D;
 
 #[derive(FromPrimitive)]
-enum Attributes {
+pub enum Attributes {
     DeviceTypeList = 0,
     ServerList = 1,
     ClientList = 2,
@@ -30,10 +30,24 @@ impl DescriptorCluster {
             data_model,
             base: Cluster::new(ID)?,
         });
+        c.base.add_attribute(attr_devtypelist_new()?)?;
         c.base.add_attribute(attr_serverlist_new()?)?;
+        c.base.add_attribute(attr_clientlist_new()?)?;
+        c.base.add_attribute(attr_partslist_new()?)?;
         Ok(c)
     }
 
+    fn encode_devtype_list(&self, tag: TagType, tw: &mut TLVWriter) {
+        let _ = tw.start_array(tag);
+        let node = self.data_model.node.read().unwrap();
+        if let Ok(e) = node.get_endpoint(self.endpoint_id) {
+            for d in e.device_types() {
+                let _ = d.to_tlv(tw, TagType::Anonymous);
+            }
+        }
+        let _ = tw.end_container();
+    }
+
     fn encode_server_list(&self, tag: TagType, tw: &mut TLVWriter) {
         let path = GenericPath {
             endpoint: Some(self.endpoint_id),
@@ -48,6 +62,26 @@ impl DescriptorCluster {
         });
         let _ = tw.end_container();
     }
+
+    fn encode_client_list(&self, tag: TagType, tw: &mut TLVWriter) {
+        let _ = tw.start_array(tag);
+        let node = self.data_model.node.read().unwrap();
+        if let Ok(e) = node.get_endpoint(self.endpoint_id) {
+            for c in e.client_clusters() {
+                let _ = tw.u32(TagType::Anonymous, *c);
+            }
+        }
+        let _ = tw.end_container();
+    }
+
+    fn encode_parts_list(&self, tag: TagType, tw: &mut TLVWriter) {
+        let _ = tw.start_array(tag);
+        let dm = self.data_model.node.read().unwrap();
+        dm.for_each_part(self.endpoint_id, |endpoint_id| {
+            let _ = tw.u16(TagType::Anonymous, endpoint_id);
+        });
+        let _ = tw.end_container();
+    }
 }
 
 impl ClusterType for DescriptorCluster {
@@ -60,9 +94,18 @@ impl ClusterType for DescriptorCluster {
 
     fn read_custom_attribute(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
         match num::FromPrimitive::from_u16(attr.attr_id) {
+            Some(Attributes::DeviceTypeList) => encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                self.encode_devtype_list(tag, tw)
+            })),
             Some(Attributes::ServerList) => encoder.encode(EncodeValue::Closure(&|tag, tw| {
                 self.encode_server_list(tag, tw)
             })),
+            Some(Attributes::ClientList) => encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                self.encode_client_list(tag, tw)
+            })),
+            Some(Attributes::PartsList) => encoder.encode(EncodeValue::Closure(&|tag, tw| {
+                self.encode_parts_list(tag, tw)
+            })),
 
             _ => {
                 error!("Attribute not supported: this shouldn't happen");
@@ -71,6 +114,15 @@ impl ClusterType for DescriptorCluster {
     }
 }
 
+fn attr_devtypelist_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::DeviceTypeList as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
 fn attr_serverlist_new() -> Result<Attribute, Error> {
     Attribute::new(
         Attributes::ServerList as u16,
@@ -79,3 +131,21 @@ fn attr_serverlist_new() -> Result<Attribute, Error> {
         Quality::NONE,
     )
 }
+
+fn attr_clientlist_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::ClientList as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+fn attr_partslist_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::PartsList as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
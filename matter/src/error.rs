@@ -1,6 +1,6 @@
 use std::{array::TryFromSliceError, fmt, sync::PoisonError, time::SystemTimeError};
 
-use async_channel::{SendError, TryRecvError};
+use async_channel::{SendError, TryRecvError, TrySendError};
 use log::error;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -43,6 +43,8 @@ pub enum Error {
     RwLock,
     TLVNotFound,
     TLVTypeMismatch,
+    // The member of a derived structure or union that couldn't be decoded, as Type::member
+    TLVField(&'static str),
     TruncatedPacket,
 }
 
@@ -94,6 +96,13 @@ impl<T> From<SendError<T>> for Error {
     }
 }
 
+impl<T> From<TrySendError<T>> for Error {
+    fn from(e: TrySendError<T>) -> Self {
+        error!("Error in channel try_send {}", e);
+        Self::Invalid
+    }
+}
+
 impl From<TryRecvError> for Error {
     fn from(e: TryRecvError) -> Self {
         error!("Error in channel try_recv {}", e);
@@ -106,3 +115,30 @@ impl<'a> fmt::Display for Error {
         write!(f, "{:?}", self)
     }
 }
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        error!("Error in TLV serialization: {}", msg);
+        Self::Invalid
+    }
+}
+
+// Deserializing fails on purpose when probing, such as for an Option or an untagged enum, so
+// these errors aren't logged
+#[cfg(feature = "serde")]
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Self::InvalidData
+    }
+
+    fn invalid_type(_unexp: serde::de::Unexpected, _exp: &dyn serde::de::Expected) -> Self {
+        Self::TLVTypeMismatch
+    }
+
+    fn missing_field(_field: &'static str) -> Self {
+        Self::TLVNotFound
+    }
+}
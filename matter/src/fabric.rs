@@ -9,8 +9,8 @@ use crate::{
     crypto::{self, crypto_dummy::KeyPairDummy, hkdf_sha256, CryptoKeyPair, HmacSha256, KeyPair},
     error::Error,
     group_keys::KeySet,
-    mdns::{self, Mdns},
-    sys::{Psm, SysMdnsService},
+    mdns::{self, Mdns, MdnsService},
+    sys::Psm,
 };
 
 const MAX_CERT_TLV_LEN: usize = 300;
@@ -39,7 +39,7 @@ pub struct Fabric {
     pub noc: Cert,
     pub ipk: KeySet,
     compressed_id: [u8; COMPRESSED_FABRIC_ID_LEN],
-    mdns_service: Option<SysMdnsService>,
+    mdns_service: Option<MdnsService>,
 }
 
 impl Fabric {
@@ -66,22 +66,27 @@ impl Fabric {
         };
         Fabric::get_compressed_id(f.root_ca.get_pubkey(), fabric_id, &mut f.compressed_id)?;
         f.ipk = KeySet::new(ipk, &f.compressed_id)?;
+        Ok(f)
+    }
 
+    /// Publish the operational service of this fabric
+    ///
+    /// The service stays published for as long as the fabric exists
+    fn publish(&mut self, mdns: &Mdns) -> Result<(), Error> {
         let mut mdns_service_name = String::with_capacity(33);
-        for c in f.compressed_id {
+        for c in self.compressed_id {
             mdns_service_name.push_str(&format!("{:02X}", c));
         }
         mdns_service_name.push('-');
         let mut node_id_be: [u8; 8] = [0; 8];
-        BigEndian::write_u64(&mut node_id_be, node_id);
+        BigEndian::write_u64(&mut node_id_be, self.node_id);
         for c in node_id_be {
             mdns_service_name.push_str(&format!("{:02X}", c));
         }
         info!("MDNS Service Name: {}", mdns_service_name);
-        f.mdns_service = Some(
-            Mdns::get()?.publish_service(&mdns_service_name, mdns::ServiceMode::Commissioned)?,
-        );
-        Ok(f)
+        self.mdns_service =
+            Some(mdns.publish_service(&mdns_service_name, mdns::ServiceMode::Commissioned)?);
+        Ok(())
     }
 
     pub fn dummy() -> Result<Self, Error> {
@@ -204,16 +209,21 @@ pub struct FabricMgrInner {
 pub struct FabricMgr {
     inner: RwLock<FabricMgrInner>,
     psm: Arc<Mutex<Psm>>,
+    mdns: Arc<Mdns>,
 }
 
 impl FabricMgr {
-    pub fn new() -> Result<Self, Error> {
+    /// Create a Fabric Manager that persists the fabrics in the given store
+    ///
+    /// The operational service of each fabric is published with the given mDNS handler
+    pub fn new(psm: Arc<Mutex<Psm>>, mdns: Arc<Mdns>) -> Result<Self, Error> {
         let dummy_fabric = Fabric::dummy()?;
         let mut mgr = FabricMgrInner::default();
         mgr.fabrics[0] = Some(dummy_fabric);
         let mut fm = Self {
             inner: RwLock::new(mgr),
-            psm: Psm::get()?,
+            psm,
+            mdns,
         };
         fm.load()?;
         Ok(fm)
@@ -229,15 +239,16 @@ impl FabricMgr {
         let psm = self.psm.lock().unwrap();
         for i in 0..MAX_SUPPORTED_FABRICS {
             let result = Fabric::load(i, &psm);
-            if let Ok(fabric) = result {
+            if let Ok(mut fabric) = result {
                 info!("Adding new fabric at index {}", i);
+                fabric.publish(&self.mdns)?;
                 mgr.fabrics[i] = Some(fabric);
             }
         }
         Ok(())
     }
 
-    pub fn add(&self, f: Fabric) -> Result<u8, Error> {
+    pub fn add(&self, mut f: Fabric) -> Result<u8, Error> {
         let mut mgr = self.inner.write()?;
         let index = mgr
             .fabrics
@@ -246,6 +257,7 @@ impl FabricMgr {
             .ok_or(Error::NoSpace)?;
 
         self.store(index, &f)?;
+        f.publish(&self.mdns)?;
 
         mgr.fabrics[index] = Some(f);
         Ok(index as u8)
@@ -1,27 +1,14 @@
-use std::sync::{Arc, Mutex, Once};
-
 use crate::{crypto, error::Error};
 
 // This is just makeshift implementation for now, not used anywhere
+#[derive(Default)]
 pub struct GroupKeys {}
 
-static mut G_GRP_KEYS: Option<Arc<Mutex<GroupKeys>>> = None;
-static INIT: Once = Once::new();
-
 impl GroupKeys {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {}
     }
 
-    pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
-        unsafe {
-            INIT.call_once(|| {
-                G_GRP_KEYS = Some(Arc::new(Mutex::new(GroupKeys::new())));
-            });
-            Ok(G_GRP_KEYS.as_ref().ok_or(Error::Invalid)?.clone())
-        }
-    }
-
     pub fn insert_key() -> Result<(), Error> {
         Ok(())
     }
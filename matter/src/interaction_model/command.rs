@@ -6,7 +6,7 @@ use super::InteractionModel;
 use super::Transaction;
 use crate::{
     error::*,
-    tlv::{get_root_node_struct, print_tlv_list, FromTLV, TLVElement, TLVWriter, TagType},
+    tlv::{get_root_node_struct_strict, print_tlv_list, FromTLV, TLVElement, TLVWriter, TagType},
     transport::{packet::Packet, proto_demux::ResponseRequired},
 };
 use log::error;
@@ -36,7 +36,7 @@ impl InteractionModel {
         proto_tx.set_proto_opcode(OpCode::InvokeResponse as u8);
 
         let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
-        let root = get_root_node_struct(rx_buf)?;
+        let root = get_root_node_struct_strict(rx_buf)?;
         let inv_req = InvReq::from_tlv(&root)?;
 
         tw.start_struct(TagType::Anonymous)?;
@@ -122,6 +122,12 @@ pub enum IMStatusCode {
     NeedsTimedInteraction = 0xc6,
 }
 
+impl IMStatusCode {
+    /// The spec assigns the same code to both INVALID_VALUE and CONSTRAINT_ERROR
+    #[allow(non_upper_case_globals)]
+    pub const InvalidValue: IMStatusCode = IMStatusCode::ConstraintError;
+}
+
 impl From<Error> for IMStatusCode {
     fn from(e: Error) -> Self {
         match e {
@@ -1,7 +1,7 @@
 use crate::{
     error::Error,
     interaction_model::core::OpCode,
-    tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType},
+    tlv::{get_root_node_struct_strict, FromTLV, TLVWriter, TagType},
     transport::{packet::Packet, proto_demux::ResponseRequired},
 };
 
@@ -20,7 +20,7 @@ impl InteractionModel {
         proto_tx.set_proto_opcode(OpCode::ReportData as u8);
 
         let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
-        let root = get_root_node_struct(rx_buf)?;
+        let root = get_root_node_struct_strict(rx_buf)?;
         let read_req = ReadReq::from_tlv(&root)?;
 
         tw.start_struct(TagType::Anonymous)?;
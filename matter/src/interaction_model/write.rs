@@ -2,7 +2,7 @@ use log::error;
 
 use crate::{
     error::Error,
-    tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType},
+    tlv::{get_root_node_struct_strict, FromTLV, TLVWriter, TagType},
     transport::{packet::Packet, proto_demux::ResponseRequired},
 };
 
@@ -18,7 +18,7 @@ impl InteractionModel {
         proto_tx.set_proto_opcode(OpCode::WriteResponse as u8);
 
         let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
-        let root = get_root_node_struct(rx_buf)?;
+        let root = get_root_node_struct_strict(rx_buf)?;
         let write_req = WriteReq::from_tlv(&root)?;
         let supress_response = write_req.supress_response.unwrap_or_default();
 
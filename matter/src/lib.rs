@@ -34,6 +34,9 @@
 //!     pid: 0xFFF1,
 //!     hw_ver: 2,
 //!     sw_ver: 1,
+//!     vendor_name: "Test Vendor".to_owned(),
+//!     product_name: "OnOff Light".to_owned(),
+//!     ..Default::default()
 //! };
 //!
 //! /// Get the Matter Object
@@ -52,6 +55,7 @@
 
 pub mod acl;
 pub mod cert;
+pub mod clock;
 pub mod core;
 pub mod crypto;
 pub mod data_model;
@@ -59,6 +63,7 @@ pub mod error;
 pub mod fabric;
 pub mod group_keys;
 pub mod interaction_model;
+pub mod limits;
 pub mod mdns;
 pub mod secure_channel;
 pub mod sys;
@@ -67,3 +72,9 @@ pub mod transport;
 pub mod utils;
 
 pub use crate::core::*;
+
+// The code generated by the derive macros logs through matter::log, so that the crates using them
+// don't need a log dependency of their own. This lets that path resolve within this crate too.
+extern crate self as matter;
+#[doc(hidden)]
+pub use log;
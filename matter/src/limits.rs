@@ -0,0 +1,93 @@
+use crate::{
+    acl,
+    data_model::objects::{CLUSTERS_PER_ENDPT, ENDPTS_PER_ACC},
+    error::Error,
+    sys::{DEFAULT_PACKET_POOL_SIZE, MAX_PACKET_POOL_SIZE},
+    transport::{exchange, proto_demux, session},
+};
+
+/// The capacities of the various resources of a Matter object
+///
+/// The defaults are suitable for a typical device. Exhausting any of these at run time results
+/// in an error, and not a panic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// The number of secure sessions, the least recently used session is evicted beyond this
+    pub max_sessions: usize,
+    /// The number of exchanges that can be open at the same time
+    pub max_exchanges: usize,
+    /// The number of standalone acknowledgements that are sent in one go
+    pub max_mrp_entries: usize,
+    /// The number of protocols, the highest protocol ID that can be registered is one less
+    pub max_protocols: usize,
+    /// The number of endpoints in the Data Model
+    pub max_endpoints: usize,
+    /// The number of server clusters on each endpoint
+    pub clusters_per_endpoint: usize,
+    /// The number of Access Control entries for each fabric
+    pub acl_entries_per_fabric: usize,
+    /// The number of packet buffers, this can't be more than [MAX_PACKET_POOL_SIZE]
+    pub packet_pool_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_sessions: session::MAX_SESSIONS,
+            max_exchanges: exchange::MAX_EXCHANGES,
+            max_mrp_entries: exchange::MAX_MRP_ENTRIES,
+            max_protocols: proto_demux::MAX_PROTOCOLS,
+            max_endpoints: ENDPTS_PER_ACC,
+            clusters_per_endpoint: CLUSTERS_PER_ENDPT,
+            acl_entries_per_fabric: acl::ENTRIES_PER_FABRIC,
+            packet_pool_size: DEFAULT_PACKET_POOL_SIZE,
+        }
+    }
+}
+
+impl Limits {
+    /// Check that the limits are usable
+    ///
+    /// Every limit must be at least 1, and the packet pool can't be larger than what the
+    /// platform reserves for it
+    pub fn validate(&self) -> Result<(), Error> {
+        let limits = [
+            self.max_sessions,
+            self.max_exchanges,
+            self.max_mrp_entries,
+            self.max_protocols,
+            self.max_endpoints,
+            self.clusters_per_endpoint,
+            self.acl_entries_per_fabric,
+            self.packet_pool_size,
+        ];
+        if limits.contains(&0) || self.packet_pool_size > MAX_PACKET_POOL_SIZE {
+            Err(Error::Invalid)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Limits;
+    use crate::{error::Error, sys::MAX_PACKET_POOL_SIZE};
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Limits::default().validate(), Ok(()));
+
+        let limits = Limits {
+            max_exchanges: 0,
+            ..Default::default()
+        };
+        assert_eq!(limits.validate(), Err(Error::Invalid));
+
+        let limits = Limits {
+            packet_pool_size: MAX_PACKET_POOL_SIZE + 1,
+            ..Default::default()
+        };
+        assert_eq!(limits.validate(), Err(Error::Invalid));
+    }
+}
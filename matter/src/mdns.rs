@@ -1,12 +1,41 @@
-use std::sync::{Arc, Mutex, Once};
+use std::{any::Any, sync::Mutex};
 
-use crate::{
-    error::Error,
-    sys::{sys_publish_service, SysMdnsService},
-    transport::udp::MATTER_PORT,
-};
+use crate::{error::Error, sys::sys_publish_service, transport::udp::MATTER_PORT};
+
+/// A published mDNS service, the service is withdrawn when this is dropped
+pub type MdnsService = Box<dyn Any>;
+
+/// The means to publish mDNS services
+pub trait MdnsBackend: Send {
+    /// Publish a mDNS service
+    /// name - can be a service name (comma separated subtypes may follow)
+    /// regtype - registration type (e.g. _matter_.tcp etc)
+    /// port - the port
+    /// txt_kvs - the key/value pairs of the TXT record
+    fn publish(
+        &self,
+        name: &str,
+        regtype: &str,
+        port: u16,
+        txt_kvs: &[[&str; 2]],
+    ) -> Result<MdnsService, Error>;
+}
+
+/// The mDNS backend of the platform
+pub struct SysMdnsBackend;
+
+impl MdnsBackend for SysMdnsBackend {
+    fn publish(
+        &self,
+        name: &str,
+        regtype: &str,
+        port: u16,
+        txt_kvs: &[[&str; 2]],
+    ) -> Result<MdnsService, Error> {
+        Ok(Box::new(sys_publish_service(name, regtype, port, txt_kvs)?))
+    }
+}
 
-#[derive(Default)]
 /// The mDNS service handler
 pub struct MdnsInner {
     /// Vendor ID
@@ -15,6 +44,11 @@ pub struct MdnsInner {
     pid: u16,
     /// Discriminator
     discriminator: u16,
+    /// The port that is advertised
+    port: u16,
+    /// Whether TCP support is advertised
+    tcp: bool,
+    backend: Box<dyn MdnsBackend>,
 }
 
 pub struct Mdns {
@@ -24,33 +58,46 @@ pub struct Mdns {
 const SHORT_DISCRIMINATOR_MASK: u16 = 0x700;
 const SHORT_DISCRIMINATOR_SHIFT: u16 = 8;
 
-static mut G_MDNS: Option<Arc<Mdns>> = None;
-static INIT: Once = Once::new();
+// TCP is supported
+const TXT_TCP: [&str; 2] = ["T", "1"];
+
+/// Whether the TXT record of a peer's service advertises that it accepts TCP connections
+///
+/// A value of 1 is how TCP support is advertised by this stack. Newer revisions of the
+/// specification use a bitmap instead, where bit 2 is set for the nodes that accept TCP
+/// connections.
+pub fn tcp_supported(txt_kvs: &[[&str; 2]]) -> bool {
+    match txt_kvs.iter().find(|[key, _]| *key == "T") {
+        Some([_, value]) => matches!(value.parse::<u8>(), Ok(t) if t == 1 || t & 0x04 != 0),
+        None => false,
+    }
+}
 
 pub enum ServiceMode {
     Commissioned,
     Commissionable,
 }
 
+impl Default for Mdns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Mdns {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             inner: Mutex::new(MdnsInner {
-                ..Default::default()
+                vid: 0,
+                pid: 0,
+                discriminator: 0,
+                port: MATTER_PORT,
+                tcp: false,
+                backend: Box::new(SysMdnsBackend),
             }),
         }
     }
 
-    /// Get a handle to the globally unique mDNS instance
-    pub fn get() -> Result<Arc<Self>, Error> {
-        unsafe {
-            INIT.call_once(|| {
-                G_MDNS = Some(Arc::new(Mdns::new()));
-            });
-            Ok(G_MDNS.as_ref().ok_or(Error::Invalid)?.clone())
-        }
-    }
-
     /// Set mDNS service specific values
     /// Values like vid, pid, discriminator etc
     // TODO: More things like device-type etc can be added here
@@ -61,24 +108,65 @@ impl Mdns {
         inner.discriminator = discriminator;
     }
 
+    /// Set the port that is advertised in the services published from now on
+    pub fn set_port(&self, port: u16) {
+        self.inner.lock().unwrap().port = port;
+    }
+
+    /// Advertise TCP support, with the T key of the TXT record, in the services published from
+    /// now on
+    pub fn set_tcp(&self, tcp: bool) {
+        self.inner.lock().unwrap().tcp = tcp;
+    }
+
+    /// Set the backend that publishes the services from now on
+    pub fn set_backend(&self, backend: Box<dyn MdnsBackend>) {
+        self.inner.lock().unwrap().backend = backend;
+    }
+
     /// Publish a mDNS service
     /// name - is the service name (comma separated subtypes may follow)
     /// mode - the current service mode
-    pub fn publish_service(&self, name: &str, mode: ServiceMode) -> Result<SysMdnsService, Error> {
+    pub fn publish_service(&self, name: &str, mode: ServiceMode) -> Result<MdnsService, Error> {
+        let inner = self.inner.lock().unwrap();
         match mode {
             ServiceMode::Commissioned => {
-                sys_publish_service(name, "_matter._tcp", MATTER_PORT, &[])
+                let txt_kvs: &[[&str; 2]] = if inner.tcp { &[TXT_TCP] } else { &[] };
+                inner
+                    .backend
+                    .publish(name, "_matter._tcp", inner.port, txt_kvs)
             }
             ServiceMode::Commissionable => {
-                let inner = self.inner.lock().unwrap();
                 let short =
                     (inner.discriminator & SHORT_DISCRIMINATOR_MASK) >> SHORT_DISCRIMINATOR_SHIFT;
                 let serv_type = format!("_matterc._udp,_S{},_L{}", short, inner.discriminator);
 
                 let str_discriminator = format!("{}", inner.discriminator);
-                let txt_kvs = [["D", &str_discriminator], ["CM", "1"]];
-                sys_publish_service(name, &serv_type, MATTER_PORT, &txt_kvs)
+                let mut txt_kvs = vec![["D", &str_discriminator], ["CM", "1"]];
+                if inner.tcp {
+                    txt_kvs.push(TXT_TCP);
+                }
+                inner
+                    .backend
+                    .publish(name, &serv_type, inner.port, &txt_kvs)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::tcp_supported;
+
+    #[test]
+    fn test_tcp_supported() {
+        assert!(tcp_supported(&[["D", "250"], ["T", "1"]]));
+        // A TCP server, in the bitmap of the newer revisions
+        assert!(tcp_supported(&[["T", "6"]]));
+        // A TCP client only
+        assert!(!tcp_supported(&[["T", "2"]]));
+        assert!(!tcp_supported(&[["T", "0"]]));
+        assert!(!tcp_supported(&[["T", "yes"]]));
+        assert!(!tcp_supported(&[["D", "250"]]));
+    }
+}
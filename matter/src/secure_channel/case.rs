@@ -11,7 +11,7 @@ use crate::{
     fabric::{Fabric, FabricMgr, FabricMgrInner},
     secure_channel::common,
     secure_channel::common::SCStatusCodes,
-    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVWriter, TagType},
+    tlv::{get_root_node_struct_strict, FromTLV, OctetStr, TLVElement, TLVWriter, TagType},
     transport::{
         network::Address,
         proto_demux::ProtoCtx,
@@ -54,11 +54,12 @@ impl CaseSession {
 
 pub struct Case {
     fabric_mgr: Arc<FabricMgr>,
+    work_q: WorkQ,
 }
 
 impl Case {
-    pub fn new(fabric_mgr: Arc<FabricMgr>) -> Self {
-        Self { fabric_mgr }
+    pub fn new(fabric_mgr: Arc<FabricMgr>, work_q: WorkQ) -> Self {
+        Self { fabric_mgr, work_q }
     }
 
     pub fn handle_casesigma3(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
@@ -85,7 +86,7 @@ impl Case {
         // Safe to unwrap here
         let fabric = fabric.as_ref().as_ref().unwrap();
 
-        let root = get_root_node_struct(ctx.rx.as_borrow_slice())?;
+        let root = get_root_node_struct_strict(ctx.rx.as_borrow_slice())?;
         let encrypted = root.find_tag(1)?.slice()?;
 
         let mut decrypted: [u8; 800] = [0; 800];
@@ -99,7 +100,7 @@ impl Case {
         let len = Case::get_sigma3_decryption(fabric.ipk.op_key(), &case_session, decrypted)?;
         let decrypted = &decrypted[..len];
 
-        let root = get_root_node_struct(decrypted)?;
+        let root = get_root_node_struct_strict(decrypted)?;
         let d = Sigma3Decrypt::from_tlv(&root)?;
 
         let initiator_noc = Cert::new(d.initiator_noc.0)?;
@@ -144,7 +145,7 @@ impl Case {
             &case_session,
         )?;
         // Queue a transport mgr request to add a new session
-        WorkQ::get()?.sync_send(Msg::NewSession(clone_data))?;
+        self.work_q.sync_send(Msg::NewSession(clone_data))?;
 
         common::create_sc_status_report(
             &mut ctx.tx,
@@ -159,7 +160,7 @@ impl Case {
 
     pub fn handle_casesigma1(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
         let rx_buf = ctx.rx.as_borrow_slice();
-        let root = get_root_node_struct(rx_buf)?;
+        let root = get_root_node_struct_strict(rx_buf)?;
         let r = Sigma1Req::from_tlv(&root)?;
 
         let local_fabric_idx = self
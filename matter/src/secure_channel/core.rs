@@ -1,12 +1,16 @@
 use std::sync::Arc;
 
 use crate::{
+    clock::{Clock, SystemClock},
     error::*,
     fabric::FabricMgr,
-    mdns::{self, Mdns},
+    mdns::{self, Mdns, MdnsService},
     secure_channel::{common::*, pake::PAKE},
-    sys::SysMdnsService,
-    transport::proto_demux::{self, ProtoCtx, ResponseRequired},
+    sys::SPAKE2_ITERATION_COUNT,
+    transport::{
+        proto_demux::{self, ProtoCtx, ResponseRequired},
+        queue::WorkQ,
+    },
 };
 use log::{error, info};
 use num;
@@ -19,22 +23,61 @@ use super::case::Case;
 
 pub struct SecureChannel {
     case: Case,
-    pake: Option<(PAKE, SysMdnsService)>,
+    pake: Option<(PAKE, MdnsService)>,
+    pbkdf_iterations: u32,
+    mdns: Arc<Mdns>,
+    work_q: WorkQ,
+    clock: Arc<dyn Clock>,
 }
 
 impl SecureChannel {
-    pub fn new(fabric_mgr: Arc<FabricMgr>) -> SecureChannel {
+    /// Create the Secure Channel
+    ///
+    /// The commissionable service is published with the given mDNS handler, and the established
+    /// sessions are handed over to the Transport Manager through the work_q
+    pub fn new(fabric_mgr: Arc<FabricMgr>, mdns: Arc<Mdns>, work_q: WorkQ) -> SecureChannel {
+        SecureChannel::new_with(
+            fabric_mgr,
+            mdns,
+            work_q,
+            SPAKE2_ITERATION_COUNT,
+            Arc::new(SystemClock::new()),
+        )
+    }
+
+    /// Create the Secure Channel, the commissioning window uses the given PBKDF2 iteration count
+    /// and clock
+    pub fn new_with(
+        fabric_mgr: Arc<FabricMgr>,
+        mdns: Arc<Mdns>,
+        work_q: WorkQ,
+        pbkdf_iterations: u32,
+        clock: Arc<dyn Clock>,
+    ) -> SecureChannel {
         SecureChannel {
             pake: None,
-            case: Case::new(fabric_mgr),
+            case: Case::new(fabric_mgr, work_q.clone()),
+            pbkdf_iterations,
+            mdns,
+            work_q,
+            clock,
         }
     }
 
     pub fn open_comm_window(&mut self, salt: &[u8; 16], passwd: u32) -> Result<(), Error> {
         let name: u64 = rand::thread_rng().gen_range(0..0xFFFFFFFFFFFFFFFF);
         let name = format!("{:016X}", name);
-        let mdns = Mdns::get()?.publish_service(&name, mdns::ServiceMode::Commissionable)?;
-        self.pake = Some((PAKE::new(salt, passwd), mdns));
+        let mdns = self
+            .mdns
+            .publish_service(&name, mdns::ServiceMode::Commissionable)?;
+        let pake = PAKE::new_with(
+            salt,
+            passwd,
+            self.pbkdf_iterations,
+            self.work_q.clone(),
+            self.clock.clone(),
+        );
+        self.pake = Some((pake, mdns));
         Ok(())
     }
 
@@ -1,14 +1,17 @@
-use std::time::{Duration, SystemTime};
+use std::{sync::Arc, time::Duration};
 
 use super::{
     common::{create_sc_status_report, SCStatusCodes},
     spake2p::Spake2P,
 };
 use crate::{
+    clock::{Clock, SystemClock},
     crypto,
     error::Error,
     sys::SPAKE2_ITERATION_COUNT,
-    tlv::{self, get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV},
+    tlv::{
+        self, get_root_node_struct_strict, FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV,
+    },
     transport::{
         exchange::ExchangeCtx,
         network::Address,
@@ -25,24 +28,26 @@ use rand::prelude::*;
 // We create a Spake2p object and set it up in the exchange-data. This object then
 // handles Spake2+ specific stuff.
 
+/// The lowest PBKDF2 iteration count allowed by the spec
+pub const PBKDF_MIN_ITERATIONS: u32 = 1000;
+/// The highest PBKDF2 iteration count allowed by the spec
+pub const PBKDF_MAX_ITERATIONS: u32 = 100000;
+
 const PASE_DISCARD_TIMEOUT_SECS: Duration = Duration::from_secs(60);
 
 const SPAKE2_SESSION_KEYS_INFO: [u8; 11] = *b"SessionKeys";
 
 struct SessionData {
-    start_time: SystemTime,
+    // As per the clock of the PAKE handler
+    start_time: Duration,
     exch_id: u16,
     peer_addr: Address,
     spake2p: Box<Spake2P>,
 }
 
 impl SessionData {
-    fn is_sess_expired(&self) -> Result<bool, Error> {
-        if SystemTime::now().duration_since(self.start_time)? > PASE_DISCARD_TIMEOUT_SECS {
-            Ok(true)
-        } else {
-            Ok(false)
-        }
+    fn is_sess_expired(&self, now: Duration) -> bool {
+        now.saturating_sub(self.start_time) > PASE_DISCARD_TIMEOUT_SECS
     }
 }
 
@@ -74,9 +79,9 @@ impl PakeState {
         }
     }
 
-    fn make_in_progress(&mut self, spake2p: Box<Spake2P>, exch_ctx: &ExchangeCtx) {
+    fn make_in_progress(&mut self, spake2p: Box<Spake2P>, exch_ctx: &ExchangeCtx, now: Duration) {
         *self = PakeState::InProgress(SessionData {
-            start_time: SystemTime::now(),
+            start_time: now,
             spake2p,
             exch_id: exch_ctx.exch.get_id(),
             peer_addr: exch_ctx.sess.get_peer_addr(),
@@ -94,20 +99,45 @@ impl Default for PakeState {
     }
 }
 
-#[derive(Default)]
 pub struct PAKE {
     salt: [u8; 16],
     passwd: u32,
+    iterations: u32,
     state: PakeState,
+    work_q: WorkQ,
+    clock: Arc<dyn Clock>,
 }
 
 impl PAKE {
-    pub fn new(salt: &[u8; 16], passwd: u32) -> Self {
+    /// Create a PAKE handler, the sessions it establishes are handed over through the work_q
+    pub fn new(salt: &[u8; 16], passwd: u32, work_q: WorkQ) -> Self {
+        PAKE::new_with(
+            salt,
+            passwd,
+            SPAKE2_ITERATION_COUNT,
+            work_q,
+            Arc::new(SystemClock::new()),
+        )
+    }
+
+    /// Create a PAKE handler, with the given PBKDF2 iteration count
+    ///
+    /// A session that is in progress is discarded after a timeout, as per the given clock
+    pub fn new_with(
+        salt: &[u8; 16],
+        passwd: u32,
+        iterations: u32,
+        work_q: WorkQ,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         // TODO: Can any PBKDF2 calculation be pre-computed here
         PAKE {
             passwd,
             salt: *salt,
-            ..Default::default()
+            iterations,
+            state: PakeState::default(),
+            work_q,
+            clock,
         }
     }
 
@@ -144,7 +174,7 @@ impl PAKE {
                 .copy_from_slice(&session_keys[32..48]);
 
             // Queue a transport mgr request to add a new session
-            WorkQ::get()?.sync_send(Msg::NewSession(clone_data))?;
+            self.work_q.sync_send(Msg::NewSession(clone_data))?;
         }
 
         create_sc_status_report(&mut ctx.tx, status_code, None)?;
@@ -160,7 +190,7 @@ impl PAKE {
         let mut pB: [u8; 65] = [0; 65];
         let mut cB: [u8; 32] = [0; 32];
         sd.spake2p
-            .start_verifier(self.passwd, SPAKE2_ITERATION_COUNT, &self.salt)?;
+            .start_verifier(self.passwd, self.iterations, &self.salt)?;
         sd.spake2p.handle_pA(pA, &mut pB, &mut cB)?;
 
         let mut tw = TLVWriter::new(ctx.tx.get_writebuf()?);
@@ -178,18 +208,19 @@ impl PAKE {
     pub fn handle_pbkdfparamrequest(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
         if !self.state.is_idle() {
             let sd = self.state.take()?;
-            if sd.is_sess_expired()? {
+            if sd.is_sess_expired(self.clock.now()) {
                 info!("Previous session expired, clearing it");
                 self.state = PakeState::Idle;
             } else {
                 info!("Previous session in-progress, denying new request");
+                self.state.set_sess_data(sd);
                 // little-endian timeout (here we've hardcoded 500ms)
                 create_sc_status_report(&mut ctx.tx, SCStatusCodes::Busy, Some(&[0xf4, 0x01]))?;
                 return Ok(());
             }
         }
 
-        let root = tlv::get_root_node(ctx.rx.as_borrow_slice())?;
+        let root = tlv::get_root_node_strict(ctx.rx.as_borrow_slice())?;
         let a = PBKDFParamReq::from_tlv(&root)?;
         if a.passcode_id != 0 {
             error!("Can't yet handle passcode_id != 0");
@@ -214,7 +245,7 @@ impl PAKE {
         };
         if !a.has_params {
             let params_resp = PBKDFParamRespParams {
-                count: SPAKE2_ITERATION_COUNT,
+                count: self.iterations,
                 salt: OctetStr(&self.salt),
             };
             resp.params = Some(params_resp);
@@ -222,7 +253,8 @@ impl PAKE {
         resp.to_tlv(&mut tw, TagType::Anonymous)?;
 
         spake2p.set_context(ctx.rx.as_borrow_slice(), ctx.tx.as_borrow_slice())?;
-        self.state.make_in_progress(spake2p, &ctx.exch_ctx);
+        self.state
+            .make_in_progress(spake2p, &ctx.exch_ctx, self.clock.now());
 
         Ok(())
     }
@@ -253,7 +285,7 @@ struct PBKDFParamResp<'a> {
 
 #[allow(non_snake_case)]
 fn extract_pasepake_1_or_3_params(buf: &[u8]) -> Result<&[u8], Error> {
-    let root = get_root_node_struct(buf)?;
+    let root = get_root_node_struct_strict(buf)?;
     let pA = root.find_tag(1)?.slice()?;
     Ok(pA)
 }
@@ -266,3 +298,30 @@ struct PBKDFParamReq<'a> {
     passcode_id: u16,
     has_params: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionData, PASE_DISCARD_TIMEOUT_SECS};
+    use crate::{
+        clock::{Clock, ManualClock},
+        secure_channel::spake2p::Spake2P,
+        transport::network::Address,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_sess_expiry() {
+        let clock = ManualClock::new();
+        let sd = SessionData {
+            start_time: clock.now(),
+            exch_id: 1,
+            peer_addr: Address::default(),
+            spake2p: Box::new(Spake2P::new()),
+        };
+
+        clock.advance(PASE_DISCARD_TIMEOUT_SECS);
+        assert!(!sd.is_sess_expired(clock.now()));
+        clock.advance(Duration::from_millis(1));
+        assert!(sd.is_sess_expired(clock.now()));
+    }
+}
@@ -1,8 +1,8 @@
 use std::{
     convert::TryInto,
-    fs::{DirBuilder, File},
+    fs::{remove_file, DirBuilder, File},
     io::{Read, Write},
-    sync::{Arc, Mutex, Once},
+    path::PathBuf,
 };
 
 use crate::error::Error;
@@ -11,65 +11,71 @@ pub const SPAKE2_ITERATION_COUNT: u32 = 2000;
 
 // The Packet Pool that is allocated from. POSIX systems can use
 // higher values unlike embedded systems
-pub const MAX_PACKET_POOL_SIZE: usize = 25;
+pub const DEFAULT_PACKET_POOL_SIZE: usize = 25;
+// The largest Packet Pool that can be configured
+pub const MAX_PACKET_POOL_SIZE: usize = 64;
 
-pub struct Psm {}
-
-static mut G_PSM: Option<Arc<Mutex<Psm>>> = None;
-static INIT: Once = Once::new();
+pub struct Psm {
+    // The directory where each key is stored as a file
+    dir: PathBuf,
+}
 
-const PSM_DIR: &str = "/tmp/plonk_psm";
+/// The default storage location
+pub const PSM_DIR: &str = "/tmp/plonk_psm";
 
 macro_rules! psm_path {
-    ($key:ident) => {
-        format!("{}/{}", PSM_DIR, $key)
+    ($self:ident, $key:ident) => {
+        $self.dir.join($key)
     };
 }
 
 impl Psm {
-    fn new() -> Result<Self, Error> {
-        let result = DirBuilder::new().create(PSM_DIR);
+    /// Create a store that keeps its data in the given directory
+    ///
+    /// The directory is created if it doesn't exist
+    pub fn new_at<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        let result = DirBuilder::new().recursive(true).create(&dir);
         if let Err(e) = result {
             if e.kind() != std::io::ErrorKind::AlreadyExists {
                 return Err(e.into());
             }
         }
 
-        Ok(Self {})
-    }
-
-    pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
-        unsafe {
-            INIT.call_once(|| {
-                G_PSM = Some(Arc::new(Mutex::new(Psm::new().unwrap())));
-            });
-            Ok(G_PSM.as_ref().ok_or(Error::Invalid)?.clone())
-        }
+        Ok(Self { dir })
     }
 
     pub fn set_kv_slice(&self, key: &str, val: &[u8]) -> Result<(), Error> {
-        let mut f = File::create(psm_path!(key))?;
+        let mut f = File::create(psm_path!(self, key))?;
         f.write_all(val)?;
         Ok(())
     }
 
     pub fn get_kv_slice(&self, key: &str, val: &mut Vec<u8>) -> Result<usize, Error> {
-        let mut f = File::open(psm_path!(key))?;
+        let mut f = File::open(psm_path!(self, key))?;
         let len = f.read_to_end(val)?;
         Ok(len)
     }
 
     pub fn set_kv_u64(&self, key: &str, val: u64) -> Result<(), Error> {
-        let mut f = File::create(psm_path!(key))?;
+        let mut f = File::create(psm_path!(self, key))?;
         f.write_all(&val.to_be_bytes())?;
         Ok(())
     }
 
     pub fn get_kv_u64(&self, key: &str, val: &mut u64) -> Result<(), Error> {
-        let mut f = File::open(psm_path!(key))?;
+        let mut f = File::open(psm_path!(self, key))?;
         let mut vec = Vec::new();
         let _ = f.read_to_end(&mut vec)?;
         *val = u64::from_be_bytes(vec.as_slice().try_into()?);
         Ok(())
     }
+
+    /// Remove the given key, a key that isn't stored is not an error
+    pub fn rm(&self, key: &str) -> Result<(), Error> {
+        match remove_file(psm_path!(self, key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
 }
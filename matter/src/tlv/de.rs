@@ -0,0 +1,600 @@
+use serde::de::{
+    self, value::StrDeserializer, value::U64Deserializer, DeserializeSeed, Deserializer,
+    IntoDeserializer, Visitor,
+};
+use serde::Deserialize;
+
+use super::ser::{LIST_TOKEN, NULLABLE_TOKEN};
+use super::{
+    get_root_node, AsList, ElementType, Nullable, OctetStr, TLVContainerIterator, TLVElement,
+    TagType, UtfStr,
+};
+use crate::error::Error;
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Nullable<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NullableVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for NullableVisitor<T> {
+            type Value = Nullable<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a nullable value")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                d: D,
+            ) -> Result<Self::Value, D::Error> {
+                Ok(match Option::<T>::deserialize(d)? {
+                    Some(t) => Nullable::NotNull(t),
+                    None => Nullable::Null,
+                })
+            }
+        }
+
+        deserializer
+            .deserialize_newtype_struct(NULLABLE_TOKEN, NullableVisitor(std::marker::PhantomData))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for AsList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ListVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ListVisitor<T> {
+            type Value = AsList<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                d: D,
+            ) -> Result<Self::Value, D::Error> {
+                T::deserialize(d).map(AsList)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(LIST_TOKEN, ListVisitor(std::marker::PhantomData))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for OctetStr<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <&'a [u8]>::deserialize(deserializer).map(OctetStr)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for UtfStr<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <&'a str>::deserialize(deserializer).map(|s| UtfStr(s.as_bytes()))
+    }
+}
+
+/// Deserialize a value from the TLV element, see [to_writer](super::to_writer) for how the Rust
+/// types map to TLV
+///
+/// Strings and byte arrays can be borrowed from the buffer of the element. A struct field is
+/// matched to the context tag that is its name, if that is a number, or else its position, and
+/// the elements with other tags are skipped. An `Option` field is `None` if its tag is missing.
+pub fn from_element<'a, T: Deserialize<'a>>(element: &TLVElement<'a>) -> Result<T, Error> {
+    T::deserialize(TLVDeserializer::new(*element))
+}
+
+/// Deserialize a value from the root element of the buffer, see [from_element]
+pub fn from_slice<'a, T: Deserialize<'a>>(buf: &'a [u8]) -> Result<T, Error> {
+    from_element(&get_root_node(buf)?)
+}
+
+/// A serde Deserializer that reads a single TLV element
+pub struct TLVDeserializer<'a> {
+    element: TLVElement<'a>,
+    // The element is a struct field, which is only there if the field isn't None
+    field: bool,
+}
+
+impl<'a> TLVDeserializer<'a> {
+    pub fn new(element: TLVElement<'a>) -> Self {
+        Self {
+            element,
+            field: false,
+        }
+    }
+
+    fn enter(&self) -> Result<TLVContainerIterator<'a>, Error> {
+        self.element.enter().ok_or(Error::TLVTypeMismatch)
+    }
+
+    fn is_octets(&self) -> bool {
+        matches!(
+            self.element.get_element_type(),
+            ElementType::Str8l(_)
+                | ElementType::Str16l(_)
+                | ElementType::Str32l(_)
+                | ElementType::Str64l(_)
+        )
+    }
+}
+
+impl<'de> Deserializer<'de> for TLVDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.element.get_element_type() {
+            ElementType::S8(a) => visitor.visit_i8(a),
+            ElementType::S16(a) => visitor.visit_i16(a),
+            ElementType::S32(a) => visitor.visit_i32(a),
+            ElementType::S64(a) => visitor.visit_i64(a),
+            ElementType::U8(a) => visitor.visit_u8(a),
+            ElementType::U16(a) => visitor.visit_u16(a),
+            ElementType::U32(a) => visitor.visit_u32(a),
+            ElementType::U64(a) => visitor.visit_u64(a),
+            ElementType::False => visitor.visit_bool(false),
+            ElementType::True => visitor.visit_bool(true),
+            ElementType::F32(a) => visitor.visit_f32(a),
+            ElementType::F64(a) => visitor.visit_f64(a),
+            ElementType::Utf8l(_)
+            | ElementType::Utf16l(_)
+            | ElementType::Utf32l(_)
+            | ElementType::Utf64l(_) => visitor.visit_borrowed_str(self.element.str()?),
+            ElementType::Str8l(_)
+            | ElementType::Str16l(_)
+            | ElementType::Str32l(_)
+            | ElementType::Str64l(_) => visitor.visit_borrowed_bytes(self.element.slice()?),
+            ElementType::Null => visitor.visit_unit(),
+            ElementType::Struct(_) | ElementType::List(_) => {
+                visitor.visit_map(MapAccess::new(self.enter()?, None))
+            }
+            ElementType::Array(_) => visitor.visit_seq(SeqAccess(self.enter()?)),
+            ElementType::EndCnt | ElementType::Last => Err(Error::InvalidData),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(self.element.bool()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i8(self.element.i8()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i16(self.element.i16()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i32(self.element.i32()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i64(self.element.i64()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u8(self.element.u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u16(self.element.u16()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u32(self.element.u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u64(self.element.u64()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(self.element.f32()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(self.element.f64()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.element.str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.element.slice()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // A struct field that is None is left out, so one that is there is always Some, even if
+        // it is null, that is then for the value inside to handle
+        if !self.field && self.element.null().is_ok() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(TLVDeserializer::new(self.element))
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.element.null()?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            LIST_TOKEN => {
+                self.element.confirm_list()?;
+                visitor.visit_newtype_struct(TLVDeserializer::new(self.element))
+            }
+            NULLABLE_TOKEN => visitor.visit_newtype_struct(TLVDeserializer::new(self.element)),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_octets() {
+            // Allow a Vec<u8> or [u8; N] to be read from an octet string
+            let bytes = self.element.slice()?.iter().copied();
+            return visitor.visit_seq(de::value::SeqDeserializer::new(bytes));
+        }
+        match self.element.get_element_type() {
+            ElementType::Array(_) | ElementType::List(_) => {
+                visitor.visit_seq(SeqAccess(self.enter()?))
+            }
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.element.get_element_type() {
+            ElementType::Struct(_) | ElementType::List(_) => {
+                visitor.visit_map(MapAccess::new(self.enter()?, None))
+            }
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.element.get_element_type() {
+            ElementType::Struct(_) | ElementType::List(_) => {
+                visitor.visit_map(MapAccess::new(self.enter()?, Some(fields)))
+            }
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if let Ok(index) = self.element.u32() {
+            return visitor.visit_enum(index.into_deserializer());
+        }
+        // The other variants are a struct with a single element, tagged with the variant
+        self.element.confirm_struct()?;
+        let value = self.enter()?.next().ok_or(Error::TLVNotFound)?;
+        match value.get_tag() {
+            TagType::Context(index) => visitor.visit_enum(EnumAccess {
+                index: index as u32,
+                value,
+            }),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqAccess<'a>(TLVContainerIterator<'a>);
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.0.next() {
+            Some(element) => seed.deserialize(TLVDeserializer::new(element)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapAccess<'a> {
+    iter: TLVContainerIterator<'a>,
+    // The fields of the struct that is read, the keys of a map are the tag numbers
+    fields: Option<&'static [&'static str]>,
+    value: Option<TLVElement<'a>>,
+    // The name of a field, that isn't one of the struct's, is that of its tag
+    name: String,
+}
+
+impl<'a> MapAccess<'a> {
+    fn new(iter: TLVContainerIterator<'a>, fields: Option<&'static [&'static str]>) -> Self {
+        Self {
+            iter,
+            fields,
+            value: None,
+            name: String::new(),
+        }
+    }
+
+    // The name of the struct field with this tag: the field named after it, or else the field at
+    // its position, if that isn't named after another tag
+    fn field_name(&mut self, fields: &'static [&'static str], tag: u8) -> &str {
+        let name = tag.to_string();
+        if let Some(f) = fields.iter().find(|f| **f == name) {
+            return f;
+        }
+        match fields.get(tag as usize) {
+            Some(f) if f.parse::<u8>().is_err() => f,
+            _ => {
+                self.name = name;
+                &self.name
+            }
+        }
+    }
+}
+
+fn tag_number(tag: TagType) -> u64 {
+    match tag {
+        TagType::Anonymous => 0,
+        TagType::Context(t) => t as u64,
+        TagType::CommonPrf16(t) | TagType::ImplPrf16(t) => t as u64,
+        TagType::CommonPrf32(t) | TagType::ImplPrf32(t) => t as u64,
+        TagType::FullQual48(t) | TagType::FullQual64(t) => t,
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let element = match self.iter.next() {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        self.value = Some(element);
+        match (self.fields, element.get_tag()) {
+            (Some(fields), TagType::Context(tag)) => {
+                let name = self.field_name(fields, tag);
+                let key: StrDeserializer<Error> = name.into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            (_, tag) => {
+                let key: U64Deserializer<Error> = tag_number(tag).into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let element = self.value.take().ok_or(Error::InvalidState)?;
+        seed.deserialize(TLVDeserializer {
+            element,
+            field: true,
+        })
+    }
+}
+
+struct EnumAccess<'a> {
+    index: u32,
+    value: TLVElement<'a>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = TLVDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, TLVDeserializer<'de>), Error> {
+        let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+        let variant = seed.deserialize(index)?;
+        Ok((variant, TLVDeserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for TLVDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.element.null()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_slice, AsList};
+    use crate::{
+        error::Error,
+        tlv::{to_slice, Nullable, OctetStr},
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Cmd<'a> {
+        endpoint: u16,
+        #[serde(borrow)]
+        data: OctetStr<'a>,
+        #[serde(rename = "5")]
+        name: &'a str,
+        optional: Option<u8>,
+        nullable: Option<Nullable<u32>>,
+        #[serde(rename = "254")]
+        fab_idx: u8,
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let b = [
+            0x15, 0x25, 0, 0x34, 0x12, 0x30, 1, 1, 0xaa, 0x2c, 5, 2, b'h', b'i', 0x34, 4, 0x24,
+            0x10, 9, 0x24, 0xfe, 2, 0x18,
+        ];
+        let cmd: Cmd = from_slice(&b).unwrap();
+        assert_eq!(
+            cmd,
+            Cmd {
+                endpoint: 0x1234,
+                data: OctetStr(&[0xaa]),
+                name: "hi",
+                optional: None,
+                nullable: Some(Nullable::Null),
+                fab_idx: 2,
+            }
+        );
+        // Zero-copy
+        assert_eq!(cmd.name.as_ptr(), b[12..].as_ptr());
+        assert_eq!(cmd.data.0.as_ptr(), b[8..].as_ptr());
+
+        // The tags the struct doesn't know are skipped, the missing fields are an error
+        let b = [0x15, 0x24, 0x10, 9, 0x24, 0, 1, 0x18];
+        assert_eq!(from_slice::<Cmd>(&b), Err(Error::TLVNotFound));
+        let b = [0x15, 0x24, 0, 1, 0x24, 1, 1, 0x18];
+        assert_eq!(from_slice::<Cmd>(&b), Err(Error::TLVTypeMismatch));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Value {
+        Off,
+        On,
+        Level(u8),
+        Color { hue: u8 },
+        Range(u8, u8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct All {
+        values: Vec<Value>,
+        list: AsList<Vec<Nullable<i16>>>,
+        map: BTreeMap<u8, String>,
+        bytes: Vec<u8>,
+        ratio: f32,
+        flag: bool,
+        big: u64,
+        neg: i64,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut map = BTreeMap::new();
+        map.insert(1, "one".to_owned());
+        map.insert(200, "two hundred".to_owned());
+        let all = All {
+            values: vec![
+                Value::Off,
+                Value::On,
+                Value::Level(3),
+                Value::Color { hue: 20 },
+                Value::Range(1, 2),
+            ],
+            list: AsList(vec![Nullable::NotNull(-300), Nullable::Null]),
+            map,
+            bytes: vec![1, 2, 3],
+            ratio: 0.5,
+            flag: true,
+            big: u64::MAX,
+            neg: i64::MIN,
+        };
+
+        let mut buf = [0u8; 128];
+        let len = to_slice(&all, &mut buf).unwrap();
+        assert_eq!(from_slice::<All>(&buf[..len]), Ok(all));
+    }
+
+    #[test]
+    fn test_deserialize_containers() {
+        // Octet strings and arrays can both be read as a sequence of bytes
+        assert_eq!(from_slice::<Vec<u8>>(&[0x10, 2, 1, 2]), Ok(vec![1, 2]));
+        assert_eq!(from_slice::<[u8; 2]>(&[0x16, 4, 1, 4, 2, 0x18]), Ok([1, 2]));
+
+        // But a list is only read into an AsList
+        let b = [0x17, 4, 1, 0x18];
+        assert_eq!(from_slice::<AsList<Vec<u8>>>(&b), Ok(AsList(vec![1])));
+        let b = [0x16, 4, 1, 0x18];
+        assert_eq!(
+            from_slice::<AsList<Vec<u8>>>(&b),
+            Err(Error::TLVTypeMismatch)
+        );
+
+        assert_eq!(
+            from_slice::<Vec<Option<u8>>>(&[0x16, 0x14, 4, 7, 0x18]),
+            Ok(vec![None, Some(7)])
+        );
+    }
+}
@@ -10,6 +10,48 @@ pub enum TagType {
     FullQual48(u64),
     FullQual64(u64),
 }
+
+/// The number of bytes an integer, or the length of a string, is encoded with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntWidth {
+    W8,
+    W16,
+    W32,
+    W64,
+}
+
+impl IntWidth {
+    pub fn bytes(self) -> usize {
+        1 << self as usize
+    }
+
+    /// The smallest width that holds the unsigned value
+    pub fn of_u64(data: u64) -> Self {
+        if data <= u8::MAX as u64 {
+            Self::W8
+        } else if data <= u16::MAX as u64 {
+            Self::W16
+        } else if data <= u32::MAX as u64 {
+            Self::W32
+        } else {
+            Self::W64
+        }
+    }
+
+    /// The smallest width that holds the signed value
+    pub fn of_i64(data: i64) -> Self {
+        if (i8::MIN as i64..=i8::MAX as i64).contains(&data) {
+            Self::W8
+        } else if (i16::MIN as i64..=i16::MAX as i64).contains(&data) {
+            Self::W16
+        } else if (i32::MIN as i64..=i32::MAX as i64).contains(&data) {
+            Self::W32
+        } else {
+            Self::W64
+        }
+    }
+}
+
 pub const TAG_SHIFT_BITS: u8 = 5;
 pub const TAG_MASK: u8 = 0xe0;
 pub const TYPE_MASK: u8 = 0x1f;
@@ -26,11 +68,23 @@ pub static TAG_SIZE_MAP: [usize; MAX_TAG_INDEX] = [
     8, // FullQual64
 ];
 
+#[cfg(feature = "serde")]
+mod de;
 mod parser;
+#[cfg(feature = "serde")]
+mod ser;
+mod strict;
 mod traits;
+mod value;
 mod writer;
 
+#[cfg(feature = "serde")]
+pub use de::*;
 pub use matter_macro_derive::{FromTLV, ToTLV};
 pub use parser::*;
+#[cfg(feature = "serde")]
+pub use ser::*;
+pub use strict::*;
 pub use traits::*;
+pub use value::*;
 pub use writer::*;
@@ -39,12 +39,12 @@ pub enum ElementType<'a> {
     F64(f64),
     Utf8l(&'a [u8]),
     Utf16l(&'a [u8]),
-    Utf32l,
-    Utf64l,
+    Utf32l(&'a [u8]),
+    Utf64l(&'a [u8]),
     Str8l(&'a [u8]),
     Str16l(&'a [u8]),
-    Str32l,
-    Str64l,
+    Str32l(&'a [u8]),
+    Str64l(&'a [u8]),
     Null,
     Struct(Pointer<'a>),
     Array(Pointer<'a>),
@@ -145,9 +145,23 @@ static VALUE_EXTRACTOR: [ExtractValue; MAX_VALUE_INDEX] = [
     // True 9
     { |_t| (0, ElementType::True) },
     // F32  10
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| {
+            (
+                0,
+                ElementType::F32(LittleEndian::read_f32(&t.buf[t.current..])),
+            )
+        }
+    },
     // F64  11
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| {
+            (
+                0,
+                ElementType::F64(LittleEndian::read_f64(&t.buf[t.current..])),
+            )
+        }
+    },
     // Utf8l 12
     {
         |t| match read_length_value(1, t) {
@@ -163,9 +177,19 @@ static VALUE_EXTRACTOR: [ExtractValue; MAX_VALUE_INDEX] = [
         }
     },
     // Utf32l 14
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| match read_length_value(4, t) {
+            Err(_) => (0, ElementType::Last),
+            Ok((size, string)) => (size, ElementType::Utf32l(string)),
+        }
+    },
     // Utf64l 15
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| match read_length_value(8, t) {
+            Err(_) => (0, ElementType::Last),
+            Ok((size, string)) => (size, ElementType::Utf64l(string)),
+        }
+    },
     // Str8l 16
     {
         |t| match read_length_value(1, t) {
@@ -181,9 +205,19 @@ static VALUE_EXTRACTOR: [ExtractValue; MAX_VALUE_INDEX] = [
         }
     },
     // Str32l 18
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| match read_length_value(4, t) {
+            Err(_) => (0, ElementType::Last),
+            Ok((size, string)) => (size, ElementType::Str32l(string)),
+        }
+    },
     // Str64l 19
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| match read_length_value(8, t) {
+            Err(_) => (0, ElementType::Last),
+            Ok((size, string)) => (size, ElementType::Str64l(string)),
+        }
+    },
     // Null  20
     { |_t| (0, ElementType::Null) },
     // Struct 21
@@ -263,12 +297,14 @@ fn read_length_value<'a>(
     t: &TLVListIterator<'a>,
 ) -> Result<(usize, &'a [u8]), Error> {
     // The current offset is the string size
-    let length: usize = LittleEndian::read_uint(&t.buf[t.current..], size_of_length_field) as usize;
-    // We'll consume the current offset (len) + the entire string
-    if length + size_of_length_field > t.left {
+    let length = LittleEndian::read_uint(&t.buf[t.current..], size_of_length_field);
+    // We'll consume the current offset (len) + the entire string, the caller has already checked
+    // that the length field itself is there. The 64-bit lengths may not even fit in a usize.
+    if length > (t.left - size_of_length_field) as u64 {
         // Return Error
         Err(Error::NoSpace)
     } else {
+        let length = length as usize;
         Ok((
             // return the additional size only
             length,
@@ -381,6 +417,33 @@ impl<'a> TLVElement<'a> {
         }
     }
 
+    pub fn i16(&self) -> Result<i16, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn i32(&self) -> Result<i32, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a.into()),
+            ElementType::S32(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn i64(&self) -> Result<i64, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a.into()),
+            ElementType::S32(a) => Ok(a.into()),
+            ElementType::S64(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
     pub fn u8(&self) -> Result<u8, Error> {
         match self.element_type {
             ElementType::U8(a) => Ok(a),
@@ -415,12 +478,43 @@ impl<'a> TLVElement<'a> {
         }
     }
 
+    pub fn f32(&self) -> Result<f32, Error> {
+        match self.element_type {
+            ElementType::F32(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn f64(&self) -> Result<f64, Error> {
+        match self.element_type {
+            ElementType::F32(a) => Ok(a.into()),
+            ElementType::F64(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    /// The bytes of an octet string or UTF-8 string, whatever the size of its length
     pub fn slice(&self) -> Result<&'a [u8], Error> {
         match self.element_type {
             ElementType::Str8l(s)
             | ElementType::Utf8l(s)
             | ElementType::Str16l(s)
-            | ElementType::Utf16l(s) => Ok(s),
+            | ElementType::Utf16l(s)
+            | ElementType::Str32l(s)
+            | ElementType::Utf32l(s)
+            | ElementType::Str64l(s)
+            | ElementType::Utf64l(s) => Ok(s),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    /// A UTF-8 string, [Error::InvalidData] is returned if it isn't valid UTF-8
+    pub fn str(&self) -> Result<&'a str, Error> {
+        match self.element_type {
+            ElementType::Utf8l(s)
+            | ElementType::Utf16l(s)
+            | ElementType::Utf32l(s)
+            | ElementType::Utf64l(s) => std::str::from_utf8(s).map_err(|_| Error::InvalidData),
             _ => Err(Error::TLVTypeMismatch),
         }
     }
@@ -508,7 +602,11 @@ impl<'a> fmt::Display for TLVElement<'a> {
             ElementType::Str8l(a)
             | ElementType::Utf8l(a)
             | ElementType::Str16l(a)
-            | ElementType::Utf16l(a) => {
+            | ElementType::Utf16l(a)
+            | ElementType::Str32l(a)
+            | ElementType::Utf32l(a)
+            | ElementType::Str64l(a)
+            | ElementType::Utf64l(a) => {
                 if let Ok(s) = std::str::from_utf8(a) {
                     write!(f, "len[{}]\"{}\"", s.len(), s)
                 } else {
@@ -537,6 +635,11 @@ impl<'a> TLVListIterator<'a> {
         }
     }
 
+    /// The number of bytes that haven't been parsed yet
+    pub(super) fn remaining(&self) -> usize {
+        self.left
+    }
+
     fn advance(&mut self, len: usize) {
         self.current += len;
         self.left -= len;
@@ -971,6 +1074,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_valid_value_long_strings_and_floats() {
+        let b = [
+            0x2e, 0x1, 0x2, 0x0, 0x0, 0x0, 0x68, 0x69, // Utf32l "hi"
+            0x33, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, // Str64l, empty
+            0x0a, 0x0, 0x0, 0xc0, 0x3f, // F32 1.5
+            0x0b, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0xc0, // F64 -2.0
+        ];
+        let mut tlv_iter = TLVList::new(&b).iter();
+        let utf = tlv_iter.next().unwrap();
+        assert_eq!(utf.get_element_type(), ElementType::Utf32l(b"hi"));
+        assert_eq!(utf.str(), Ok("hi"));
+        assert_eq!(utf.slice(), Ok(&b"hi"[..]));
+        let octets = tlv_iter.next().unwrap();
+        assert_eq!(octets.get_element_type(), ElementType::Str64l(&[]));
+        assert_eq!(octets.str(), Err(Error::TLVTypeMismatch));
+        let f = tlv_iter.next().unwrap();
+        assert_eq!(f.f32(), Ok(1.5));
+        assert_eq!(f.f64(), Ok(1.5));
+        let d = tlv_iter.next().unwrap();
+        assert_eq!(d.f64(), Ok(-2.0));
+        assert_eq!(d.f32(), Err(Error::TLVTypeMismatch));
+        assert_eq!(tlv_iter.next(), None);
+
+        // A 64-bit length that is beyond the end of the list, or can't even be a usize
+        for len in [0x3_u64, u64::MAX] {
+            let mut b = vec![0x13];
+            b.extend_from_slice(&len.to_le_bytes());
+            b.extend_from_slice(&[0x1, 0x2]);
+            assert_eq!(TLVList::new(&b).iter().next(), None);
+        }
+    }
+
+    #[test]
+    fn test_signed_accessors() {
+        let b = [0x00, 0xfe, 0x01, 0xd4, 0xfe];
+        let mut tlv_iter = TLVList::new(&b).iter();
+        let s8 = tlv_iter.next().unwrap();
+        assert_eq!(s8.i8(), Ok(-2));
+        assert_eq!(s8.i64(), Ok(-2));
+        let s16 = tlv_iter.next().unwrap();
+        assert_eq!(s16.i8(), Err(Error::TLVTypeMismatch));
+        assert_eq!(s16.i16(), Ok(-300));
+        assert_eq!(s16.i32(), Ok(-300));
+        assert_eq!(s16.u16(), Err(Error::TLVTypeMismatch));
+    }
+
     #[test]
     fn test_no_iterator_for_int() {
         // The 0x24 is a a tagged integer, here the integer is 2
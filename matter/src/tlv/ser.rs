@@ -0,0 +1,686 @@
+use serde::ser::{self, Impossible, Serialize};
+
+use super::{Nullable, OctetStr, TLVWriter, TagType, UtfStr};
+use crate::{error::Error, utils::writebuf::WriteBuf};
+
+// The names of the newtype structs that the TLV serializer and deserializer treat specially
+pub(crate) const NULLABLE_TOKEN: &str = "$tlv::Nullable";
+pub(crate) const LIST_TOKEN: &str = "$tlv::List";
+
+/// Encode the wrapped sequence, struct or map as a TLV list, instead of an array or a struct
+///
+/// Any other data format sees the wrapped value as is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AsList<T>(pub T);
+
+impl<T: Serialize> Serialize for AsList<T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(LIST_TOKEN, &self.0)
+    }
+}
+
+impl<T: Serialize> Serialize for Nullable<T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match self {
+            Nullable::Null => None,
+            Nullable::NotNull(t) => Some(t),
+        };
+        serializer.serialize_newtype_struct(NULLABLE_TOKEN, &value)
+    }
+}
+
+impl<'a> Serialize for OctetStr<'a> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'a> Serialize for UtfStr<'a> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let s = std::str::from_utf8(self.0).map_err(ser::Error::custom)?;
+        serializer.serialize_str(s)
+    }
+}
+
+/// Serialize the value as the TLV element with the given tag
+///
+/// The Rust types map to TLV as follows:
+/// - integers, floats and booleans map to the TLV type of the same size, with the integers taking
+///   the smallest size that holds their value
+/// - strings and chars are UTF-8 strings, byte arrays (`serialize_bytes`) are octet strings
+/// - structs and maps are TLV structs: a field is tagged with its name, if that is a number (set
+///   with `#[serde(rename = "1")]`), or else with its position in the struct. Fields that are
+///   `None` are left out
+/// - sequences and tuples are TLV arrays, see [AsList] for TLV lists
+/// - [Nullable::Null], unit, and `None` anywhere else than in a struct field, are TLV nulls
+/// - unit variants are the index of the variant, the other variants are a struct with a single
+///   element, tagged with the index of the variant
+pub fn to_writer<T: Serialize + ?Sized>(
+    value: &T,
+    tw: &mut TLVWriter,
+    tag: TagType,
+) -> Result<(), Error> {
+    value.serialize(TLVSerializer::new(tw, tag))
+}
+
+/// Serialize the value as an anonymous TLV element in the buffer, see [to_writer]
+///
+/// Returns the length of the encoded value
+pub fn to_slice<T: Serialize + ?Sized>(value: &T, buf: &mut [u8]) -> Result<usize, Error> {
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    to_writer(value, &mut tw, TagType::Anonymous)?;
+    Ok(tw.get_tail())
+}
+
+/// A serde Serializer that writes a single TLV element
+pub struct TLVSerializer<'s, 'a, 'b> {
+    tw: &'s mut TLVWriter<'a, 'b>,
+    tag: TagType,
+    // A struct field is left out, rather than null, when it is None
+    field: bool,
+    // The next container is a list
+    list: bool,
+}
+
+impl<'s, 'a, 'b> TLVSerializer<'s, 'a, 'b> {
+    pub fn new(tw: &'s mut TLVWriter<'a, 'b>, tag: TagType) -> Self {
+        Self {
+            tw,
+            tag,
+            field: false,
+            list: false,
+        }
+    }
+
+    fn value(self) -> Self {
+        Self {
+            field: false,
+            ..self
+        }
+    }
+
+    fn start_container(self, ends: usize) -> Result<Compound<'s, 'a, 'b>, Error> {
+        if self.list {
+            self.tw.start_list(self.tag)?;
+        } else {
+            self.tw.start_struct(self.tag)?;
+        }
+        Ok(Compound::new(self.tw, ends))
+    }
+
+    fn start_seq(self) -> Result<Compound<'s, 'a, 'b>, Error> {
+        if self.list {
+            self.tw.start_list(self.tag)?;
+        } else {
+            self.tw.start_array(self.tag)?;
+        }
+        Ok(Compound::new(self.tw, 1))
+    }
+}
+
+fn variant_tag(index: u32) -> Result<TagType, Error> {
+    if index <= 0xff {
+        Ok(TagType::Context(index as u8))
+    } else {
+        Err(Error::Invalid)
+    }
+}
+
+impl<'s, 'a, 'b> ser::Serializer for TLVSerializer<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'s, 'a, 'b>;
+    type SerializeTuple = Compound<'s, 'a, 'b>;
+    type SerializeTupleStruct = Compound<'s, 'a, 'b>;
+    type SerializeTupleVariant = Compound<'s, 'a, 'b>;
+    type SerializeMap = Compound<'s, 'a, 'b>;
+    type SerializeStruct = Compound<'s, 'a, 'b>;
+    type SerializeStructVariant = Compound<'s, 'a, 'b>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.tw.bool(self.tag, v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.tw.i8(self.tag, v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.tw.i16(self.tag, v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.tw.i32(self.tag, v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.tw.i64(self.tag, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.tw.u8(self.tag, v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.tw.u16(self.tag, v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.tw.u32(self.tag, v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.tw.u64(self.tag, v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.tw.f32(self.tag, v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.tw.f64(self.tag, v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        let mut buf = [0u8; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.tw.utf64(self.tag, v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.tw.str64(self.tag, v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        if self.field {
+            Ok(())
+        } else {
+            self.tw.null(self.tag)
+        }
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self.value())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.tw.null(self.tag)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        self.tw.u32(self.tag, variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        match name {
+            NULLABLE_TOKEN => value.serialize(self.value()),
+            LIST_TOKEN => value.serialize(Self {
+                list: true,
+                ..self.value()
+            }),
+            _ => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let tag = variant_tag(variant_index)?;
+        self.tw.start_struct(self.tag)?;
+        value.serialize(TLVSerializer::new(self.tw, tag))?;
+        self.tw.end_container()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, 'a, 'b>, Error> {
+        self.start_seq()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s, 'a, 'b>, Error> {
+        self.start_seq()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, 'b>, Error> {
+        self.start_seq()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, 'b>, Error> {
+        let tag = variant_tag(variant_index)?;
+        self.tw.start_struct(self.tag)?;
+        self.tw.start_array(tag)?;
+        Ok(Compound::new(self.tw, 2))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, 'a, 'b>, Error> {
+        self.start_container(1)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, 'b>, Error> {
+        self.start_container(1)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, 'b>, Error> {
+        let tag = variant_tag(variant_index)?;
+        self.tw.start_struct(self.tag)?;
+        self.tw.start_struct(tag)?;
+        Ok(Compound::new(self.tw, 2))
+    }
+}
+
+/// The elements of a TLV container that is being serialized
+pub struct Compound<'s, 'a, 'b> {
+    tw: &'s mut TLVWriter<'a, 'b>,
+    // The position of the next field of a struct
+    index: usize,
+    // The tag of the next value of a map
+    key: Option<u8>,
+    // The number of containers to close at the end
+    ends: usize,
+}
+
+impl<'s, 'a, 'b> Compound<'s, 'a, 'b> {
+    fn new(tw: &'s mut TLVWriter<'a, 'b>, ends: usize) -> Self {
+        Self {
+            tw,
+            index: 0,
+            key: None,
+            ends,
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(TLVSerializer::new(self.tw, TagType::Anonymous))
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, tag: u8, value: &T) -> Result<(), Error> {
+        value.serialize(TLVSerializer {
+            tw: self.tw,
+            tag: TagType::Context(tag),
+            field: true,
+            list: false,
+        })
+    }
+
+    fn end(self) -> Result<(), Error> {
+        for _ in 0..self.ends {
+            self.tw.end_container()?;
+        }
+        Ok(())
+    }
+}
+
+// The tag of a struct field is its name, if that is a number, or else its position
+fn field_tag(key: &str, index: usize) -> Result<u8, Error> {
+    match key.parse::<u8>() {
+        Ok(tag) => Ok(tag),
+        Err(_) if index <= 0xff => Ok(index as u8),
+        Err(_) => Err(Error::Invalid),
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeSeq for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeTuple for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeTupleStruct for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeTupleVariant for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeMap for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let tag = self.key.take().ok_or(Error::InvalidState)?;
+        self.field(tag, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeStruct for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let tag = field_tag(key, self.index)?;
+        self.index += 1;
+        self.field(tag, value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), Error> {
+        // The fields that follow keep their position
+        self.index += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, 'b> ser::SerializeStructVariant for Compound<'s, 'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        ser::SerializeStruct::skip_field(self, key)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+// The keys of a map are context tags, so they have to be numbers that fit in a u8
+struct KeySerializer;
+
+macro_rules! key_from_int {
+    ($($f:ident: $t:ty)*) => {
+        $(
+            fn $f(self, v: $t) -> Result<u8, Error> {
+                if (0..=0xff).contains(&(v as i128)) {
+                    Ok(v as u8)
+                } else {
+                    Err(Error::Invalid)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! key_invalid {
+    ($($f:ident($($t:ty),*))*) => {
+        $(
+            fn $f(self, $(_: $t),*) -> Result<u8, Error> {
+                Err(Error::Invalid)
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = u8;
+    type Error = Error;
+    type SerializeSeq = Impossible<u8, Error>;
+    type SerializeTuple = Impossible<u8, Error>;
+    type SerializeTupleStruct = Impossible<u8, Error>;
+    type SerializeTupleVariant = Impossible<u8, Error>;
+    type SerializeMap = Impossible<u8, Error>;
+    type SerializeStruct = Impossible<u8, Error>;
+    type SerializeStructVariant = Impossible<u8, Error>;
+
+    key_from_int!(serialize_i8: i8 serialize_i16: i16 serialize_i32: i32 serialize_i64: i64
+        serialize_u8: u8 serialize_u16: u16 serialize_u32: u32 serialize_u64: u64);
+
+    key_invalid!(serialize_bool(bool) serialize_f32(f32) serialize_f64(f64) serialize_char(char)
+        serialize_bytes(&[u8]) serialize_none() serialize_unit()
+        serialize_unit_struct(&'static str)
+        serialize_unit_variant(&'static str, u32, &'static str));
+
+    fn serialize_str(self, v: &str) -> Result<u8, Error> {
+        v.parse().map_err(|_| Error::Invalid)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<u8, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<u8, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<u8, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::Invalid)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_slice, AsList};
+    use crate::{error::Error, tlv::Nullable};
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = [0u8; 64];
+        let len = to_slice(value, &mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[derive(Serialize)]
+    struct Cmd<'a> {
+        endpoint: u16,
+        #[serde(rename = "3")]
+        name: &'a str,
+        optional: Option<u8>,
+        #[serde(rename = "254")]
+        fab_idx: u8,
+    }
+
+    #[test]
+    fn test_serialize_struct() {
+        let cmd = Cmd {
+            endpoint: 0x1234,
+            name: "hi",
+            optional: None,
+            fab_idx: 2,
+        };
+        // The fields are tagged by their position, or the number they are renamed to, and the
+        // None field is left out
+        assert_eq!(
+            encode(&cmd),
+            [0x15, 0x25, 0, 0x34, 0x12, 0x2c, 3, 2, b'h', b'i', 0x24, 0xfe, 2, 0x18]
+        );
+
+        let cmd = Cmd {
+            optional: Some(5),
+            ..cmd
+        };
+        assert_eq!(encode(&cmd)[10..13], [0x24, 2, 5]);
+    }
+
+    #[test]
+    fn test_serialize_containers() {
+        assert_eq!(encode(&[1u8, 2]), [0x16, 4, 1, 4, 2, 0x18]);
+        assert_eq!(encode(&AsList((1u8, -1i8))), [0x17, 4, 1, 0, 0xff, 0x18]);
+
+        // Nulls, for the Nullables and the None that aren't struct fields
+        let values = vec![Nullable::Null, Nullable::NotNull(3u8)];
+        assert_eq!(encode(&values), [0x16, 0x14, 4, 3, 0x18]);
+        assert_eq!(encode(&[None, Some(true)]), [0x16, 0x14, 9, 0x18]);
+
+        let mut map = BTreeMap::new();
+        map.insert(2u8, "a");
+        map.insert(7u8, "b");
+        assert_eq!(
+            encode(&map),
+            [0x15, 0x2c, 2, 1, b'a', 0x2c, 7, 1, b'b', 0x18]
+        );
+        let mut map = BTreeMap::new();
+        map.insert("key", 1u8);
+        let mut buf = [0u8; 16];
+        assert_eq!(to_slice(&map, &mut buf), Err(Error::Invalid));
+    }
+
+    #[derive(Serialize)]
+    enum Value {
+        Off,
+        On,
+        Level(u8),
+        Color { hue: u8 },
+    }
+
+    #[test]
+    fn test_serialize_enum() {
+        assert_eq!(encode(&Value::Off), [4, 0]);
+        assert_eq!(encode(&Value::On), [4, 1]);
+        assert_eq!(encode(&Value::Level(9)), [0x15, 0x24, 2, 9, 0x18]);
+        assert_eq!(
+            encode(&Value::Color { hue: 9 }),
+            [0x15, 0x35, 3, 0x24, 0, 9, 0x18, 0x18]
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::convert::TryFrom;
+
+use log::error;
+
+use super::{ElementType, TLVElement, TLVList, TagType};
+use crate::error::Error;
+
+/// The maximum nesting of containers that [TLVValidator::new] accepts
+pub const TLV_MAX_DEPTH: usize = 16;
+
+/// A strict parser, for the TLV that comes from a peer
+///
+/// The other parsing functions take the TLV as it comes: they stop at the first element that
+/// doesn't parse, and look no further than the elements that are asked for. This checks the
+/// whole buffer before handing out its root element:
+/// - the containers are nested no deeper than the maximum depth, and are all terminated
+/// - the members of a struct are tagged, and no two of them have the same tag
+/// - the elements of an array, and the ends of containers, are anonymous
+/// - nothing follows the root element
+/// - optionally, the integers are encoded with the smallest width that holds their value
+///
+/// Any of these is reported as [Error::InvalidData].
+#[derive(Debug, Clone, Copy)]
+pub struct TLVValidator {
+    max_depth: usize,
+    canonical_ints: bool,
+}
+
+enum Container {
+    // With the tags of the members so far
+    Struct(Vec<TagType>),
+    Array,
+    List,
+}
+
+impl TLVValidator {
+    pub fn new() -> Self {
+        Self::new_with(TLV_MAX_DEPTH, false)
+    }
+
+    pub fn new_with(max_depth: usize, canonical_ints: bool) -> Self {
+        Self {
+            max_depth,
+            canonical_ints,
+        }
+    }
+
+    pub fn validate(&self, b: &[u8]) -> Result<(), Error> {
+        self.root(b).map(|_| ())
+    }
+
+    /// The root element of the buffer, if the buffer is valid
+    pub fn root<'a>(&self, b: &'a [u8]) -> Result<TLVElement<'a>, Error> {
+        let mut iter = TLVList::new(b).iter();
+        let root = iter.next().ok_or_else(|| invalid("Invalid root element"))?;
+        let mut stack = Vec::new();
+        self.check(&root, &mut stack)?;
+        while !stack.is_empty() {
+            let element = iter
+                .next()
+                .ok_or_else(|| invalid("Invalid element, or unterminated container"))?;
+            self.check(&element, &mut stack)?;
+        }
+        if iter.remaining() != 0 {
+            error!("{} bytes after the root element", iter.remaining());
+            return Err(Error::InvalidData);
+        }
+        Ok(root)
+    }
+
+    fn check(&self, element: &TLVElement, stack: &mut Vec<Container>) -> Result<(), Error> {
+        let tag = element.get_tag();
+        if let ElementType::EndCnt = element.get_element_type() {
+            if tag != TagType::Anonymous {
+                return Err(invalid("Tagged end of container"));
+            }
+            stack
+                .pop()
+                .ok_or_else(|| invalid("End of container outside a container"))?;
+            return Ok(());
+        }
+
+        match stack.last_mut() {
+            Some(Container::Struct(tags)) => {
+                if tag == TagType::Anonymous {
+                    return Err(invalid("Anonymous member in a struct"));
+                }
+                if tags.contains(&tag) {
+                    error!("Duplicate tag {:?} in a struct", tag);
+                    return Err(Error::InvalidData);
+                }
+                tags.push(tag);
+            }
+            Some(Container::Array) if tag != TagType::Anonymous => {
+                return Err(invalid("Tagged element in an array"));
+            }
+            _ => (),
+        }
+
+        if self.canonical_ints && !is_canonical(element.get_element_type()) {
+            error!("Non-canonical integer {:?}", element.get_element_type());
+            return Err(Error::InvalidData);
+        }
+
+        let container = match element.get_element_type() {
+            ElementType::Struct(_) => Container::Struct(Vec::new()),
+            ElementType::Array(_) => Container::Array,
+            ElementType::List(_) => Container::List,
+            _ => return Ok(()),
+        };
+        if stack.len() >= self.max_depth {
+            return Err(invalid("Containers nested too deep"));
+        }
+        stack.push(container);
+        Ok(())
+    }
+}
+
+impl Default for TLVValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    error!("{}", msg);
+    Error::InvalidData
+}
+
+// An integer that would fit in a smaller width isn't canonical
+fn is_canonical(element_type: ElementType) -> bool {
+    match element_type {
+        ElementType::S16(a) => i8::try_from(a).is_err(),
+        ElementType::S32(a) => i16::try_from(a).is_err(),
+        ElementType::S64(a) => i32::try_from(a).is_err(),
+        ElementType::U16(a) => a > u8::MAX as u16,
+        ElementType::U32(a) => a > u16::MAX as u32,
+        ElementType::U64(a) => a > u32::MAX as u64,
+        _ => true,
+    }
+}
+
+/// The root element of the buffer, from the [TLVValidator] with the default limits
+pub fn get_root_node_strict(b: &[u8]) -> Result<TLVElement<'_>, Error> {
+    TLVValidator::new().root(b)
+}
+
+/// The root struct of the buffer, see [get_root_node_strict]
+pub fn get_root_node_struct_strict(b: &[u8]) -> Result<TLVElement<'_>, Error> {
+    get_root_node_strict(b)?.confirm_struct()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_root_node_strict, get_root_node_struct_strict, TLVValidator};
+    use crate::error::Error;
+
+    #[test]
+    fn test_valid() {
+        // A struct, with an array of lists of anonymous and tagged elements
+        let b = [
+            0x15, 0x24, 0, 1, 0x36, 1, 0x17, 0x04, 2, 0x24, 3, 4, 0x18, 0x17, 0x18, 0x18, 0x35, 2,
+            0x18, 0x18,
+        ];
+        let root = get_root_node_struct_strict(&b).unwrap();
+        assert_eq!(root.find_tag(0).unwrap().u8(), Ok(1));
+        assert_eq!(
+            get_root_node_strict(&[0x05, 0x34, 0x12]).unwrap().u16(),
+            Ok(0x1234)
+        );
+    }
+
+    #[test]
+    fn test_structure() {
+        // Unterminated container, at the end and in the middle
+        assert_eq!(
+            get_root_node_strict(&[0x15, 0x24, 0, 1]),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            get_root_node_strict(&[0x15, 0x35, 0, 0x18]),
+            Err(Error::InvalidData)
+        );
+        // Trailing garbage, as an element and as bytes that don't parse
+        assert_eq!(
+            get_root_node_strict(&[0x15, 0x18, 0x04, 1]),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            get_root_node_strict(&[0x04, 1, 0x1f]),
+            Err(Error::InvalidData)
+        );
+        // Stray end of container
+        assert_eq!(get_root_node_strict(&[0x18]), Err(Error::InvalidData));
+        assert_eq!(get_root_node_strict(&[]), Err(Error::InvalidData));
+        // Truncated value
+        assert_eq!(
+            get_root_node_strict(&[0x15, 0x25, 0, 1]),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        // Duplicate tag in a struct, but not in a list
+        let b = [0x15, 0x24, 1, 1, 0x24, 1, 2, 0x18];
+        assert_eq!(get_root_node_strict(&b), Err(Error::InvalidData));
+        let b = [0x17, 0x24, 1, 1, 0x24, 1, 2, 0x18];
+        assert!(get_root_node_strict(&b).is_ok());
+        // Anonymous member of a struct, tagged element in an array, tagged end of container
+        let b = [0x15, 0x04, 1, 0x18];
+        assert_eq!(get_root_node_strict(&b), Err(Error::InvalidData));
+        let b = [0x16, 0x24, 1, 1, 0x18];
+        assert_eq!(get_root_node_strict(&b), Err(Error::InvalidData));
+        let b = [0x15, 0x38, 1];
+        assert_eq!(get_root_node_strict(&b), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn test_limits() {
+        let b = [0x16, 0x16, 0x16, 0x18, 0x18, 0x18];
+        assert!(TLVValidator::new_with(3, false).validate(&b).is_ok());
+        assert_eq!(
+            TLVValidator::new_with(2, false).validate(&b),
+            Err(Error::InvalidData)
+        );
+        let mut deep = vec![0x16; 100];
+        deep.extend_from_slice(&[0x18; 100]);
+        assert_eq!(TLVValidator::new().validate(&deep), Err(Error::InvalidData));
+
+        // 0x12 in 16 bits, -1 in 32 bits, and the smallest encoding of 0x100 and -129
+        let canonical = TLVValidator::new_with(4, true);
+        assert!(TLVValidator::new().validate(&[0x05, 0x12, 0]).is_ok());
+        assert_eq!(
+            canonical.validate(&[0x05, 0x12, 0]),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            canonical.validate(&[0x02, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::InvalidData)
+        );
+        assert!(canonical.validate(&[0x05, 0, 1]).is_ok());
+        assert!(canonical.validate(&[0x01, 0x7f, 0xff]).is_ok());
+    }
+}
@@ -52,7 +52,7 @@ macro_rules! fromtlv_for {
     };
 }
 
-fromtlv_for!(u8 u16 u32 u64 bool);
+fromtlv_for!(i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 bool);
 
 pub trait ToTLV {
     fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error>;
@@ -81,7 +81,39 @@ impl<'a, T: ToTLV, const N: usize> ToTLV for [T; N] {
 }
 
 // Generate ToTLV for standard data types
-totlv_for!(i8 u8 u16 u32 u64 bool);
+totlv_for!(i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 bool);
+
+impl<T: ToTLV + ?Sized> ToTLV for &T {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        (**self).to_tlv(tw, tag)
+    }
+}
+
+/// A UTF-8 string, borrowed from the TLV list
+impl<'a> FromTLV<'a> for &'a str {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<&'a str, Error> {
+        t.str()
+    }
+}
+
+impl ToTLV for str {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.utf64(tag, self.as_bytes())
+    }
+}
+
+/// An octet string, borrowed from the TLV list
+impl<'a> FromTLV<'a> for &'a [u8] {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<&'a [u8], Error> {
+        t.slice()
+    }
+}
+
+impl ToTLV for [u8] {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.str64(tag, self)
+    }
+}
 
 // We define a few common data types that will be required here
 //
@@ -103,9 +135,15 @@ impl<'a> UtfStr<'a> {
     }
 }
 
+impl<'a> FromTLV<'a> for UtfStr<'a> {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<UtfStr<'a>, Error> {
+        t.slice().map(UtfStr)
+    }
+}
+
 impl<'a> ToTLV for UtfStr<'a> {
     fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
-        tw.utf16(tag, self.0)
+        tw.utf64(tag, self.0)
     }
 }
 
@@ -127,7 +165,7 @@ impl<'a> FromTLV<'a> for OctetStr<'a> {
 
 impl<'a> ToTLV for OctetStr<'a> {
     fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
-        tw.str16(tag, self.0)
+        tw.str64(tag, self.0)
     }
 }
 
@@ -140,7 +178,7 @@ impl FromTLV<'_> for Vec<u8> {
 
 impl ToTLV for Vec<u8> {
     fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
-        tw.str16(tag, self.as_slice())
+        tw.str64(tag, self.as_slice())
     }
 }
 
@@ -162,7 +200,7 @@ impl FromTLV<'_> for String {
 
 impl ToTLV for String {
     fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
-        tw.utf16(tag, self.as_bytes())
+        tw.utf64(tag, self.as_bytes())
     }
 }
 
@@ -249,6 +287,12 @@ impl<'a, T: FromTLV<'a>> FromTLV<'a> for TLVArrayOwned<T> {
     }
 }
 
+impl<T> From<Vec<T>> for TLVArrayOwned<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self(vec)
+    }
+}
+
 impl<T: ToTLV> ToTLV for TLVArrayOwned<T> {
     fn to_tlv(&self, tw: &mut TLVWriter, tag_type: TagType) -> Result<(), Error> {
         tw.start_array(tag_type)?;
@@ -349,10 +393,107 @@ impl<'a, T> FromTLV<'a> for TLVArray<'a, T> {
 
 #[cfg(test)]
 mod tests {
-    use super::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV};
-    use crate::{error::Error, tlv::TLVList, utils::writebuf::WriteBuf};
+    use super::{FromTLV, Nullable, OctetStr, TLVElement, TLVWriter, TagType, ToTLV};
+    use crate::{
+        data_model::objects::GlobalElements, error::Error, tlv::TLVList, utils::writebuf::WriteBuf,
+    };
     use matter_macro_derive::{FromTLV, ToTLV};
 
+    // Encodes the value, and decodes it back
+    fn round_trip<T>(value: T, buf: &mut [u8]) -> T
+    where
+        T: ToTLV + for<'a> FromTLV<'a>,
+    {
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        value.to_tlv(&mut tw, TagType::Context(1)).unwrap();
+        let root = TLVList::new(writebuf.as_slice()).iter().next().unwrap();
+        assert_eq!(root.get_tag(), TagType::Context(1));
+        T::from_tlv(&root).unwrap()
+    }
+
+    #[test]
+    fn test_primitives_round_trip() {
+        let mut buf = [0u8; 16];
+        for v in [i8::MIN, -1, 0, i8::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [i16::MIN, -129, 128, i16::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [i32::MIN, -32769, 32768, i32::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [i64::MIN, -1, i64::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [0, u8::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [0, u16::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [0, u32::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [0, u64::MAX] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [f32::MIN, -0.5, f32::INFINITY] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        for v in [f64::MIN_POSITIVE, 1e300] {
+            assert_eq!(round_trip(v, &mut buf), v);
+        }
+        assert!(round_trip(f64::NAN, &mut buf).is_nan());
+        assert!(round_trip(true, &mut buf));
+        assert!(!round_trip(false, &mut buf));
+    }
+
+    #[test]
+    fn test_primitives_mismatch() {
+        // A negative value isn't decoded as unsigned, and a wider value isn't narrowed
+        let b = [0x20, 0x01, 0xff];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(i64::from_tlv(&root), Ok(-1));
+        assert_eq!(u8::from_tlv(&root), Err(Error::TLVTypeMismatch));
+        assert_eq!(f32::from_tlv(&root), Err(Error::TLVTypeMismatch));
+
+        let b = [0x21, 0x01, 0x00, 0x01];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(i32::from_tlv(&root), Ok(256));
+        assert_eq!(i8::from_tlv(&root), Err(Error::TLVTypeMismatch));
+
+        // A single precision float is widened to a double
+        let b = [0x2a, 0x01, 0x00, 0x00, 0xc0, 0x3f];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(f64::from_tlv(&root), Ok(1.5));
+    }
+
+    #[test]
+    fn test_borrowed_strings() {
+        let mut buf = [0u8; 16];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        tw.start_struct(TagType::Anonymous).unwrap();
+        "hi".to_tlv(&mut tw, TagType::Context(0)).unwrap();
+        [1u8, 2][..].to_tlv(&mut tw, TagType::Context(1)).unwrap();
+        tw.end_container().unwrap();
+
+        let root = TLVList::new(&buf).iter().next().unwrap();
+        let s: &str = FromTLV::from_tlv(&root.find_tag(0).unwrap()).unwrap();
+        assert_eq!(s, "hi");
+        let o: &[u8] = FromTLV::from_tlv(&root.find_tag(1).unwrap()).unwrap();
+        assert_eq!(o, [1, 2]);
+        // An octet string isn't a UTF-8 string
+        assert_eq!(
+            <&str>::from_tlv(&root.find_tag(1).unwrap()),
+            Err(Error::TLVTypeMismatch)
+        );
+    }
+
     #[derive(ToTLV)]
     struct TestDerive {
         a: u16,
@@ -486,4 +627,159 @@ mod tests {
             [21, 36, 1, 10, 24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
         );
     }
+
+    #[derive(FromTLV, ToTLV, PartialEq, Debug)]
+    struct TestDeriveTags<'a> {
+        a: u8,
+        #[tagval(5)]
+        b: &'a [u8],
+        c: Option<Nullable<u8>>,
+        d: [u16; 2],
+    }
+
+    #[test]
+    fn test_derive_tags() {
+        let mut buf: [u8; 32] = [0; 32];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        // The members after b get the tags that follow 5
+        let abc = TestDeriveTags {
+            a: 1,
+            b: &[2, 3],
+            c: Some(Nullable::Null),
+            d: [4, 0x506],
+        };
+        abc.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let b = [
+            21, 36, 0, 1, 48, 5, 2, 2, 3, 52, 6, 54, 7, 4, 4, 5, 6, 5, 24, 24,
+        ];
+        assert_eq!(writebuf.as_slice(), b);
+
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(TestDeriveTags::from_tlv(&root).unwrap(), abc);
+    }
+
+    #[derive(FromTLV, Debug)]
+    struct TestDeriveReversed {
+        #[tagval(2)]
+        a: u8,
+        #[tagval(1)]
+        b: u8,
+    }
+
+    #[derive(FromTLV, Debug)]
+    #[tlvargs(unordered)]
+    struct TestDeriveUnordered {
+        a: u8,
+        b: u8,
+    }
+
+    #[test]
+    fn test_derive_fromtlv_unordered() {
+        let b = [21, 36, 1, 10, 36, 0, 20, 36, 2, 30, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        let test = TestDeriveReversed::from_tlv(&root).unwrap();
+        assert_eq!(test.a, 30);
+        assert_eq!(test.b, 10);
+        let test = TestDeriveUnordered::from_tlv(&root).unwrap();
+        assert_eq!(test.a, 20);
+        assert_eq!(test.b, 10);
+    }
+
+    #[derive(FromTLV, ToTLV, Debug)]
+    struct TestDeriveConstTag {
+        #[tagval(GlobalElements::FabricIndex)]
+        fab_idx: u8,
+        next: u8,
+    }
+
+    #[test]
+    fn test_derive_const_tag() {
+        let mut buf: [u8; 20] = [0; 20];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        let abc = TestDeriveConstTag {
+            fab_idx: 3,
+            next: 4,
+        };
+        abc.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let b = [21, 36, 0xFE, 3, 36, 0xFF, 4, 24];
+        assert_eq!(writebuf.as_slice(), b);
+
+        let root = TLVList::new(&b).iter().next().unwrap();
+        let test = TestDeriveConstTag::from_tlv(&root).unwrap();
+        assert_eq!(test.fab_idx, 3);
+        assert_eq!(test.next, 4);
+    }
+
+    #[test]
+    fn test_derive_fromtlv_errors() {
+        // A member of the wrong type, and a missing member
+        let b = [21, 37, 0, 10, 0, 0x30, 1, 0, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(
+            TestDeriveSimple::from_tlv(&root).map(|_| ()),
+            Err(Error::TLVField("TestDeriveSimple::b"))
+        );
+        let b = [21, 37, 0, 10, 0, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(
+            TestDeriveSimple::from_tlv(&root).map(|_| ()),
+            Err(Error::TLVField("TestDeriveSimple::b"))
+        );
+        // The innermost member is named
+        let b = [21, 36, 0, 1, 53, 1, 37, 0, 10, 0, 24, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(
+            TestDeriveNested::from_tlv(&root).map(|_| ()),
+            Err(Error::TLVField("TestDeriveSimple::b"))
+        );
+    }
+
+    #[derive(FromTLV)]
+    struct TestDeriveNested {
+        _a: u8,
+        _simple: TestDeriveSimple,
+    }
+
+    #[derive(ToTLV, FromTLV, PartialEq, Debug)]
+    enum TestDeriveUnion<'a> {
+        #[tagval(1)]
+        Str(OctetStr<'a>),
+        Value(u8),
+    }
+
+    #[test]
+    fn test_derive_from_to_tlv_union() {
+        let b = [21, 48, 1, 2, b'h', b'i', 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(
+            TestDeriveUnion::from_tlv(&root),
+            Ok(TestDeriveUnion::Str(OctetStr(b"hi")))
+        );
+
+        let mut buf: [u8; 20] = [0; 20];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        TestDeriveUnion::Value(7)
+            .to_tlv(&mut tw, TagType::Anonymous)
+            .unwrap();
+        assert_eq!(writebuf.as_slice(), [21, 36, 2, 7, 24]);
+
+        // An unknown tag, and a member of the wrong type
+        let b = [21, 36, 0, 7, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(TestDeriveUnion::from_tlv(&root), Err(Error::Invalid));
+        let b = [21, 48, 2, 0, 24];
+        let root = TLVList::new(&b).iter().next().unwrap();
+        assert_eq!(
+            TestDeriveUnion::from_tlv(&root),
+            Err(Error::TLVField("TestDeriveUnion::Value"))
+        );
+    }
 }
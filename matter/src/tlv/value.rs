@@ -0,0 +1,710 @@
+use std::fmt;
+
+use log::error;
+#[cfg(feature = "serde")]
+use serde_json::{Map, Number, Value};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+
+use super::{
+    get_root_node, ElementType, FromTLV, IntWidth, TLVElement, TLVWriter, TagType, ToTLV,
+    TLV_MAX_DEPTH,
+};
+use crate::error::Error;
+
+/// An owned TLV element, with the element's value, but not its tag
+///
+/// The integers, and the lengths of the strings, keep the width they were encoded with, so any
+/// TLV is written back byte for byte. The values that are converted from a Rust value, or from
+/// JSON, have the smallest width that holds them, as [TLVWriter] does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TLVValue {
+    Int(i64, IntWidth),
+    UInt(u64, IntWidth),
+    Bool(bool),
+    Float(f32),
+    Double(f64),
+    String(String, IntWidth),
+    Bytes(Vec<u8>, IntWidth),
+    Null,
+    Struct(Vec<(TagType, TLVValue)>),
+    Array(Vec<TLVValue>),
+    List(Vec<(TagType, TLVValue)>),
+}
+
+impl TLVValue {
+    /// The value of the element, with containers nested no deeper than [TLV_MAX_DEPTH]
+    pub fn from_element(element: &TLVElement) -> Result<Self, Error> {
+        Self::from_element_at(element, 0)
+    }
+
+    fn from_element_at(element: &TLVElement, depth: usize) -> Result<Self, Error> {
+        let value = match element.get_element_type() {
+            ElementType::S8(a) => Self::Int(a.into(), IntWidth::W8),
+            ElementType::S16(a) => Self::Int(a.into(), IntWidth::W16),
+            ElementType::S32(a) => Self::Int(a.into(), IntWidth::W32),
+            ElementType::S64(a) => Self::Int(a, IntWidth::W64),
+            ElementType::U8(a) => Self::UInt(a.into(), IntWidth::W8),
+            ElementType::U16(a) => Self::UInt(a.into(), IntWidth::W16),
+            ElementType::U32(a) => Self::UInt(a.into(), IntWidth::W32),
+            ElementType::U64(a) => Self::UInt(a, IntWidth::W64),
+            ElementType::False => Self::Bool(false),
+            ElementType::True => Self::Bool(true),
+            ElementType::F32(a) => Self::Float(a),
+            ElementType::F64(a) => Self::Double(a),
+            ElementType::Utf8l(_) => Self::string(element, IntWidth::W8)?,
+            ElementType::Utf16l(_) => Self::string(element, IntWidth::W16)?,
+            ElementType::Utf32l(_) => Self::string(element, IntWidth::W32)?,
+            ElementType::Utf64l(_) => Self::string(element, IntWidth::W64)?,
+            ElementType::Str8l(a) => Self::Bytes(a.to_vec(), IntWidth::W8),
+            ElementType::Str16l(a) => Self::Bytes(a.to_vec(), IntWidth::W16),
+            ElementType::Str32l(a) => Self::Bytes(a.to_vec(), IntWidth::W32),
+            ElementType::Str64l(a) => Self::Bytes(a.to_vec(), IntWidth::W64),
+            ElementType::Null => Self::Null,
+            ElementType::Struct(_) => Self::Struct(Self::members(element, depth)?),
+            ElementType::List(_) => Self::List(Self::members(element, depth)?),
+            ElementType::Array(_) => {
+                let mut elements = Vec::new();
+                for (tag, value) in Self::members(element, depth)? {
+                    if tag != TagType::Anonymous {
+                        error!("Tagged element {:?} in an array", tag);
+                        return Err(Error::InvalidData);
+                    }
+                    elements.push(value);
+                }
+                Self::Array(elements)
+            }
+            ElementType::EndCnt | ElementType::Last => return Err(Error::InvalidData),
+        };
+        Ok(value)
+    }
+
+    fn string(element: &TLVElement, width: IntWidth) -> Result<Self, Error> {
+        Ok(Self::String(element.str()?.to_owned(), width))
+    }
+
+    /// The value of the root element of the buffer
+    pub fn from_slice(buf: &[u8]) -> Result<Self, Error> {
+        Self::from_element(&get_root_node(buf)?)
+    }
+
+    fn members(element: &TLVElement, depth: usize) -> Result<Vec<(TagType, TLVValue)>, Error> {
+        if depth >= TLV_MAX_DEPTH {
+            error!("Containers nested too deep");
+            return Err(Error::InvalidData);
+        }
+        let iter = element.enter().ok_or(Error::TLVTypeMismatch)?;
+        iter.map(|e| Ok((e.get_tag(), Self::from_element_at(&e, depth + 1)?)))
+            .collect()
+    }
+
+    /// The element with this context tag, in a struct or a list
+    pub fn get(&self, tag: u8) -> Option<&TLVValue> {
+        match self {
+            Self::Struct(members) | Self::List(members) => members
+                .iter()
+                .find(|(t, _)| *t == TagType::Context(tag))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The name of the type in the JSON format of the Matter SDK
+    pub fn type_name(&self) -> Result<String, Error> {
+        let name = match self {
+            Self::Int(..) => "INT",
+            Self::UInt(..) => "UINT",
+            Self::Bool(_) => "BOOL",
+            Self::Float(_) => "FLOAT",
+            Self::Double(_) => "DOUBLE",
+            Self::String(..) => "STRING",
+            Self::Bytes(..) => "BYTES",
+            Self::Null => "NULL",
+            Self::Struct(_) => "STRUCT",
+            Self::List(_) => "LIST",
+            Self::Array(elements) => {
+                // All the elements of an array have the same type
+                let mut names = elements.iter().map(|e| e.type_name());
+                let name = match names.next() {
+                    Some(name) => name?,
+                    None => "?".to_owned(),
+                };
+                for n in names {
+                    if n? != name {
+                        error!("The elements of an array have different types");
+                        return Err(Error::Invalid);
+                    }
+                }
+                return Ok(format!("ARRAY-{}", name));
+            }
+        };
+        Ok(name.to_owned())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TLVValue {
+    /// Convert a struct to the JSON format of the Matter SDK
+    ///
+    /// The members of the struct are the members of a JSON object, with keys such as `"1:UINT"`:
+    /// the context tag, and the type name. Integers that don't fit in 32 bits are strings, and
+    /// octet strings are base64. The Matter SDK has no lists, they are arrays of objects with a
+    /// single member each, keyed like the members of a struct, or by the type name alone for the
+    /// anonymous members.
+    ///
+    /// JSON has no widths, the integers and the strings are converted with the smallest one.
+    pub fn to_json(&self) -> Result<String, Error> {
+        match self {
+            Self::Struct(_) => {
+                serde_json::to_string_pretty(&self.json_value()?).map_err(|_| Error::Invalid)
+            }
+            _ => {
+                error!("Only a struct can be converted to JSON");
+                Err(Error::Invalid)
+            }
+        }
+    }
+
+    /// Parse the JSON format of the Matter SDK, see [TLVValue::to_json]
+    ///
+    /// The members of the structs and of the lists are kept in their order.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let value: Value = serde_json::from_str(json).map_err(|e| {
+            error!("Invalid JSON: {}", e);
+            Error::InvalidData
+        })?;
+        Self::from_json_value("STRUCT", &value)
+    }
+
+    fn json_value(&self) -> Result<Value, Error> {
+        let value = match self {
+            Self::Int(a, _) => match i32::try_from(*a) {
+                Ok(a) => Value::from(a),
+                Err(_) => Value::String(a.to_string()),
+            },
+            Self::UInt(a, _) => match u32::try_from(*a) {
+                Ok(a) => Value::from(a),
+                Err(_) => Value::String(a.to_string()),
+            },
+            Self::Bool(a) => Value::Bool(*a),
+            Self::Float(a) => float_json(*a as f64)?,
+            Self::Double(a) => float_json(*a)?,
+            Self::String(a, _) => Value::String(a.clone()),
+            Self::Bytes(a, _) => Value::String(base64::encode(a)),
+            Self::Null => Value::Null,
+            Self::Struct(members) => {
+                let mut map = Map::new();
+                for (tag, value) in members {
+                    if *tag == TagType::Anonymous {
+                        error!("Anonymous member in a struct");
+                        return Err(Error::Invalid);
+                    }
+                    if members.iter().filter(|(t, _)| t == tag).count() > 1 {
+                        error!("Duplicate tag {:?} in a struct", tag);
+                        return Err(Error::Invalid);
+                    }
+                    map.insert(json_key(*tag, value)?, value.json_value()?);
+                }
+                Value::Object(map)
+            }
+            Self::List(members) => {
+                let mut elements = Vec::new();
+                for (tag, value) in members {
+                    let mut map = Map::new();
+                    map.insert(json_key(*tag, value)?, value.json_value()?);
+                    elements.push(Value::Object(map));
+                }
+                Value::Array(elements)
+            }
+            Self::Array(elements) => {
+                // This checks that all the elements are of the same type
+                self.type_name()?;
+                Value::Array(
+                    elements
+                        .iter()
+                        .map(|e| e.json_value())
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+        };
+        Ok(value)
+    }
+
+    fn from_json_value(type_name: &str, value: &Value) -> Result<Self, Error> {
+        let result = match (type_name, value) {
+            ("INT", Value::Number(a)) => a.as_i64().map(Self::from),
+            ("INT", Value::String(a)) => a.parse::<i64>().ok().map(Self::from),
+            ("UINT", Value::Number(a)) => a.as_u64().map(Self::from),
+            ("UINT", Value::String(a)) => a.parse::<u64>().ok().map(Self::from),
+            ("BOOL", Value::Bool(a)) => Some(Self::Bool(*a)),
+            ("FLOAT", _) => json_float(value).map(|a| Self::Float(a as f32)),
+            ("DOUBLE", _) => json_float(value).map(Self::Double),
+            ("STRING", Value::String(a)) => Some(Self::from(a.as_str())),
+            ("BYTES", Value::String(a)) => base64::decode(a).ok().map(Self::from),
+            ("NULL", Value::Null) => Some(Self::Null),
+            ("STRUCT", Value::Object(map)) => {
+                let mut members = Vec::new();
+                for (key, value) in map {
+                    members.push(Self::from_json_member(key, value, false)?);
+                }
+                Some(Self::Struct(members))
+            }
+            ("LIST", Value::Array(elements)) => {
+                let mut members = Vec::new();
+                for element in elements {
+                    match element {
+                        Value::Object(map) if map.len() == 1 => {
+                            for (key, value) in map {
+                                members.push(Self::from_json_member(key, value, true)?);
+                            }
+                        }
+                        _ => {
+                            error!("Invalid JSON list member {}", element);
+                            return Err(Error::InvalidData);
+                        }
+                    }
+                }
+                Some(Self::List(members))
+            }
+            (_, Value::Array(elements)) if type_name.starts_with("ARRAY-") => {
+                let element_type = &type_name["ARRAY-".len()..];
+                if element_type == "?" && !elements.is_empty() {
+                    None
+                } else {
+                    Some(Self::Array(
+                        elements
+                            .iter()
+                            .map(|e| Self::from_json_value(element_type, e))
+                            .collect::<Result<_, _>>()?,
+                    ))
+                }
+            }
+            _ => None,
+        };
+        result.ok_or_else(|| {
+            error!("Invalid JSON value {} for {}", value, type_name);
+            Error::InvalidData
+        })
+    }
+
+    // The key is the tag and the type, optionally following a name. Only the members of a list
+    // may be anonymous, with just the type.
+    fn from_json_member(
+        key: &str,
+        value: &Value,
+        anonymous: bool,
+    ) -> Result<(TagType, Self), Error> {
+        let mut parts = key.rsplit(':');
+        let type_name = parts.next().ok_or(Error::InvalidData)?;
+        let tag = match parts.next() {
+            None if anonymous => Some(TagType::Anonymous),
+            tag => tag.and_then(|t| t.parse().ok()).map(TagType::Context),
+        };
+        let tag = tag.ok_or_else(|| {
+            error!("Invalid JSON key {}", key);
+            Error::InvalidData
+        })?;
+        Ok((tag, Self::from_json_value(type_name, value)?))
+    }
+}
+
+// The key of a member of a struct or a list, see [TLVValue::to_json]
+#[cfg(feature = "serde")]
+fn json_key(tag: TagType, value: &TLVValue) -> Result<String, Error> {
+    match tag {
+        TagType::Anonymous => value.type_name(),
+        TagType::Context(tag) => Ok(format!("{}:{}", tag, value.type_name()?)),
+        _ => {
+            error!("Only context tags have a JSON representation: {:?}", tag);
+            Err(Error::Invalid)
+        }
+    }
+}
+
+// JSON has no infinities, the SDK writes them as strings
+#[cfg(feature = "serde")]
+fn float_json(a: f64) -> Result<Value, Error> {
+    if a == f64::INFINITY {
+        Ok(Value::String("Infinity".to_owned()))
+    } else if a == f64::NEG_INFINITY {
+        Ok(Value::String("-Infinity".to_owned()))
+    } else {
+        Number::from_f64(a).map(Value::Number).ok_or_else(|| {
+            error!("NaN has no JSON representation");
+            Error::Invalid
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+fn json_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(a) => a.as_f64(),
+        Value::String(a) if a == "Infinity" => Some(f64::INFINITY),
+        Value::String(a) if a == "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+impl<'a> FromTLV<'a> for TLVValue {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        Self::from_element(t)
+    }
+}
+
+impl ToTLV for TLVValue {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        match self {
+            Self::Int(a, width) => tw.i64_with_width(tag, *a, *width),
+            Self::UInt(a, width) => tw.u64_with_width(tag, *a, *width),
+            Self::Bool(a) => tw.bool(tag, *a),
+            Self::Float(a) => tw.f32(tag, *a),
+            Self::Double(a) => tw.f64(tag, *a),
+            Self::String(a, width) => tw.utf_with_width(tag, a.as_bytes(), *width),
+            Self::Bytes(a, width) => tw.str_with_width(tag, a, *width),
+            Self::Null => tw.null(tag),
+            Self::Struct(members) => {
+                tw.start_struct(tag)?;
+                for (tag, value) in members {
+                    value.to_tlv(tw, *tag)?;
+                }
+                tw.end_container()
+            }
+            Self::Array(elements) => {
+                tw.start_array(tag)?;
+                for value in elements {
+                    value.to_tlv(tw, TagType::Anonymous)?;
+                }
+                tw.end_container()
+            }
+            Self::List(members) => {
+                tw.start_list(tag)?;
+                for (tag, value) in members {
+                    value.to_tlv(tw, *tag)?;
+                }
+                tw.end_container()
+            }
+        }
+    }
+}
+
+macro_rules! from_primitive {
+    ($($t:ty => $v:ident),*) => {
+        $(
+            impl From<$t> for TLVValue {
+                fn from(a: $t) -> Self {
+                    Self::$v(a.into())
+                }
+            }
+        )*
+    };
+}
+
+from_primitive!(bool => Bool, f32 => Float, f64 => Double);
+
+// The integers and the strings take the smallest width that holds them
+macro_rules! from_int {
+    ($($t:ty => $v:ident, $width:ident),*) => {
+        $(
+            impl From<$t> for TLVValue {
+                fn from(a: $t) -> Self {
+                    Self::$v(a.into(), IntWidth::$width(a.into()))
+                }
+            }
+        )*
+    };
+}
+
+from_int!(i8 => Int, of_i64, i16 => Int, of_i64, i32 => Int, of_i64, i64 => Int, of_i64,
+    u8 => UInt, of_u64, u16 => UInt, of_u64, u32 => UInt, of_u64, u64 => UInt, of_u64);
+
+macro_rules! from_str {
+    ($($t:ty => $v:ident),*) => {
+        $(
+            impl From<$t> for TLVValue {
+                fn from(a: $t) -> Self {
+                    let width = IntWidth::of_u64(a.len() as u64);
+                    Self::$v(a.into(), width)
+                }
+            }
+        )*
+    };
+}
+
+from_str!(String => String, &str => String, Vec<u8> => Bytes, &[u8] => Bytes);
+
+impl TLVValue {
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        match self {
+            Self::Int(a, _) => write!(f, "{} (INT)", a),
+            Self::UInt(a, _) => write!(f, "{} (UINT)", a),
+            Self::Bool(a) => write!(f, "{}", a),
+            Self::Float(a) => write!(f, "{:?} (FLOAT)", a),
+            Self::Double(a) => write!(f, "{:?} (DOUBLE)", a),
+            Self::String(a, _) => write!(f, "{:?}", a),
+            Self::Bytes(a, _) => write!(f, "len[{}]{:02x?}", a.len(), a),
+            Self::Null => write!(f, "null"),
+            Self::Struct(members) => fmt_members(f, indent, "{", "}", members),
+            Self::List(members) => fmt_members(f, indent, "[[", "]]", members),
+            Self::Array(elements) => {
+                if elements.is_empty() {
+                    return write!(f, "[]");
+                }
+                writeln!(f, "[")?;
+                for e in elements {
+                    write!(f, "{:1$}", "", indent + 4)?;
+                    e.fmt_indented(f, indent + 4)?;
+                    writeln!(f, ",")?;
+                }
+                write!(f, "{:1$}]", "", indent)
+            }
+        }
+    }
+}
+
+fn fmt_members(
+    f: &mut fmt::Formatter<'_>,
+    indent: usize,
+    start: &str,
+    end: &str,
+    members: &[(TagType, TLVValue)],
+) -> fmt::Result {
+    if members.is_empty() {
+        return write!(f, "{}{}", start, end);
+    }
+    writeln!(f, "{}", start)?;
+    for (tag, value) in members {
+        write!(f, "{:1$}", "", indent + 4)?;
+        match tag {
+            TagType::Anonymous => (),
+            TagType::Context(tag) => write!(f, "{}: ", tag)?,
+            _ => write!(f, "{:?}: ", tag)?,
+        }
+        value.fmt_indented(f, indent + 4)?;
+        writeln!(f, ",")?;
+    }
+    write!(f, "{:indent$}{}", "", end, indent = indent)
+}
+
+impl fmt::Display for TLVValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TLVValue;
+    use crate::{
+        error::Error,
+        tlv::{IntWidth, TLVWriter, TagType, ToTLV},
+        utils::writebuf::WriteBuf,
+    };
+    #[cfg(feature = "serde")]
+    use serde_json::Value;
+
+    fn encode(value: &TLVValue) -> Vec<u8> {
+        let mut buf = [0u8; 128];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        value.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let len = tw.get_tail();
+        buf[..len].to_vec()
+    }
+
+    const TLV: [u8; 53] = [
+        0x15, 0x24, 1, 5, 0x20, 2, 0xfd, 0x2c, 3, 2, b'h', b'i', 0x30, 4, 3, 1, 2, 3, 0x29, 5,
+        0x34, 6, 0x36, 7, 4, 1, 5, 0x34, 0x12, 0x18, 0x35, 8, 0x27, 0, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0x18, 0x2a, 9, 0, 0, 0, 0x3f, 0x36, 10, 0x18, 0x18,
+    ];
+
+    #[test]
+    fn test_tlv_round_trip() {
+        let value = TLVValue::from_slice(&TLV).unwrap();
+        assert_eq!(
+            value,
+            TLVValue::Struct(vec![
+                (TagType::Context(1), 5u8.into()),
+                (TagType::Context(2), (-3i8).into()),
+                (TagType::Context(3), "hi".into()),
+                (TagType::Context(4), vec![1u8, 2, 3].into()),
+                (TagType::Context(5), true.into()),
+                (TagType::Context(6), TLVValue::Null),
+                (
+                    TagType::Context(7),
+                    TLVValue::Array(vec![1u8.into(), 0x1234u16.into()])
+                ),
+                (
+                    TagType::Context(8),
+                    TLVValue::Struct(vec![(TagType::Context(0), u64::MAX.into())])
+                ),
+                (TagType::Context(9), 0.5f32.into()),
+                (TagType::Context(10), TLVValue::Array(vec![])),
+            ])
+        );
+        assert_eq!(value.get(3), Some(&"hi".into()));
+        assert_eq!(encode(&value), TLV);
+
+        // Arrays nested deeper than the strict parser takes
+        let mut deep = vec![0x16; 17];
+        deep.extend_from_slice(&[0x18; 17]);
+        assert_eq!(TLVValue::from_slice(&deep), Err(Error::InvalidData));
+        assert!(TLVValue::from_slice(&deep[1..33]).is_ok());
+    }
+
+    #[test]
+    fn test_non_minimal_round_trip() {
+        // Integers and string lengths wider than they need to be, as any encoder may write them
+        let tlv = [
+            0x15, 0x25, 1, 5, 0, 0x22, 2, 0xfd, 0xff, 0xff, 0xff, 0x2d, 3, 2, 0, b'h', b'i', 0x32,
+            4, 1, 0, 0, 0, 7, 0x27, 5, 1, 0, 0, 0, 0, 0, 0, 0, 0x23, 6, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0x37, 7, 0x0e, 1, 0, 0, 0, b'a', 0x24, 1, 5, 0x18, 0x18,
+        ];
+        let value = TLVValue::from_slice(&tlv).unwrap();
+        assert_eq!(value.get(1), Some(&TLVValue::UInt(5, IntWidth::W16)));
+        assert_eq!(value.get(2), Some(&TLVValue::Int(-3, IntWidth::W32)));
+        assert_eq!(
+            value.get(3),
+            Some(&TLVValue::String("hi".to_owned(), IntWidth::W16))
+        );
+        assert_eq!(value.get(4), Some(&TLVValue::Bytes(vec![7], IntWidth::W32)));
+        assert_ne!(value.get(1), Some(&5u8.into()));
+        assert_eq!(encode(&value), tlv);
+
+        // The value has to fit in its width
+        let mut buf = [0u8; 16];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        assert_eq!(
+            TLVValue::UInt(0x100, IntWidth::W8).to_tlv(&mut tw, TagType::Anonymous),
+            Err(Error::Invalid)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_round_trip() {
+        let value = TLVValue::from_slice(&TLV).unwrap();
+        let json = value.to_json().unwrap();
+        let expected = r#"{
+            "1:UINT": 5,
+            "2:INT": -3,
+            "3:STRING": "hi",
+            "4:BYTES": "AQID",
+            "5:BOOL": true,
+            "6:NULL": null,
+            "7:ARRAY-UINT": [1, 4660],
+            "8:STRUCT": { "0:UINT": "18446744073709551615" },
+            "9:FLOAT": 0.5,
+            "10:ARRAY-?": []
+        }"#;
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            serde_json::from_str::<Value>(expected).unwrap()
+        );
+
+        assert_eq!(TLVValue::from_json(expected).unwrap(), value);
+        assert_eq!(encode(&TLVValue::from_json(&json).unwrap()), TLV);
+
+        // The members keep their order, whatever their tags
+        let json = r#"{"2:UINT": 1, "1:UINT": 2}"#;
+        let value = TLVValue::from_json(json).unwrap();
+        assert_eq!(
+            value,
+            TLVValue::Struct(vec![
+                (TagType::Context(2), 1u8.into()),
+                (TagType::Context(1), 2u8.into()),
+            ])
+        );
+        assert_eq!(TLVValue::from_json(&value.to_json().unwrap()), Ok(value));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_list() {
+        let value = TLVValue::Struct(vec![(
+            TagType::Context(1),
+            TLVValue::List(vec![
+                (TagType::Context(2), 5u8.into()),
+                (TagType::Anonymous, "a".into()),
+                (TagType::Context(2), TLVValue::Array(vec![])),
+            ]),
+        )]);
+        let json = value.to_json().unwrap();
+        let expected = r#"{
+            "1:LIST": [{ "2:UINT": 5 }, { "STRING": "a" }, { "2:ARRAY-?": [] }]
+        }"#;
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            serde_json::from_str::<Value>(expected).unwrap()
+        );
+        assert_eq!(TLVValue::from_json(&json), Ok(value));
+
+        // Each member of a list is an object of its own, and only those may be anonymous
+        assert_eq!(
+            TLVValue::from_json(r#"{"1:LIST": [{"2:UINT": 5, "3:UINT": 6}]}"#),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            TLVValue::from_json(r#"{"1:LIST": [5]}"#),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            TLVValue::from_json(r#"{"UINT": 5}"#),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_formats() {
+        // The keys may start with a name, and the integers may be strings
+        let value = TLVValue::from_json(
+            r#"{"onOff:0:BOOL": false, "1:INT": "-7", "2:DOUBLE": "-Infinity"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            TLVValue::Struct(vec![
+                (TagType::Context(0), false.into()),
+                (TagType::Context(1), (-7i32).into()),
+                (TagType::Context(2), f64::NEG_INFINITY.into()),
+            ])
+        );
+
+        // Mismatched types, and what the JSON format can't hold
+        assert_eq!(
+            TLVValue::from_json(r#"{"1:UINT": -1}"#),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            TLVValue::from_json(r#"{"1:ARRAY-?": [1]}"#),
+            Err(Error::InvalidData)
+        );
+        let mixed = TLVValue::Struct(vec![(
+            TagType::Context(1),
+            TLVValue::Array(vec![1u8.into(), "a".into()]),
+        )]);
+        assert_eq!(mixed.to_json(), Err(Error::Invalid));
+        let anonymous = TLVValue::Struct(vec![(TagType::Anonymous, 1u8.into())]);
+        assert_eq!(anonymous.to_json(), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn test_display() {
+        let value = TLVValue::Struct(vec![
+            (TagType::Context(1), 5u8.into()),
+            (
+                TagType::Context(2),
+                TLVValue::Array(vec![TLVValue::List(vec![(TagType::Anonymous, "a".into())])]),
+            ),
+            (TagType::Context(3), TLVValue::Struct(vec![])),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            "{\n    1: 5 (UINT),\n    2: [\n        [[\n            \"a\",\n        ]],\n    ],\n    3: {},\n}"
+        );
+    }
+}
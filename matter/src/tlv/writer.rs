@@ -1,4 +1,4 @@
-use super::{TagType, TAG_SHIFT_BITS, TAG_SIZE_MAP};
+use super::{IntWidth, TagType, TAG_SHIFT_BITS, TAG_SIZE_MAP};
 use crate::{error::*, utils::writebuf::WriteBuf};
 use log::error;
 
@@ -50,6 +50,11 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         tag_type: TagType,
         val_type: WriteElementType,
     ) -> Result<(), Error> {
+        self.put_control(tag_type, val_type as u8)
+    }
+
+    #[inline(always)]
+    fn put_control(&mut self, tag_type: TagType, val_type: u8) -> Result<(), Error> {
         let (tag_id, tag_val) = match tag_type {
             TagType::Anonymous => (0_u8, 0),
             TagType::Context(v) => (1, v as u64),
@@ -60,8 +65,7 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
             TagType::FullQual48(v) => (6, v as u64),
             TagType::FullQual64(v) => (7, v as u64),
         };
-        self.buf
-            .le_u8(((tag_id) << TAG_SHIFT_BITS) | (val_type as u8))?;
+        self.buf.le_u8(((tag_id) << TAG_SHIFT_BITS) | val_type)?;
         if tag_type != TagType::Anonymous {
             self.buf.le_uint(TAG_SIZE_MAP[tag_id as usize], tag_val)?;
         }
@@ -73,6 +77,33 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         self.buf.le_i8(data)
     }
 
+    pub fn i16(&mut self, tag_type: TagType, data: i16) -> Result<(), Error> {
+        if (i8::MIN as i16..=i8::MAX as i16).contains(&data) {
+            self.i8(tag_type, data as i8)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S16)?;
+            self.buf.le_i16(data)
+        }
+    }
+
+    pub fn i32(&mut self, tag_type: TagType, data: i32) -> Result<(), Error> {
+        if (i16::MIN as i32..=i16::MAX as i32).contains(&data) {
+            self.i16(tag_type, data as i16)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S32)?;
+            self.buf.le_i32(data)
+        }
+    }
+
+    pub fn i64(&mut self, tag_type: TagType, data: i64) -> Result<(), Error> {
+        if (i32::MIN as i64..=i32::MAX as i64).contains(&data) {
+            self.i32(tag_type, data as i32)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S64)?;
+            self.buf.le_i64(data)
+        }
+    }
+
     pub fn u8(&mut self, tag_type: TagType, data: u8) -> Result<(), Error> {
         self.put_control_tag(tag_type, WriteElementType::U8)?;
         self.buf.le_u8(data)
@@ -111,8 +142,18 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         }
     }
 
+    pub fn f32(&mut self, tag_type: TagType, data: f32) -> Result<(), Error> {
+        self.put_control_tag(tag_type, WriteElementType::F32)?;
+        self.buf.le_f32(data)
+    }
+
+    pub fn f64(&mut self, tag_type: TagType, data: f64) -> Result<(), Error> {
+        self.put_control_tag(tag_type, WriteElementType::F64)?;
+        self.buf.le_f64(data)
+    }
+
     pub fn str8(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
-        if data.len() > 256 {
+        if data.len() > 0xff {
             error!("use put_str16() instead");
             return Err(Error::Invalid);
         }
@@ -124,10 +165,36 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
     pub fn str16(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
         if data.len() <= 0xff {
             self.str8(tag_type, data)
-        } else {
+        } else if data.len() <= 0xffff {
             self.put_control_tag(tag_type, WriteElementType::Str16l)?;
             self.buf.le_u16(data.len() as u16)?;
             self.buf.copy_from_slice(data)
+        } else {
+            error!("use put_str32() instead");
+            Err(Error::Invalid)
+        }
+    }
+
+    pub fn str32(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
+        if data.len() <= 0xffff {
+            self.str16(tag_type, data)
+        } else if data.len() as u64 <= 0xffffffff {
+            self.put_control_tag(tag_type, WriteElementType::Str32l)?;
+            self.buf.le_u32(data.len() as u32)?;
+            self.buf.copy_from_slice(data)
+        } else {
+            error!("use put_str64() instead");
+            Err(Error::Invalid)
+        }
+    }
+
+    pub fn str64(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
+        if data.len() as u64 <= 0xffffffff {
+            self.str32(tag_type, data)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::Str64l)?;
+            self.buf.le_u64(data.len() as u64)?;
+            self.buf.copy_from_slice(data)
         }
     }
 
@@ -160,6 +227,10 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
     }
 
     pub fn utf8(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
+        if data.len() > 0xff {
+            error!("use put_utf16() instead");
+            return Err(Error::Invalid);
+        }
         self.put_control_tag(tag_type, WriteElementType::Utf8l)?;
         self.buf.le_u8(data.len() as u8)?;
         self.buf.copy_from_slice(data)
@@ -168,11 +239,115 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
     pub fn utf16(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
         if data.len() <= 0xff {
             self.utf8(tag_type, data)
-        } else {
+        } else if data.len() <= 0xffff {
             self.put_control_tag(tag_type, WriteElementType::Utf16l)?;
             self.buf.le_u16(data.len() as u16)?;
             self.buf.copy_from_slice(data)
+        } else {
+            error!("use put_utf32() instead");
+            Err(Error::Invalid)
+        }
+    }
+
+    pub fn utf32(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
+        if data.len() <= 0xffff {
+            self.utf16(tag_type, data)
+        } else if data.len() as u64 <= 0xffffffff {
+            self.put_control_tag(tag_type, WriteElementType::Utf32l)?;
+            self.buf.le_u32(data.len() as u32)?;
+            self.buf.copy_from_slice(data)
+        } else {
+            error!("use put_utf64() instead");
+            Err(Error::Invalid)
+        }
+    }
+
+    pub fn utf64(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
+        if data.len() as u64 <= 0xffffffff {
+            self.utf32(tag_type, data)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::Utf64l)?;
+            self.buf.le_u64(data.len() as u64)?;
+            self.buf.copy_from_slice(data)
+        }
+    }
+
+    /// Write the integer with the given width, rather than the smallest one that holds it
+    pub fn i64_with_width(
+        &mut self,
+        tag_type: TagType,
+        data: i64,
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        if IntWidth::of_i64(data) > width {
+            error!("{} doesn't fit in {:?}", data, width);
+            return Err(Error::Invalid);
+        }
+        self.put_sized(tag_type, WriteElementType::S8, width)?;
+        // The two's complement, truncated to the width
+        let mask = u64::MAX >> (64 - 8 * width.bytes());
+        self.buf.le_uint(width.bytes(), data as u64 & mask)
+    }
+
+    /// Write the integer with the given width, rather than the smallest one that holds it
+    pub fn u64_with_width(
+        &mut self,
+        tag_type: TagType,
+        data: u64,
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        if IntWidth::of_u64(data) > width {
+            error!("{} doesn't fit in {:?}", data, width);
+            return Err(Error::Invalid);
         }
+        self.put_sized(tag_type, WriteElementType::U8, width)?;
+        self.buf.le_uint(width.bytes(), data)
+    }
+
+    /// Write the octet string, with its length encoded with the given width
+    pub fn str_with_width(
+        &mut self,
+        tag_type: TagType,
+        data: &[u8],
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        self.sized_str(tag_type, WriteElementType::Str8l, data, width)
+    }
+
+    /// Write the UTF-8 string, with its length encoded with the given width
+    pub fn utf_with_width(
+        &mut self,
+        tag_type: TagType,
+        data: &[u8],
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        self.sized_str(tag_type, WriteElementType::Utf8l, data, width)
+    }
+
+    fn sized_str(
+        &mut self,
+        tag_type: TagType,
+        base: WriteElementType,
+        data: &[u8],
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        if IntWidth::of_u64(data.len() as u64) > width {
+            error!("A length of {} doesn't fit in {:?}", data.len(), width);
+            return Err(Error::Invalid);
+        }
+        self.put_sized(tag_type, base, width)?;
+        self.buf.le_uint(width.bytes(), data.len() as u64)?;
+        self.buf.copy_from_slice(data)
+    }
+
+    // The element types of the widths of a type follow each other, from 8 to 64 bits
+    fn put_sized(
+        &mut self,
+        tag_type: TagType,
+        base: WriteElementType,
+        width: IntWidth,
+    ) -> Result<(), Error> {
+        self.put_control(tag_type, base as u8 + width as u8)
     }
 
     fn no_val(&mut self, tag_type: TagType, element: WriteElementType) -> Result<(), Error> {
@@ -218,8 +393,8 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
 
 #[cfg(test)]
 mod tests {
-    use super::{TLVWriter, TagType};
-    use crate::utils::writebuf::WriteBuf;
+    use super::{IntWidth, TLVWriter, TagType};
+    use crate::{error::Error, utils::writebuf::WriteBuf};
 
     #[test]
     fn test_write_success() {
@@ -280,6 +455,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_write_signed_and_float() {
+        let mut buf: [u8; 40] = [0; 40];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        // The integers take the smallest size that holds their value
+        tw.i16(TagType::Anonymous, -2).unwrap();
+        tw.i16(TagType::Anonymous, -300).unwrap();
+        tw.i32(TagType::Anonymous, 0x123456).unwrap();
+        tw.i64(TagType::Anonymous, -0x123456789).unwrap();
+        tw.f32(TagType::Context(1), 1.5).unwrap();
+        tw.f64(TagType::Anonymous, -2.0).unwrap();
+        assert_eq!(
+            buf,
+            [
+                0, 0xfe, 1, 0xd4, 0xfe, 2, 0x56, 0x34, 0x12, 0, 3, 0x77, 0x98, 0xba, 0xdc, 0xfe,
+                0xff, 0xff, 0xff, 42, 1, 0, 0, 0xc0, 0x3f, 11, 0, 0, 0, 0, 0, 0, 0, 0xc0, 0, 0, 0,
+                0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_long_strings() {
+        let mut buf = vec![0; 0x10020];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        let long = vec![0x61; 0x10000];
+        assert_eq!(
+            tw.str8(TagType::Anonymous, &long[..0x100]),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            tw.utf8(TagType::Anonymous, &long[..0x100]),
+            Err(Error::Invalid)
+        );
+        assert_eq!(tw.str16(TagType::Anonymous, &long), Err(Error::Invalid));
+        assert_eq!(tw.utf16(TagType::Anonymous, &long), Err(Error::Invalid));
+        assert_eq!(tw.get_tail(), 0);
+
+        // Short strings still use the smallest length
+        tw.utf64(TagType::Anonymous, b"ab").unwrap();
+        tw.str32(TagType::Anonymous, &long).unwrap();
+        assert_eq!(tw.get_tail(), 4 + 5 + 0x10000);
+        assert_eq!(&buf[..9], [12, 2, 0x61, 0x62, 18, 0, 0, 1, 0]);
+    }
+
     #[test]
     fn test_put_str16_as() {
         let mut buf: [u8; 20] = [0; 20];
@@ -302,4 +528,44 @@ mod tests {
             [36, 1, 13, 48, 2, 5, 10, 11, 12, 13, 14, 48, 3, 2, 10, 11, 36, 4, 13, 0]
         );
     }
+
+    #[test]
+    fn test_write_with_width() {
+        let mut buf: [u8; 40] = [0; 40];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        tw.u64_with_width(TagType::Anonymous, 5, IntWidth::W16)
+            .unwrap();
+        tw.i64_with_width(TagType::Context(1), -2, IntWidth::W32)
+            .unwrap();
+        tw.i64_with_width(TagType::Anonymous, i64::MIN, IntWidth::W64)
+            .unwrap();
+        tw.utf_with_width(TagType::Anonymous, b"hi", IntWidth::W16)
+            .unwrap();
+        tw.str_with_width(TagType::Anonymous, &[7], IntWidth::W32)
+            .unwrap();
+        // The value has to fit in the width
+        assert_eq!(
+            tw.u64_with_width(TagType::Anonymous, 0x100, IntWidth::W8),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            tw.i64_with_width(TagType::Anonymous, -129, IntWidth::W8),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            tw.str_with_width(TagType::Anonymous, &[0; 0x100], IntWidth::W8),
+            Err(Error::Invalid)
+        );
+        let len = tw.get_tail();
+        assert_eq!(
+            buf[..len],
+            [
+                5, 5, 0, 0x22, 1, 0xfe, 0xff, 0xff, 0xff, 3, 0, 0, 0, 0, 0, 0, 0, 0x80, 13, 2, 0,
+                b'h', b'i', 18, 1, 0, 0, 0, 7
+            ]
+        );
+    }
 }
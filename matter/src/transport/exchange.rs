@@ -1,15 +1,14 @@
-use boxslab::{BoxSlab, Slab};
 use colored::*;
 use log::{error, info, trace};
 use std::any::Any;
 use std::fmt;
+use std::time::Duration;
 
 use crate::error::Error;
 use crate::secure_channel;
 
-use heapless::LinearMap;
-
-use super::packet::PacketPool;
+use super::network::Readiness;
+use super::packet::BoxPacket;
 use super::session::CloneData;
 use super::{mrp::ReliableMessage, packet::Packet, session::SessionHandle, session::SessionMgr};
 
@@ -105,11 +104,7 @@ impl Exchange {
         self.data.take()?.downcast::<T>().ok()
     }
 
-    fn send(
-        &mut self,
-        mut proto_tx: BoxSlab<PacketPool>,
-        session: &mut SessionHandle,
-    ) -> Result<(), Error> {
+    fn send(&mut self, mut proto_tx: BoxPacket, session: &mut SessionHandle) -> Result<(), Error> {
         trace!("payload: {:x?}", proto_tx.as_borrow_slice());
         info!(
             "{} with proto id: {} opcode: {}",
@@ -155,22 +150,89 @@ pub fn get_complementary_role(is_initiator: bool) -> Role {
     }
 }
 
-const MAX_EXCHANGES: usize = 8;
+/// The default for the maximum number of exchanges that are open at the same time
+pub const MAX_EXCHANGES: usize = 8;
+
+// The open exchanges, keyed by the exchange ID, with a capacity set at run time
+struct Exchanges {
+    entries: Vec<(u16, Exchange)>,
+    max: usize,
+}
+
+impl Default for Exchanges {
+    fn default() -> Self {
+        Self::new(MAX_EXCHANGES)
+    }
+}
+
+impl Exchanges {
+    fn new(max: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(max),
+            max,
+        }
+    }
+
+    fn contains_key(&self, id: &u16) -> bool {
+        self.entries.iter().any(|(k, _)| k == id)
+    }
+
+    fn get_mut(&mut self, id: &u16) -> Option<&mut Exchange> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == id)
+            .map(|(_, e)| e)
+    }
+
+    fn insert(&mut self, id: u16, exchange: Exchange) -> Result<(), Exchange> {
+        if self.entries.len() < self.max {
+            self.entries.push((id, exchange));
+            Ok(())
+        } else {
+            Err(exchange)
+        }
+    }
+
+    fn remove(&mut self, id: &u16) -> Option<Exchange> {
+        let index = self.entries.iter().position(|(k, _)| k == id)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&u16, &Exchange)> {
+        self.entries.iter().map(|(k, e)| (k, e))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (&u16, &mut Exchange)> {
+        self.entries.iter_mut().map(|(k, e)| (&*k, e))
+    }
+}
 
-#[derive(Default)]
 pub struct ExchangeMgr {
     // keys: exch-id
-    exchanges: LinearMap<u16, Exchange, MAX_EXCHANGES>,
+    exchanges: Exchanges,
     sess_mgr: SessionMgr,
+    max_mrp_entries: usize,
 }
 
+/// The default for the maximum number of standalone acknowledgements sent in one go
 pub const MAX_MRP_ENTRIES: usize = 4;
 
+impl Default for ExchangeMgr {
+    fn default() -> Self {
+        Self::new(SessionMgr::default())
+    }
+}
+
 impl ExchangeMgr {
     pub fn new(sess_mgr: SessionMgr) -> Self {
+        Self::new_with(sess_mgr, MAX_EXCHANGES, MAX_MRP_ENTRIES)
+    }
+
+    pub fn new_with(sess_mgr: SessionMgr, max_exchanges: usize, max_mrp_entries: usize) -> Self {
         Self {
             sess_mgr,
-            exchanges: Default::default(),
+            exchanges: Exchanges::new(max_exchanges),
+            max_mrp_entries,
         }
     }
 
@@ -178,10 +240,7 @@ impl ExchangeMgr {
         &mut self.sess_mgr
     }
 
-    pub fn _get_with_id(
-        exchanges: &mut LinearMap<u16, Exchange, MAX_EXCHANGES>,
-        exch_id: u16,
-    ) -> Option<&mut Exchange> {
+    fn _get_with_id(exchanges: &mut Exchanges, exch_id: u16) -> Option<&mut Exchange> {
         exchanges.get_mut(&exch_id)
     }
 
@@ -190,7 +249,7 @@ impl ExchangeMgr {
     }
 
     fn _get(
-        exchanges: &mut LinearMap<u16, Exchange, MAX_EXCHANGES>,
+        exchanges: &mut Exchanges,
         sess_idx: usize,
         id: u16,
         role: Role,
@@ -225,9 +284,10 @@ impl ExchangeMgr {
     }
 
     /// The Exchange Mgr receive is like a big processing function
-    pub fn recv(&mut self) -> Result<Option<(BoxSlab<PacketPool>, ExchangeCtx)>, Error> {
+    pub fn recv(&mut self) -> Result<Option<(BoxPacket, ExchangeCtx)>, Error> {
         // Get the session
         let (mut proto_rx, index) = self.sess_mgr.recv()?;
+        let now = self.sess_mgr.clock().now();
 
         let index = match index {
             Some(s) => s,
@@ -255,7 +315,7 @@ impl ExchangeMgr {
         )?;
 
         // Message Reliability Protocol
-        exch.mrp.recv(&proto_rx)?;
+        exch.mrp.recv(&proto_rx, now)?;
 
         if exch.is_state_open() {
             Ok(Some((
@@ -272,7 +332,7 @@ impl ExchangeMgr {
         }
     }
 
-    pub fn send(&mut self, exch_id: u16, proto_tx: BoxSlab<PacketPool>) -> Result<(), Error> {
+    pub fn send(&mut self, exch_id: u16, proto_tx: BoxPacket) -> Result<(), Error> {
         let exchange =
             ExchangeMgr::_get_with_id(&mut self.exchanges, exch_id).ok_or(Error::NoExchange)?;
         let mut session = self.sess_mgr.get_session_handle(exchange.sess_idx);
@@ -280,24 +340,37 @@ impl ExchangeMgr {
     }
 
     pub fn purge(&mut self) {
-        let mut to_purge: LinearMap<u16, (), MAX_EXCHANGES> = LinearMap::new();
+        self.exchanges
+            .entries
+            .retain(|(_, exchange)| !exchange.is_purgeable());
+    }
 
-        for (exch_id, exchange) in self.exchanges.iter() {
-            if exchange.is_purgeable() {
-                let _ = to_purge.insert(*exch_id, ());
-            }
-        }
-        for (exch_id, _) in to_purge.iter() {
-            self.exchanges.remove(&*exch_id);
-        }
+    /// Get the exchanges that have acknowledgements pending
+    ///
+    /// At most max_mrp_entries are returned, the rest are picked up by a later call
+    pub fn pending_acks(&mut self, expired_entries: &mut Vec<u16>) {
+        let now = self.sess_mgr.clock().now();
+        let pending = self
+            .exchanges
+            .iter()
+            .filter(|(_, exchange)| exchange.mrp.is_ack_ready(now))
+            .map(|(exch_id, _)| *exch_id)
+            .take(self.max_mrp_entries);
+        expired_entries.extend(pending);
     }
 
-    pub fn pending_acks(&mut self, expired_entries: &mut LinearMap<u16, (), MAX_MRP_ENTRIES>) {
-        for (exch_id, exchange) in self.exchanges.iter() {
-            if exchange.mrp.is_ack_ready() {
-                expired_entries.insert(*exch_id, ()).unwrap();
-            }
-        }
+    /// The time left until the earliest pending acknowledgement has to be sent
+    pub fn next_ack_due(&self) -> Option<Duration> {
+        let now = self.sess_mgr.clock().now();
+        self.exchanges
+            .iter()
+            .filter_map(|(_, exchange)| exchange.mrp.ack_due(now))
+            .min()
+    }
+
+    /// Wait until a packet can be received, see [ExchangeMgr::recv]
+    pub fn readable(&self) -> Result<Readiness<'_>, Error> {
+        self.sess_mgr.readable()
     }
 
     pub fn evict_session(&mut self, index: usize) -> Result<(), Error> {
@@ -305,8 +378,8 @@ impl ExchangeMgr {
         // If we enter here, we have an LRU session that needs to be reclaimed
         // As per the spec, we need to send a CLOSE here
 
+        let mut tx = Packet::new_tx(self.sess_mgr.packet_pool())?;
         let mut session = self.sess_mgr.get_session_handle(index);
-        let mut tx = Slab::<PacketPool>::new(Packet::new_tx()?).ok_or(Error::NoSpace)?;
         secure_channel::common::create_sc_status_report(
             &mut tx,
             secure_channel::common::SCStatusCodes::CloseSession,
@@ -364,7 +437,7 @@ impl fmt::Display for ExchangeMgr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "{{  Session Mgr: {},", self.sess_mgr)?;
         writeln!(f, "  Exchanges: [")?;
-        for s in &self.exchanges {
+        for s in self.exchanges.iter() {
             writeln!(f, "{{ {}, }},", s.1)?;
         }
         writeln!(f, "  ]")?;
@@ -378,7 +451,7 @@ mod tests {
     use crate::{
         error::Error,
         transport::{
-            network::{Address, NetworkInterface},
+            network::{Address, NetworkInterface, Readiness},
             session::{CloneData, SessionMgr, SessionMode, MAX_SESSIONS},
         },
     };
@@ -416,6 +489,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_max_exchanges() {
+        let sess_mgr = SessionMgr::new();
+        let mut mgr = ExchangeMgr::new_with(sess_mgr, 2, 1);
+        let _ = ExchangeMgr::_get(&mut mgr.exchanges, 1, 2, Role::Responder, true).unwrap();
+        let _ = ExchangeMgr::_get(&mut mgr.exchanges, 1, 3, Role::Responder, true).unwrap();
+        assert_eq!(
+            ExchangeMgr::_get(&mut mgr.exchanges, 1, 4, Role::Responder, true).err(),
+            Some(Error::NoSpace)
+        );
+
+        // Close e1, which makes room for another exchange
+        let e1 = ExchangeMgr::_get(&mut mgr.exchanges, 1, 2, Role::Responder, false).unwrap();
+        e1.close();
+        mgr.purge();
+        assert_eq!(
+            ExchangeMgr::_get(&mut mgr.exchanges, 1, 4, Role::Responder, true).is_ok(),
+            true
+        );
+    }
+
     fn get_clone_data(peer_sess_id: u16, local_sess_id: u16) -> CloneData {
         CloneData::new(
             12341234,
@@ -459,6 +553,10 @@ mod tests {
         fn send(&self, _out_buf: &[u8], _addr: Address) -> Result<usize, Error> {
             Ok(0)
         }
+
+        fn readable(&self) -> Readiness<'_> {
+            Box::pin(smol::future::ready(Ok(())))
+        }
     }
 
     #[test]
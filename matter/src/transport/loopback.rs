@@ -0,0 +1,414 @@
+use async_channel::{bounded, Receiver, Sender};
+use log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use smol::future;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Error,
+};
+
+use super::network::{Address, NetworkInterface, Readiness};
+
+/// The impairments that the packets sent over a [LoopbackNetwork] go through
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkConfig {
+    /// The probability, between 0 and 1, that a packet is dropped
+    pub loss: f64,
+    /// The probability, between 0 and 1, that a packet is delivered twice
+    pub duplication: f64,
+    /// The probability, between 0 and 1, that a packet is delivered before the packet that was
+    /// sent just ahead of it
+    pub reordering: f64,
+    /// The range of the delay that is added to every packet
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl LinkConfig {
+    fn validate(&self) -> Result<(), Error> {
+        let probabilities = [self.loss, self.duplication, self.reordering];
+        if probabilities.iter().any(|p| !(0.0..=1.0).contains(p)) || self.min_delay > self.max_delay
+        {
+            return Err(Error::Invalid);
+        }
+        Ok(())
+    }
+}
+
+struct Datagram {
+    data: Vec<u8>,
+    src: SocketAddr,
+    // The packet isn't delivered before this time of the network's clock
+    deliver_at: Duration,
+}
+
+struct Port {
+    queue: VecDeque<Datagram>,
+    doorbell: Sender<()>,
+}
+
+struct NetworkInner {
+    config: LinkConfig,
+    rng: StdRng,
+    ports: HashMap<SocketAddr, Port>,
+    clock: Arc<dyn Clock>,
+}
+
+/// An in-memory network, that connects any number of [LoopbackInterface]s
+///
+/// This allows several Matter nodes to talk to each other in tests, without any sockets. The
+/// random impairments of the [LinkConfig] are drawn from a seeded generator, so that a test
+/// with the same seed sees the same packets being lost, duplicated or reordered. The delays
+/// follow the clock of the network, which can be a [ManualClock](crate::clock::ManualClock)
+/// shared with the nodes.
+#[derive(Clone)]
+pub struct LoopbackNetwork {
+    inner: Arc<Mutex<NetworkInner>>,
+}
+
+impl Default for LoopbackNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoopbackNetwork {
+    /// A network that delivers all the packets, in order, without any delay
+    pub fn new() -> Self {
+        Self::new_with_clock(Arc::new(SystemClock::new()))
+    }
+
+    /// A network that delivers all the packets, in order, following the given clock
+    pub fn new_with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(NetworkInner {
+                config: LinkConfig::default(),
+                rng: StdRng::seed_from_u64(0),
+                ports: HashMap::new(),
+                clock,
+            })),
+        }
+    }
+
+    /// A network with the given impairments, drawn from a generator with the given seed
+    pub fn new_with(config: LinkConfig, seed: u64) -> Result<Self, Error> {
+        let network = Self::new();
+        network.set_config(config)?;
+        network.inner.lock()?.rng = StdRng::seed_from_u64(seed);
+        Ok(network)
+    }
+
+    /// Change the impairments of the packets that are sent from now on
+    pub fn set_config(&self, config: LinkConfig) -> Result<(), Error> {
+        config.validate()?;
+        self.inner.lock()?.config = config;
+        Ok(())
+    }
+
+    /// Create an interface with the given address
+    ///
+    /// Returns [Error::Invalid] if the address is already in use
+    pub fn interface(&self, addr: SocketAddr) -> Result<LoopbackInterface, Error> {
+        let mut inner = self.inner.lock()?;
+        if inner.ports.contains_key(&addr) {
+            return Err(Error::Invalid);
+        }
+        let (doorbell, rx) = bounded(1);
+        inner.ports.insert(
+            addr,
+            Port {
+                queue: VecDeque::new(),
+                doorbell,
+            },
+        );
+        Ok(LoopbackInterface {
+            addr,
+            network: self.inner.clone(),
+            doorbell: rx,
+            clock: inner.clock.clone(),
+        })
+    }
+}
+
+/// An interface of a [LoopbackNetwork]
+///
+/// The address of the interface is released once it is dropped.
+pub struct LoopbackInterface {
+    addr: SocketAddr,
+    network: Arc<Mutex<NetworkInner>>,
+    doorbell: Receiver<()>,
+    clock: Arc<dyn Clock>,
+}
+
+impl LoopbackInterface {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // Returns Ok if a packet can be received, or else the time the next packet is due, if any
+    fn next_delivery(&self) -> Result<Result<(), Option<Duration>>, Error> {
+        let inner = self.network.lock()?;
+        let now = self.clock.now();
+        let mut next = None;
+        if let Some(port) = inner.ports.get(&self.addr) {
+            for d in port.queue.iter() {
+                if d.deliver_at <= now {
+                    return Ok(Ok(()));
+                }
+                next = Some(next.map_or(d.deliver_at, |n: Duration| n.min(d.deliver_at)));
+            }
+        }
+        Ok(Err(next))
+    }
+}
+
+impl Drop for LoopbackInterface {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.network.lock() {
+            inner.ports.remove(&self.addr);
+        }
+    }
+}
+
+impl NetworkInterface for LoopbackInterface {
+    fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error> {
+        let mut inner = self.network.lock()?;
+        let port = inner.ports.get_mut(&self.addr).ok_or(Error::Network)?;
+        let now = self.clock.now();
+        let index = port
+            .queue
+            .iter()
+            .position(|d| d.deliver_at <= now)
+            .ok_or(Error::Network)?;
+        let datagram = port.queue.remove(index).ok_or(Error::Network)?;
+        // Like UDP, whatever doesn't fit in the buffer is discarded
+        let len = datagram.data.len().min(in_buf.len());
+        in_buf[..len].copy_from_slice(&datagram.data[..len]);
+        Ok((len, Address::Udp(datagram.src)))
+    }
+
+    fn send(&self, out_buf: &[u8], addr: Address) -> Result<usize, Error> {
+        let dest = match addr {
+            Address::Udp(dest) => dest,
+            Address::Tcp(_) => return Err(Error::InvalidPeerAddr),
+        };
+        let mut inner = self.network.lock()?;
+        let NetworkInner {
+            config, rng, ports, ..
+        } = &mut *inner;
+        let port = match ports.get_mut(&dest) {
+            Some(port) => port,
+            // Nobody is listening on this address, the packet is lost
+            None => return Ok(out_buf.len()),
+        };
+
+        if rng.gen_bool(config.loss) {
+            info!("Loopback: dropping packet to {}", dest);
+            return Ok(out_buf.len());
+        }
+        let copies = if rng.gen_bool(config.duplication) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let delay = if config.max_delay > config.min_delay {
+                rng.gen_range(config.min_delay..=config.max_delay)
+            } else {
+                config.min_delay
+            };
+            let datagram = Datagram {
+                data: out_buf.to_vec(),
+                src: self.addr,
+                deliver_at: self.clock.now() + delay,
+            };
+            if !port.queue.is_empty() && rng.gen_bool(config.reordering) {
+                port.queue.insert(port.queue.len() - 1, datagram);
+            } else {
+                port.queue.push_back(datagram);
+            }
+        }
+        // A full doorbell already has a wake up pending
+        let _ = port.doorbell.try_send(());
+        Ok(out_buf.len())
+    }
+
+    fn readable(&self) -> Readiness<'_> {
+        Box::pin(async move {
+            loop {
+                let next = match self.next_delivery()? {
+                    Ok(()) => return Ok(()),
+                    Err(next) => next,
+                };
+                let doorbell = async {
+                    let _ = self.doorbell.recv().await;
+                };
+                match next {
+                    Some(at) => future::or(doorbell, self.clock.sleep_until(at)).await,
+                    None => doorbell.await,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinkConfig, LoopbackNetwork};
+    use crate::{
+        clock::ManualClock,
+        error::Error,
+        transport::network::{Address, NetworkInterface},
+    };
+    use smol::future;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    fn recv(iface: &dyn NetworkInterface) -> Result<(Vec<u8>, Address), Error> {
+        let mut buf = [0u8; 16];
+        let (len, src) = iface.recv(&mut buf)?;
+        Ok((buf[..len].to_vec(), src))
+    }
+
+    #[test]
+    fn test_delivery() {
+        let network = LoopbackNetwork::new();
+        let a = network.interface(addr(1)).unwrap();
+        let b = network.interface(addr(2)).unwrap();
+        assert_eq!(network.interface(addr(1)).err(), Some(Error::Invalid));
+
+        assert_eq!(a.send(&[1, 2, 3], Address::Udp(addr(2))), Ok(3));
+        smol::block_on(b.readable()).unwrap();
+        assert_eq!(recv(&b), Ok((vec![1, 2, 3], Address::Udp(addr(1)))));
+        assert!(recv(&b).is_err());
+
+        // Packets to an unknown address are lost
+        assert_eq!(a.send(&[1], Address::Udp(addr(3))), Ok(1));
+
+        // The address is available again once the interface is dropped
+        drop(b);
+        assert!(network.interface(addr(2)).is_ok());
+    }
+
+    #[test]
+    fn test_loss_and_duplication() {
+        let network = LoopbackNetwork::new_with(
+            LinkConfig {
+                loss: 1.0,
+                ..Default::default()
+            },
+            1,
+        )
+        .unwrap();
+        let a = network.interface(addr(1)).unwrap();
+        let b = network.interface(addr(2)).unwrap();
+        a.send(&[1], Address::Udp(addr(2))).unwrap();
+        assert!(recv(&b).is_err());
+
+        network
+            .set_config(LinkConfig {
+                duplication: 1.0,
+                ..Default::default()
+            })
+            .unwrap();
+        a.send(&[2], Address::Udp(addr(2))).unwrap();
+        assert_eq!(recv(&b), Ok((vec![2], Address::Udp(addr(1)))));
+        assert_eq!(recv(&b), Ok((vec![2], Address::Udp(addr(1)))));
+        assert!(recv(&b).is_err());
+
+        assert_eq!(
+            network.set_config(LinkConfig {
+                loss: 2.0,
+                ..Default::default()
+            }),
+            Err(Error::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_reordering() {
+        let network = LoopbackNetwork::new_with(
+            LinkConfig {
+                reordering: 1.0,
+                ..Default::default()
+            },
+            1,
+        )
+        .unwrap();
+        let a = network.interface(addr(1)).unwrap();
+        let b = network.interface(addr(2)).unwrap();
+        for i in 1..4 {
+            a.send(&[i], Address::Udp(addr(2))).unwrap();
+        }
+        // Each packet is queued ahead of the last packet in the queue
+        for i in [2, 3, 1] {
+            assert_eq!(recv(&b), Ok((vec![i], Address::Udp(addr(1)))));
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let delay = Duration::from_millis(30);
+        let network = LoopbackNetwork::new_with(
+            LinkConfig {
+                min_delay: delay,
+                max_delay: delay,
+                ..Default::default()
+            },
+            1,
+        )
+        .unwrap();
+        let a = network.interface(addr(1)).unwrap();
+        let b = network.interface(addr(2)).unwrap();
+
+        let start = Instant::now();
+        a.send(&[1], Address::Udp(addr(2))).unwrap();
+        assert!(recv(&b).is_err());
+        smol::block_on(b.readable()).unwrap();
+        assert!(start.elapsed() >= delay);
+        assert_eq!(recv(&b), Ok((vec![1], Address::Udp(addr(1)))));
+    }
+
+    #[test]
+    fn test_delay_manual_clock() {
+        let delay = Duration::from_secs(10);
+        let clock = Arc::new(ManualClock::new());
+        let network = LoopbackNetwork::new_with_clock(clock.clone());
+        network
+            .set_config(LinkConfig {
+                min_delay: delay,
+                max_delay: delay,
+                ..Default::default()
+            })
+            .unwrap();
+        let a = network.interface(addr(1)).unwrap();
+        let b = network.interface(addr(2)).unwrap();
+
+        let start = Instant::now();
+        a.send(&[1], Address::Udp(addr(2))).unwrap();
+        clock.advance(delay - Duration::from_millis(1));
+        assert!(recv(&b).is_err());
+        let (result, ()) = smol::block_on(future::zip(b.readable(), async {
+            future::yield_now().await;
+            clock.advance(Duration::from_millis(1));
+        }));
+        result.unwrap();
+        // The packet is due as soon as the clock moves, without waiting in real time
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(recv(&b), Ok((vec![1], Address::Udp(addr(1)))));
+    }
+}
@@ -1,35 +1,115 @@
 use async_channel::Receiver;
-use boxslab::{BoxSlab, Slab};
-use heapless::LinearMap;
 use log::{debug, error, info};
+use smol::future;
+use std::{
+    future::Future,
+    net::{Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use crate::clock::{Clock, SystemClock};
 use crate::error::*;
+use crate::limits::Limits;
 
 use crate::transport::mrp::ReliableMessage;
-use crate::transport::packet::PacketPool;
-use crate::transport::{exchange, packet::Packet, proto_demux, queue, session, udp};
+use crate::transport::packet::{BoxPacket, Packet, PacketPool};
+use crate::transport::{exchange, proto_demux, session, udp};
 
+use super::network::NetworkInterface;
 use super::proto_demux::ProtoCtx;
-use super::queue::Msg;
+use super::queue::{Callback, Msg, WorkQ};
+use super::timer::TimerWheel;
+
+// 256 slots of 10ms, i.e. one turn of the timer wheel is a little over 2.5 seconds
+const TIMER_WHEEL_SLOTS: usize = 256;
+const TIMER_WHEEL_TICK: Duration = Duration::from_millis(10);
+
+enum Timeout {
+    MrpAck,
+    Callback(Callback),
+}
+
+enum Event {
+    Stop,
+    Msg(Msg),
+    Timer,
+    Rx,
+    Error(Error),
+}
 
 pub struct Mgr {
     exch_mgr: exchange::ExchangeMgr,
     proto_demux: proto_demux::ProtoDemux,
     rx_q: Receiver<Msg>,
+    work_q: WorkQ,
+    // The packets of this instance, shared with the Session Manager
+    packet_pool: Arc<Mutex<PacketPool>>,
+    timers: TimerWheel<Timeout>,
+    // The time for which an MRP acknowledgement timer is armed, if any
+    mrp_timer: Option<Duration>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Mgr {
     pub fn new() -> Result<Mgr, Error> {
-        let mut sess_mgr = session::SessionMgr::new();
-        let udp_transport = Box::new(udp::UdpListener::new()?);
-        sess_mgr.add_network_interface(udp_transport)?;
+        Mgr::new_with(
+            &Limits::default(),
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, udp::MATTER_PORT)),
+        )
+    }
+
+    /// Create the Transport Manager, with the given capacities, listening on the given address
+    pub fn new_with(limits: &Limits, addr: SocketAddr) -> Result<Mgr, Error> {
+        let udp_transport = Box::new(udp::UdpListener::new_with(addr)?);
+        Mgr::new_with_network(limits, udp_transport, Arc::new(SystemClock::new()))
+    }
+
+    /// Create the Transport Manager, with the given capacities, on the given network interface
+    ///
+    /// All the timeouts of the transport follow the given clock
+    pub fn new_with_network(
+        limits: &Limits,
+        network: Box<dyn NetworkInterface>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Mgr, Error> {
+        let packet_pool = Arc::new(Mutex::new(PacketPool::new(limits.packet_pool_size)?));
+        let mut sess_mgr =
+            session::SessionMgr::new_with(limits.max_sessions, packet_pool.clone(), clock.clone());
+        sess_mgr.add_network_interface(network)?;
+        let (work_q, rx_q) = WorkQ::new();
         Ok(Mgr {
-            proto_demux: proto_demux::ProtoDemux::new(),
-            exch_mgr: exchange::ExchangeMgr::new(sess_mgr),
-            rx_q: queue::WorkQ::init()?,
+            proto_demux: proto_demux::ProtoDemux::new_with(limits.max_protocols),
+            exch_mgr: exchange::ExchangeMgr::new_with(
+                sess_mgr,
+                limits.max_exchanges,
+                limits.max_mrp_entries,
+            ),
+            rx_q,
+            work_q,
+            packet_pool,
+            timers: TimerWheel::new(TIMER_WHEEL_SLOTS, TIMER_WHEEL_TICK),
+            mrp_timer: None,
+            clock,
         })
     }
 
+    /// Receive and send over one more network interface, such as a
+    /// [TcpListener](crate::transport::tcp::TcpListener)
+    pub fn add_network_interface(
+        &mut self,
+        network: Box<dyn NetworkInterface>,
+    ) -> Result<(), Error> {
+        self.exch_mgr.get_sess_mgr().add_network_interface(network)
+    }
+
+    /// The queue that the protocols and the application use to send messages to this Transport
+    /// Manager
+    pub fn work_q(&self) -> WorkQ {
+        self.work_q.clone()
+    }
+
     // Allows registration of different protocols with the Transport/Protocol Demux
     pub fn register_protocol(
         &mut self,
@@ -38,11 +118,7 @@ impl Mgr {
         self.proto_demux.register(proto_id_handle)
     }
 
-    fn send_to_exchange(
-        &mut self,
-        exch_id: u16,
-        proto_tx: BoxSlab<PacketPool>,
-    ) -> Result<(), Error> {
+    fn send_to_exchange(&mut self, exch_id: u16, proto_tx: BoxPacket) -> Result<(), Error> {
         self.exch_mgr.send(exch_id, proto_tx)
     }
 
@@ -61,7 +137,9 @@ impl Mgr {
         let (rx, exch_ctx) = result.unwrap();
 
         debug!("Exchange is {:?}", exch_ctx.exch);
-        let tx = Self::new_tx()?;
+        // The response can be as large as the interface of the peer allows
+        let tx_size = exch_ctx.sess.max_msg_size()?;
+        let tx = Packet::new_tx_with_size(&self.packet_pool, tx_size)?;
 
         let mut proto_ctx = ProtoCtx::new(exch_ctx, rx, tx);
         // Proto Dispatch
@@ -94,65 +172,156 @@ impl Mgr {
         Ok(())
     }
 
-    fn handle_queue_msgs(&mut self) -> Result<(), Error> {
-        if let Ok(msg) = self.rx_q.try_recv() {
-            match msg {
-                Msg::NewSession(clone_data) => {
-                    // If a new session was created, add it
-                    let _ = self
-                        .exch_mgr
-                        .add_session(clone_data)
-                        .map_err(|e| error!("Error adding new session {:?}", e));
-                }
-                _ => {
-                    error!("Queue Message Type not yet handled {:?}", msg);
-                }
+    fn handle_queue_msg(&mut self, msg: Msg) -> Result<(), Error> {
+        match msg {
+            Msg::NewSession(clone_data) => {
+                // If a new session was created, add it
+                let _ = self
+                    .exch_mgr
+                    .add_session(clone_data)
+                    .map_err(|e| error!("Error adding new session {:?}", e));
+            }
+            Msg::Timer(delay, callback) => {
+                self.timers
+                    .add(self.clock.now() + delay, Timeout::Callback(callback));
+            }
+            _ => {
+                error!("Queue Message Type not yet handled {:?}", msg);
             }
         }
         Ok(())
     }
 
-    pub fn start(&mut self) -> Result<(), Error> {
-        loop {
-            // Handle network operations
-            if self.handle_rxtx().is_err() {
-                error!("Error in handle_rxtx");
-                continue;
+    fn send_pending_acks(&mut self) {
+        let mut acks_to_send = Vec::new();
+        self.exch_mgr.pending_acks(&mut acks_to_send);
+        for exch_id in acks_to_send.iter() {
+            info!("Sending MRP Standalone ACK for  exch {}", exch_id);
+            let mut proto_tx = match Packet::new_tx(&self.packet_pool) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("Error creating proto_tx {:?}", e);
+                    break;
+                }
+            };
+            ReliableMessage::prepare_ack(*exch_id, &mut proto_tx);
+            if let Err(e) = self.send_to_exchange(*exch_id, proto_tx) {
+                error!("Error in sending Ack {:?}", e);
+            }
+        }
+    }
+
+    fn handle_timers(&mut self) {
+        let mut expired = Vec::new();
+        self.timers.expire(self.clock.now(), &mut expired);
+        for timeout in expired {
+            match timeout {
+                Timeout::MrpAck => {
+                    self.mrp_timer = None;
+                    self.send_pending_acks();
+                }
+                Timeout::Callback(callback) => callback(),
             }
+        }
 
-            if self.handle_queue_msgs().is_err() {
-                error!("Error in handle_queue_msg");
-                continue;
+        // Make sure we wake up in time for the earliest acknowledgement that is due
+        if let Some(due) = self.exch_mgr.next_ack_due() {
+            let at = self.clock.now() + due;
+            if !matches!(self.mrp_timer, Some(armed) if armed <= at) {
+                self.timers.add(at, Timeout::MrpAck);
+                self.mrp_timer = Some(at);
             }
+        }
+    }
+
+    // Wait for whichever happens first: the stop future completing, a message on the queue,
+    // the earliest timer expiring, or a packet arriving on the network
+    async fn wait_event(&self, stop: Pin<&mut impl Future<Output = ()>>) -> Event {
+        let stop = async {
+            stop.await;
+            Event::Stop
+        };
+        let queue = async {
+            match self.rx_q.recv().await {
+                Ok(msg) => Event::Msg(msg),
+                // We hold a sender ourselves, so the queue is never closed
+                Err(_) => future::pending().await,
+            }
+        };
+        let timer = async {
+            match self.timers.next_deadline() {
+                Some(at) => {
+                    self.clock.sleep_until(at).await;
+                    Event::Timer
+                }
+                None => future::pending().await,
+            }
+        };
+        let network = async {
+            let readable = match self.exch_mgr.readable() {
+                Ok(r) => r,
+                Err(e) => return Event::Error(e),
+            };
+            match readable.await {
+                Ok(()) => Event::Rx,
+                Err(e) => Event::Error(e),
+            }
+        };
+        future::or(stop, future::or(queue, future::or(timer, network))).await
+    }
+
+    /// Run the event loop until a [Msg::Shutdown] is received
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.run_until(future::pending())
+    }
+
+    /// Run the event loop until the `stop` future completes, or a [Msg::Shutdown] is received
+    ///
+    /// This blocks the current thread, see [Mgr::run] for the asynchronous version.
+    pub fn run_until<F: Future<Output = ()>>(&mut self, stop: F) -> Result<(), Error> {
+        future::block_on(self.run(stop))
+    }
 
-            // Handle any pending acknowledgement send
-            let mut acks_to_send: LinearMap<u16, (), { exchange::MAX_MRP_ENTRIES }> =
-                LinearMap::new();
-            self.exch_mgr.pending_acks(&mut acks_to_send);
-            for exch_id in acks_to_send.keys() {
-                info!("Sending MRP Standalone ACK for  exch {}", exch_id);
-                let mut proto_tx = match Self::new_tx() {
-                    Ok(p) => p,
-                    Err(e) => {
-                        error!("Error creating proto_tx {:?}", e);
-                        break;
+    /// Run the event loop until the `stop` future completes, or a [Msg::Shutdown] is received
+    ///
+    /// The loop waits on the network, the timers and the queue together, so the timers are run
+    /// in time even if no packets are received. The network and the timers are driven by the
+    /// reactor of async-io, so this can be awaited on from any executor.
+    pub async fn run<F: Future<Output = ()>>(&mut self, stop: F) -> Result<(), Error> {
+        smol::pin!(stop);
+        loop {
+            match self.wait_event(stop.as_mut()).await {
+                Event::Stop => break,
+                Event::Msg(Msg::Shutdown) => {
+                    info!("Shutting down the Transport Manager");
+                    break;
+                }
+                Event::Msg(msg) => {
+                    if self.handle_queue_msg(msg).is_err() {
+                        error!("Error in handle_queue_msg");
                     }
-                };
-                ReliableMessage::prepare_ack(*exch_id, &mut proto_tx);
-                if let Err(e) = self.send_to_exchange(*exch_id, proto_tx) {
-                    error!("Error in sending Ack {:?}", e);
+                }
+                Event::Timer => (),
+                Event::Rx => {
+                    // Handle network operations
+                    if self.handle_rxtx().is_err() {
+                        error!("Error in handle_rxtx");
+                    }
+                }
+                Event::Error(e) => {
+                    error!("Error waiting on the network {:?}", e);
+                    return Err(e);
                 }
             }
 
+            self.handle_timers();
+
             // Handle exchange purging
             //    This need not be done in each turn of the loop, maybe once in 5 times or so?
             self.exch_mgr.purge();
 
             info!("Exchange Mgr: {}", self.exch_mgr);
         }
-    }
-
-    fn new_tx() -> Result<BoxSlab<PacketPool>, Error> {
-        Slab::<PacketPool>::new(Packet::new_tx()?).ok_or(Error::PacketPoolExhaust)
+        Ok(())
     }
 }
@@ -1,4 +1,5 @@
 pub mod exchange;
+pub mod loopback;
 pub mod mgr;
 pub mod mrp;
 pub mod network;
@@ -8,4 +9,6 @@ pub mod proto_demux;
 pub mod proto_hdr;
 pub mod queue;
 pub mod session;
+pub mod tcp;
+pub mod timer;
 pub mod udp;
@@ -1,5 +1,4 @@
 use std::time::Duration;
-use std::time::SystemTime;
 
 use crate::{error::*, secure_channel, transport::packet::Packet};
 use log::error;
@@ -28,14 +27,15 @@ impl RetransEntry {
 pub struct AckEntry {
     // The msg counter that we should acknowledge
     msg_ctr: u32,
-    // The max time after which this entry must be ACK
-    ack_timeout: SystemTime,
+    // The max time after which this entry must be ACK, as per the clock of the stack
+    ack_timeout: Duration,
 }
 
 impl AckEntry {
-    pub fn new(msg_ctr: u32) -> Result<Self, Error> {
+    /// Create an entry for a message received at the time `now`
+    pub fn new(msg_ctr: u32, now: Duration) -> Result<Self, Error> {
         if let Some(ack_timeout) =
-            SystemTime::now().checked_add(Duration::from_millis(MRP_STANDALONE_ACK_TIMEOUT))
+            now.checked_add(Duration::from_millis(MRP_STANDALONE_ACK_TIMEOUT))
         {
             Ok(Self {
                 msg_ctr,
@@ -50,8 +50,13 @@ impl AckEntry {
         self.msg_ctr
     }
 
-    pub fn has_timed_out(&self) -> bool {
-        self.ack_timeout > SystemTime::now()
+    pub fn has_timed_out(&self, now: Duration) -> bool {
+        now >= self.ack_timeout
+    }
+
+    /// The time left until this entry must be acknowledged
+    pub fn due_in(&self, now: Duration) -> Duration {
+        self.ack_timeout.saturating_sub(now)
     }
 }
 
@@ -73,15 +78,20 @@ impl ReliableMessage {
     }
 
     // Check any pending acknowledgements / retransmissions and take action
-    pub fn is_ack_ready(&self) -> bool {
+    pub fn is_ack_ready(&self, now: Duration) -> bool {
         // Acknowledgements
         if let Some(ack_entry) = self.ack {
-            ack_entry.has_timed_out()
+            ack_entry.has_timed_out(now)
         } else {
             false
         }
     }
 
+    /// The time left until the pending acknowledgement, if any, has to be sent
+    pub fn ack_due(&self, now: Duration) -> Option<Duration> {
+        self.ack.map(|ack_entry| ack_entry.due_in(now))
+    }
+
     pub fn prepare_ack(_exch_id: u16, proto_tx: &mut Packet) {
         secure_channel::common::create_mrp_standalone_ack(proto_tx);
     }
@@ -115,7 +125,7 @@ impl ReliableMessage {
      * -  there can be only one pending retransmission per exchange (so this is per-exchange)
      * -  duplicate detection should happen per session (obviously), so that part is per-session
      */
-    pub fn recv(&mut self, proto_rx: &Packet) -> Result<(), Error> {
+    pub fn recv(&mut self, proto_rx: &Packet, now: Duration) -> Result<(), Error> {
         if proto_rx.proto.is_ack() {
             // Handle received Acks
             let ack_msg_ctr = proto_rx.proto.get_ack_msg_ctr().ok_or(Error::Invalid)?;
@@ -136,8 +146,36 @@ impl ReliableMessage {
                 return Err(Error::Invalid);
             }
 
-            self.ack = Some(AckEntry::new(proto_rx.plain.ctr)?);
+            self.ack = Some(AckEntry::new(proto_rx.plain.ctr, now)?);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AckEntry, MRP_STANDALONE_ACK_TIMEOUT};
+    use crate::clock::{Clock, ManualClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_ack_timeout() {
+        let clock = ManualClock::new();
+        clock.advance(Duration::from_secs(5));
+        let entry = AckEntry::new(10, clock.now()).unwrap();
+        // The standalone ack is only due once the timeout has elapsed
+        assert!(!entry.has_timed_out(clock.now()));
+        assert_eq!(
+            entry.due_in(clock.now()),
+            Duration::from_millis(MRP_STANDALONE_ACK_TIMEOUT)
+        );
+
+        clock.advance(Duration::from_millis(MRP_STANDALONE_ACK_TIMEOUT - 1));
+        assert!(!entry.has_timed_out(clock.now()));
+        assert_eq!(entry.due_in(clock.now()), Duration::from_millis(1));
+
+        clock.advance(Duration::from_millis(1));
+        assert!(entry.has_timed_out(clock.now()));
+        assert_eq!(entry.due_in(clock.now()), Duration::ZERO);
+    }
+}
@@ -1,13 +1,30 @@
 use std::{
     fmt::{Debug, Display},
+    future::Future,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    task::Poll,
 };
 
+use smol::future;
+
 use crate::error::Error;
 
+use super::packet::MAX_RX_BUF_SIZE;
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum Address {
     Udp(SocketAddr),
+    Tcp(SocketAddr),
+}
+
+impl Address {
+    /// The IP address and port of the peer, whatever the transport
+    pub fn socket_addr(&self) -> SocketAddr {
+        match self {
+            Address::Udp(addr) | Address::Tcp(addr) => *addr,
+        }
+    }
 }
 
 impl Default for Address {
@@ -20,6 +37,7 @@ impl Display for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Address::Udp(addr) => writeln!(f, "{}", addr),
+            Address::Tcp(addr) => writeln!(f, "tcp:{}", addr),
         }
     }
 }
@@ -28,11 +46,52 @@ impl Debug for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Address::Udp(addr) => writeln!(f, "{}", addr),
+            Address::Tcp(addr) => writeln!(f, "tcp:{}", addr),
         }
     }
 }
 
+/// Completes once a network interface has a packet waiting to be received
+pub type Readiness<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + 'a>>;
+
+/// A network interface, driven by the event loop of the Transport Manager
+///
+/// The event loop only calls [recv](NetworkInterface::recv) once [readable](NetworkInterface::readable)
+/// has completed, and it may run on any executor, so none of the methods should block. A message
+/// that can't be sent right away should be queued by [send](NetworkInterface::send), and sent
+/// from the [readable](NetworkInterface::readable) future, which the event loop always waits on.
 pub trait NetworkInterface {
     fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error>;
     fn send(&self, out_buf: &[u8], addr: Address) -> Result<usize, Error>;
+    /// Wait until [recv](NetworkInterface::recv) can be called without blocking
+    fn readable(&self) -> Readiness<'_>;
+
+    /// Whether the packets to the given address are sent over this interface
+    ///
+    /// By default, an interface carries the UDP addresses.
+    fn handles(&self, addr: Address) -> bool {
+        matches!(addr, Address::Udp(_))
+    }
+
+    /// Release whatever the interface holds for the given peer, once no session uses it anymore
+    fn close(&self, _addr: Address) {}
+
+    /// The largest message that is received or sent over this interface
+    ///
+    /// The packets of the messages over this interface are sized accordingly.
+    fn max_msg_size(&self) -> usize {
+        MAX_RX_BUF_SIZE
+    }
+}
+
+/// Completes as soon as any of the given readiness futures completes
+pub fn any_readable(mut all: Vec<Readiness<'_>>) -> Readiness<'_> {
+    Box::pin(future::poll_fn(move |cx| {
+        for readiness in all.iter_mut() {
+            if let Poll::Ready(result) = readiness.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+        }
+        Poll::Pending
+    }))
 }
@@ -1,11 +1,9 @@
 use log::{error, trace};
-use std::sync::Mutex;
-
-use boxslab::box_slab;
+use std::sync::{Arc, Mutex};
 
 use crate::{
     error::Error,
-    sys::MAX_PACKET_POOL_SIZE,
+    sys::{DEFAULT_PACKET_POOL_SIZE, MAX_PACKET_POOL_SIZE},
     utils::{parsebuf::ParseBuf, writebuf::WriteBuf},
 };
 
@@ -16,51 +14,62 @@ use super::{
 };
 
 pub const MAX_RX_BUF_SIZE: usize = 1583;
-type Buffer = [u8; MAX_RX_BUF_SIZE];
+type Buffer = [u8];
 
+/// The packets of a Matter instance
+///
+/// Every packet holds one of the buffers of the pool, until it is dropped. The buffers are sized
+/// for the packet, so that the interfaces with larger messages, such as TCP, can have them.
 // TODO: I am not very happy with this construction, need to find another way to do this
-pub struct BufferPool {
-    buffers: [Option<Buffer>; MAX_PACKET_POOL_SIZE],
+pub struct PacketPool {
+    // The buffers are only allocated when in use, the length is the configured pool size
+    buffers: Vec<Option<Box<Buffer>>>,
+}
+
+/// A packet, allocated from a PacketPool
+pub type BoxPacket = Box<Packet<'static>>;
+
+impl Default for PacketPool {
+    fn default() -> Self {
+        Self {
+            buffers: (0..DEFAULT_PACKET_POOL_SIZE).map(|_| None).collect(),
+        }
+    }
 }
 
-impl BufferPool {
-    const INIT: Option<Buffer> = None;
-    fn get() -> &'static Mutex<BufferPool> {
-        static mut BUFFER_HOLDER: Option<Mutex<BufferPool>> = None;
-        static ONCE: Once = Once::new();
-        unsafe {
-            ONCE.call_once(|| {
-                BUFFER_HOLDER = Some(Mutex::new(BufferPool {
-                    buffers: [BufferPool::INIT; MAX_PACKET_POOL_SIZE],
-                }));
-            });
-            BUFFER_HOLDER.as_ref().unwrap()
+impl PacketPool {
+    /// Create a pool with room for the given number of packets
+    ///
+    /// This can't be more than MAX_PACKET_POOL_SIZE
+    pub fn new(size: usize) -> Result<Self, Error> {
+        if size == 0 || size > MAX_PACKET_POOL_SIZE {
+            return Err(Error::Invalid);
         }
+        Ok(Self {
+            buffers: (0..size).map(|_| None).collect(),
+        })
     }
 
-    pub fn alloc() -> Option<(usize, &'static mut Buffer)> {
+    fn alloc(pool: &Mutex<PacketPool>, size: usize) -> Option<(usize, &'static mut Buffer)> {
         trace!("Buffer Alloc called\n");
 
-        let mut pool = BufferPool::get().lock().unwrap();
-        for i in 0..MAX_PACKET_POOL_SIZE {
-            if pool.buffers[i].is_none() {
-                pool.buffers[i] = Some([0; MAX_RX_BUF_SIZE]);
-                // Sigh! to by-pass the borrow-checker telling us we are stealing a mutable reference
-                // from under the lock
-                // In this case the lock only protects against the setting of Some/None,
-                // the objects then are independently accessed in a unique way
-                let buffer = unsafe { &mut *(pool.buffers[i].as_mut().unwrap() as *mut Buffer) };
-                return Some((i, buffer));
-            }
-        }
-        None
+        let mut pool = pool.lock().unwrap();
+        let index = pool.buffers.iter().position(|b| b.is_none())?;
+        let buffer = pool.buffers[index].insert(vec![0; size].into_boxed_slice());
+        // Sigh! to by-pass the borrow-checker telling us we are stealing a mutable reference
+        // from under the lock
+        // In this case the lock only protects against the setting of Some/None,
+        // the objects then are independently accessed in a unique way. The Packet holds
+        // a reference to the pool, so the buffer isn't freed before the Packet is dropped
+        let buffer = unsafe { &mut *(buffer.as_mut() as *mut Buffer) };
+        Some((index, buffer))
     }
 
-    pub fn free(index: usize) {
+    fn free(pool: &Mutex<PacketPool>, index: usize) {
         trace!("Buffer Free called\n");
-        let mut pool = BufferPool::get().lock().unwrap();
-        if pool.buffers[index].is_some() {
-            pool.buffers[index] = None;
+        let mut pool = pool.lock().unwrap();
+        if let Some(b) = pool.buffers.get_mut(index) {
+            *b = None;
         }
     }
 }
@@ -83,37 +92,58 @@ pub struct Packet<'a> {
     pub peer: Address,
     data: Direction<'a>,
     buffer_index: usize,
+    packet_pool: Arc<Mutex<PacketPool>>,
 }
 
 impl<'a> Packet<'a> {
     const HDR_RESERVE: usize = plain_hdr::max_plain_hdr_len() + proto_hdr::max_proto_hdr_len();
 
-    pub fn new_rx() -> Result<Self, Error> {
-        let (buffer_index, buffer) = BufferPool::alloc().ok_or(Error::NoSpace)?;
+    pub fn new_rx(packet_pool: &Arc<Mutex<PacketPool>>) -> Result<BoxPacket, Error> {
+        Packet::new_rx_with_size(packet_pool, MAX_RX_BUF_SIZE)
+    }
+
+    /// A packet to receive a message of up to `size` bytes
+    pub fn new_rx_with_size(
+        packet_pool: &Arc<Mutex<PacketPool>>,
+        size: usize,
+    ) -> Result<BoxPacket, Error> {
+        let (buffer_index, buffer) =
+            PacketPool::alloc(packet_pool, size).ok_or(Error::PacketPoolExhaust)?;
         let buf_len = buffer.len();
-        Ok(Self {
+        Ok(Box::new(Packet {
             plain: Default::default(),
             proto: Default::default(),
             buffer_index,
+            packet_pool: packet_pool.clone(),
             peer: Address::default(),
             data: Direction::Rx(ParseBuf::new(buffer, buf_len), RxState::Uninit),
-        })
+        }))
+    }
+
+    pub fn new_tx(packet_pool: &Arc<Mutex<PacketPool>>) -> Result<BoxPacket, Error> {
+        Packet::new_tx_with_size(packet_pool, MAX_RX_BUF_SIZE)
     }
 
-    pub fn new_tx() -> Result<Self, Error> {
-        let (buffer_index, buffer) = BufferPool::alloc().ok_or(Error::NoSpace)?;
+    /// A packet to send a message of up to `size` bytes, headers included
+    pub fn new_tx_with_size(
+        packet_pool: &Arc<Mutex<PacketPool>>,
+        size: usize,
+    ) -> Result<BoxPacket, Error> {
+        let (buffer_index, buffer) =
+            PacketPool::alloc(packet_pool, size).ok_or(Error::PacketPoolExhaust)?;
         let buf_len = buffer.len();
 
         let mut wb = WriteBuf::new(buffer, buf_len);
         wb.reserve(Packet::HDR_RESERVE)?;
 
-        let mut p = Self {
+        let mut p = Box::new(Packet {
             plain: Default::default(),
             proto: Default::default(),
             buffer_index,
+            packet_pool: packet_pool.clone(),
             peer: Address::default(),
             data: Direction::Tx(wb),
-        };
+        });
         // Reliability on by default
         p.proto.set_reliable();
         Ok(p)
@@ -214,9 +244,45 @@ impl<'a> Packet<'a> {
 
 impl<'a> Drop for Packet<'a> {
     fn drop(&mut self) {
-        BufferPool::free(self.buffer_index);
+        PacketPool::free(&self.packet_pool, self.buffer_index);
         trace!("Dropping Packet......");
     }
 }
 
-box_slab!(PacketPool, Packet<'static>, MAX_PACKET_POOL_SIZE);
+#[cfg(test)]
+mod tests {
+    use super::{Packet, PacketPool};
+    use crate::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_pool_per_instance() {
+        let pool1 = Arc::new(Mutex::new(PacketPool::new(2).unwrap()));
+        let pool2 = Arc::new(Mutex::new(PacketPool::new(2).unwrap()));
+
+        let rx = Packet::new_rx(&pool1).unwrap();
+        let _tx = Packet::new_tx(&pool1).unwrap();
+        assert_eq!(Packet::new_rx(&pool1).err(), Some(Error::PacketPoolExhaust));
+        // Exhausting one pool doesn't affect the others
+        let _rx2 = Packet::new_rx(&pool2).unwrap();
+        let _tx2 = Packet::new_tx(&pool2).unwrap();
+
+        // Dropping a packet returns it to its pool
+        drop(rx);
+        Packet::new_rx(&pool1).unwrap();
+    }
+
+    #[test]
+    fn test_packet_size() {
+        let pool = Arc::new(Mutex::new(PacketPool::new(2).unwrap()));
+        let mut rx = Packet::new_rx_with_size(&pool, 4000).unwrap();
+        assert_eq!(rx.as_borrow_slice().len(), 4000);
+        let mut tx = Packet::new_tx_with_size(&pool, 4000).unwrap();
+        let wb = tx.get_writebuf().unwrap();
+        wb.append(&[0xAB; 3000]).unwrap();
+        assert_eq!(wb.as_borrow_slice().len(), 3000);
+        drop(rx);
+        let mut rx = Packet::new_rx(&pool).unwrap();
+        assert_eq!(rx.as_borrow_slice().len(), super::MAX_RX_BUF_SIZE);
+    }
+}
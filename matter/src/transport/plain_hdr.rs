@@ -66,6 +66,17 @@ impl PlainHdr {
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
             self.peer_nodeid = Some(msg.le_u64()?);
         }
+        // The destination is us, it is only skipped over
+        if self
+            .flags
+            .contains(MsgFlags::DSIZ_UNICAST_NODEID | MsgFlags::DSIZ_GROUPCAST_NODEID)
+        {
+            return Err(Error::Invalid);
+        } else if self.flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+            msg.le_u64()?;
+        } else if self.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+            msg.le_u16()?;
+        }
 
         info!(
             "[decode] flags: {:?}, session type: {:#?}, sess_id: {}, ctr: {}",
@@ -106,3 +117,35 @@ pub const fn max_plain_hdr_len() -> usize {
     // [optional] destination node ID
         8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_node_ids() {
+        // Source and destination node IDs, followed by the protocol header
+        let mut b = [
+            0x05, 0, 0, 0, 1, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 8, 7, 6, 5, 4, 3, 2, 1, 0x05,
+        ];
+        let b_len = b.len();
+        let mut parsebuf = ParseBuf::new(&mut b, b_len);
+        let mut plain_hdr = PlainHdr::default();
+        plain_hdr.decode(&mut parsebuf).unwrap();
+        assert_eq!(plain_hdr.ctr, 1);
+        assert_eq!(plain_hdr.get_src_u64(), Some(0x0807060504030201));
+        assert_eq!(parsebuf.as_slice(), [0x05]);
+
+        // A group destination, and both kinds of destination
+        let mut b = [0x02, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0x05];
+        let b_len = b.len();
+        let mut parsebuf = ParseBuf::new(&mut b, b_len);
+        plain_hdr.decode(&mut parsebuf).unwrap();
+        assert_eq!(parsebuf.as_slice(), [0x05]);
+
+        let mut b = [0x03, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0x05];
+        let b_len = b.len();
+        let mut parsebuf = ParseBuf::new(&mut b, b_len);
+        assert_eq!(plain_hdr.decode(&mut parsebuf), Err(Error::Invalid));
+    }
+}
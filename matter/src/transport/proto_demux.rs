@@ -1,11 +1,10 @@
-use boxslab::BoxSlab;
-
 use crate::error::*;
 
 use super::exchange::ExchangeCtx;
-use super::packet::PacketPool;
+use super::packet::BoxPacket;
 
-const MAX_PROTOCOLS: usize = 4;
+/// The default for the number of protocols that can be registered
+pub const MAX_PROTOCOLS: usize = 4;
 
 #[derive(PartialEq)]
 pub enum ResponseRequired {
@@ -13,7 +12,8 @@ pub enum ResponseRequired {
     No,
 }
 pub struct ProtoDemux {
-    proto_id_handlers: [Option<Box<dyn HandleProto>>; MAX_PROTOCOLS],
+    // Indexed by the protocol ID
+    proto_id_handlers: Vec<Option<Box<dyn HandleProto>>>,
 }
 
 /// This is the context in which a receive packet is being processed
@@ -21,17 +21,13 @@ pub struct ProtoCtx<'a> {
     /// This is the exchange context, that includes the exchange and the session
     pub exch_ctx: ExchangeCtx<'a>,
     /// This is the received buffer for this transaction
-    pub rx: BoxSlab<PacketPool>,
+    pub rx: BoxPacket,
     /// This is the transmit buffer for this transaction
-    pub tx: BoxSlab<PacketPool>,
+    pub tx: BoxPacket,
 }
 
 impl<'a> ProtoCtx<'a> {
-    pub fn new(
-        exch_ctx: ExchangeCtx<'a>,
-        rx: BoxSlab<PacketPool>,
-        tx: BoxSlab<PacketPool>,
-    ) -> Self {
+    pub fn new(exch_ctx: ExchangeCtx<'a>, rx: BoxPacket, tx: BoxPacket) -> Self {
         Self { exch_ctx, rx, tx }
     }
 }
@@ -54,23 +50,35 @@ impl Default for ProtoDemux {
 
 impl ProtoDemux {
     pub fn new() -> ProtoDemux {
+        ProtoDemux::new_with(MAX_PROTOCOLS)
+    }
+
+    pub fn new_with(max_protocols: usize) -> ProtoDemux {
         ProtoDemux {
-            proto_id_handlers: [None, None, None, None],
+            proto_id_handlers: (0..max_protocols).map(|_| None).collect(),
         }
     }
 
     pub fn register(&mut self, proto_id_handle: Box<dyn HandleProto>) -> Result<(), Error> {
         let proto_id = proto_id_handle.get_proto_id();
-        self.proto_id_handlers[proto_id] = Some(proto_id_handle);
+        let handler = self
+            .proto_id_handlers
+            .get_mut(proto_id)
+            .ok_or(Error::NoSpace)?;
+        if handler.is_some() {
+            // Another handler already owns this protocol ID
+            return Err(Error::Invalid);
+        }
+        *handler = Some(proto_id_handle);
         Ok(())
     }
 
     pub fn handle(&mut self, proto_ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
         let proto_id = proto_ctx.rx.get_proto_id() as usize;
-        if proto_id >= MAX_PROTOCOLS {
-            return Err(Error::Invalid);
-        }
-        return self.proto_id_handlers[proto_id]
+        return self
+            .proto_id_handlers
+            .get_mut(proto_id)
+            .ok_or(Error::Invalid)?
             .as_mut()
             .ok_or(Error::NoHandler)?
             .handle_proto_id(proto_ctx);
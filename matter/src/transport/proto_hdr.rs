@@ -208,6 +208,10 @@ fn decrypt_in_place(
     get_iv(recvd_ctr, peer_nodeid, &mut iv)?;
 
     let cipher_text = parsebuf.as_borrow_slice();
+    if cipher_text.len() < crypto::AEAD_MIC_LEN_BYTES {
+        // Not even room for the MIC
+        return Err(Error::TruncatedPacket);
+    }
     //println!("AAD: {:x?}", aad);
     //println!("Cipher Text: {:x?}", cipher_text);
     //println!("IV: {:x?}", iv);
@@ -272,6 +276,20 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_decrypt_truncated() {
+        let mut input_buf = [0u8; 20];
+        let input_buf_len = input_buf.len();
+        let mut parsebuf = ParseBuf::new(&mut input_buf, input_buf_len);
+        parsebuf.le_u32().unwrap();
+        parsebuf.le_u32().unwrap();
+
+        assert_eq!(
+            decrypt_in_place(1, 0, &mut parsebuf, &[0u8; 16]),
+            Err(Error::TruncatedPacket)
+        );
+    }
+
     #[test]
     pub fn test_encrypt_success() {
         // These values are captured from an execution run of the chip-tool binary
@@ -1,47 +1,55 @@
-use std::sync::Once;
+use std::{fmt, time::Duration};
 
-use async_channel::{bounded, Receiver, Sender};
+use async_channel::{unbounded, Receiver, Sender};
 
 use crate::error::Error;
 
 use super::session::CloneData;
 
-#[derive(Debug)]
+/// A callback that is run in the context of the Transport Manager's event loop
+pub type Callback = Box<dyn FnOnce() + Send>;
+
 pub enum Msg {
     Tx(),
     Rx(),
     NewSession(CloneData),
+    /// Stop the event loop
+    Shutdown,
+    /// Run the callback once the delay has expired
+    Timer(Duration, Callback),
+}
+
+impl fmt::Debug for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Msg::Tx() => write!(f, "Tx"),
+            Msg::Rx() => write!(f, "Rx"),
+            Msg::NewSession(clone_data) => write!(f, "NewSession({:?})", clone_data),
+            Msg::Shutdown => write!(f, "Shutdown"),
+            Msg::Timer(delay, _) => write!(f, "Timer({:?})", delay),
+        }
+    }
 }
 
+/// The sending end of the queue of messages to the Transport Manager
+///
+/// The queue is unbounded, so that sending never blocks. The messages are also sent from the
+/// Transport Manager's own event loop, for example by the callbacks of timers, and blocking there
+/// would stop the queue from ever being drained.
 #[derive(Clone)]
 pub struct WorkQ {
     tx: Sender<Msg>,
 }
 
-static mut G_WQ: Option<WorkQ> = None;
-static INIT: Once = Once::new();
-
 impl WorkQ {
-    pub fn init() -> Result<Receiver<Msg>, Error> {
-        let (tx, rx) = bounded::<Msg>(3);
-        WorkQ::configure(tx);
-        Ok(rx)
-    }
-
-    fn configure(tx: Sender<Msg>) {
-        unsafe {
-            INIT.call_once(|| {
-                G_WQ = Some(WorkQ { tx });
-            });
-        }
-    }
-
-    pub fn get() -> Result<WorkQ, Error> {
-        unsafe { G_WQ.as_ref().cloned().ok_or(Error::Invalid) }
+    /// Create a queue, along with its receiving end
+    pub fn new() -> (WorkQ, Receiver<Msg>) {
+        let (tx, rx) = unbounded::<Msg>();
+        (WorkQ { tx }, rx)
     }
 
     pub fn sync_send(&self, msg: Msg) -> Result<(), Error> {
-        smol::block_on(self.send(msg))
+        self.tx.try_send(msg).map_err(|e| e.into())
     }
 
     pub async fn send(&self, msg: Msg) -> Result<(), Error> {
@@ -2,22 +2,23 @@ use core::fmt;
 use std::{
     any::Any,
     ops::{Deref, DerefMut},
-    time::SystemTime,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use crate::{
+    clock::{Clock, SystemClock},
     error::*,
     transport::{plain_hdr, proto_hdr},
     utils::writebuf::WriteBuf,
 };
-use boxslab::{BoxSlab, Slab};
 use colored::*;
 use log::{info, trace};
 use rand::Rng;
 
 use super::{
-    network::{Address, NetworkInterface},
-    packet::{Packet, PacketPool},
+    network::{any_readable, Address, NetworkInterface, Readiness},
+    packet::{BoxPacket, Packet, PacketPool},
 };
 
 const MATTER_AES128_KEY_SIZE: usize = 16;
@@ -51,7 +52,10 @@ pub struct Session {
     msg_ctr: u32,
     mode: SessionMode,
     data: Option<Box<dyn Any>>,
-    last_use: SystemTime,
+    // As per the clock of the Session Manager
+    last_use: Duration,
+    // Send to the peer over TCP, even though it reached us over UDP
+    prefer_tcp: bool,
 }
 
 #[derive(Debug)]
@@ -105,7 +109,8 @@ impl Session {
             msg_ctr: rand::thread_rng().gen_range(0..MATTER_MSG_CTR_RANGE),
             mode: SessionMode::PlainText,
             data: None,
-            last_use: SystemTime::now(),
+            last_use: Duration::ZERO,
+            prefer_tcp: false,
         }
     }
 
@@ -123,7 +128,8 @@ impl Session {
             msg_ctr: rand::thread_rng().gen_range(0..MATTER_MSG_CTR_RANGE),
             mode: clone_from.mode,
             data: None,
-            last_use: SystemTime::now(),
+            last_use: Duration::ZERO,
+            prefer_tcp: false,
         }
     }
 
@@ -160,6 +166,24 @@ impl Session {
         self.peer_addr
     }
 
+    /// Send the messages to the peer over TCP, on the same port as the one it reached us on
+    ///
+    /// This is meant for the peers that advertise TCP support, see
+    /// [tcp_supported](crate::mdns::tcp_supported). The messages received from the peer over
+    /// either transport still belong to this session.
+    pub fn set_prefer_tcp(&mut self, prefer_tcp: bool) {
+        self.prefer_tcp = prefer_tcp;
+    }
+
+    /// The address the messages to the peer are sent to
+    pub fn get_tx_addr(&self) -> Address {
+        if self.prefer_tcp {
+            Address::Tcp(self.peer_addr.socket_addr())
+        } else {
+            self.peer_addr
+        }
+    }
+
     pub fn is_encrypted(&self) -> bool {
         match self.mode {
             SessionMode::Case(_) | SessionMode::Pase => true,
@@ -207,7 +231,6 @@ impl Session {
     }
 
     pub fn recv(&mut self, proto_rx: &mut Packet) -> Result<(), Error> {
-        self.last_use = SystemTime::now();
         proto_rx.proto_decode(self.peer_nodeid.unwrap_or_default(), self.get_dec_key())
     }
 
@@ -222,8 +245,7 @@ impl Session {
 
     // TODO: Most of this can now be moved into the 'Packet' module
     fn do_send(&mut self, proto_tx: &mut Packet) -> Result<(), Error> {
-        self.last_use = SystemTime::now();
-        proto_tx.peer = self.peer_addr;
+        proto_tx.peer = self.get_tx_addr();
 
         // Generate encrypted header
         let mut tmp_buf: [u8; proto_hdr::max_proto_hdr_len()] = [0; proto_hdr::max_proto_hdr_len()];
@@ -277,11 +299,17 @@ impl fmt::Display for Session {
     }
 }
 
+/// The default for the maximum number of sessions
 pub const MAX_SESSIONS: usize = 16;
 pub struct SessionMgr {
     next_sess_id: u16,
-    sessions: [Option<Session>; MAX_SESSIONS],
-    network: Option<Box<dyn NetworkInterface>>,
+    // The length is the maximum number of sessions
+    sessions: Vec<Option<Session>>,
+    networks: Vec<Box<dyn NetworkInterface>>,
+    // The interface that is received from first, so that a busy one doesn't starve the others
+    rx_turn: usize,
+    packet_pool: Arc<Mutex<PacketPool>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for SessionMgr {
@@ -292,27 +320,66 @@ impl Default for SessionMgr {
 
 impl SessionMgr {
     pub fn new() -> SessionMgr {
+        SessionMgr::new_with(
+            MAX_SESSIONS,
+            Arc::new(Mutex::new(PacketPool::default())),
+            Arc::new(SystemClock::new()),
+        )
+    }
+
+    /// Create a Session Manager, the received packets are allocated from the given pool
+    ///
+    /// The clock tracks the use of the sessions, and is shared with the layers above
+    pub fn new_with(
+        max_sessions: usize,
+        packet_pool: Arc<Mutex<PacketPool>>,
+        clock: Arc<dyn Clock>,
+    ) -> SessionMgr {
         SessionMgr {
-            sessions: Default::default(),
+            sessions: (0..max_sessions).map(|_| None).collect(),
             next_sess_id: 1,
-            network: None,
+            networks: Vec::new(),
+            rx_turn: 0,
+            packet_pool,
+            clock,
         }
     }
 
+    /// The pool that the packets of this Session Manager are allocated from
+    pub fn packet_pool(&self) -> &Arc<Mutex<PacketPool>> {
+        &self.packet_pool
+    }
+
+    /// The clock of this Session Manager
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Receive and send over one more network interface
+    ///
+    /// The packets are sent over the first interface that [handles](NetworkInterface::handles)
+    /// the address of the peer.
     pub fn add_network_interface(
         &mut self,
         interface: Box<dyn NetworkInterface>,
     ) -> Result<(), Error> {
-        if self.network.is_none() {
-            self.network = Some(interface);
-            Ok(())
-        } else {
-            Err(Error::Invalid)
+        self.networks.push(interface);
+        Ok(())
+    }
+
+    fn network_for(&self, addr: Address) -> Result<&dyn NetworkInterface, Error> {
+        if self.networks.is_empty() {
+            return Err(Error::NoNetworkInterface);
         }
+        self.networks
+            .iter()
+            .find(|n| n.handles(addr))
+            .map(|n| n.as_ref())
+            .ok_or(Error::InvalidPeerAddr)
     }
 
     pub fn mut_by_index(&mut self, index: usize) -> Option<&mut Session> {
-        self.sessions[index].as_mut()
+        self.sessions.get_mut(index).and_then(|s| s.as_mut())
     }
 
     fn get_next_sess_id(&mut self) -> u16 {
@@ -339,17 +406,13 @@ impl SessionMgr {
     }
 
     pub fn get_lru(&mut self) -> usize {
-        let mut lru_index = 0;
-        let mut lru_ts = SystemTime::now();
-        for i in 0..MAX_SESSIONS {
-            if let Some(s) = &self.sessions[i] {
-                if s.last_use < lru_ts {
-                    lru_ts = s.last_use;
-                    lru_index = i;
-                }
-            }
-        }
-        lru_index
+        // The first of the sessions that were used at the same time wins
+        self.sessions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.as_ref().map(|s| (i, s.last_use)))
+            .min_by_key(|(_, last_use)| *last_use)
+            .map_or(0, |(i, _)| i)
     }
 
     pub fn add(&mut self, peer_addr: Address, peer_nodeid: Option<u64>) -> Result<usize, Error> {
@@ -360,14 +423,36 @@ impl SessionMgr {
     /// This assumes that the higher layer has taken care of doing anything required
     /// as per the spec before the session is erased
     pub fn remove(&mut self, idx: usize) {
-        self.sessions[idx] = None;
+        let session = match self.sessions[idx].take() {
+            Some(session) => session,
+            None => return,
+        };
+        // Let the interfaces release their connections with the peer, unless another session
+        // still uses them
+        let mut addrs = vec![session.peer_addr];
+        if session.get_tx_addr() != session.peer_addr {
+            addrs.push(session.get_tx_addr());
+        }
+        for addr in addrs {
+            let in_use = self
+                .sessions
+                .iter()
+                .flatten()
+                .any(|s| s.peer_addr == addr || s.get_tx_addr() == addr);
+            if !in_use {
+                if let Ok(network) = self.network_for(addr) {
+                    network.close(addr);
+                }
+            }
+        }
     }
 
     /// We could have returned a SessionHandle here. But the borrow checker doesn't support
     /// non-lexical lifetimes. This makes it harder for the caller of this function to take
     /// action in the error return path
-    pub fn add_session(&mut self, session: Session) -> Result<usize, Error> {
+    pub fn add_session(&mut self, mut session: Session) -> Result<usize, Error> {
         if let Some(index) = self.get_empty_slot() {
+            session.last_use = self.clock.now();
             self.sessions[index] = Some(session);
             Ok(index)
         } else {
@@ -395,7 +480,7 @@ impl SessionMgr {
                     nodeid_matches = false;
                 }
                 x.local_sess_id == sess_id
-                    && x.peer_addr == peer_addr
+                    && (x.peer_addr == peer_addr || x.get_tx_addr() == peer_addr)
                     && x.is_encrypted() == is_encrypted
                     && nodeid_matches
             } else {
@@ -439,7 +524,13 @@ impl SessionMgr {
             rx.plain.get_src_u64(),
             rx.plain.is_encrypted(),
         ) {
-            Ok(s) => Some(s),
+            Ok(s) => {
+                let now = self.clock.now();
+                if let Some(session) = self.mut_by_index(s) {
+                    session.last_use = now;
+                }
+                Some(s)
+            }
             Err(Error::NoSpace) => None,
             Err(e) => {
                 return Err(e);
@@ -448,12 +539,40 @@ impl SessionMgr {
         Ok(sess_index)
     }
 
-    pub fn recv(&mut self) -> Result<(BoxSlab<PacketPool>, Option<usize>), Error> {
-        let mut rx = Slab::<PacketPool>::new(Packet::new_rx()?).ok_or(Error::PacketPoolExhaust)?;
+    /// Wait until a packet can be received from any of the network interfaces
+    pub fn readable(&self) -> Result<Readiness<'_>, Error> {
+        match self.networks.len() {
+            0 => Err(Error::NoNetworkInterface),
+            1 => Ok(self.networks[0].readable()),
+            _ => Ok(any_readable(
+                self.networks.iter().map(|n| n.readable()).collect(),
+            )),
+        }
+    }
 
-        let network = self.network.as_ref().ok_or(Error::NoNetworkInterface)?;
+    /// The largest message that can be exchanged with the given peer
+    pub fn max_msg_size(&self, addr: Address) -> Result<usize, Error> {
+        Ok(self.network_for(addr)?.max_msg_size())
+    }
 
-        let (len, src) = network.recv(rx.as_borrow_slice())?;
+    pub fn recv(&mut self) -> Result<(BoxPacket, Option<usize>), Error> {
+        if self.networks.is_empty() {
+            return Err(Error::NoNetworkInterface);
+        }
+        let count = self.networks.len();
+        let start = self.rx_turn % count;
+        self.rx_turn = self.rx_turn.wrapping_add(1);
+        // Only some of the interfaces may have something to receive
+        let mut result = Err(Error::Network);
+        for i in 0..count {
+            let network = &self.networks[(start + i) % count];
+            let mut rx = Packet::new_rx_with_size(&self.packet_pool, network.max_msg_size())?;
+            result = network.recv(rx.as_borrow_slice()).map(|r| (rx, r));
+            if result.is_ok() {
+                break;
+            }
+        }
+        let (mut rx, (len, src)) = result?;
         rx.get_parsebuf()?.set_len(len);
         rx.peer = src;
 
@@ -468,18 +587,13 @@ impl SessionMgr {
         Ok((rx, sess_handle))
     }
 
-    pub fn send(
-        &mut self,
-        sess_idx: usize,
-        mut proto_tx: BoxSlab<PacketPool>,
-    ) -> Result<(), Error> {
-        self.sessions[sess_idx]
-            .as_mut()
-            .ok_or(Error::NoSession)?
-            .do_send(&mut proto_tx)?;
+    pub fn send(&mut self, sess_idx: usize, mut proto_tx: BoxPacket) -> Result<(), Error> {
+        let session = self.sessions[sess_idx].as_mut().ok_or(Error::NoSession)?;
+        session.last_use = self.clock.now();
+        session.do_send(&mut proto_tx)?;
 
-        let network = self.network.as_ref().ok_or(Error::NoNetworkInterface)?;
         let peer = proto_tx.peer;
+        let network = self.network_for(peer)?;
         network.send(proto_tx.as_borrow_slice(), peer)?;
         println!("Message Sent to {}", peer);
         Ok(())
@@ -514,9 +628,15 @@ impl<'a> SessionHandle<'a> {
         self.sess_mgr.get_next_sess_id()
     }
 
-    pub fn send(&mut self, proto_tx: BoxSlab<PacketPool>) -> Result<(), Error> {
+    pub fn send(&mut self, proto_tx: BoxPacket) -> Result<(), Error> {
         self.sess_mgr.send(self.sess_idx, proto_tx)
     }
+
+    /// The largest message that can be exchanged in this session, see
+    /// [SessionMgr::max_msg_size]
+    pub fn max_msg_size(&self) -> Result<usize, Error> {
+        self.sess_mgr.max_msg_size(self.get_tx_addr())
+    }
 }
 
 impl<'a> Deref for SessionHandle<'a> {
@@ -537,7 +657,20 @@ impl<'a> DerefMut for SessionHandle<'a> {
 #[cfg(test)]
 mod tests {
 
-    use crate::transport::network::Address;
+    use crate::{
+        clock::ManualClock,
+        error::Error,
+        transport::{
+            network::{Address, NetworkInterface, Readiness},
+            packet::PacketPool,
+        },
+    };
+    use smol::future;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
     use super::SessionMgr;
 
@@ -567,4 +700,116 @@ mod tests {
         assert_eq!(sm.get_next_sess_id(), 65535);
         assert_eq!(sm.get_next_sess_id(), 2);
     }
+
+    #[test]
+    fn test_lru() {
+        let clock = Arc::new(ManualClock::new());
+        let mut sm = SessionMgr::new_with(
+            4,
+            Arc::new(Mutex::new(PacketPool::default())),
+            clock.clone(),
+        );
+        let addr = |port| Address::Udp(SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+        for port in 1..4 {
+            sm.add(addr(port), None).unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(sm.get_lru(), 0);
+
+        // A new session in the slot of the oldest one is the most recently used
+        sm.remove(0);
+        assert_eq!(sm.get_lru(), 1);
+        assert_eq!(sm.add(addr(4), None), Ok(0));
+        assert_eq!(sm.get_lru(), 1);
+    }
+
+    // Records the addresses it is asked to close
+    struct ClosingNetwork {
+        tcp: bool,
+        closed: Arc<Mutex<Vec<Address>>>,
+    }
+
+    impl NetworkInterface for ClosingNetwork {
+        fn recv(&self, _in_buf: &mut [u8]) -> Result<(usize, Address), Error> {
+            Err(Error::Network)
+        }
+
+        fn send(&self, out_buf: &[u8], _addr: Address) -> Result<usize, Error> {
+            Ok(out_buf.len())
+        }
+
+        fn readable(&self) -> Readiness<'_> {
+            Box::pin(future::pending())
+        }
+
+        fn handles(&self, addr: Address) -> bool {
+            matches!(addr, Address::Tcp(_)) == self.tcp
+        }
+
+        fn close(&self, addr: Address) {
+            self.closed.lock().unwrap().push(addr);
+        }
+    }
+
+    #[test]
+    fn test_close_connection() {
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let mut sm = SessionMgr::new();
+        for tcp in [false, true] {
+            sm.add_network_interface(Box::new(ClosingNetwork {
+                tcp,
+                closed: closed.clone(),
+            }))
+            .unwrap();
+        }
+        let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 5540));
+        let idx_1 = sm.add(Address::Tcp(peer), None).unwrap();
+        let idx_2 = sm.add(Address::Tcp(peer), None).unwrap();
+        let idx_3 = sm.add(Address::Udp(peer), None).unwrap();
+
+        // The TCP connection is only closed once no session uses it anymore
+        sm.remove(idx_1);
+        assert!(closed.lock().unwrap().is_empty());
+        sm.remove(idx_2);
+        assert_eq!(*closed.lock().unwrap(), [Address::Tcp(peer)]);
+        sm.remove(idx_3);
+        assert_eq!(
+            *closed.lock().unwrap(),
+            [Address::Tcp(peer), Address::Udp(peer)]
+        );
+    }
+
+    #[test]
+    fn test_prefer_tcp() {
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let mut sm = SessionMgr::new();
+        for tcp in [false, true] {
+            sm.add_network_interface(Box::new(ClosingNetwork {
+                tcp,
+                closed: closed.clone(),
+            }))
+            .unwrap();
+        }
+        let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 5540));
+        let idx_1 = sm.add(Address::Udp(peer), None).unwrap();
+        let idx_2 = sm.add(Address::Udp(peer), None).unwrap();
+        let mut sess = sm.get_session_handle(idx_1);
+        sess.set_local_sess_id(1);
+        sess.set_prefer_tcp(true);
+        assert_eq!(sess.get_tx_addr(), Address::Tcp(peer));
+        assert_eq!(sess.get_peer_addr(), Address::Udp(peer));
+
+        // The messages from the peer over TCP belong to the session
+        assert_eq!(sm._get(1, Address::Tcp(peer), None, false), Some(idx_1));
+        assert_eq!(sm._get(1, Address::Udp(peer), None, false), Some(idx_1));
+
+        // The TCP connection is only used by the first session
+        sm.remove(idx_1);
+        assert_eq!(*closed.lock().unwrap(), [Address::Tcp(peer)]);
+        sm.remove(idx_2);
+        assert_eq!(
+            *closed.lock().unwrap(),
+            [Address::Tcp(peer), Address::Udp(peer)]
+        );
+    }
 }
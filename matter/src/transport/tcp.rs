@@ -0,0 +1,562 @@
+use log::{error, info, warn};
+use smol::{
+    future,
+    net::{Ipv6Addr, SocketAddr},
+    Async,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    io::{self, ErrorKind, Read, Write},
+    net::{self, Shutdown, TcpStream},
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+use crate::error::*;
+
+use super::{
+    network::{Address, NetworkInterface, Readiness},
+    udp::MATTER_PORT,
+};
+
+/// The size of the length that is prefixed to every message sent over TCP
+pub const LEN_PREFIX_SIZE: usize = 4;
+
+/// The largest message that is accepted over TCP
+///
+/// A peer that sends a larger one is disconnected, rather than having us buffer it.
+pub const MAX_TCP_MSG_SIZE: usize = 64 * 1024;
+
+/// The most connections that are open at once, the connections accepted beyond that are closed
+pub const MAX_TCP_CONNS: usize = 16;
+
+/// The most bytes that are held for a connection, in either direction
+///
+/// Nothing more is read from a peer while its messages haven't been received by the stack, and
+/// nothing more is sent to a peer that doesn't keep up.
+pub const MAX_CONN_BUFFER: usize = 2 * (LEN_PREFIX_SIZE + MAX_TCP_MSG_SIZE);
+
+/// Prefix the message with its length, as a 32-bit little endian value
+pub fn encode_frame(msg: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(LEN_PREFIX_SIZE + msg.len());
+    frame.extend_from_slice(&(msg.len() as u32).to_le_bytes());
+    frame.extend_from_slice(msg);
+    frame
+}
+
+/// Splits the byte stream of a connection into the messages it carries
+#[derive(Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the bytes that were read from the connection
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// The number of bytes that are held, until their message is complete
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The next complete message, if any
+    ///
+    /// Returns [Error::InvalidData] if the length of the message is over [MAX_TCP_MSG_SIZE], the
+    /// stream can't be resynchronised after that.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if self.buf.len() < LEN_PREFIX_SIZE {
+            return Ok(None);
+        }
+        let mut len = [0u8; LEN_PREFIX_SIZE];
+        len.copy_from_slice(&self.buf[..LEN_PREFIX_SIZE]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_TCP_MSG_SIZE {
+            return Err(Error::InvalidData);
+        }
+        if self.buf.len() < LEN_PREFIX_SIZE + len {
+            return Ok(None);
+        }
+        let frame = self.buf[LEN_PREFIX_SIZE..LEN_PREFIX_SIZE + len].to_vec();
+        self.buf.drain(..LEN_PREFIX_SIZE + len);
+        Ok(Some(frame))
+    }
+}
+
+type Connect = Pin<Box<dyn Future<Output = io::Result<Async<TcpStream>>> + Send>>;
+
+enum Stream {
+    Connecting(Connect),
+    Connected(Async<TcpStream>),
+}
+
+struct Conn {
+    stream: Stream,
+    decoder: FrameDecoder,
+    // The bytes of the messages that were read, but not received by the stack yet
+    rx_pending: usize,
+    // The frames that are waiting to be written
+    tx: Vec<u8>,
+}
+
+impl Conn {
+    fn new(stream: Stream) -> Self {
+        Self {
+            stream,
+            decoder: FrameDecoder::new(),
+            rx_pending: 0,
+            tx: Vec::new(),
+        }
+    }
+
+    // Move the connection forward, without blocking: complete the connect, write what is queued
+    // and read the messages. Returns false once the connection is over.
+    fn poll(
+        &mut self,
+        peer: SocketAddr,
+        rx_queue: &mut VecDeque<(SocketAddr, Vec<u8>)>,
+        cx: &mut Context<'_>,
+    ) -> bool {
+        if let Stream::Connecting(connect) = &mut self.stream {
+            match connect.as_mut().poll(cx) {
+                Poll::Ready(Ok(stream)) => {
+                    info!("TCP connection to {} is open", peer);
+                    if let Err(e) = stream.get_ref().set_nodelay(true) {
+                        warn!("Error setting TCP_NODELAY for {}: {:?}", peer, e);
+                    }
+                    self.stream = Stream::Connected(stream);
+                }
+                Poll::Ready(Err(e)) => {
+                    error!("Error connecting to {} over TCP: {:?}", peer, e);
+                    return false;
+                }
+                Poll::Pending => return true,
+            }
+        }
+        let Conn {
+            stream,
+            decoder,
+            rx_pending,
+            tx,
+        } = self;
+        let stream = match stream {
+            Stream::Connected(stream) => stream,
+            Stream::Connecting(_) => return true,
+        };
+
+        while !tx.is_empty() {
+            match stream.get_ref().write(tx) {
+                Ok(0) => {
+                    error!("TCP connection with {} can't be written to", peer);
+                    return false;
+                }
+                Ok(len) => {
+                    tx.drain(..len);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => match stream.poll_writable(cx) {
+                    Poll::Ready(Ok(())) => (),
+                    Poll::Ready(Err(e)) => {
+                        error!("Error sending to {} over TCP: {:?}", peer, e);
+                        return false;
+                    }
+                    Poll::Pending => break,
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => {
+                    error!("Error sending to {} over TCP: {:?}", peer, e);
+                    return false;
+                }
+            }
+        }
+
+        let mut buf = [0u8; 4096];
+        loop {
+            let room = MAX_CONN_BUFFER.saturating_sub(decoder.buffered() + *rx_pending);
+            if room == 0 {
+                // Wait for the stack to receive the messages, before reading any more
+                return true;
+            }
+            let max = room.min(buf.len());
+            match stream.get_ref().read(&mut buf[..max]) {
+                Ok(0) => {
+                    info!("TCP connection closed by {}", peer);
+                    return false;
+                }
+                Ok(len) => {
+                    decoder.push(&buf[..len]);
+                    loop {
+                        match decoder.next_frame() {
+                            Ok(Some(frame)) => {
+                                *rx_pending += frame.len();
+                                rx_queue.push_back((peer, frame));
+                            }
+                            Ok(None) => break,
+                            Err(_) => {
+                                error!("Invalid message length from {}, disconnecting", peer);
+                                return false;
+                            }
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => match stream.poll_readable(cx) {
+                    Poll::Ready(Ok(())) => (),
+                    Poll::Ready(Err(e)) => {
+                        error!("Error on the TCP connection with {}: {:?}", peer, e);
+                        return false;
+                    }
+                    Poll::Pending => return true,
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => {
+                    error!("Error on the TCP connection with {}: {:?}", peer, e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn shutdown(&self) {
+        if let Stream::Connected(stream) = &self.stream {
+            let _ = stream.get_ref().shutdown(Shutdown::Both);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Conns {
+    conns: HashMap<SocketAddr, Conn>,
+    // The complete messages that were received, in order
+    rx_queue: VecDeque<(SocketAddr, Vec<u8>)>,
+    // The event loop waiting on the interface, to be woken up when there is something to send
+    waker: Option<Waker>,
+}
+
+/// Matter over TCP
+///
+/// The messages are framed with [encode_frame]. The connections are keyed by the address of the
+/// peer: those accepted from the peers, and those opened the first time a message is sent to an
+/// [Address::Tcp] that isn't connected yet. A connection is closed when the peer closes it, or
+/// when the Session Manager no longer has a session with the peer.
+///
+/// Nothing blocks: the connections are opened, written to and read from by the
+/// [readable](NetworkInterface::readable) future. There are at most [MAX_TCP_CONNS] connections,
+/// each holding at most [MAX_CONN_BUFFER] bytes.
+pub struct TcpListener {
+    listener: Async<net::TcpListener>,
+    conns: Mutex<Conns>,
+}
+
+impl TcpListener {
+    pub fn new() -> Result<TcpListener, Error> {
+        TcpListener::new_with(SocketAddr::from((Ipv6Addr::UNSPECIFIED, MATTER_PORT)))
+    }
+
+    /// Listen on the given address and port
+    pub fn new_with(addr: SocketAddr) -> Result<TcpListener, Error> {
+        Ok(TcpListener {
+            listener: Async::<net::TcpListener>::bind(addr)?,
+            conns: Mutex::new(Conns::default()),
+        })
+    }
+
+    /// The address the connections are accepted on
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.get_ref().local_addr()?)
+    }
+
+    // Accept the pending connections, and move all the connections forward, without blocking
+    //
+    // The given context is woken up once there is more to do.
+    fn poll_io(&self, cx: &mut Context<'_>) -> Result<(), Error> {
+        let mut conns = self.conns.lock()?;
+        conns.waker = Some(cx.waker().clone());
+        loop {
+            match self.listener.get_ref().accept() {
+                Ok((stream, peer)) => {
+                    if conns.conns.len() >= MAX_TCP_CONNS {
+                        warn!("Too many TCP connections, closing the one from {}", peer);
+                        continue;
+                    }
+                    info!("Accepted TCP connection from {}", peer);
+                    // The errors of one connection are only its own
+                    if let Err(e) = stream.set_nodelay(true) {
+                        warn!("Error setting TCP_NODELAY for {}: {:?}", peer, e);
+                    }
+                    match Async::new(stream) {
+                        Ok(stream) => {
+                            conns
+                                .conns
+                                .insert(peer, Conn::new(Stream::Connected(stream)));
+                        }
+                        Err(e) => error!("Error registering the connection from {}: {:?}", peer, e),
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    match self.listener.poll_readable(cx) {
+                        Poll::Ready(result) => result?,
+                        Poll::Pending => break,
+                    }
+                }
+                Err(e) => {
+                    error!("Error accepting a TCP connection: {:?}", e);
+                    break;
+                }
+            }
+        }
+
+        let Conns {
+            conns, rx_queue, ..
+        } = &mut *conns;
+        conns.retain(|peer, conn| conn.poll(*peer, rx_queue, cx));
+        Ok(())
+    }
+}
+
+impl NetworkInterface for TcpListener {
+    fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error> {
+        let mut conns = self.conns.lock()?;
+        let (peer, frame) = conns.rx_queue.pop_front().ok_or(Error::Network)?;
+        if let Some(conn) = conns.conns.get_mut(&peer) {
+            conn.rx_pending = conn.rx_pending.saturating_sub(frame.len());
+        }
+        if frame.len() > in_buf.len() {
+            error!(
+                "Dropping a message of {} bytes from {}, it doesn't fit",
+                frame.len(),
+                peer
+            );
+            return Err(Error::NoSpace);
+        }
+        in_buf[..frame.len()].copy_from_slice(&frame);
+        Ok((frame.len(), Address::Tcp(peer)))
+    }
+
+    fn send(&self, out_buf: &[u8], addr: Address) -> Result<usize, Error> {
+        let peer = match addr {
+            Address::Tcp(peer) => peer,
+            Address::Udp(_) => return Err(Error::InvalidPeerAddr),
+        };
+        if out_buf.len() > MAX_TCP_MSG_SIZE {
+            error!("Message of {} bytes is too large for TCP", out_buf.len());
+            return Err(Error::NoSpace);
+        }
+        let mut conns = self.conns.lock()?;
+        let Conns { conns, waker, .. } = &mut *conns;
+        if !conns.contains_key(&peer) {
+            if conns.len() >= MAX_TCP_CONNS {
+                error!("Too many TCP connections, can't connect to {}", peer);
+                return Err(Error::NoSpace);
+            }
+            info!("Opening TCP connection to {}", peer);
+            let connect = Box::pin(Async::<TcpStream>::connect(peer));
+            conns.insert(peer, Conn::new(Stream::Connecting(connect)));
+        }
+        let conn = conns.get_mut(&peer).ok_or(Error::Network)?;
+        if conn.tx.len() + LEN_PREFIX_SIZE + out_buf.len() > MAX_CONN_BUFFER {
+            error!("Too much data pending for {} over TCP", peer);
+            return Err(Error::NoSpace);
+        }
+        conn.tx.extend_from_slice(&encode_frame(out_buf));
+        // The frame is written by the readable future, which the event loop waits on
+        if let Some(waker) = waker.take() {
+            waker.wake();
+        }
+        Ok(out_buf.len())
+    }
+
+    fn readable(&self) -> Readiness<'_> {
+        Box::pin(future::poll_fn(move |cx| {
+            self.poll_io(cx)?;
+            if self.conns.lock()?.rx_queue.is_empty() {
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        }))
+    }
+
+    fn handles(&self, addr: Address) -> bool {
+        matches!(addr, Address::Tcp(_))
+    }
+
+    fn close(&self, addr: Address) {
+        if let Ok(mut conns) = self.conns.lock() {
+            if let Some(conn) = conns.conns.remove(&addr.socket_addr()) {
+                info!("Closing TCP connection to {}", addr.socket_addr());
+                conn.shutdown();
+            }
+        }
+    }
+
+    fn max_msg_size(&self) -> usize {
+        MAX_TCP_MSG_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_frame, FrameDecoder, TcpListener, MAX_CONN_BUFFER, MAX_TCP_CONNS, MAX_TCP_MSG_SIZE,
+    };
+    use crate::{
+        error::Error,
+        transport::network::{Address, NetworkInterface},
+    };
+    use smol::future;
+    use std::{
+        io::Write,
+        net::{Ipv4Addr, SocketAddr, TcpStream},
+        task::Poll,
+        time::Duration,
+    };
+
+    fn listener() -> TcpListener {
+        TcpListener::new_with(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap()
+    }
+
+    // Move the connections of the interface forward, once
+    fn poll(iface: &TcpListener) {
+        smol::block_on(future::poll_fn(|cx| Poll::Ready(iface.poll_io(cx)))).unwrap();
+    }
+
+    // Wait for a message, while the peer is driven too, for its connections and sends to go
+    // through
+    fn recv(iface: &TcpListener, peer: &TcpListener) -> (Vec<u8>, Address) {
+        smol::block_on(future::poll_fn(|cx| {
+            peer.poll_io(cx).unwrap();
+            iface.readable().as_mut().poll(cx)
+        }))
+        .unwrap();
+        let mut buf = vec![0u8; MAX_TCP_MSG_SIZE];
+        let (len, src) = iface.recv(&mut buf).unwrap();
+        (buf[..len].to_vec(), src)
+    }
+
+    // Poll the interface until the condition holds
+    fn poll_until(iface: &TcpListener, cond: impl Fn(&TcpListener) -> bool) {
+        for _ in 0..100 {
+            poll(iface);
+            if cond(iface) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Condition never met");
+    }
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(encode_frame(&[1, 2, 3]), [3, 0, 0, 0, 1, 2, 3]);
+
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.next_frame(), Ok(None));
+        // A message split across reads, followed by an empty one and part of the next one
+        decoder.push(&[2, 0]);
+        assert_eq!(decoder.next_frame(), Ok(None));
+        decoder.push(&[0, 0, 7]);
+        assert_eq!(decoder.next_frame(), Ok(None));
+        decoder.push(&[8, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(decoder.next_frame(), Ok(Some(vec![7, 8])));
+        assert_eq!(decoder.next_frame(), Ok(Some(vec![])));
+        assert_eq!(decoder.next_frame(), Ok(None));
+        assert_eq!(decoder.buffered(), 2);
+        decoder.push(&[0, 0, 9]);
+        assert_eq!(decoder.next_frame(), Ok(Some(vec![9])));
+        assert_eq!(decoder.buffered(), 0);
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&((MAX_TCP_MSG_SIZE + 1) as u32).to_le_bytes());
+        assert_eq!(decoder.next_frame(), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn test_send_recv() {
+        let a = listener();
+        let b = listener();
+        let b_addr = Address::Tcp(b.local_addr().unwrap());
+        assert!(a.handles(b_addr));
+        assert!(!a.handles(Address::Udp(b_addr.socket_addr())));
+        assert_eq!(
+            a.send(&[1], Address::Udp(b_addr.socket_addr())),
+            Err(Error::InvalidPeerAddr)
+        );
+
+        // The connection is opened by the first message, which doesn't wait for it
+        assert_eq!(a.send(&[1, 2, 3], b_addr), Ok(3));
+        assert_eq!(a.send(&[4], b_addr), Ok(1));
+        let (msg, a_addr) = recv(&b, &a);
+        assert_eq!(msg, [1, 2, 3]);
+        assert_eq!(recv(&b, &a), (vec![4], a_addr));
+
+        // The response goes back over the same connection, whatever its size
+        let large = vec![0x5A; 4000];
+        assert_eq!(b.send(&large, a_addr), Ok(4000));
+        assert_eq!(recv(&a, &b), (large, b_addr));
+
+        // Once closed, the peer notices and a new connection is opened for the next message
+        b.close(a_addr);
+        poll_until(&a, |a| a.conns.lock().unwrap().conns.is_empty());
+        assert_eq!(a.send(&[7], b_addr), Ok(1));
+        let (msg, new_addr) = recv(&b, &a);
+        assert_eq!(msg, [7]);
+        assert_ne!(new_addr, a_addr);
+    }
+
+    #[test]
+    fn test_limits() {
+        let a = listener();
+        let b = listener();
+        let b_addr = Address::Tcp(b.local_addr().unwrap());
+
+        // The messages that can't be sent are refused, rather than buffered without limit
+        let max = vec![0u8; MAX_TCP_MSG_SIZE];
+        assert_eq!(
+            a.send(&[0; MAX_TCP_MSG_SIZE + 1], b_addr),
+            Err(Error::NoSpace)
+        );
+        assert_eq!(a.send(&max, b_addr), Ok(MAX_TCP_MSG_SIZE));
+        assert_eq!(a.send(&max, b_addr), Ok(MAX_TCP_MSG_SIZE));
+        assert_eq!(a.send(&[1], b_addr), Err(Error::NoSpace));
+
+        // A peer that sends faster than the stack receives is only read from up to the limit
+        let mut peer = TcpStream::connect(a.local_addr().unwrap()).unwrap();
+        let sender = std::thread::spawn(move || {
+            for _ in 0..3 {
+                peer.write_all(&encode_frame(&[0u8; MAX_TCP_MSG_SIZE]))
+                    .unwrap();
+            }
+            peer
+        });
+        poll_until(&a, |a| a.conns.lock().unwrap().rx_queue.len() == 2);
+        std::thread::sleep(Duration::from_millis(50));
+        poll(&a);
+        {
+            let conns = a.conns.lock().unwrap();
+            assert_eq!(conns.rx_queue.len(), 2);
+            let conn = conns.conns.values().find(|c| c.rx_pending > 0).unwrap();
+            assert!(conn.rx_pending + conn.decoder.buffered() <= MAX_CONN_BUFFER);
+        }
+        // Receiving a message makes room for the next one
+        let mut buf = vec![0u8; MAX_TCP_MSG_SIZE];
+        assert_eq!(a.recv(&mut buf).unwrap().0, MAX_TCP_MSG_SIZE);
+        poll_until(&a, |a| a.conns.lock().unwrap().rx_queue.len() == 2);
+        let _peer = sender.join().unwrap();
+
+        // Only so many connections are accepted, and opened
+        let c = listener();
+        let _peers: Vec<_> = (0..MAX_TCP_CONNS + 1)
+            .map(|_| TcpStream::connect(c.local_addr().unwrap()).unwrap())
+            .collect();
+        std::thread::sleep(Duration::from_millis(50));
+        poll(&c);
+        assert_eq!(c.conns.lock().unwrap().conns.len(), MAX_TCP_CONNS);
+        assert_eq!(c.send(&[1], b_addr), Err(Error::NoSpace));
+    }
+}
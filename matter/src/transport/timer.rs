@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+/// A hashed timer wheel
+///
+/// The timers are hashed into a fixed number of slots by their expiry tick, so that adding a
+/// timer is cheap, and expiring the timers only has to look at the slots of the ticks that have
+/// elapsed since the last call. Timers that are further away than a full turn of the wheel share
+/// a slot with the nearer ones, and are simply skipped until their deadline is reached.
+///
+/// The deadlines are expressed in the time of a [Clock](crate::clock::Clock).
+pub struct TimerWheel<T> {
+    tick: Duration,
+    slots: Vec<Vec<(Duration, T)>>,
+    // The tick up to which the timers have been expired
+    current: u64,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new(num_slots: usize, tick: Duration) -> Self {
+        let mut slots = Vec::with_capacity(num_slots);
+        slots.resize_with(num_slots.max(1), Vec::new);
+        Self {
+            tick: tick.max(Duration::from_millis(1)),
+            slots,
+            current: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn ticks(&self, at: Duration) -> u64 {
+        (at.as_nanos() / self.tick.as_nanos()) as u64
+    }
+
+    /// Add a timer that expires at the given time
+    pub fn add(&mut self, at: Duration, timer: T) {
+        let tick = self.ticks(at).max(self.current);
+        let slot = (tick % self.slots.len() as u64) as usize;
+        self.slots[slot].push((at, timer));
+        self.len += 1;
+    }
+
+    /// The earliest time at which a timer expires
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.slots
+            .iter()
+            .flat_map(|slot| slot.iter().map(|(at, _)| *at))
+            .min()
+    }
+
+    /// Move all the timers that have expired at `now` to `expired`
+    ///
+    /// Timers that expire in the same tick aren't guaranteed to be returned in the order of their
+    /// deadlines.
+    pub fn expire(&mut self, now: Duration, expired: &mut Vec<T>) {
+        let end = self.ticks(now).max(self.current);
+        let num_slots = self.slots.len() as u64;
+        let count = (end - self.current + 1).min(num_slots);
+        for i in 0..count {
+            let slot = &mut self.slots[((self.current + i) % num_slots) as usize];
+            let mut j = 0;
+            while j < slot.len() {
+                if slot[j].0 <= now {
+                    expired.push(slot.swap_remove(j).1);
+                    self.len -= 1;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+        self.current = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimerWheel;
+    use std::time::Duration;
+
+    #[test]
+    fn test_expire() {
+        let mut wheel = TimerWheel::new(8, Duration::from_millis(10));
+        let now = Duration::from_secs(100);
+        wheel.add(now + Duration::from_millis(30), 3);
+        wheel.add(now + Duration::from_millis(10), 1);
+        wheel.add(now + Duration::from_millis(20), 2);
+        assert_eq!(wheel.len(), 3);
+        assert_eq!(wheel.next_deadline(), Some(now + Duration::from_millis(10)));
+
+        let mut expired = Vec::new();
+        wheel.expire(now + Duration::from_millis(5), &mut expired);
+        assert!(expired.is_empty());
+
+        wheel.expire(now + Duration::from_millis(25), &mut expired);
+        expired.sort_unstable();
+        assert_eq!(expired, [1, 2]);
+        assert_eq!(wheel.next_deadline(), Some(now + Duration::from_millis(30)));
+
+        expired.clear();
+        wheel.expire(now + Duration::from_millis(30), &mut expired);
+        assert_eq!(expired, [3]);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_multiple_turns() {
+        // One turn of the wheel is 40ms
+        let mut wheel = TimerWheel::new(4, Duration::from_millis(10));
+        let now = Duration::from_secs(100);
+        wheel.add(now + Duration::from_millis(15), "near");
+        wheel.add(now + Duration::from_millis(55), "far");
+        // Already expired when it is added
+        wheel.add(now, "past");
+
+        let mut expired = Vec::new();
+        wheel.expire(now + Duration::from_millis(20), &mut expired);
+        expired.sort_unstable();
+        assert_eq!(expired, ["near", "past"]);
+
+        // The slot of the far timer is visited, but its deadline hasn't been reached yet
+        expired.clear();
+        wheel.expire(now + Duration::from_millis(50), &mut expired);
+        assert!(expired.is_empty());
+
+        // More than a full turn elapses between the calls
+        wheel.expire(now + Duration::from_millis(200), &mut expired);
+        assert_eq!(expired, ["far"]);
+        assert!(wheel.is_empty());
+    }
+}
@@ -1,12 +1,24 @@
 use crate::error::*;
-use smol::net::{Ipv6Addr, UdpSocket};
+use log::error;
+use smol::{
+    future,
+    net::{Ipv6Addr, SocketAddr},
+    Async,
+};
+use std::{collections::VecDeque, io::ErrorKind, net::UdpSocket, sync::Mutex};
 
-use super::network::{Address, NetworkInterface};
+use super::network::{Address, NetworkInterface, Readiness};
 
-// We could get rid of the smol here, but keeping it around in case we have to process
-// any other events in this thread's context
+// The most datagrams that can wait for the socket to be writable. Beyond that, they are dropped
+// and left for MRP to retransmit.
+const MAX_PENDING_SENDS: usize = 16;
+
+// The socket is registered with smol, so that the event loop can wait on it along with its
+// timers and queue
 pub struct UdpListener {
-    socket: UdpSocket,
+    socket: Async<UdpSocket>,
+    // The datagrams that couldn't be sent without blocking, in order
+    pending: Mutex<VecDeque<(Vec<u8>, SocketAddr)>>,
 }
 
 // Currently matches with the one in connectedhomeip repo
@@ -17,24 +29,126 @@ pub const MATTER_PORT: u16 = 5540;
 
 impl UdpListener {
     pub fn new() -> Result<UdpListener, Error> {
+        UdpListener::new_with(SocketAddr::from((Ipv6Addr::UNSPECIFIED, MATTER_PORT)))
+    }
+
+    /// Listen on the given address and port
+    pub fn new_with(addr: SocketAddr) -> Result<UdpListener, Error> {
         Ok(UdpListener {
-            socket: smol::block_on(UdpSocket::bind((Ipv6Addr::UNSPECIFIED, MATTER_PORT)))?,
+            socket: Async::<UdpSocket>::bind(addr)?,
+            pending: Mutex::new(VecDeque::new()),
         })
     }
+
+    // Send the datagrams that are waiting, without blocking
+    //
+    // Returns whether all of them were sent
+    fn send_pending(&self) -> Result<bool, Error> {
+        let mut pending = self.pending.lock()?;
+        while let Some((buf, addr)) = pending.front() {
+            match self.socket.get_ref().send_to(buf, *addr) {
+                Ok(_) => (),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => error!("Error sending to {}: {:?}", addr, e),
+            }
+            pending.pop_front();
+        }
+        Ok(true)
+    }
 }
 
 impl NetworkInterface for UdpListener {
     fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error> {
-        let (size, addr) = smol::block_on(self.socket.recv_from(in_buf)).map_err(|e| {
-            println!("Error on the network: {:?}", e);
+        let (size, addr) = self.socket.get_ref().recv_from(in_buf).map_err(|e| {
+            // Nothing to receive, another interface of the Session Manager is the one that is
+            // readable
+            if e.kind() != ErrorKind::WouldBlock {
+                error!("Error on the network: {:?}", e);
+            }
             Error::Network
         })?;
         Ok((size, Address::Udp(addr)))
     }
 
     fn send(&self, out_buf: &[u8], addr: Address) -> Result<usize, Error> {
-        match addr {
-            Address::Udp(addr) => Ok(smol::block_on(self.socket.send_to(out_buf, addr))?),
+        let addr = match addr {
+            Address::Udp(addr) => addr,
+            Address::Tcp(_) => return Err(Error::InvalidPeerAddr),
+        };
+        let mut pending = self.pending.lock()?;
+        // Don't overtake the datagrams that are already waiting
+        if pending.is_empty() {
+            match self.socket.get_ref().send_to(out_buf, addr) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                result => return Ok(result?),
+            }
+        }
+        if pending.len() >= MAX_PENDING_SENDS {
+            error!(
+                "Too many datagrams waiting to be sent, dropping the one to {}",
+                addr
+            );
+            return Err(Error::NoSpace);
         }
+        pending.push_back((out_buf.to_vec(), addr));
+        Ok(out_buf.len())
+    }
+
+    // The datagrams that are waiting get sent as the socket becomes writable, while the event
+    // loop waits for something to receive
+    fn readable(&self) -> Readiness<'_> {
+        let readable = async move {
+            self.socket.readable().await.map_err(|e| {
+                error!("Error on the network: {:?}", e);
+                Error::Network
+            })
+        };
+        let flushed = async move {
+            while !self.send_pending()? {
+                self.socket.writable().await?;
+            }
+            future::pending().await
+        };
+        Box::pin(future::or(readable, flushed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UdpListener;
+    use crate::transport::network::{Address, NetworkInterface};
+    use smol::future;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    fn listener() -> UdpListener {
+        UdpListener::new_with(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap()
+    }
+
+    fn recv(iface: &UdpListener) -> Vec<u8> {
+        smol::block_on(iface.readable()).unwrap();
+        let mut buf = [0u8; 16];
+        let (len, _) = iface.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn test_pending_sends() {
+        let a = listener();
+        let b = listener();
+        let b_addr = b.socket.get_ref().local_addr().unwrap();
+
+        // A datagram that couldn't be sent right away, the next ones wait behind it
+        a.pending.lock().unwrap().push_back((vec![1], b_addr));
+        assert_eq!(a.send(&[2, 3], Address::Udp(b_addr)), Ok(2));
+        assert_eq!(a.pending.lock().unwrap().len(), 2);
+
+        // They are sent while waiting for something to receive
+        assert!(smol::block_on(future::poll_once(a.readable())).is_none());
+        assert!(a.pending.lock().unwrap().is_empty());
+        assert_eq!(recv(&b), [1]);
+        assert_eq!(recv(&b), [2, 3]);
+
+        assert_eq!(a.send(&[4], Address::Udp(b_addr)), Ok(1));
+        assert_eq!(recv(&b), [4]);
     }
 }
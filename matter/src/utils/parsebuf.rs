@@ -117,7 +117,7 @@ mod tests {
             Ok(_) => panic!("This should have returned error"),
             Err(_) => (),
         }
-        assert_eq!(buf.as_slice(), []);
+        assert_eq!(buf.as_slice(), [0u8; 0]);
     }
 
     #[test]
@@ -133,7 +133,7 @@ mod tests {
         assert_eq!(buf.as_borrow_slice(), [0xa, 0xb]);
 
         assert_eq!(buf.tail(2).unwrap(), [0xa, 0xb]);
-        assert_eq!(buf.as_slice(), []);
+        assert_eq!(buf.as_slice(), [0u8; 0]);
     }
 
     #[test]
@@ -156,7 +156,7 @@ mod tests {
         let mut test_slice: [u8; 11] = [0x01, 65, 0, 0xbe, 0xba, 0xfe, 0xca, 0xa, 0xb, 0xc, 0xd];
         let mut buf = ParseBuf::new(&mut test_slice, 11);
 
-        assert_eq!(buf.parsed_as_slice(), []);
+        assert_eq!(buf.parsed_as_slice(), [0u8; 0]);
         assert_eq!(buf.le_u8().unwrap(), 0x1);
         assert_eq!(buf.le_u16().unwrap(), 65);
         assert_eq!(buf.le_u32().unwrap(), 0xcafebabe);
@@ -127,6 +127,26 @@ impl<'a> WriteBuf<'a> {
         })
     }
 
+    pub fn le_i16(&mut self, data: i16) -> Result<(), Error> {
+        self.le_u16(data as u16)
+    }
+
+    pub fn le_i32(&mut self, data: i32) -> Result<(), Error> {
+        self.le_u32(data as u32)
+    }
+
+    pub fn le_i64(&mut self, data: i64) -> Result<(), Error> {
+        self.le_u64(data as u64)
+    }
+
+    pub fn le_f32(&mut self, data: f32) -> Result<(), Error> {
+        self.le_u32(data.to_bits())
+    }
+
+    pub fn le_f64(&mut self, data: f64) -> Result<(), Error> {
+        self.le_u64(data.to_bits())
+    }
+
     pub fn le_uint(&mut self, nbytes: usize, data: u64) -> Result<(), Error> {
         self.append_with(nbytes, |x| {
             LittleEndian::write_uint(&mut x.buf[x.end..], data, nbytes);
@@ -0,0 +1,297 @@
+use matter::{
+    core::{CommissioningData, MatterBuilder},
+    data_model::{
+        cluster_basic_information::BasicInfoConfig,
+        sdm::dev_att::{DataType, DevAttDataFetcher},
+    },
+    error::Error,
+    mdns::{MdnsBackend, MdnsService},
+    transport::proto_demux::{HandleProto, ProtoCtx, ResponseRequired},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+struct DummyDevAtt {}
+impl DevAttDataFetcher for DummyDevAtt {
+    fn get_devatt_data(&self, _data_type: DataType, _data: &mut [u8]) -> Result<usize, Error> {
+        Ok(2)
+    }
+}
+
+// Records the registration type and port of the published services
+struct RecordingMdns {
+    published: Arc<Mutex<Vec<(String, u16)>>>,
+}
+
+impl MdnsBackend for RecordingMdns {
+    fn publish(
+        &self,
+        _name: &str,
+        regtype: &str,
+        port: u16,
+        _txt_kvs: &[[&str; 2]],
+    ) -> Result<MdnsService, Error> {
+        self.published
+            .lock()
+            .unwrap()
+            .push((regtype.to_owned(), port));
+        Ok(Box::new(()))
+    }
+}
+
+struct DummyProto {
+    proto_id: usize,
+}
+
+impl HandleProto for DummyProto {
+    fn handle_proto_id(&mut self, _proto_ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        Ok(ResponseRequired::No)
+    }
+
+    fn get_proto_id(&self) -> usize {
+        self.proto_id
+    }
+}
+
+fn builder() -> MatterBuilder {
+    let dev_det = BasicInfoConfig {
+        vid: 10,
+        pid: 11,
+        hw_ver: 12,
+        sw_ver: 13,
+        ..Default::default()
+    };
+    let comm_data = CommissioningData {
+        passwd: 123456,
+        discriminator: 250,
+        ..Default::default()
+    };
+    MatterBuilder::new(dev_det, Box::new(DummyDevAtt {}), comm_data)
+        .bind_addr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+// A storage location that isn't shared with the other tests
+fn storage_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("matter_builder_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+/// The port, storage location, mDNS backend and extra protocols are all used
+fn test_builder_config() {
+    let _ = env_logger::try_init();
+    let published = Arc::new(Mutex::new(Vec::new()));
+    let dir = storage_dir("config");
+
+    let matter = builder()
+        .port(15541)
+        .storage_dir(&dir)
+        .mdns_backend(Box::new(RecordingMdns {
+            published: published.clone(),
+        }))
+        .protocol(Box::new(DummyProto { proto_id: 2 }))
+        .build()
+        .unwrap();
+
+    // Nothing is commissioned in the new storage location, so the device is commissionable
+    assert!(dir.is_dir());
+    assert!(matter.get_fabric_mgr().is_empty());
+    assert!(published
+        .lock()
+        .unwrap()
+        .contains(&("_matterc._udp,_S0,_L250".to_owned(), 15541)));
+
+    let dm = matter.get_data_model();
+    assert!(dm.node.read().unwrap().get_endpoint(0).is_ok());
+}
+
+#[test]
+/// The root endpoint is left to the application
+fn test_builder_skip_root_node() {
+    let _ = env_logger::try_init();
+    let matter = builder()
+        .port(15542)
+        .storage_dir(storage_dir("skip_root"))
+        .skip_root_node_defaults()
+        .build()
+        .unwrap();
+
+    let dm = matter.get_data_model();
+    assert!(dm.node.read().unwrap().get_endpoint(0).is_err());
+}
+
+#[test]
+/// Unusable settings are reported as errors
+fn test_builder_errors() {
+    let _ = env_logger::try_init();
+    assert_eq!(
+        builder()
+            .port(15543)
+            .storage_dir(storage_dir("pbkdf"))
+            .pbkdf_iterations(10)
+            .build()
+            .err(),
+        Some(Error::Invalid)
+    );
+
+    // The Secure Channel already has protocol ID 0
+    assert_eq!(
+        builder()
+            .port(15544)
+            .storage_dir(storage_dir("protocol"))
+            .protocol(Box::new(DummyProto { proto_id: 0 }))
+            .build()
+            .err(),
+        Some(Error::Invalid)
+    );
+}
+
+#[test]
+/// Several Matter objects can exist side by side, without sharing any state
+fn test_builder_instances() {
+    let _ = env_logger::try_init();
+    let published_1 = Arc::new(Mutex::new(Vec::new()));
+    let published_2 = Arc::new(Mutex::new(Vec::new()));
+
+    let _matter_1 = builder()
+        .port(15545)
+        .storage_dir(storage_dir("instance_1"))
+        .mdns_backend(Box::new(RecordingMdns {
+            published: published_1.clone(),
+        }))
+        .build()
+        .unwrap();
+    let _matter_2 = builder()
+        .port(15546)
+        .storage_dir(storage_dir("instance_2"))
+        .mdns_backend(Box::new(RecordingMdns {
+            published: published_2.clone(),
+        }))
+        .build()
+        .unwrap();
+
+    // Each object publishes its own commissionable service, with its own backend
+    let service = "_matterc._udp,_S0,_L250".to_owned();
+    assert_eq!(*published_1.lock().unwrap(), [(service.clone(), 15545)]);
+    assert_eq!(*published_2.lock().unwrap(), [(service, 15546)]);
+}
+
+#[test]
+/// The daemon returns once it is stopped through its handle, from another thread or from a
+/// scheduled callback
+fn test_shutdown_handle() {
+    let _ = env_logger::try_init();
+    let mut matter = builder()
+        .port(15547)
+        .storage_dir(storage_dir("shutdown"))
+        .build()
+        .unwrap();
+
+    let handle = matter.handle();
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        handle.shutdown().unwrap();
+    });
+    matter.start_daemon().unwrap();
+    thread.join().unwrap();
+
+    // The timers run even though no packets are received
+    let fired = Arc::new(AtomicBool::new(false));
+    let fired_clone = fired.clone();
+    let handle = matter.handle();
+    let shutdown_handle = handle.clone();
+    handle
+        .schedule(Duration::from_millis(20), move || {
+            fired_clone.store(true, Ordering::SeqCst);
+            shutdown_handle.shutdown().unwrap();
+        })
+        .unwrap();
+    let start = Instant::now();
+    matter.start_daemon().unwrap();
+    assert!(fired.load(Ordering::SeqCst));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+/// Callbacks can schedule more work than the queue to the daemon used to hold, without blocking
+/// the daemon that runs them
+fn test_schedule_from_callback() {
+    const CALLBACKS: usize = 16;
+
+    let _ = env_logger::try_init();
+    let mut matter = builder()
+        .port(15551)
+        .storage_dir(storage_dir("schedule_from_callback"))
+        .build()
+        .unwrap();
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let handle = matter.handle();
+    let count_clone = count.clone();
+    let inner_handle = handle.clone();
+    handle
+        .schedule(Duration::ZERO, move || {
+            for _ in 0..CALLBACKS {
+                let count = count_clone.clone();
+                let shutdown_handle = inner_handle.clone();
+                inner_handle
+                    .schedule(Duration::ZERO, move || {
+                        if count.fetch_add(1, Ordering::SeqCst) + 1 == CALLBACKS {
+                            shutdown_handle.shutdown().unwrap();
+                        }
+                    })
+                    .unwrap();
+            }
+        })
+        .unwrap();
+    matter.start_daemon().unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), CALLBACKS);
+}
+
+#[test]
+/// The daemon returns once the stop future completes
+fn test_run_until() {
+    let _ = env_logger::try_init();
+    let mut matter = builder()
+        .port(15548)
+        .storage_dir(storage_dir("run_until"))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    matter
+        .run_until(async {
+            smol::Timer::after(Duration::from_millis(30)).await;
+        })
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+/// The daemon can be awaited on along with other futures, on the same thread
+fn test_run_async() {
+    let _ = env_logger::try_init();
+    let mut matter = builder()
+        .port(15549)
+        .storage_dir(storage_dir("run_async"))
+        .build()
+        .unwrap();
+
+    let handle = matter.handle();
+    let (result, ()) = smol::block_on(smol::future::zip(
+        matter.run(smol::future::pending()),
+        async {
+            smol::Timer::after(Duration::from_millis(30)).await;
+            handle.shutdown_async().await.unwrap();
+        },
+    ));
+    assert_eq!(result, Ok(()));
+}
@@ -2,8 +2,8 @@ use std::sync::{Arc, Mutex, Once};
 
 use matter::{
     data_model::objects::{
-        Access, AttrDetails, AttrValue, Attribute, Cluster, ClusterType, EncodeValue, Encoder,
-        Quality,
+        Access, AttrDetails, AttrValue, Attribute, Cluster, ClusterType, Constraint, EncodeValue,
+        Encoder, Quality,
     },
     error::Error,
     interaction_model::{
@@ -51,6 +51,8 @@ impl TestChecker {
 }
 
 pub const WRITE_LIST_MAX: usize = 5;
+// Less than what the TestChecker can hold, so that the constraint is hit first
+pub const ATTR_WRITE_LIST_MAX_LEN: usize = 4;
 pub struct EchoCluster {
     pub base: Cluster,
     pub multiplier: u8,
@@ -63,10 +65,14 @@ pub enum Attributes {
     AttWrite = 2,
     AttCustom = 3,
     AttWriteList = 4,
+    AttWriteRange = 5,
+    AttWriteEnum = 6,
 }
 
 pub const ATTR_CUSTOM_VALUE: u32 = 0xcafebeef;
 pub const ATTR_WRITE_DEFAULT_VALUE: u16 = 0xcafe;
+pub const ATTR_WRITE_RANGE_MAX: i64 = 100;
+pub const ATTR_WRITE_ENUM_VALUES: [u64; 3] = [0, 1, 4];
 
 impl ClusterType for EchoCluster {
     fn base(&self) -> &Cluster {
@@ -110,6 +116,17 @@ impl ClusterType for EchoCluster {
         }
     }
 
+    fn list_len(&self, attr_id: u16) -> Option<usize> {
+        match num::FromPrimitive::from_u16(attr_id) {
+            Some(Attributes::AttWriteList) => {
+                let tc_handle = TestChecker::get().unwrap();
+                let tc = tc_handle.lock().unwrap();
+                Some(tc.write_list.iter().flatten().count())
+            }
+            _ => None,
+        }
+    }
+
     fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
         let cmd = cmd_req
             .cmd
@@ -178,12 +195,33 @@ impl EchoCluster {
             Access::READ | Access::NEED_VIEW,
             Quality::NONE,
         )?)?;
-        c.base.add_attribute(Attribute::new(
-            Attributes::AttWriteList as u16,
-            AttrValue::Custom,
-            Access::WRITE | Access::NEED_ADMIN,
-            Quality::NONE,
-        )?)?;
+        c.base.add_attribute(
+            Attribute::new(
+                Attributes::AttWriteList as u16,
+                AttrValue::Custom,
+                Access::WRITE | Access::NEED_ADMIN,
+                Quality::NONE,
+            )?
+            .with_constraint(Constraint::MaxListLen(ATTR_WRITE_LIST_MAX_LEN)),
+        )?;
+        c.base.add_attribute(
+            Attribute::new(
+                Attributes::AttWriteRange as u16,
+                AttrValue::Uint8(0),
+                Access::WRITE | Access::NEED_ADMIN,
+                Quality::NONE,
+            )?
+            .with_constraint(Constraint::Range(0, ATTR_WRITE_RANGE_MAX)),
+        )?;
+        c.base.add_attribute(
+            Attribute::new(
+                Attributes::AttWriteEnum as u16,
+                AttrValue::Uint8(0),
+                Access::WRITE | Access::NEED_ADMIN,
+                Quality::NONE,
+            )?
+            .with_constraint(Constraint::Enum(&ATTR_WRITE_ENUM_VALUES)),
+        )?;
         Ok(c)
     }
 
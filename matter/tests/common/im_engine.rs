@@ -1,5 +1,4 @@
 use crate::common::echo_cluster;
-use boxslab::Slab;
 use matter::{
     acl::{AclEntry, AclMgr, AuthMode},
     data_model::{
@@ -12,13 +11,15 @@ use matter::{
     error::Error,
     fabric::FabricMgr,
     interaction_model::{core::OpCode, messages::ib::CmdPath, messages::msg, InteractionModel},
+    limits::Limits,
+    mdns::Mdns,
+    sys::Psm,
     tlv::{TLVWriter, TagType, ToTLV},
     transport::packet::Packet,
     transport::proto_demux::HandleProto,
     transport::{
         exchange::{self, Exchange, ExchangeCtx},
         network::Address,
-        packet::PacketPool,
         proto_demux::ProtoCtx,
         session::{CloneData, SessionMgr, SessionMode},
     },
@@ -26,7 +27,11 @@ use matter::{
 };
 use std::{
     net::{Ipv4Addr, SocketAddr},
-    sync::Arc,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 pub struct DummyDevAtt {}
@@ -41,6 +46,15 @@ pub struct ImEngine {
     pub dm: DataModel,
     pub acl_mgr: Arc<AclMgr>,
     pub im: Box<InteractionModel>,
+    // Removed when the engine is dropped
+    psm_dir: PathBuf,
+}
+
+// The tests, and the test binaries, run in parallel, each engine gets a storage directory of its own
+fn psm_dir() -> PathBuf {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("matter_im_engine_{}_{}", std::process::id(), id))
 }
 
 pub struct ImInput<'a> {
@@ -64,6 +78,12 @@ impl<'a> ImInput<'a> {
     }
 }
 
+impl Drop for ImEngine {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.psm_dir);
+    }
+}
+
 impl ImEngine {
     /// Create the interaction model engine
     pub fn new() -> Self {
@@ -72,16 +92,30 @@ impl ImEngine {
             pid: 11,
             hw_ver: 12,
             sw_ver: 13,
+            ..Default::default()
         };
         let dev_att = Box::new(DummyDevAtt {});
-        let fabric_mgr = Arc::new(FabricMgr::new().unwrap());
-        let acl_mgr = Arc::new(AclMgr::new_with(false).unwrap());
+        let psm_dir = psm_dir();
+        let _ = std::fs::remove_dir_all(&psm_dir);
+        let psm = Psm::new_at(&psm_dir).unwrap();
+        let fabric_mgr =
+            Arc::new(FabricMgr::new(Arc::new(Mutex::new(psm)), Arc::new(Mdns::new())).unwrap());
+        let acl_mgr = Arc::new(AclMgr::new_with(None).unwrap());
         acl_mgr.erase_all();
         let mut default_acl = AclEntry::new(1, Privilege::ADMIN, AuthMode::Case);
         // Only allow the standard peer node id of the IM Engine
         default_acl.add_subject(IM_ENGINE_PEER_ID).unwrap();
         acl_mgr.add(default_acl).unwrap();
-        let dm = DataModel::new(dev_det, dev_att, fabric_mgr.clone(), acl_mgr.clone()).unwrap();
+        // Keep the tests independent of any values persisted by earlier runs
+        let dm = DataModel::new_with(
+            dev_det,
+            dev_att,
+            fabric_mgr.clone(),
+            acl_mgr.clone(),
+            None,
+            &Limits::default(),
+        )
+        .unwrap();
 
         {
             let mut d = dm.node.write().unwrap();
@@ -94,7 +128,12 @@ impl ImEngine {
 
         let im = Box::new(InteractionModel::new(Box::new(dm.clone())));
 
-        Self { dm, acl_mgr, im }
+        Self {
+            dm,
+            acl_mgr,
+            im,
+            psm_dir,
+        }
     }
 
     /// Run a transaction through the interaction model engine
@@ -115,13 +154,14 @@ impl ImEngine {
             SessionMode::Case(1),
         );
         let sess_idx = sess_mgr.clone_session(&clone_data).unwrap();
+        let packet_pool = sess_mgr.packet_pool().clone();
         let sess = sess_mgr.get_session_handle(sess_idx);
         let exch_ctx = ExchangeCtx {
             exch: &mut exch,
             sess,
         };
-        let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
-        let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
+        let mut rx = Packet::new_rx(&packet_pool).unwrap();
+        let tx = Packet::new_tx(&packet_pool).unwrap();
         // Create fake rx packet
         rx.set_proto_id(0x01);
         rx.set_proto_opcode(input.action as u8);
@@ -144,7 +184,7 @@ pub fn im_engine(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataMo
     let mut engine = ImEngine::new();
     let input = ImInput::new(action, data_in);
     let output_len = engine.process(&input, data_out);
-    (engine.dm, output_len)
+    (engine.dm.clone(), output_len)
 }
 
 pub struct TestData<'a, 'b> {
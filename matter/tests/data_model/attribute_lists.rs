@@ -146,4 +146,50 @@ fn attr_list_ops() {
         let tc = tc_handle.lock().unwrap();
         assert_eq!([None, None, None, None, None], tc.write_list);
     }
+
+    // Test 7: Overwrite Operation - more entries than the MaxListLen constraint allows
+    let overwrite_val: [u32; 5] = [20, 21, 22, 23, 24];
+    let input = &[AttrData::new(
+        None,
+        att_path,
+        EncodeValue::Value(&overwrite_val),
+    )];
+    let expected = &[AttrStatus::new(&att_data, IMStatusCode::ConstraintError, 0)];
+    let _ = handle_write_reqs(input, expected);
+
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!([None, None, None, None, None], tc.write_list);
+    }
+
+    // Test 8: Add Operation - up to the MaxListLen constraint, and no further
+    let overwrite_val: [u32; 4] = [20, 21, 22, 23];
+    let input = &[AttrData::new(
+        None,
+        att_path,
+        EncodeValue::Value(&overwrite_val),
+    )];
+    let expected = &[AttrStatus::new(&att_data, IMStatusCode::Sucess, 0)];
+    let _ = handle_write_reqs(input, expected);
+
+    for list_index in [None, Some(Nullable::Null)] {
+        att_path.list_index = list_index;
+        let input = &[AttrData::new(None, att_path, EncodeValue::Value(&val0))];
+        let expected = &[AttrStatus::new(&att_data, IMStatusCode::ConstraintError, 0)];
+        let _ = handle_write_reqs(input, expected);
+    }
+
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!(
+            [Some(20), Some(21), Some(22), Some(23), None],
+            tc.write_list
+        );
+    }
+
+    // Clean up for any other users of the TestChecker
+    att_path.list_index = None;
+    let input = &[AttrData::new(None, att_path, delete_all)];
+    let expected = &[AttrStatus::new(&att_data, IMStatusCode::Sucess, 0)];
+    let _ = handle_write_reqs(input, expected);
 }
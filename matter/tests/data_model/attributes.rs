@@ -2,7 +2,7 @@ use matter::{
     data_model::{
         cluster_on_off,
         core::DataModel,
-        objects::{AttrValue, EncodeValue, GlobalElements},
+        objects::{AttrChange, AttrValue, EncodeValue, GlobalElements},
     },
     interaction_model::{
         core::{IMStatusCode, OpCode},
@@ -18,7 +18,11 @@ use matter::{
 
 use crate::{
     attr_data, attr_status,
-    common::{attributes::*, echo_cluster, im_engine::im_engine},
+    common::{
+        attributes::*,
+        echo_cluster,
+        im_engine::{im_engine, ImEngine, ImInput},
+    },
 };
 
 fn handle_read_reqs(input: &[AttrPath], expected: &[AttrResp]) {
@@ -329,6 +333,31 @@ fn test_read_wc_endpoint_wc_attribute() {
     handle_read_reqs(input, expected);
 }
 
+#[test]
+fn test_read_accepted_commands() {
+    // 1 Attr Read Request
+    // - AcceptedCommandList of the On/Off cluster, generated from its command handlers
+    let _ = env_logger::try_init();
+    let accepted_cmds = GenericPath::new(
+        Some(1),
+        Some(cluster_on_off::ID),
+        Some(GlobalElements::AcceptedCmdList as u32),
+    );
+    let input = &[AttrPath::new(&accepted_cmds)];
+
+    let mut buf = [0u8; 100];
+    let cmd_list_tlvs = get_tlvs(
+        &mut buf,
+        &[
+            cluster_on_off::Commands::Off as u16,
+            cluster_on_off::Commands::On as u16,
+            cluster_on_off::Commands::Toggle as u16,
+        ],
+    );
+    let expected = &[attr_data!(accepted_cmds, cmd_list_tlvs.get_element_type())];
+    handle_read_reqs(input, expected);
+}
+
 #[test]
 fn test_write_success() {
     // 2 Attr Write Request
@@ -547,3 +576,131 @@ fn test_write_unsupported_fields() {
         .unwrap()
     );
 }
+
+#[test]
+fn test_write_constraints() {
+    // 4 writes on endpoint 0
+    // - AttWriteRange within range - Success
+    // - AttWriteRange out of range - ConstraintError
+    // - AttWriteEnum with a valid value - Success
+    // - AttWriteEnum with an unknown value - InvalidValue
+    let _ = env_logger::try_init();
+    let in_range = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, echo_cluster::ATTR_WRITE_RANGE_MAX as u8);
+    };
+    let out_of_range = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, echo_cluster::ATTR_WRITE_RANGE_MAX as u8 + 1);
+    };
+    let valid_enum = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, 4);
+    };
+    let invalid_enum = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, 3);
+    };
+
+    let range_att = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::AttWriteRange as u32),
+    );
+    let enum_att = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::AttWriteEnum as u32),
+    );
+
+    let input = &[
+        AttrData::new(
+            None,
+            AttrPath::new(&range_att),
+            EncodeValue::Closure(&in_range),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&range_att),
+            EncodeValue::Closure(&out_of_range),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&enum_att),
+            EncodeValue::Closure(&valid_enum),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&enum_att),
+            EncodeValue::Closure(&invalid_enum),
+        ),
+    ];
+    let expected = &[
+        AttrStatus::new(&range_att, IMStatusCode::Sucess, 0),
+        AttrStatus::new(&range_att, IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&enum_att, IMStatusCode::Sucess, 0),
+        AttrStatus::new(&enum_att, IMStatusCode::InvalidValue, 0),
+    ];
+
+    // The rejected writes must leave the previously written values untouched
+    let dm = handle_write_reqs(input, expected);
+    assert_eq!(
+        AttrValue::Uint8(echo_cluster::ATTR_WRITE_RANGE_MAX as u8),
+        dm.read_attribute_raw(
+            0,
+            echo_cluster::ID,
+            echo_cluster::Attributes::AttWriteRange as u16
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        AttrValue::Uint8(4),
+        dm.read_attribute_raw(
+            0,
+            echo_cluster::ID,
+            echo_cluster::Attributes::AttWriteEnum as u16
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+/// The watchers of the Data Model are notified of the attributes that are written
+fn test_write_watch() {
+    let _ = env_logger::try_init();
+    let mut engine = ImEngine::new();
+    let changes = engine.dm.watch().unwrap();
+
+    let attr_data = |tag, t: &mut TLVWriter| {
+        let _ = t.u16(tag, 20);
+    };
+    let ep1_att = GenericPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::AttWrite as u32),
+    );
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&ep1_att),
+        EncodeValue::Closure(&attr_data),
+    )];
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    WriteReq::new(false, input)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+    let mut out_buf = [0u8; 400];
+    engine.process(
+        &ImInput::new(OpCode::WriteRequest, wb.as_borrow_slice()),
+        &mut out_buf,
+    );
+
+    assert_eq!(
+        smol::block_on(changes.recv()),
+        Ok(AttrChange {
+            endpoint: 1,
+            cluster: echo_cluster::ID,
+            attr: Some(echo_cluster::Attributes::AttWrite as u16),
+        })
+    );
+    assert!(changes.try_recv().is_err());
+}
@@ -0,0 +1,124 @@
+use matter::{
+    data_model::{
+        cluster_on_off,
+        device_types::{DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_ROOT_NODE},
+        objects::{DeviceType, EncodeValue},
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::OpCode,
+        messages::GenericPath,
+        messages::{
+            ib::{AttrData, AttrPath, AttrResp},
+            msg::{ReadReq, ReportDataMsg},
+        },
+    },
+    tlv::{self, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+use crate::common::im_engine::{ImEngine, ImInput};
+
+// Read an attribute of the Descriptor cluster, and hand over the entries of the list to the closure
+fn read_descriptor_list<F>(im: &mut ImEngine, endpoint: u16, attr: descriptor::Attributes, f: F)
+where
+    F: FnMut(TLVElement),
+{
+    let path = GenericPath::new(Some(endpoint), Some(descriptor::ID), Some(attr as u32));
+    let input = &[AttrPath::new(&path)];
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    let read_req = ReadReq::new(true).set_attr_requests(input);
+    read_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let mut out_buf = [0u8; 400];
+    let input = ImInput::new(OpCode::ReadRequest, wb.as_borrow_slice());
+    let out_buf_len = im.process(&input, &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let received = ReportDataMsg::from_tlv(&root).unwrap();
+
+    let report = received
+        .attr_reports
+        .as_ref()
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    match report {
+        AttrResp::Data(AttrData {
+            data: EncodeValue::Tlv(t),
+            ..
+        }) => t.confirm_array().unwrap().enter().unwrap().for_each(f),
+        _ => panic!("Expected data for the attribute"),
+    }
+}
+
+fn read_device_types(im: &mut ImEngine, endpoint: u16) -> Vec<DeviceType> {
+    let mut device_types = Vec::new();
+    read_descriptor_list(im, endpoint, descriptor::Attributes::DeviceTypeList, |e| {
+        device_types.push(DeviceType {
+            dtype: e.find_tag(0).unwrap().u32().unwrap(),
+            drev: e.find_tag(1).unwrap().u16().unwrap(),
+        })
+    });
+    device_types
+}
+
+fn read_u32_list(im: &mut ImEngine, endpoint: u16, attr: descriptor::Attributes) -> Vec<u32> {
+    let mut list = Vec::new();
+    read_descriptor_list(im, endpoint, attr, |e| list.push(e.u32().unwrap()));
+    list
+}
+
+#[test]
+/// The DeviceTypeList lists the device types of each endpoint
+fn test_device_type_list() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+
+    assert_eq!(read_device_types(&mut im, 0), [DEV_TYPE_ROOT_NODE]);
+    assert_eq!(read_device_types(&mut im, 1), [DEV_TYPE_ON_OFF_LIGHT]);
+}
+
+#[test]
+/// The ServerList and ClientList list the server and client clusters of each endpoint
+fn test_server_client_list() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+
+    let server_list = read_u32_list(&mut im, 1, descriptor::Attributes::ServerList);
+    assert!(server_list.contains(&cluster_on_off::ID));
+    assert!(server_list.contains(&descriptor::ID));
+    assert!(read_u32_list(&mut im, 1, descriptor::Attributes::ClientList).is_empty());
+
+    im.dm
+        .node
+        .write()
+        .unwrap()
+        .get_endpoint_mut(1)
+        .unwrap()
+        .add_client_cluster(cluster_on_off::ID)
+        .unwrap();
+    assert_eq!(
+        read_u32_list(&mut im, 1, descriptor::Attributes::ClientList),
+        [cluster_on_off::ID]
+    );
+}
+
+#[test]
+/// The PartsList of the root endpoint lists all the other endpoints
+fn test_root_parts_list() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+
+    let mut parts = Vec::new();
+    read_descriptor_list(&mut im, 0, descriptor::Attributes::PartsList, |e| {
+        parts.push(e.u16().unwrap())
+    });
+    assert_eq!(parts, [1]);
+}
@@ -0,0 +1,173 @@
+use matter::{
+    data_model::{
+        cluster_on_off,
+        device_types::{
+            device_type_add, validate_node, ClusterReq, DeviceTypeDef, Nonconformance,
+            DEV_TYPE_AGGREGATOR, DEV_TYPE_ON_OFF_LIGHT,
+        },
+        objects::{Cluster, ClusterType, DeviceType},
+    },
+    error::Error,
+};
+
+use crate::common::im_engine::ImEngine;
+
+// A cluster with the On/Off cluster's ID, but none of its attributes or commands
+struct BareOnOff {
+    base: Cluster,
+}
+
+impl ClusterType for BareOnOff {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+}
+
+const DEV_TYPE_SAMPLE: DeviceType = DeviceType {
+    dtype: 0xFFF1_0001,
+    drev: 1,
+};
+
+static SAMPLE_DEF: DeviceTypeDef = DeviceTypeDef {
+    device_type: DEV_TYPE_SAMPLE,
+    name: "Sample",
+    server_clusters: &[ClusterReq::new(cluster_on_off::ID)],
+    client_clusters: &[0x0003],
+};
+
+#[test]
+/// Adding a device type records it on the endpoint, and adds its mandatory clusters
+fn test_device_type_add() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    let mut node = im.dm.node.write().unwrap();
+    node.set_max_endpoints(4);
+
+    let endpoint = node.add_endpoint().unwrap() as u16;
+    device_type_add(&mut node, endpoint, DEV_TYPE_ON_OFF_LIGHT).unwrap();
+    let e = node.get_endpoint(endpoint).unwrap();
+    assert_eq!(e.device_types(), [DEV_TYPE_ON_OFF_LIGHT]);
+    assert!(e.get_cluster(cluster_on_off::ID).is_ok());
+
+    assert_eq!(
+        device_type_add(&mut node, endpoint, DEV_TYPE_SAMPLE),
+        Err(Error::Invalid)
+    );
+}
+
+#[test]
+/// Device types that aren't built-in can be registered, and are then added and validated like
+/// the built-in ones
+fn test_register_device_type() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    let mut node = im.dm.node.write().unwrap();
+    node.set_max_endpoints(4);
+    node.register_device_type(&SAMPLE_DEF);
+
+    let endpoint = node.add_endpoint().unwrap() as u16;
+    device_type_add(&mut node, endpoint, DEV_TYPE_SAMPLE).unwrap();
+    let e = node.get_endpoint(endpoint).unwrap();
+    assert_eq!(e.device_types(), [DEV_TYPE_SAMPLE]);
+    assert_eq!(e.client_clusters(), [0x0003]);
+
+    let issues = validate_node(&node);
+    assert!(issues.contains(&Nonconformance::MissingServerCluster {
+        endpoint,
+        dtype: DEV_TYPE_SAMPLE.dtype,
+        cluster: cluster_on_off::ID,
+    }));
+}
+
+#[test]
+/// The root node and the light that come with the crate conform to their device types
+fn test_validate_stock_node() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    let node = im.dm.node.read().unwrap();
+
+    assert_eq!(validate_node(&node), []);
+}
+
+#[test]
+/// Validation reports the mandatory clusters that are missing
+fn test_validate_missing_clusters() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    let mut node = im.dm.node.write().unwrap();
+    node.set_max_endpoints(4);
+
+    let aggregator = node.add_endpoint().unwrap() as u16;
+    device_type_add(&mut node, aggregator, DEV_TYPE_AGGREGATOR).unwrap();
+    let issues = validate_node(&node);
+    // The aggregator only needs the Descriptor cluster
+    assert!(!issues.iter().any(|i| matches!(
+        *i,
+        Nonconformance::MissingServerCluster { endpoint, .. } if endpoint == aggregator
+    )));
+
+    // A light without the On/Off cluster
+    let light = node.add_endpoint().unwrap() as u16;
+    node.get_endpoint_mut(light)
+        .unwrap()
+        .add_device_type(DEV_TYPE_ON_OFF_LIGHT)
+        .unwrap();
+    let issues = validate_node(&node);
+    assert_eq!(
+        issues,
+        [Nonconformance::MissingServerCluster {
+            endpoint: light,
+            dtype: DEV_TYPE_ON_OFF_LIGHT.dtype,
+            cluster: cluster_on_off::ID,
+        }]
+    );
+}
+
+#[test]
+/// Validation reports the mandatory attributes and commands that are missing
+fn test_validate_missing_attributes_commands() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    let mut node = im.dm.node.write().unwrap();
+    node.set_max_endpoints(4);
+
+    let endpoint = node.add_endpoint().unwrap() as u16;
+    node.add_cluster(
+        endpoint as u32,
+        Box::new(BareOnOff {
+            base: Cluster::new(cluster_on_off::ID).unwrap(),
+        }),
+    )
+    .unwrap();
+    node.get_endpoint_mut(endpoint)
+        .unwrap()
+        .add_device_type(DEV_TYPE_ON_OFF_LIGHT)
+        .unwrap();
+
+    let issues = validate_node(&node);
+    assert!(issues.contains(&Nonconformance::MissingAttribute {
+        endpoint,
+        cluster: cluster_on_off::ID,
+        attr: cluster_on_off::Attributes::OnOff as u16,
+    }));
+    for cmd in [
+        cluster_on_off::Commands::Off,
+        cluster_on_off::Commands::On,
+        cluster_on_off::Commands::Toggle,
+    ] {
+        assert!(issues.contains(&Nonconformance::MissingCommand {
+            endpoint,
+            cluster: cluster_on_off::ID,
+            cmd: cmd as u32,
+        }));
+    }
+    // The fully populated light on endpoint 1 has no such issues
+    assert!(!issues.iter().any(|i| matches!(
+        *i,
+        Nonconformance::MissingAttribute { endpoint: 1, .. }
+            | Nonconformance::MissingCommand { endpoint: 1, .. }
+    )));
+}
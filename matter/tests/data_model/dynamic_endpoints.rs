@@ -0,0 +1,304 @@
+use matter::{
+    data_model::{
+        cluster_bridged_device_basic_information::{self as bridged, BridgedDeviceInfo},
+        device_types::{device_type_add_aggregator, device_type_add_bridged_node},
+        objects::{AttrValue, EncodeValue},
+        system_model::descriptor,
+    },
+    error::Error,
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrPath, AttrResp, AttrStatus},
+            msg::{ReadReq, ReportDataMsg, WriteReq},
+        },
+        messages::{msg, GenericPath},
+    },
+    tlv::{self, ElementType, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+use crate::{
+    attr_data, attr_status,
+    common::{
+        attributes::*,
+        im_engine::{ImEngine, ImInput},
+    },
+};
+
+// The IM Engine already has the root endpoint (0) and an on/off light (1)
+const AGGREGATOR: u16 = 2;
+const BRIDGED: u16 = 3;
+
+fn gen_read_reqs_output<'a>(
+    im: &mut ImEngine,
+    input: &[AttrPath],
+    out_buf: &'a mut [u8],
+) -> ReportDataMsg<'a> {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let read_req = ReadReq::new(true).set_attr_requests(input);
+    read_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let input = ImInput::new(OpCode::ReadRequest, wb.as_borrow_slice());
+    let out_buf_len = im.process(&input, out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    ReportDataMsg::from_tlv(&root).unwrap()
+}
+
+fn handle_read_reqs(im: &mut ImEngine, input: &[AttrPath], expected: &[AttrResp]) {
+    let mut out_buf = [0u8; 400];
+    let received = gen_read_reqs_output(im, input, &mut out_buf);
+    assert_attr_report(&received, expected)
+}
+
+fn handle_write_reqs(im: &mut ImEngine, input: &[AttrData], expected: &[AttrStatus]) {
+    let mut buf = [0u8; 400];
+    let mut out_buf = [0u8; 400];
+
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let write_req = WriteReq::new(false, input);
+    write_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let input = ImInput::new(OpCode::WriteRequest, wb.as_borrow_slice());
+    let out_buf_len = im.process(&input, &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+
+    let response_iter = root
+        .find_tag(msg::WriteRespTag::WriteResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .enter()
+        .unwrap();
+    let received: Vec<AttrStatus> = response_iter
+        .map(|r| AttrStatus::from_tlv(&r).unwrap())
+        .collect();
+    assert_eq!(expected, received.as_slice());
+}
+
+// Read the PartsList of the Descriptor cluster on an endpoint
+fn read_parts_list(im: &mut ImEngine, endpoint: u16) -> Vec<u16> {
+    let path = GenericPath::new(
+        Some(endpoint),
+        Some(descriptor::ID),
+        Some(descriptor::Attributes::PartsList as u32),
+    );
+    let mut out_buf = [0u8; 400];
+    let received = gen_read_reqs_output(im, &[AttrPath::new(&path)], &mut out_buf);
+    let report = received
+        .attr_reports
+        .as_ref()
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    match report {
+        AttrResp::Data(AttrData {
+            data: EncodeValue::Tlv(t),
+            ..
+        }) => t
+            .confirm_array()
+            .unwrap()
+            .enter()
+            .unwrap()
+            .map(|e| e.u16().unwrap())
+            .collect(),
+        _ => panic!("Expected the PartsList data"),
+    }
+}
+
+fn read_descriptor_data_ver(im: &ImEngine, endpoint: u16) -> u32 {
+    let node = im.dm.node.read().unwrap();
+    let d = node.get_cluster(endpoint, descriptor::ID).unwrap();
+    d.base().get_dataver()
+}
+
+fn add_bridge(im: &ImEngine) {
+    let mut node = im.dm.node.write().unwrap();
+    node.set_max_endpoints(5);
+    let aggregator = device_type_add_aggregator(&mut node).unwrap();
+    assert_eq!(aggregator, AGGREGATOR as u32);
+    let info = BridgedDeviceInfo {
+        vendor_name: Some("Vendor".to_owned()),
+        product_name: Some("Bulb".to_owned()),
+        node_label: Some("Kitchen".to_owned()),
+        reachable: true,
+        ..Default::default()
+    };
+    let bridged = device_type_add_bridged_node(&mut node, AGGREGATOR, info).unwrap();
+    assert_eq!(bridged, BRIDGED as u32);
+}
+
+#[test]
+/// The PartsList of an endpoint includes its parts, and the parts of those parts
+fn test_parts_list() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+    assert_eq!(read_parts_list(&mut im, 0), [1]);
+
+    add_bridge(&im);
+    assert_eq!(read_parts_list(&mut im, 0), [1, AGGREGATOR, BRIDGED]);
+    assert_eq!(read_parts_list(&mut im, AGGREGATOR), [BRIDGED]);
+    assert!(read_parts_list(&mut im, BRIDGED).is_empty());
+}
+
+#[test]
+/// Adding or removing an endpoint changes the data version of the Descriptor clusters whose
+/// PartsList changed
+fn test_add_remove_endpoint_data_ver() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+    let root_data_ver = read_descriptor_data_ver(&im, 0);
+    let light_data_ver = read_descriptor_data_ver(&im, 1);
+
+    add_bridge(&im);
+    assert_eq!(
+        root_data_ver.wrapping_add(2),
+        read_descriptor_data_ver(&im, 0)
+    );
+    assert_eq!(light_data_ver, read_descriptor_data_ver(&im, 1));
+    let aggregator_data_ver = read_descriptor_data_ver(&im, AGGREGATOR);
+
+    im.dm
+        .node
+        .write()
+        .unwrap()
+        .remove_endpoint(BRIDGED)
+        .unwrap();
+    assert_eq!(
+        root_data_ver.wrapping_add(3),
+        read_descriptor_data_ver(&im, 0)
+    );
+    assert_eq!(
+        aggregator_data_ver.wrapping_add(1),
+        read_descriptor_data_ver(&im, AGGREGATOR)
+    );
+    assert_eq!(read_parts_list(&mut im, 0), [1, AGGREGATOR]);
+
+    // The removed endpoint is no longer reachable
+    let path = GenericPath::new(
+        Some(BRIDGED),
+        Some(bridged::ID),
+        Some(bridged::Attributes::Reachable as u32),
+    );
+    let expected = &[attr_status!(&path, IMStatusCode::UnsupportedEndpoint)];
+    handle_read_reqs(&mut im, &[AttrPath::new(&path)], expected);
+}
+
+#[test]
+/// The root endpoint, and endpoints that still have parts, can't be removed
+fn test_remove_endpoint_errors() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    add_bridge(&im);
+
+    let mut node = im.dm.node.write().unwrap();
+    assert_eq!(node.remove_endpoint(0), Err(Error::Invalid));
+    assert_eq!(node.remove_endpoint(AGGREGATOR), Err(Error::Invalid));
+    assert_eq!(node.remove_endpoint(10), Err(Error::EndpointNotFound));
+    assert_eq!(
+        node.add_endpoint_with_parent(10),
+        Err(Error::EndpointNotFound)
+    );
+
+    // The node is full
+    node.set_max_endpoints(4);
+    assert_eq!(node.add_endpoint(), Err(Error::NoSpace));
+
+    node.remove_endpoint(BRIDGED).unwrap();
+    node.remove_endpoint(AGGREGATOR).unwrap();
+}
+
+#[test]
+/// The IDs of removed endpoints aren't handed out again right away
+fn test_endpoint_ids_not_reused() {
+    let _ = env_logger::try_init();
+    let im = ImEngine::new();
+    add_bridge(&im);
+
+    let mut node = im.dm.node.write().unwrap();
+    node.remove_endpoint(BRIDGED).unwrap();
+    assert_eq!(node.add_endpoint_with_parent(AGGREGATOR), Ok(4));
+}
+
+#[test]
+/// Read and write the attributes of the Bridged Device Basic Information cluster
+fn test_bridged_device_basic_info() {
+    let _ = env_logger::try_init();
+    let mut im = ImEngine::new();
+    add_bridge(&im);
+
+    let path = |attr: bridged::Attributes| {
+        GenericPath::new(Some(BRIDGED), Some(bridged::ID), Some(attr as u32))
+    };
+    let vendor_name = path(bridged::Attributes::VendorName);
+    let reachable = path(bridged::Attributes::Reachable);
+    let node_label = path(bridged::Attributes::NodeLabel);
+    let serial_number = path(bridged::Attributes::SerialNumber);
+    let input = &[
+        AttrPath::new(&vendor_name),
+        AttrPath::new(&reachable),
+        AttrPath::new(&serial_number),
+    ];
+    let expected = &[
+        attr_data!(vendor_name, ElementType::Utf8l(b"Vendor")),
+        attr_data!(reachable, ElementType::True),
+        attr_status!(&serial_number, IMStatusCode::UnsupportedAttribute),
+    ];
+    handle_read_reqs(&mut im, input, expected);
+
+    // The node label can be written, but only up to 32 characters
+    let long_label = [b'a'; 33];
+    let new_label = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, b"Hall");
+    };
+    let too_long_label = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, &long_label);
+    };
+    let input = &[
+        AttrData::new(
+            None,
+            AttrPath::new(&node_label),
+            EncodeValue::Closure(&new_label),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&node_label),
+            EncodeValue::Closure(&too_long_label),
+        ),
+    ];
+    let expected = &[
+        AttrStatus::new(&node_label, IMStatusCode::Sucess, 0),
+        AttrStatus::new(&node_label, IMStatusCode::ConstraintError, 0),
+    ];
+    handle_write_reqs(&mut im, input, expected);
+    let expected = &[attr_data!(node_label, ElementType::Utf8l(b"Hall"))];
+    handle_read_reqs(&mut im, &[AttrPath::new(&node_label)], expected);
+
+    // The device became unreachable
+    im.dm
+        .node
+        .write()
+        .unwrap()
+        .get_cluster_mut(BRIDGED, bridged::ID)
+        .unwrap()
+        .base_mut()
+        .write_attribute_raw(
+            bridged::Attributes::Reachable as u16,
+            AttrValue::Bool(false),
+        )
+        .unwrap();
+    let expected = &[attr_data!(reachable, ElementType::False)];
+    handle_read_reqs(&mut im, &[AttrPath::new(&reachable)], expected);
+}
@@ -0,0 +1,207 @@
+use matter::{
+    data_model::objects::{Access, AttrValue, Attribute, Cluster, ClusterType, Node, Quality},
+    sys::Psm,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+// A cluster id that no real cluster uses, so that the stored keys don't clash
+const TEST_CLUSTER_ID: u32 = 0xFFF1FC10;
+const TEST_ENDPOINT: u16 = 7;
+
+enum Attributes {
+    Persistent = 0,
+    Volatile = 1,
+}
+
+// A storage directory of the test, unique to the process and removed on drop
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Self(dir)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+struct TestCluster {
+    base: Cluster,
+}
+
+impl ClusterType for TestCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+}
+
+fn attr_key(endpoint: u16) -> String {
+    format!(
+        "dm{}_{:x}_{:x}",
+        endpoint,
+        TEST_CLUSTER_ID,
+        Attributes::Persistent as u16
+    )
+}
+
+fn dataver_key(endpoint: u16) -> String {
+    format!("dm{}_{:x}_dv", endpoint, TEST_CLUSTER_ID)
+}
+
+fn new_cluster() -> Cluster {
+    let mut c = Cluster::new(TEST_CLUSTER_ID).unwrap();
+    c.add_attribute(
+        Attribute::new(
+            Attributes::Persistent as u16,
+            AttrValue::Uint16(0),
+            Access::RV,
+            Quality::PERSISTENT,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    c.add_attribute(
+        Attribute::new(
+            Attributes::Volatile as u16,
+            AttrValue::Uint16(0),
+            Access::RV,
+            Quality::NONE,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    c
+}
+
+#[test]
+fn test_persistent_attributes_survive_restart() {
+    let _ = env_logger::try_init();
+    let dir = TestDir::new("matter_persistence");
+    let psm = Psm::new_at(&dir.0).unwrap();
+    let psm = Arc::new(Mutex::new(psm));
+
+    let mut c = new_cluster();
+    c.enable_persistence(TEST_ENDPOINT, psm.clone()).unwrap();
+    c.write_attribute_raw(Attributes::Persistent as u16, AttrValue::Uint16(0x55aa))
+        .unwrap();
+    c.write_attribute_raw(Attributes::Volatile as u16, AttrValue::Uint16(0x1234))
+        .unwrap();
+    let data_ver = c.get_dataver();
+
+    // Simulate a reboot, with the cluster created afresh
+    let mut c = new_cluster();
+    c.enable_persistence(TEST_ENDPOINT, psm.clone()).unwrap();
+    assert_eq!(
+        AttrValue::Uint16(0x55aa),
+        *c.read_attribute_raw(Attributes::Persistent as u16).unwrap()
+    );
+    assert_eq!(
+        AttrValue::Uint16(0),
+        *c.read_attribute_raw(Attributes::Volatile as u16).unwrap()
+    );
+    // The data version moves past the ones that were used before the reboot
+    assert_ne!(data_ver, c.get_dataver());
+    assert_ne!(data_ver.wrapping_add(1), c.get_dataver());
+}
+
+#[test]
+/// Changes to the data version aren't written to the store one by one
+fn test_data_version_reserved() {
+    let _ = env_logger::try_init();
+    let dir = TestDir::new("matter_persistence_dataver");
+    let psm = Psm::new_at(&dir.0).unwrap();
+    let psm = Arc::new(Mutex::new(psm));
+
+    let mut c = new_cluster();
+    c.enable_persistence(TEST_ENDPOINT, psm.clone()).unwrap();
+    let mut reserved = 0;
+    psm.lock()
+        .unwrap()
+        .get_kv_u64(&dataver_key(TEST_ENDPOINT), &mut reserved)
+        .unwrap();
+    let first = c.get_dataver();
+    for i in 0..10 {
+        c.write_attribute_raw(Attributes::Volatile as u16, AttrValue::Uint16(i))
+            .unwrap();
+    }
+    let mut stored = 0;
+    psm.lock()
+        .unwrap()
+        .get_kv_u64(&dataver_key(TEST_ENDPOINT), &mut stored)
+        .unwrap();
+    assert_eq!(reserved, stored);
+
+    // After a reboot, the cluster starts past all the versions used so far
+    let mut c = new_cluster();
+    c.enable_persistence(TEST_ENDPOINT, psm).unwrap();
+    assert_eq!(reserved as u32, c.get_dataver());
+    assert!((reserved as u32).wrapping_sub(first) > 10);
+}
+
+#[test]
+/// Removing an endpoint removes the stored values of its clusters
+fn test_remove_endpoint_removes_stored() {
+    let _ = env_logger::try_init();
+    let dir = TestDir::new("matter_persistence_remove");
+    let psm = Psm::new_at(&dir.0).unwrap();
+    let psm = Arc::new(Mutex::new(psm));
+
+    let mut node = Node::new().unwrap();
+    node.enable_persistence(psm.clone()).unwrap();
+    node.add_endpoint().unwrap();
+    let endpoint = node.add_endpoint().unwrap();
+    node.add_cluster(
+        endpoint,
+        Box::new(TestCluster {
+            base: new_cluster(),
+        }),
+    )
+    .unwrap();
+    let endpoint = endpoint as u16;
+    node.get_cluster_mut(endpoint, TEST_CLUSTER_ID)
+        .unwrap()
+        .base_mut()
+        .write_attribute_raw(Attributes::Persistent as u16, AttrValue::Uint16(0x55aa))
+        .unwrap();
+    let mut buf = Vec::new();
+    let psm_ref = psm.lock().unwrap();
+    psm_ref.get_kv_slice(&attr_key(endpoint), &mut buf).unwrap();
+    drop(psm_ref);
+
+    node.remove_endpoint(endpoint).unwrap();
+    let psm = psm.lock().unwrap();
+    assert!(psm.get_kv_slice(&attr_key(endpoint), &mut buf).is_err());
+    let mut data_ver = 0;
+    assert!(psm
+        .get_kv_u64(&dataver_key(endpoint), &mut data_ver)
+        .is_err());
+}
+
+#[test]
+fn test_corrupted_attribute_keeps_default() {
+    let _ = env_logger::try_init();
+    let dir = TestDir::new("matter_persistence_corrupted");
+    let psm = Psm::new_at(&dir.0).unwrap();
+    // A UTF-8 string, where an unsigned integer is expected
+    psm.set_kv_slice(&attr_key(TEST_ENDPOINT), &[0x0c, 0x01, 0x41])
+        .unwrap();
+    let psm = Arc::new(Mutex::new(psm));
+
+    let mut c = new_cluster();
+    c.enable_persistence(TEST_ENDPOINT, psm).unwrap();
+    assert_eq!(
+        AttrValue::Uint16(0),
+        *c.read_attribute_raw(Attributes::Persistent as u16).unwrap()
+    );
+}
@@ -5,4 +5,8 @@ mod data_model {
     mod attribute_lists;
     mod attributes;
     mod commands;
+    mod descriptor;
+    mod device_types;
+    mod dynamic_endpoints;
+    mod persistence;
 }
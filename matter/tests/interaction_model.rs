@@ -1,4 +1,3 @@
-use boxslab::Slab;
 use matter::error::Error;
 use matter::interaction_model::core::OpCode;
 use matter::interaction_model::messages::msg::InvReq;
@@ -12,7 +11,6 @@ use matter::transport::exchange::Exchange;
 use matter::transport::exchange::ExchangeCtx;
 use matter::transport::network::Address;
 use matter::transport::packet::Packet;
-use matter::transport::packet::PacketPool;
 use matter::transport::proto_demux::HandleProto;
 use matter::transport::proto_demux::ProtoCtx;
 use matter::transport::session::SessionMgr;
@@ -114,13 +112,14 @@ fn handle_data(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataMode
             false,
         )
         .unwrap();
+    let packet_pool = sess_mgr.packet_pool().clone();
     let sess = sess_mgr.get_session_handle(sess_idx);
     let exch_ctx = ExchangeCtx {
         exch: &mut exch,
         sess,
     };
-    let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
-    let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
+    let mut rx = Packet::new_rx(&packet_pool).unwrap();
+    let tx = Packet::new_tx(&packet_pool).unwrap();
     // Create fake rx packet
     rx.set_proto_id(0x01);
     rx.set_proto_opcode(action as u8);
@@ -128,6 +127,7 @@ fn handle_data(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataMode
     let in_data_len = data_in.len();
     let rx_buf = rx.as_borrow_slice();
     rx_buf[..in_data_len].copy_from_slice(data_in);
+    rx.get_parsebuf().unwrap().set_len(in_data_len);
 
     let mut ctx = ProtoCtx::new(exch_ctx, rx, tx);
 
@@ -0,0 +1,263 @@
+use matter::{
+    clock::{Clock, ManualClock, SystemClock},
+    core::{CommissioningData, Matter, MatterBuilder},
+    data_model::{
+        cluster_basic_information::BasicInfoConfig,
+        sdm::dev_att::{DataType, DevAttDataFetcher},
+    },
+    error::Error,
+    mdns::{MdnsBackend, MdnsService},
+    transport::{
+        loopback::LoopbackNetwork,
+        network::{Address, NetworkInterface},
+        proto_demux::{HandleProto, ProtoCtx, ResponseRequired},
+        tcp::TcpListener,
+    },
+};
+use smol::future;
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+struct DummyDevAtt {}
+impl DevAttDataFetcher for DummyDevAtt {
+    fn get_devatt_data(&self, _data_type: DataType, _data: &mut [u8]) -> Result<usize, Error> {
+        Ok(2)
+    }
+}
+
+struct NoMdns {}
+impl MdnsBackend for NoMdns {
+    fn publish(
+        &self,
+        _name: &str,
+        _regtype: &str,
+        _port: u16,
+        _txt_kvs: &[[&str; 2]],
+    ) -> Result<MdnsService, Error> {
+        Ok(Box::new(()))
+    }
+}
+
+// Consumes the messages of a protocol, without ever responding
+struct SilentProto {}
+impl HandleProto for SilentProto {
+    fn handle_proto_id(&mut self, _proto_ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        Ok(ResponseRequired::No)
+    }
+
+    fn get_proto_id(&self) -> usize {
+        SILENT_PROTO_ID as usize
+    }
+}
+
+// Responds with the payload of the messages it receives
+struct EchoProto {}
+impl HandleProto for EchoProto {
+    fn handle_proto_id(&mut self, proto_ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        let payload = proto_ctx.rx.as_borrow_slice().to_vec();
+        proto_ctx.tx.set_proto_id(ECHO_PROTO_ID);
+        proto_ctx
+            .tx
+            .set_proto_opcode(proto_ctx.rx.get_proto_opcode());
+        proto_ctx.tx.get_writebuf()?.append(&payload)?;
+        Ok(ResponseRequired::Yes)
+    }
+
+    fn get_proto_id(&self) -> usize {
+        ECHO_PROTO_ID as usize
+    }
+}
+
+const SILENT_PROTO_ID: u16 = 2;
+const ECHO_PROTO_ID: u16 = 3;
+const PEER_NODE_ID: u64 = 0x1234;
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+// An unencrypted message from the peer, that requires an acknowledgement
+fn reliable_msg(msg_ctr: u32) -> Vec<u8> {
+    proto_msg(SILENT_PROTO_ID, msg_ctr)
+}
+
+// An unencrypted and reliable message from the peer, for the given protocol
+fn proto_msg(proto_id: u16, msg_ctr: u32) -> Vec<u8> {
+    // Message header: source node ID present, unsecured session
+    let mut msg = vec![0x04, 0x00, 0x00, 0x00];
+    msg.extend_from_slice(&msg_ctr.to_le_bytes());
+    msg.extend_from_slice(&PEER_NODE_ID.to_le_bytes());
+    // Protocol header: initiator and reliable, exchange 7
+    msg.extend_from_slice(&[0x05, 0x01, 0x07, 0x00]);
+    msg.extend_from_slice(&proto_id.to_le_bytes());
+    msg
+}
+
+fn builder(network: &LoopbackNetwork, name: &str, clock: Arc<dyn Clock>) -> MatterBuilder {
+    let dev_det = BasicInfoConfig {
+        vid: 10,
+        pid: 11,
+        hw_ver: 12,
+        sw_ver: 13,
+        ..Default::default()
+    };
+    let dir = std::env::temp_dir().join(format!("matter_loopback_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    MatterBuilder::new(
+        dev_det,
+        Box::new(DummyDevAtt {}),
+        CommissioningData::default(),
+    )
+    .network(Box::new(network.interface(addr(2)).unwrap()))
+    .mdns_backend(Box::new(NoMdns {}))
+    .storage_dir(dir)
+    .protocol(Box::new(SilentProto {}))
+    .protocol(Box::new(EchoProto {}))
+    .clock(clock)
+}
+
+fn matter(network: &LoopbackNetwork, name: &str, clock: Arc<dyn Clock>) -> Box<Matter> {
+    builder(network, name, clock).build().unwrap()
+}
+
+// Checks the MRP Standalone Acknowledgement of the message with the given counter
+fn assert_standalone_ack(ack: &[u8], msg_ctr: u32) {
+    // The message header is followed by the destination node ID
+    let proto = &ack[16..];
+    // Acknowledgement flag, MRP Standalone Acknowledgement of the Secure Channel protocol
+    assert_eq!(proto[0] & 0x02, 0x02);
+    assert_eq!(proto[1], 0x10);
+    assert_eq!(&proto[2..4], &[0x07, 0x00]);
+    assert_eq!(&proto[4..6], &[0x00, 0x00]);
+    assert_eq!(&proto[6..10], &msg_ctr.to_le_bytes());
+}
+
+#[test]
+/// A standalone acknowledgement is sent once the MRP timer expires, even though nothing else
+/// is received in the meantime
+fn test_mrp_standalone_ack() {
+    let _ = env_logger::try_init();
+    let network = LoopbackNetwork::new();
+    let peer = network.interface(addr(1)).unwrap();
+    let mut matter = matter(&network, "mrp", Arc::new(SystemClock::new()));
+
+    let handle = matter.handle();
+    let start = Instant::now();
+    let (result, ack) = smol::block_on(future::zip(matter.run(future::pending()), async {
+        peer.send(&reliable_msg(100), Address::Udp(addr(2)))
+            .unwrap();
+        peer.readable().await.unwrap();
+        let mut buf = [0u8; 64];
+        let (len, src) = peer.recv(&mut buf).unwrap();
+        handle.shutdown_async().await.unwrap();
+        assert_eq!(src, Address::Udp(addr(2)));
+        buf[..len].to_vec()
+    }));
+    assert_eq!(result, Ok(()));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    assert_standalone_ack(&ack, 100);
+}
+
+#[test]
+/// The MRP timers follow the clock of the stack, rather than the system time
+fn test_mrp_manual_clock() {
+    let _ = env_logger::try_init();
+    let clock = Arc::new(ManualClock::new());
+    let network = LoopbackNetwork::new_with_clock(clock.clone());
+    let peer = network.interface(addr(1)).unwrap();
+    let mut matter = matter(&network, "mrp_manual_clock", clock.clone());
+
+    let handle = matter.handle();
+    let start = Instant::now();
+    let (result, ack) = smol::block_on(future::zip(matter.run(future::pending()), async {
+        peer.send(&reliable_msg(200), Address::Udp(addr(2)))
+            .unwrap();
+        loop {
+            // Move the clock past the acknowledgement timeout, the daemon notices on its own
+            clock.advance(Duration::from_millis(200));
+            let acked = future::or(
+                async {
+                    peer.readable().await.unwrap();
+                    true
+                },
+                async {
+                    smol::Timer::after(Duration::from_millis(50)).await;
+                    false
+                },
+            );
+            if acked.await {
+                break;
+            }
+        }
+        let mut buf = [0u8; 64];
+        let (len, _) = peer.recv(&mut buf).unwrap();
+        handle.shutdown_async().await.unwrap();
+        buf[..len].to_vec()
+    }));
+    assert_eq!(result, Ok(()));
+    // No need to wait for the timeout in real time
+    assert!(start.elapsed() < Duration::from_millis(200));
+    assert_standalone_ack(&ack, 200);
+}
+
+#[test]
+/// The messages received over TCP are acknowledged over the same connection, alongside the
+/// loopback network
+fn test_mrp_over_tcp() {
+    let _ = env_logger::try_init();
+    let network = LoopbackNetwork::new();
+    let mut matter = builder(&network, "mrp_tcp", Arc::new(SystemClock::new()))
+        .bind_addr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .port(15550)
+        .tcp()
+        .build()
+        .unwrap();
+    let peer = TcpListener::new_with(addr(0)).unwrap();
+
+    let handle = matter.handle();
+    let (result, ack) = smol::block_on(future::zip(matter.run(future::pending()), async {
+        peer.send(&reliable_msg(300), Address::Tcp(addr(15550)))
+            .unwrap();
+        peer.readable().await.unwrap();
+        let mut buf = [0u8; 64];
+        let (len, src) = peer.recv(&mut buf).unwrap();
+        handle.shutdown_async().await.unwrap();
+        assert_eq!(src, Address::Tcp(addr(15550)));
+        buf[..len].to_vec()
+    }));
+    assert_eq!(result, Ok(()));
+    assert_standalone_ack(&ack, 300);
+}
+
+#[test]
+/// The messages over TCP aren't limited to the size of a UDP datagram, in either direction
+fn test_large_msg_over_tcp() {
+    let _ = env_logger::try_init();
+    let network = LoopbackNetwork::new();
+    let mut matter = builder(&network, "large_tcp", Arc::new(SystemClock::new()))
+        .bind_addr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .port(15552)
+        .tcp()
+        .build()
+        .unwrap();
+    let peer = TcpListener::new_with(addr(0)).unwrap();
+    let payload: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+
+    let handle = matter.handle();
+    let (result, resp) = smol::block_on(future::zip(matter.run(future::pending()), async {
+        let mut msg = proto_msg(ECHO_PROTO_ID, 400);
+        msg.extend_from_slice(&payload);
+        peer.send(&msg, Address::Tcp(addr(15552))).unwrap();
+        peer.readable().await.unwrap();
+        let mut buf = vec![0u8; peer.max_msg_size()];
+        let (len, _) = peer.recv(&mut buf).unwrap();
+        handle.shutdown_async().await.unwrap();
+        buf[..len].to_vec()
+    }));
+    assert_eq!(result, Ok(()));
+    assert!(resp.ends_with(&payload));
+}
@@ -0,0 +1,391 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::Lit::{Int, Str};
+use syn::NestedMeta::Meta;
+use syn::{
+    Attribute, DeriveInput, Expr, FnArg, ImplItem, ItemImpl, Meta::List, Meta::NameValue,
+    Meta::Path, MetaList, MetaNameValue, Type,
+};
+
+/// The arguments of the cluster() attribute on a struct
+struct ClusterArgs {
+    id: Expr,
+    attributes: Option<syn::Path>,
+    commands: bool,
+    read: Option<syn::Ident>,
+    write: Option<syn::Ident>,
+    list_len: Option<syn::Ident>,
+}
+
+fn parse_str_as<T: syn::parse::Parse>(litstr: &syn::LitStr) -> T {
+    litstr
+        .parse::<T>()
+        .unwrap_or_else(|_| panic!("Couldn't parse {}", litstr.value()))
+}
+
+/// The key/value pairs and the bare paths in an attribute
+pub(crate) type AttrArgs = (Vec<(String, syn::Lit)>, Vec<String>);
+
+/// Returns the arguments of an attribute like #[name(a = "b", c)]
+pub(crate) fn parse_args(attrs: &[Attribute], name: &str) -> Option<AttrArgs> {
+    for attr in attrs {
+        if !attr.path.is_ident(name) {
+            continue;
+        }
+        let mut pairs = Vec::new();
+        let mut flags = Vec::new();
+        if let List(MetaList { nested, .. }) = attr.parse_meta().unwrap() {
+            for a in nested {
+                match a {
+                    Meta(NameValue(MetaNameValue {
+                        path: key_path,
+                        lit: key_val,
+                        ..
+                    })) => pairs.push((key_path.get_ident().unwrap().to_string(), key_val)),
+                    Meta(Path(p)) => flags.push(p.get_ident().unwrap().to_string()),
+                    _ => panic!("Unsupported argument in {}", name),
+                }
+            }
+        }
+        return Some((pairs, flags));
+    }
+    None
+}
+
+fn parse_cluster_args(ast: &DeriveInput) -> ClusterArgs {
+    let (pairs, flags) =
+        parse_args(&ast.attrs, "cluster").expect("The cluster(id = ...) attribute is required");
+
+    let mut id = None;
+    let mut args = ClusterArgs {
+        id: syn::parse_quote!(0),
+        attributes: None,
+        commands: flags.iter().any(|f| f == "commands"),
+        read: None,
+        write: None,
+        list_len: None,
+    };
+    for (key, val) in pairs {
+        match (key.as_str(), val) {
+            ("id", Int(litint)) => {
+                id = Some(Expr::Lit(syn::ExprLit {
+                    attrs: Vec::new(),
+                    lit: Int(litint),
+                }))
+            }
+            ("id", Str(litstr)) => id = Some(parse_str_as(&litstr)),
+            ("attributes", Str(litstr)) => args.attributes = Some(parse_str_as(&litstr)),
+            ("read", Str(litstr)) => args.read = Some(parse_str_as(&litstr)),
+            ("write", Str(litstr)) => args.write = Some(parse_str_as(&litstr)),
+            ("list_len", Str(litstr)) => args.list_len = Some(parse_str_as(&litstr)),
+            (k, _) => panic!("Unsupported cluster argument {}", k),
+        }
+    }
+    args.id = id.expect("The cluster id is required");
+    args
+}
+
+/// Find the member of type Cluster in the structure
+fn find_base(fields: &syn::FieldsNamed) -> &syn::Ident {
+    fields
+        .named
+        .iter()
+        .find(|f| {
+            if let Type::Path(p) = &f.ty {
+                p.path.segments.last().map(|s| s.ident == "Cluster") == Some(true)
+            } else {
+                false
+            }
+        })
+        .and_then(|f| f.ident.as_ref())
+        .expect("The structure must have a member of type Cluster")
+}
+
+pub fn gen_clustertype(ast: DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let args = parse_cluster_args(&ast);
+
+    let fields = if let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(ref fields),
+        ..
+    }) = ast.data
+    {
+        fields
+    } else {
+        panic!("Derive ClusterType - Only supported Struct for now");
+    };
+    let base = find_base(fields);
+
+    let id = &args.id;
+    let add_attributes = args
+        .attributes
+        .as_ref()
+        .map(|a| quote! { #a::add_to(&mut base)?; });
+    let (add_commands, handle_command) = if args.commands {
+        (
+            Some(quote! { base.set_accepted_commands(Self::ACCEPTED_COMMANDS)?; }),
+            Some(quote! {
+                fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+                    self.dispatch_command(cmd_req)
+                }
+            }),
+        )
+    } else {
+        (None, None)
+    };
+    let read = args.read.as_ref().map(|r| {
+        quote! {
+            fn read_custom_attribute(&self, encoder: &mut dyn Encoder, attr: &AttrDetails) {
+                self.#r(encoder, attr)
+            }
+        }
+    });
+    let write = args.write.as_ref().map(|w| {
+        quote! {
+            fn write_attribute(
+                &mut self,
+                attr: &AttrDetails,
+                data: &TLVElement,
+            ) -> Result<(), IMStatusCode> {
+                self.#w(attr, data)
+            }
+        }
+    });
+    let list_len = args.list_len.as_ref().map(|l| {
+        quote! {
+            fn list_len(&self, attr_id: u16) -> Option<usize> {
+                self.#l(attr_id)
+            }
+        }
+    });
+
+    let expanded = quote! {
+        impl #name {
+            /// Create the base cluster, populated with the declared attributes and the
+            /// accepted commands
+            pub fn new_base() -> Result<Cluster, Error> {
+                let mut base = Cluster::new(#id)?;
+                #add_attributes
+                #add_commands
+                Ok(base)
+            }
+        }
+
+        impl ClusterType for #name {
+            fn base(&self) -> &Cluster {
+                &self.#base
+            }
+
+            fn base_mut(&mut self) -> &mut Cluster {
+                &mut self.#base
+            }
+
+            #read
+
+            #write
+
+            #list_len
+
+            #handle_command
+        }
+    };
+    //    panic!("The generated code is {}", expanded);
+    expanded.into()
+}
+
+/// Convert a string like "READ | WRITE | NEED_ADMIN" to Access::READ | Access::WRITE | ...
+fn parse_flags(ty: &str, value: &str) -> proc_macro2::TokenStream {
+    let ty = format_ident!("{}", ty);
+    let flags = value
+        .split('|')
+        .map(|f| syn::Ident::new(f.trim(), Span::call_site()));
+    quote! { #( #ty::#flags )|* }
+}
+
+pub fn gen_clusterattributes(ast: DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let data_enum = if let syn::Data::Enum(data_enum) = ast.data {
+        data_enum
+    } else {
+        panic!("Derive ClusterAttributes - Only supported Enum");
+    };
+
+    let mut variants = Vec::new();
+    let mut values = Vec::new();
+    let mut accesses = Vec::new();
+    let mut qualities = Vec::new();
+
+    for v in data_enum.variants.iter() {
+        let (pairs, _) = match parse_args(&v.attrs, "attribute") {
+            Some(a) => a,
+            // Attributes without any annotation are not added automatically
+            None => continue,
+        };
+        let mut value = quote! { AttrValue::Custom };
+        let mut access = parse_flags("Access", "RV");
+        let mut quality = parse_flags("Quality", "NONE");
+        for (key, val) in pairs {
+            match (key.as_str(), val) {
+                ("default", Str(litstr)) => {
+                    let e: Expr = parse_str_as(&litstr);
+                    value = quote! { #e };
+                }
+                ("access", Str(litstr)) => access = parse_flags("Access", &litstr.value()),
+                ("quality", Str(litstr)) => quality = parse_flags("Quality", &litstr.value()),
+                (k, _) => panic!("Unsupported attribute argument {}", k),
+            }
+        }
+        variants.push(&v.ident);
+        values.push(value);
+        accesses.push(access);
+        qualities.push(quality);
+    }
+
+    let expanded = quote! {
+        impl #name {
+            /// Add all the annotated attributes to the cluster
+            pub fn add_to(base: &mut Cluster) -> Result<(), Error> {
+                #(
+                    base.add_attribute(Attribute::new(
+                        #name::#variants as u16,
+                        #values,
+                        #accesses,
+                        #qualities,
+                    )?)?;
+                )*
+                Ok(())
+            }
+        }
+    };
+    //    panic!("The generated code is {}", expanded);
+    expanded.into()
+}
+
+/// The type of the argument, if it is of the form '&mut CommandReq'
+fn is_cmd_req(ty: &Type) -> bool {
+    if let Type::Reference(r) = ty {
+        if let Type::Path(p) = r.elem.as_ref() {
+            return p.path.segments.last().map(|s| s.ident == "CommandReq") == Some(true);
+        }
+    }
+    false
+}
+
+pub fn gen_cluster_commands(mut item: ItemImpl) -> TokenStream {
+    let self_ty = &item.self_ty;
+    let mut cmd_ids = Vec::new();
+    let mut handlers = Vec::new();
+
+    for impl_item in item.items.iter_mut() {
+        let method = if let ImplItem::Method(m) = impl_item {
+            m
+        } else {
+            continue;
+        };
+        let (pairs, _) = match parse_args(&method.attrs, "command") {
+            Some(a) => a,
+            None => continue,
+        };
+        // The command attribute is consumed here, so the compiler doesn't trip on it
+        method.attrs.retain(|a| !a.path.is_ident("command"));
+
+        let mut id: Option<Expr> = None;
+        let mut response: Option<Expr> = None;
+        for (key, val) in pairs {
+            match (key.as_str(), val) {
+                ("id", Str(litstr)) => id = Some(parse_str_as(&litstr)),
+                ("response", Str(litstr)) => response = Some(parse_str_as(&litstr)),
+                (k, _) => panic!("Unsupported command argument {}", k),
+            }
+        }
+        let id = id.expect("The command id is required");
+
+        // Arguments after 'self': an optional request, followed by an optional '&mut CommandReq'
+        let mut req_type = None;
+        let mut pass_cmd_req = false;
+        for arg in method.sig.inputs.iter() {
+            if let FnArg::Typed(pat_type) = arg {
+                if is_cmd_req(&pat_type.ty) {
+                    pass_cmd_req = true;
+                } else if req_type.is_none() {
+                    req_type = Some(pat_type.ty.as_ref().clone());
+                } else {
+                    panic!(
+                        "Unexpected argument in command handler {}",
+                        method.sig.ident
+                    );
+                }
+            }
+        }
+
+        let method_name = &method.sig.ident;
+        let decode = req_type.as_ref().map(|t| {
+            quote! {
+                let req = <#t>::from_tlv(&cmd_req.data)
+                    .map_err(|_| IMStatusCode::InvalidCommand)?;
+            }
+        });
+        let mut call_args = Vec::new();
+        if req_type.is_some() {
+            call_args.push(quote! { req });
+        }
+        if pass_cmd_req {
+            call_args.push(quote! { cmd_req });
+        }
+        let call = quote! { self.#method_name(#(#call_args),*) };
+
+        let handler = if let Some(response) = response {
+            // The handler returns the response, which is encoded as the response command
+            quote! {
+                #decode
+                let resp = #call?;
+                let endpoint = cmd_req.cmd.path.endpoint.unwrap_or_default();
+                let cluster = ClusterType::base(self).id();
+                let invoke_resp = ib::InvResp::cmd_new(
+                    endpoint,
+                    cluster,
+                    #response as u16,
+                    EncodeValue::Value(&resp),
+                );
+                let _ = invoke_resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+                cmd_req.trans.complete();
+                Ok(())
+            }
+        } else if req_type.is_some() {
+            // The handler returns only the status
+            quote! {
+                #decode
+                #call?;
+                cmd_req.trans.complete();
+                Err(IMStatusCode::Sucess)
+            }
+        } else {
+            // The handler takes care of everything
+            quote! { #call }
+        };
+        cmd_ids.push(id);
+        handlers.push(handler);
+    }
+
+    let expanded = quote! {
+        #item
+
+        impl #self_ty {
+            /// The commands accepted by this cluster
+            pub const ACCEPTED_COMMANDS: &'static [u32] = &[ #( #cmd_ids as u32, )* ];
+
+            fn dispatch_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+                let cmd = cmd_req.cmd.path.leaf.ok_or(IMStatusCode::UnsupportedCommand)?;
+                #(
+                    if cmd == #cmd_ids as u32 {
+                        return { #handlers };
+                    }
+                )*
+                Err(IMStatusCode::UnsupportedCommand)
+            }
+        }
+    };
+    //    panic!("The generated code is {}", expanded);
+    expanded.into()
+}
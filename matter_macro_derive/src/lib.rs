@@ -3,17 +3,19 @@ use proc_macro2::Span;
 use quote::{format_ident, quote};
 use syn::Lit::{Int, Str};
 use syn::NestedMeta::{Lit, Meta};
-use syn::{parse_macro_input, DeriveInput, Lifetime};
+use syn::{parse_macro_input, Attribute, DeriveInput, ItemImpl, Lifetime};
 use syn::{
-    Meta::{List, NameValue},
-    MetaList, MetaNameValue, Type,
+    Meta::{List, Path},
+    MetaList, Type,
 };
 
+mod cluster;
+
 struct TlvArgs {
     start: u8,
     datatype: String,
     unordered: bool,
-    lifetime: syn::Lifetime,
+    lifetime: Option<syn::Lifetime>,
 }
 
 impl Default for TlvArgs {
@@ -22,7 +24,7 @@ impl Default for TlvArgs {
             start: 0,
             datatype: "struct".to_string(),
             unordered: false,
-            lifetime: Lifetime::new("'_", Span::call_site()),
+            lifetime: None,
         }
     }
 }
@@ -30,63 +32,127 @@ impl Default for TlvArgs {
 fn parse_tlvargs(ast: &DeriveInput) -> TlvArgs {
     let mut tlvargs: TlvArgs = Default::default();
 
-    if ast.attrs.len() > 0 {
-        if let List(MetaList {
-            path,
-            paren_token: _,
-            nested,
-        }) = ast.attrs[0].parse_meta().unwrap()
-        {
-            if path.is_ident("tlvargs") {
-                for a in nested {
-                    if let Meta(NameValue(MetaNameValue {
-                        path: key_path,
-                        eq_token: _,
-                        lit: key_val,
-                    })) = a
-                    {
-                        if key_path.is_ident("start") {
-                            if let Int(litint) = key_val {
-                                tlvargs.start = litint.base10_parse::<u8>().unwrap();
-                            }
-                        } else if key_path.is_ident("lifetime") {
-                            if let Str(litstr) = key_val {
-                                tlvargs.lifetime =
-                                    Lifetime::new(&litstr.value(), Span::call_site());
-                            }
-                        } else if key_path.is_ident("datatype") {
-                            if let Str(litstr) = key_val {
-                                tlvargs.datatype = litstr.value();
-                            }
-                        } else if key_path.is_ident("unordered") {
-                            tlvargs.unordered = true;
-                        }
-                    }
+    if let Some((pairs, flags)) = cluster::parse_args(&ast.attrs, "tlvargs") {
+        for (key, val) in pairs {
+            match (key.as_str(), val) {
+                ("start", Int(litint)) => tlvargs.start = litint.base10_parse::<u8>().unwrap(),
+                ("lifetime", Str(litstr)) => {
+                    tlvargs.lifetime = Some(Lifetime::new(&litstr.value(), Span::call_site()))
                 }
+                ("datatype", Str(litstr)) => tlvargs.datatype = litstr.value(),
+                (k, _) => panic!("Unsupported tlvargs argument {}", k),
             }
         }
+        tlvargs.unordered = flags.iter().any(|f| f == "unordered");
     }
     tlvargs
 }
 
-fn parse_tag_val(field: &syn::Field) -> Option<u8> {
-    if field.attrs.len() > 0 {
-        if let List(MetaList {
-            path,
-            paren_token: _,
-            nested,
-        }) = field.attrs[0].parse_meta().unwrap()
-        {
-            if path.is_ident("tagval") {
-                for a in nested {
-                    if let Lit(Int(litint)) = a {
-                        return Some(litint.base10_parse::<u8>().unwrap());
-                    }
-                }
+/// The lifetime of the FromTLV implementation, if it isn't in the tlvargs, this is the first
+/// lifetime of the type
+fn fromtlv_lifetime(tlvargs: &TlvArgs, generics: &syn::Generics) -> Lifetime {
+    tlvargs
+        .lifetime
+        .clone()
+        .or_else(|| generics.lifetimes().next().map(|l| l.lifetime.clone()))
+        .unwrap_or_else(|| Lifetime::new("'_", Span::call_site()))
+}
+
+/// The context tag of a member: an integer, or a constant (like
+/// GlobalElements::FabricIndex) plus an offset
+#[derive(Clone)]
+struct Tag {
+    base: Option<syn::Path>,
+    offset: u16,
+}
+
+impl Tag {
+    /// The value of the tag, if it is known here
+    fn value(&self) -> Option<u16> {
+        match self.base {
+            None => Some(self.offset),
+            Some(_) => None,
+        }
+    }
+
+    fn to_tokens(&self) -> proc_macro2::TokenStream {
+        let offset = u8::try_from(self.offset)
+            .unwrap_or_else(|_| panic!("Tag {} is larger than 255", self.offset));
+        match &self.base {
+            None => quote! { #offset },
+            Some(base) if offset == 0 => quote! { (#base as u8) },
+            Some(base) => quote! { ((#base as u8) + #offset) },
+        }
+    }
+}
+
+fn parse_tag_val(attrs: &[Attribute]) -> Option<Tag> {
+    let attr = attrs.iter().find(|a| a.path.is_ident("tagval"))?;
+    if let List(MetaList { nested, .. }) = attr.parse_meta().unwrap() {
+        match nested.first() {
+            Some(Lit(Int(litint))) => {
+                return Some(Tag {
+                    base: None,
+                    offset: litint.base10_parse::<u8>().unwrap() as u16,
+                })
             }
+            Some(Meta(Path(path))) => {
+                return Some(Tag {
+                    base: Some(path.clone()),
+                    offset: 0,
+                })
+            }
+            _ => (),
         }
     }
-    None
+    panic!("The tagval attribute takes an integer or a constant");
+}
+
+/// The tags of the members (or the variants) with these attributes. A member without the tagval
+/// attribute gets the tag that follows the one of the previous member, starting from 'start'.
+fn assign_tags<'a>(attrs: impl Iterator<Item = &'a Vec<Attribute>>, start: u8) -> Vec<Tag> {
+    let mut next = Tag {
+        base: None,
+        offset: start as u16,
+    };
+    let tags: Vec<Tag> = attrs
+        .map(|a| {
+            let tag = parse_tag_val(a).unwrap_or_else(|| next.clone());
+            next = Tag {
+                base: tag.base.clone(),
+                offset: tag.offset + 1,
+            };
+            tag
+        })
+        .collect();
+
+    let mut values: Vec<u16> = tags.iter().filter_map(Tag::value).collect();
+    values.sort_unstable();
+    if let Some(w) = values.windows(2).find(|w| w[0] == w[1]) {
+        panic!("Tag {} is used more than once", w[0]);
+    }
+    tags
+}
+
+/// Whether the members can be decoded in the order they are declared in. This is assumed for
+/// the tags that are constants.
+fn tags_increasing(tags: &[Tag]) -> bool {
+    let values: Vec<u16> = tags.iter().filter_map(Tag::value).collect();
+    values.windows(2).all(|w| w[0] < w[1])
+}
+
+/// The variants of an enum, each of which has a single unnamed member
+fn enum_variants(data_enum: &syn::DataEnum) -> (Vec<&syn::Ident>, Vec<&Type>) {
+    data_enum
+        .variants
+        .iter()
+        .map(|v| match &v.fields {
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                (&v.ident, &fields.unnamed[0].ty)
+            }
+            _ => panic!("Variant {} must have a single unnamed member", v.ident),
+        })
+        .unzip()
 }
 
 /// Generate a ToTlv implementation for a structure
@@ -94,32 +160,19 @@ fn gen_totlv_for_struct(
     fields: &syn::FieldsNamed,
     struct_name: &proc_macro2::Ident,
     tlvargs: TlvArgs,
-    generics: syn::Generics,
+    generics: &syn::Generics,
 ) -> TokenStream {
-    let mut tag_start = tlvargs.start;
     let datatype = format_ident!("start_{}", tlvargs.datatype);
 
-    let mut idents = Vec::new();
-    let mut tags = Vec::new();
-
-    for field in fields.named.iter() {
-        //        let field_name: &syn::Ident = field.ident.as_ref().unwrap();
-        //        let name: String = field_name.to_string();
-        //        let literal_key_str = syn::LitStr::new(&name, field.span());
-        //        let type_name = &field.ty;
-        //        keys.push(quote! { #literal_key_str });
-        idents.push(&field.ident);
-        //        types.push(type_name.to_token_stream());
-        if let Some(a) = parse_tag_val(&field) {
-            tags.push(a);
-        } else {
-            tags.push(tag_start);
-            tag_start += 1;
-        }
-    }
+    let idents: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    let tags: Vec<_> = assign_tags(fields.named.iter().map(|f| &f.attrs), tlvargs.start)
+        .iter()
+        .map(Tag::to_tokens)
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let expanded = quote! {
-        impl #generics ToTLV for #struct_name #generics {
+        impl #impl_generics ToTLV for #struct_name #ty_generics #where_clause {
             fn to_tlv(&self, tw: &mut TLVWriter, tag_type: TagType) -> Result<(), Error> {
                 tw. #datatype (tag_type)?;
                 #(
@@ -138,33 +191,21 @@ fn gen_totlv_for_enum(
     data_enum: syn::DataEnum,
     enum_name: &proc_macro2::Ident,
     tlvargs: TlvArgs,
-    generics: syn::Generics,
+    generics: &syn::Generics,
 ) -> TokenStream {
-    let mut tag_start = tlvargs.start;
-
-    let mut variant_names = Vec::new();
-    let mut types = Vec::new();
-    let mut tags = Vec::new();
-
-    for v in data_enum.variants.iter() {
-        variant_names.push(&v.ident);
-        if let syn::Fields::Unnamed(fields) = &v.fields {
-            if let Type::Path(path) = &fields.unnamed[0].ty {
-                types.push(&path.path.segments[0].ident);
-            } else {
-                panic!("Path not found {:?}", v.fields);
-            }
-        } else {
-            panic!("Unnamed field not found {:?}", v.fields);
-        }
-        tags.push(tag_start);
-        tag_start += 1;
-    }
+    let datatype = format_ident!("start_{}", tlvargs.datatype);
+
+    let (variant_names, _) = enum_variants(&data_enum);
+    let tags: Vec<_> = assign_tags(data_enum.variants.iter().map(|v| &v.attrs), tlvargs.start)
+        .iter()
+        .map(Tag::to_tokens)
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let expanded = quote! {
-           impl #generics ToTLV for #enum_name #generics {
+           impl #impl_generics ToTLV for #enum_name #ty_generics #where_clause {
            fn to_tlv(&self, tw: &mut TLVWriter, tag_type: TagType) -> Result<(), Error> {
-                   tw.start_struct(tag_type)?;
+                   tw. #datatype (tag_type)?;
                    match self {
                        #(
                            Self::#variant_names(c) => { c.to_tlv(tw, TagType::Context(#tags))?; },
@@ -181,8 +222,8 @@ fn gen_totlv_for_enum(
 
 /// Derive ToTLV Macro
 ///
-/// This macro works for structures. It will create an implementation
-/// of the ToTLV trait for that structure.  All the members of the
+/// This macro works for structures and enums. It will create an
+/// implementation of the ToTLV trait for that type. All the members of the
 /// structure, sequentially, will get Context tags starting from 0
 /// Some configurations are possible through the 'tlvargs' attributes.
 /// For example:
@@ -195,12 +236,19 @@ fn gen_totlv_for_enum(
 ///        (Default: struct)
 ///
 /// Additionally, structure members can use the tagval attribute to
-/// define a specific tag to be used
+/// define a specific tag to be used, either an integer or a constant
 /// For example:
-///  #[argval(22)]
+///  #[tagval(22)]
 ///  name: u8,
+///  #[tagval(GlobalElements::FabricIndex)]
+///  fab_idx: u8,
 /// In the above case, the 'name' attribute will be encoded/decoded with
-/// the tag 22
+/// the tag 22. The members that follow a member with the tagval attribute
+/// get the tags that follow its tag.
+///
+/// An enum is a tagged union: each variant has a single member, and it is
+/// encoded as a structure with just that member, tagged with the tag of the
+/// variant. The variants get their tags like the members of a structure.
 
 #[proc_macro_derive(ToTLV, attributes(tlvargs, tagval))]
 pub fn derive_totlv(item: TokenStream) -> TokenStream {
@@ -208,7 +256,7 @@ pub fn derive_totlv(item: TokenStream) -> TokenStream {
     let name = &ast.ident;
 
     let tlvargs = parse_tlvargs(&ast);
-    let generics = ast.generics;
+    let generics = &ast.generics;
 
     if let syn::Data::Struct(syn::DataStruct {
         fields: syn::Fields::Named(ref fields),
@@ -231,43 +279,48 @@ fn gen_fromtlv_for_struct(
     fields: &syn::FieldsNamed,
     struct_name: &proc_macro2::Ident,
     tlvargs: TlvArgs,
-    generics: syn::Generics,
+    generics: &syn::Generics,
 ) -> TokenStream {
-    let mut tag_start = tlvargs.start;
-    let lifetime = tlvargs.lifetime;
+    let lifetime = fromtlv_lifetime(&tlvargs, generics);
     let datatype = format_ident!("confirm_{}", tlvargs.datatype);
 
-    let mut idents = Vec::new();
-    let mut types = Vec::new();
-    let mut tags = Vec::new();
-
-    for field in fields.named.iter() {
-        let type_name = &field.ty;
-        if let Some(a) = parse_tag_val(&field) {
-            // TODO: The current limitation with this is that a hard-coded integer
-            // value has to be mentioned in the tagval attribute. This is because
-            // our tags vector is for integers, and pushing an 'identifier' on it
-            // wouldn't work.
-            tags.push(a);
-        } else {
-            tags.push(tag_start);
-            tag_start += 1;
-        }
-        idents.push(&field.ident);
-
-        if let Type::Path(path) = type_name {
-            types.push(&path.path.segments[0].ident);
-        } else {
-            panic!("Don't know what to do {:?}", type_name);
-        }
-    }
+    let tags = assign_tags(fields.named.iter().map(|f| &f.attrs), tlvargs.start);
+    let unordered = tlvargs.unordered || !tags_increasing(&tags);
+    let tags: Vec<_> = tags.iter().map(Tag::to_tokens).collect();
+    let idents: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    let types: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Name the member that couldn't be decoded in the error, unless a member
+    // of a nested structure was already named (and logged)
+    let on_error: Vec<_> = idents
+        .iter()
+        .zip(tags.iter())
+        .map(|(ident, tag)| {
+            quote! {
+                .map_err(|e| match e {
+                    Error::TLVField(_) => e,
+                    _ => {
+                        matter::log::error!(
+                            "Couldn't decode {}::{} with tag {}: {:?}",
+                            stringify!(#struct_name),
+                            stringify!(#ident),
+                            #tag,
+                            e
+                        );
+                        Error::TLVField(concat!(stringify!(#struct_name), "::", stringify!(#ident)))
+                    }
+                })
+            }
+        })
+        .collect();
 
-    // Currently we don't use find_tag() because the tags come in sequential
-    // order. If ever the tags start coming out of order, we can use find_tag()
-    // instead
-    let expanded = if !tlvargs.unordered {
+    // The tags come in sequential order, unless the tags of the members
+    // aren't increasing, or the unordered argument says otherwise. Then the
+    // members are looked up with find_tag()
+    let expanded = if !unordered {
         quote! {
-           impl #generics FromTLV <#lifetime> for #struct_name #generics {
+           impl #impl_generics FromTLV <#lifetime> for #struct_name #ty_generics #where_clause {
                fn from_tlv(t: &TLVElement<#lifetime>) -> Result<Self, Error> {
                    let mut t_iter = t.#datatype ()?.enter().ok_or(Error::Invalid)?;
                    let mut item = t_iter.next();
@@ -275,10 +328,10 @@ fn gen_fromtlv_for_struct(
                        let #idents = if Some(true) == item.map(|x| x.check_ctx_tag(#tags)) {
                            let backup = item;
                            item = t_iter.next();
-                           #types::from_tlv(&backup.unwrap())
+                           <#types>::from_tlv(&backup.unwrap())
                        } else {
-                           #types::tlv_not_found()
-                       }?;
+                           <#types>::tlv_not_found()
+                       } #on_error ?;
                    )*
                    Ok(Self {
                        #(#idents,
@@ -289,14 +342,15 @@ fn gen_fromtlv_for_struct(
         }
     } else {
         quote! {
-           impl #generics FromTLV <#lifetime> for #struct_name #generics {
+           impl #impl_generics FromTLV <#lifetime> for #struct_name #ty_generics #where_clause {
                fn from_tlv(t: &TLVElement<#lifetime>) -> Result<Self, Error> {
+                   let t = t.#datatype ()?;
                    #(
                        let #idents = if let Ok(s) = t.find_tag(#tags as u32) {
-                           #types::from_tlv(&s)
+                           <#types>::from_tlv(&s)
                        } else {
-                           #types::tlv_not_found()
-                       }?;
+                           <#types>::tlv_not_found()
+                       } #on_error ?;
                    )*
 
                    Ok(Self {
@@ -316,45 +370,50 @@ fn gen_fromtlv_for_enum(
     data_enum: syn::DataEnum,
     enum_name: &proc_macro2::Ident,
     tlvargs: TlvArgs,
-    generics: syn::Generics,
+    generics: &syn::Generics,
 ) -> TokenStream {
-    let mut tag_start = tlvargs.start;
-    let lifetime = tlvargs.lifetime;
-
-    let mut variant_names = Vec::new();
-    let mut types = Vec::new();
-    let mut tags = Vec::new();
-
-    for v in data_enum.variants.iter() {
-        variant_names.push(&v.ident);
-        if let syn::Fields::Unnamed(fields) = &v.fields {
-            if let Type::Path(path) = &fields.unnamed[0].ty {
-                types.push(&path.path.segments[0].ident);
-            } else {
-                panic!("Path not found {:?}", v.fields);
-            }
-        } else {
-            panic!("Unnamed field not found {:?}", v.fields);
-        }
-        tags.push(tag_start);
-        tag_start += 1;
-    }
+    let lifetime = fromtlv_lifetime(&tlvargs, generics);
+    let datatype = format_ident!("confirm_{}", tlvargs.datatype);
+
+    let (variant_names, types) = enum_variants(&data_enum);
+    let tags: Vec<_> = assign_tags(data_enum.variants.iter().map(|v| &v.attrs), tlvargs.start)
+        .iter()
+        .map(Tag::to_tokens)
+        .collect();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let expanded = quote! {
-           impl #generics FromTLV <#lifetime> for #enum_name #generics {
+           impl #impl_generics FromTLV <#lifetime> for #enum_name #ty_generics #where_clause {
                fn from_tlv(t: &TLVElement<#lifetime>) -> Result<Self, Error> {
-                   let mut t_iter = t.confirm_struct()?.enter().ok_or(Error::Invalid)?;
-                   let mut item = t_iter.next().ok_or(Error::Invalid)?;
-                   if let TagType::Context(tag) = item.get_tag() {
-                       match tag {
-                           #(
-                               #tags => Ok(Self::#variant_names(#types::from_tlv(&item)?)),
-                           )*
-                           _ => Err(Error::Invalid),
-                       }
+                   let mut t_iter = t.#datatype ()?.enter().ok_or(Error::Invalid)?;
+                   let item = t_iter.next().ok_or_else(|| {
+                       matter::log::error!("No member in {}", stringify!(#enum_name));
+                       Error::Invalid
+                   })?;
+                   let tag = if let TagType::Context(tag) = item.get_tag() {
+                       tag
                    } else {
-                       Err(Error::TLVTypeMismatch)
-                   }
+                       return Err(Error::TLVTypeMismatch);
+                   };
+                   #(
+                       if tag == #tags {
+                           return <#types>::from_tlv(&item).map(Self::#variant_names).map_err(|e| match e {
+                               Error::TLVField(_) => e,
+                               _ => {
+                                   matter::log::error!(
+                                       "Couldn't decode {}::{} with tag {}: {:?}",
+                                       stringify!(#enum_name),
+                                       stringify!(#variant_names),
+                                       tag,
+                                       e
+                                   );
+                                   Error::TLVField(concat!(stringify!(#enum_name), "::", stringify!(#variant_names)))
+                               }
+                           });
+                       }
+                   )*
+                   matter::log::error!("No variant of {} with tag {}", stringify!(#enum_name), tag);
+                   Err(Error::Invalid)
                }
            }
     };
@@ -365,8 +424,8 @@ fn gen_fromtlv_for_enum(
 
 /// Derive FromTLV Macro
 ///
-/// This macro works for structures. It will create an implementation
-/// of the FromTLV trait for that structure.  All the members of the
+/// This macro works for structures and enums. It will create an
+/// implementation of the FromTLV trait for that type. All the members of the
 /// structure, sequentially, will get Context tags starting from 0
 /// Some configurations are possible through the 'tlvargs' attributes.
 /// For example:
@@ -377,19 +436,24 @@ fn gen_fromtlv_for_enum(
 /// datatype: This can be used to define whether this data structure is
 ///        to be decoded as a structure or list. Possible values: list
 ///        (Default: struct)
-/// lifetime: If the structure has a lifetime annotation, use this variable
-///        to indicate that. The 'impl' will then use that lifetime
-///        indicator.
+/// lifetime: The lifetime of the TLV that the structure borrows from, if
+///        this isn't the first lifetime of the structure (Default: the
+///        first lifetime)
 /// unordered: By default, the decoder expects that the tags are in
 ///        sequentially increasing order. Set this if that is not the case.
+///        This is implied if the tags of the members aren't increasing.
 ///
 /// Additionally, structure members can use the tagval attribute to
-/// define a specific tag to be used
+/// define a specific tag to be used, either an integer or a constant
 /// For example:
-///  #[argval(22)]
+///  #[tagval(22)]
 ///  name: u8,
 /// In the above case, the 'name' attribute will be encoded/decoded with
-/// the tag 22
+/// the tag 22. See the ToTLV macro for the tags of the other members, and
+/// for enums.
+///
+/// If a member can't be decoded, the error is logged with the name and the
+/// tag of the member.
 
 #[proc_macro_derive(FromTLV, attributes(tlvargs, tagval))]
 pub fn derive_fromtlv(item: TokenStream) -> TokenStream {
@@ -398,7 +462,7 @@ pub fn derive_fromtlv(item: TokenStream) -> TokenStream {
 
     let tlvargs = parse_tlvargs(&ast);
 
-    let generics = ast.generics;
+    let generics = &ast.generics;
 
     if let syn::Data::Struct(syn::DataStruct {
         fields: syn::Fields::Named(ref fields),
@@ -415,3 +479,81 @@ pub fn derive_fromtlv(item: TokenStream) -> TokenStream {
         )
     }
 }
+
+/// Derive ClusterType Macro
+///
+/// This macro works for structures that have a member of type Cluster. It
+/// will create an implementation of the ClusterType trait for that
+/// structure, along with a new_base() method that creates the Cluster with
+/// all the attributes and commands populated.
+/// For example:
+///  #[cluster(id = "ID", attributes = "Attributes", commands)]
+///
+/// id: The cluster ID, either an integer or a constant
+/// attributes: An enum that derives ClusterAttributes. All its annotated
+///        attributes are added to the cluster.
+/// commands: Set this if the commands are handled in an impl block with the
+///        cluster_commands attribute. The commands from that block are
+///        dispatched by handle_command() and are reported in the
+///        AcceptedCommandList.
+/// read: The method that handles reads of the custom attributes, this has the
+///        same signature as ClusterType::read_custom_attribute()
+/// write: The method that handles attribute writes, this has the same
+///        signature as ClusterType::write_attribute()
+/// list_len: The method that returns the number of entries in a custom list
+///        attribute, this has the same signature as ClusterType::list_len()
+///
+/// The AttributeList is maintained by the Cluster as usual.
+
+#[proc_macro_derive(ClusterType, attributes(cluster))]
+pub fn derive_clustertype(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as DeriveInput);
+    cluster::gen_clustertype(ast)
+}
+
+/// Derive ClusterAttributes Macro
+///
+/// This macro works for the enum of attribute IDs of a cluster. Each variant
+/// annotated with the 'attribute' attribute is added to the cluster by the
+/// generated add_to() method.
+/// For example:
+///  #[attribute(default = "AttrValue::Bool(false)", access = "RV", quality = "PERSISTENT")]
+///  OnOff = 0,
+///
+/// default: The initial value of the attribute (Default: AttrValue::Custom)
+/// access: The Access flags, multiple flags can be combined with '|'
+///        (Default: RV)
+/// quality: The Quality flags, multiple flags can be combined with '|'
+///        (Default: NONE)
+
+#[proc_macro_derive(ClusterAttributes, attributes(attribute))]
+pub fn derive_clusterattributes(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as DeriveInput);
+    cluster::gen_clusterattributes(ast)
+}
+
+/// Cluster Commands Macro
+///
+/// This attribute works on an impl block of a cluster. The methods annotated
+/// with the 'command' attribute are the command handlers. A
+/// dispatch_command() method and the ACCEPTED_COMMANDS list are generated.
+/// For example:
+///  #[command(id = "Commands::ArmFailsafe", response = "Commands::ArmFailsafeResp")]
+///  fn handle_arm_failsafe(&mut self, req: FailSafeParams, cmd_req: &mut CommandReq)
+///        -> Result<CommonResponse, IMStatusCode>
+///
+/// id: The command ID
+/// response: The ID of the response command. The value returned by the
+///        handler is encoded as the data of this response command.
+///
+/// The handler may accept a request type that implements FromTLV, which is
+/// decoded from the command data, and/or the CommandReq itself. Without a
+/// response, a handler that accepts a request returns just the status. A
+/// handler that only accepts the CommandReq is responsible for the entire
+/// response.
+
+#[proc_macro_attribute]
+pub fn cluster_commands(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+    cluster::gen_cluster_commands(item)
+}
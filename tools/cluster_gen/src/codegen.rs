@@ -0,0 +1,704 @@
+use crate::model::*;
+use crate::parser::parse_int;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// The module that holds the types that aren't tied to a cluster
+pub const GLOBAL_TYPES_MODULE: &str = "global_types";
+
+/// A generated Rust module
+pub struct Module {
+    pub name: String,
+    pub code: String,
+}
+
+/// Generate one module per cluster, the global types module, and a mod.rs listing them
+pub fn generate(spec: &Spec) -> Vec<Module> {
+    let mut modules = Vec::new();
+    let has_global = !spec.global.is_empty();
+
+    if has_global {
+        let mut g = Gen::new(spec, None);
+        g.types(&spec.global);
+        modules.push(Module {
+            name: GLOBAL_TYPES_MODULE.to_owned(),
+            code: g.finish("the global type definitions"),
+        });
+    }
+    for cluster in spec.clusters.iter() {
+        let mut g = Gen::new(spec, Some(cluster));
+        g.cluster(cluster);
+        modules.push(Module {
+            name: snake_case(&cluster.name),
+            code: g.finish(&cluster.source),
+        });
+    }
+
+    let mut mod_rs = String::new();
+    for m in modules.iter() {
+        writeln!(mod_rs, "pub mod {};", m.name).unwrap();
+    }
+    modules.push(Module {
+        name: "mod".to_owned(),
+        code: mod_rs,
+    });
+    modules
+}
+
+/// How strings and lists are represented
+#[derive(Clone, Copy, PartialEq)]
+enum Storage {
+    /// Borrowed from the TLV buffer, used for the requests
+    Borrowed,
+    /// Owned copies, used for everything that is created by the cluster
+    Owned,
+}
+
+struct Gen<'a> {
+    spec: &'a Spec,
+    cluster: Option<&'a ClusterDef>,
+    /// The generated items, the imports are added on top of these at the end
+    body: String,
+    uses: BTreeSet<&'static str>,
+    tlv_uses: BTreeSet<&'static str>,
+}
+
+impl<'a> Gen<'a> {
+    fn new(spec: &'a Spec, cluster: Option<&'a ClusterDef>) -> Self {
+        Self {
+            spec,
+            cluster,
+            body: String::new(),
+            uses: BTreeSet::new(),
+            tlv_uses: BTreeSet::new(),
+        }
+    }
+
+    fn finish(self, source: &str) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "// This file is generated by cluster_gen from {}, do not edit it by hand",
+            source
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        if self.cluster.is_some() && !self.spec.global.is_empty() {
+            writeln!(out, "#[allow(unused_imports)]").unwrap();
+            writeln!(out, "use super::{}::*;", GLOBAL_TYPES_MODULE).unwrap();
+        }
+        for u in self.uses.iter() {
+            writeln!(out, "use {};", u).unwrap();
+        }
+        match self.tlv_uses.len() {
+            0 => (),
+            1 => writeln!(
+                out,
+                "use crate::tlv::{};",
+                self.tlv_uses.iter().next().unwrap()
+            )
+            .unwrap(),
+            _ => {
+                let list: Vec<&str> = self.tlv_uses.iter().copied().collect();
+                writeln!(out, "use crate::tlv::{{{}}};", list.join(", ")).unwrap()
+            }
+        }
+        writeln!(out).unwrap();
+        out.push_str(self.body.trim_end());
+        out.push('\n');
+        out
+    }
+
+    fn cluster(&mut self, c: &ClusterDef) {
+        let name = format!("{}Cluster", camel_case(&c.name));
+        let attributes: Vec<&AttributeDef> =
+            c.attributes.iter().filter(|a| a.id < 0xfff8).collect();
+        let requests: Vec<&CommandDef> = c.commands.iter().filter(|c| c.to_server).collect();
+        let responses: Vec<&CommandDef> = c.commands.iter().filter(|c| !c.to_server).collect();
+
+        self.uses.insert("crate::data_model::objects::*");
+        self.uses.insert("crate::error::*");
+
+        writeln!(self.body, "pub const ID: u32 = {:#06x};", c.id).unwrap();
+        if let Some(revision) = c.revision {
+            writeln!(self.body, "pub const CLUSTER_REVISION: u16 = {};", revision).unwrap();
+        }
+        writeln!(self.body).unwrap();
+
+        // The ID enums
+        let mut custom_reads = Vec::new();
+        let mut custom_writes = Vec::new();
+        if !attributes.is_empty() {
+            self.uses.insert("num_derive::FromPrimitive");
+            writeln!(self.body, "#[derive(FromPrimitive, ClusterAttributes)]").unwrap();
+            writeln!(self.body, "pub enum Attributes {{").unwrap();
+            for a in attributes.iter() {
+                let variant = camel_case(&a.name);
+                if !a.ty.optional {
+                    let (annotation, custom) = self.attribute_annotation(a);
+                    writeln!(self.body, "    {}", annotation).unwrap();
+                    if custom {
+                        if a.read.is_some() {
+                            custom_reads.push(variant.clone());
+                        }
+                        if a.write.is_some() {
+                            custom_writes.push(variant.clone());
+                        }
+                    }
+                }
+                writeln!(self.body, "    {} = {:#x},", variant, a.id).unwrap();
+            }
+            writeln!(self.body, "}}\n").unwrap();
+        }
+        if !requests.is_empty() {
+            self.id_enum("Commands", requests.iter().map(|c| (c.name.as_str(), c.id)));
+        }
+        if !responses.is_empty() {
+            self.id_enum(
+                "RespCommands",
+                responses.iter().map(|c| (c.name.as_str(), c.id)),
+            );
+        }
+        if !c.events.is_empty() {
+            self.id_enum("Events", c.events.iter().map(|e| (e.name.as_str(), e.id)));
+        }
+
+        self.types(&c.types);
+
+        // The request, response and event payloads
+        for cmd in requests.iter().filter(|c| !c.fields.is_empty()) {
+            let name = format!("{}Req", camel_case(&cmd.name));
+            self.tlv_struct(&name, &cmd.fields, Storage::Borrowed);
+        }
+        let mut handled_responses = Vec::new();
+        for cmd in requests.iter() {
+            if let CommandResponse::Command(r) = &cmd.response {
+                if let Some(resp) = responses.iter().find(|c| &c.name == r) {
+                    if !handled_responses.contains(&resp.name) {
+                        handled_responses.push(resp.name.clone());
+                        let name = camel_case(&resp.name);
+                        self.tlv_struct(&name, &resp.fields, Storage::Owned);
+                    }
+                }
+            }
+        }
+        for e in c.events.iter() {
+            let name = format!("{}Event", camel_case(&e.name));
+            self.tlv_struct(&name, &e.fields, Storage::Owned);
+        }
+
+        // The cluster itself
+        let mut args = vec!["id = \"ID\"".to_owned()];
+        if !attributes.is_empty() {
+            args.push("attributes = \"Attributes\"".to_owned());
+        }
+        if !requests.is_empty() {
+            args.push("commands".to_owned());
+        }
+        if !custom_reads.is_empty() {
+            args.push("read = \"read_custom\"".to_owned());
+        }
+        if !custom_writes.is_empty() {
+            args.push("write = \"write_custom\"".to_owned());
+        }
+        writeln!(self.body, "#[derive(ClusterType)]").unwrap();
+        writeln!(self.body, "#[cluster({})]", args.join(", ")).unwrap();
+        writeln!(self.body, "pub struct {} {{", name).unwrap();
+        writeln!(self.body, "    base: Cluster,").unwrap();
+        writeln!(self.body, "}}\n").unwrap();
+
+        writeln!(self.body, "impl {} {{", name).unwrap();
+        writeln!(
+            self.body,
+            "    pub fn new() -> Result<Box<Self>, Error> {{
+        Ok(Box::new(Self {{
+            base: Self::new_base()?,
+        }}))
+    }}"
+        )
+        .unwrap();
+        if !custom_reads.is_empty() {
+            self.uses.insert("log::error");
+            writeln!(
+                self.body,
+                "
+    fn read_custom(&self, _encoder: &mut dyn Encoder, attr: &AttrDetails) {{
+        match num::FromPrimitive::from_u16(attr.attr_id) {{"
+            )
+            .unwrap();
+            for v in custom_reads.iter() {
+                writeln!(
+                    self.body,
+                    "            Some(Attributes::{}) => {{
+                // TODO: Encode the attribute
+            }}",
+                    v
+                )
+                .unwrap();
+            }
+            writeln!(
+                self.body,
+                "            _ => {{
+                error!(\"Unsupported Attribute: this shouldn't happen\");
+            }}
+        }}
+    }}"
+            )
+            .unwrap();
+        }
+        if !custom_writes.is_empty() {
+            self.uses
+                .insert("crate::interaction_model::core::IMStatusCode");
+            self.tlv_uses.insert("TLVElement");
+            writeln!(
+                self.body,
+                "
+    fn write_custom(&mut self, attr: &AttrDetails, data: &TLVElement) -> Result<(), IMStatusCode> {{
+        match num::FromPrimitive::from_u16(attr.attr_id) {{"
+            )
+            .unwrap();
+            for v in custom_writes.iter() {
+                writeln!(
+                    self.body,
+                    "            Some(Attributes::{}) => {{
+                // TODO: Decode and store the attribute
+                Err(IMStatusCode::UnsupportedWrite)
+            }}",
+                    v
+                )
+                .unwrap();
+            }
+            writeln!(
+                self.body,
+                "            _ => self.base.write_attribute_from_tlv(attr.attr_id, data),
+        }}
+    }}"
+            )
+            .unwrap();
+        }
+        writeln!(self.body, "}}\n").unwrap();
+
+        if !requests.is_empty() {
+            self.command_handlers(&name, &requests, &responses);
+        }
+    }
+
+    fn command_handlers(
+        &mut self,
+        name: &str,
+        requests: &[&CommandDef],
+        responses: &[&CommandDef],
+    ) {
+        self.uses
+            .insert("crate::interaction_model::command::CommandReq");
+        self.uses
+            .insert("crate::interaction_model::core::IMStatusCode");
+
+        writeln!(self.body, "#[cluster_commands]").unwrap();
+        writeln!(self.body, "impl {} {{", name).unwrap();
+        for (i, cmd) in requests.iter().enumerate() {
+            if i != 0 {
+                writeln!(self.body).unwrap();
+            }
+            let response = match &cmd.response {
+                CommandResponse::Command(r) => responses.iter().find(|c| &c.name == r),
+                CommandResponse::Status => None,
+            };
+            let variant = camel_case(&cmd.name);
+            match response {
+                Some(r) => {
+                    self.uses.insert("crate::interaction_model::messages::ib");
+                    self.tlv_uses.insert("TagType");
+                    self.tlv_uses.insert("ToTLV");
+                    writeln!(
+                        self.body,
+                        "    #[command(id = \"Commands::{}\", response = \"RespCommands::{}\")]",
+                        variant,
+                        camel_case(&r.name)
+                    )
+                    .unwrap()
+                }
+                None => {
+                    writeln!(self.body, "    #[command(id = \"Commands::{}\")]", variant).unwrap()
+                }
+            }
+            let mut args = vec!["&mut self".to_owned()];
+            if !cmd.fields.is_empty() {
+                args.push(format!("_req: {}Req", variant));
+            }
+            args.push("_cmd_req: &mut CommandReq".to_owned());
+            let ret = response.map_or("()".to_owned(), |r| camel_case(&r.name));
+            writeln!(
+                self.body,
+                "    fn handle_{}({}) -> Result<{}, IMStatusCode> {{
+        Err(IMStatusCode::UnsupportedCommand)
+    }}",
+                snake_case(&cmd.name),
+                args.join(", "),
+                ret
+            )
+            .unwrap();
+        }
+        writeln!(self.body, "}}\n").unwrap();
+    }
+
+    /// Returns the #[attribute(...)] annotation, and whether the attribute is of the Custom kind
+    fn attribute_annotation(&self, a: &AttributeDef) -> (String, bool) {
+        let mut args = Vec::new();
+        let value = if a.ty.list || a.ty.nullable {
+            None
+        } else {
+            let default = a.default.as_deref().unwrap_or("0");
+            let int = || parse_int(default).unwrap_or(0);
+            match self.resolve(&a.ty.base) {
+                DataType::Bool => Some(format!(
+                    "AttrValue::Bool({})",
+                    default.eq_ignore_ascii_case("true") || int() != 0
+                )),
+                DataType::U8 => Some(format!("AttrValue::Uint8({})", int())),
+                DataType::U16 => Some(format!("AttrValue::Uint16({})", int())),
+                DataType::U32 => Some(format!("AttrValue::Uint32({})", int())),
+                DataType::U64 => Some(format!("AttrValue::Uint64({})", int())),
+                _ => None,
+            }
+        };
+        let custom = value.is_none();
+        if let Some(v) = value {
+            args.push(format!("default = \"{}\"", v));
+        }
+
+        let mut access = Vec::new();
+        match (a.read, a.write) {
+            (Some(Privilege::View), None) => access.push("RV"),
+            (read, write) => {
+                if read.is_some() {
+                    access.push("READ");
+                }
+                if write.is_some() {
+                    access.push("WRITE");
+                }
+                for p in [read, write].iter().flatten() {
+                    let flag = match p {
+                        Privilege::View => "NEED_VIEW",
+                        Privilege::Operate => "NEED_OPERATE",
+                        Privilege::Manage => "NEED_MANAGE",
+                        Privilege::Administer => "NEED_ADMIN",
+                    };
+                    if !access.contains(&flag) {
+                        access.push(flag);
+                    }
+                }
+            }
+        }
+        if a.fabric_scoped {
+            access.push("FAB_SCOPED");
+        }
+        if a.fabric_sensitive {
+            access.push("FAB_SENSITIVE");
+        }
+        if a.timed {
+            access.push("TIMED_ONLY");
+        }
+        if access != ["RV"] {
+            args.push(format!("access = \"{}\"", access.join(" | ")));
+        }
+
+        let mut quality = Vec::new();
+        if a.persistent {
+            quality.push("PERSISTENT");
+        }
+        if a.ty.nullable {
+            quality.push("NULLABLE");
+        }
+        if a.fixed {
+            quality.push("FIXED");
+        }
+        if !quality.is_empty() {
+            args.push(format!("quality = \"{}\"", quality.join(" | ")));
+        }
+
+        if args.is_empty() {
+            ("#[attribute]".to_owned(), custom)
+        } else {
+            (format!("#[attribute({})]", args.join(", ")), custom)
+        }
+    }
+
+    fn id_enum<'b>(&mut self, name: &str, items: impl Iterator<Item = (&'b str, u32)>) {
+        self.uses.insert("num_derive::FromPrimitive");
+        writeln!(self.body, "#[derive(FromPrimitive)]").unwrap();
+        writeln!(self.body, "pub enum {} {{", name).unwrap();
+        for (n, id) in items {
+            writeln!(self.body, "    {} = {:#x},", camel_case(n), id).unwrap();
+        }
+        writeln!(self.body, "}}\n").unwrap();
+    }
+
+    fn types(&mut self, types: &Types) {
+        for e in types.enums.iter() {
+            self.uses.insert("num_derive::FromPrimitive");
+            writeln!(
+                self.body,
+                "#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive)]"
+            )
+            .unwrap();
+            writeln!(self.body, "#[repr({})]", prim_name(&e.base).unwrap_or("u8")).unwrap();
+            writeln!(self.body, "pub enum {} {{", camel_case(&e.name)).unwrap();
+            for (n, v) in e.items.iter() {
+                writeln!(self.body, "    {} = {:#x},", camel_case(n), v).unwrap();
+            }
+            writeln!(self.body, "}}\n").unwrap();
+        }
+        for b in types.bitmaps.iter() {
+            self.uses.insert("bitflags::bitflags");
+            writeln!(self.body, "bitflags! {{").unwrap();
+            writeln!(self.body, "    #[derive(Default)]").unwrap();
+            writeln!(
+                self.body,
+                "    pub struct {}: {} {{",
+                camel_case(&b.name),
+                prim_name(&b.base).unwrap_or("u32")
+            )
+            .unwrap();
+            for (n, m) in b.bits.iter() {
+                writeln!(
+                    self.body,
+                    "        const {} = {:#x};",
+                    snake_case(n).to_uppercase(),
+                    m
+                )
+                .unwrap();
+            }
+            writeln!(self.body, "    }}\n}}\n").unwrap();
+        }
+        for s in types.structs.iter() {
+            self.tlv_struct(&camel_case(&s.name), &s.fields, Storage::Owned);
+        }
+    }
+
+    fn tlv_struct(&mut self, name: &str, fields: &[Field], storage: Storage) {
+        for t in ["FromTLV", "TLVElement", "TLVWriter", "TagType", "ToTLV"].iter() {
+            self.tlv_uses.insert(t);
+        }
+        // The TLV derives return the crate's Error
+        self.uses.insert("crate::error::*");
+        let lifetime = storage == Storage::Borrowed
+            && fields
+                .iter()
+                .any(|f| f.ty.list || matches!(f.ty.base, DataType::OctetStr | DataType::CharStr));
+
+        writeln!(self.body, "#[derive(FromTLV, ToTLV)]").unwrap();
+        if lifetime {
+            writeln!(self.body, "#[tlvargs(lifetime = \"'a\")]").unwrap();
+            writeln!(self.body, "pub struct {}<'a> {{", name).unwrap();
+        } else {
+            writeln!(self.body, "pub struct {} {{", name).unwrap();
+        }
+        // The TLV derives assign sequential tags, anything else needs an explicit tagval
+        let mut next_tag = 0;
+        for f in fields.iter() {
+            if f.id == next_tag {
+                next_tag += 1;
+            } else {
+                writeln!(self.body, "    #[tagval({:#x})]", f.id).unwrap();
+            }
+            if let DataType::Named(n) = &f.ty.base {
+                if self.find_enum_or_bitmap(n).is_some() {
+                    writeln!(self.body, "    // {}", camel_case(n)).unwrap();
+                }
+            }
+            let ty = self.field_type(&f.ty, storage);
+            writeln!(self.body, "    pub {}: {},", field_name(&f.name), ty).unwrap();
+        }
+        writeln!(self.body, "}}\n").unwrap();
+    }
+
+    fn field_type(&mut self, ty: &FieldType, storage: Storage) -> String {
+        let base = match self.resolve(&ty.base) {
+            DataType::OctetStr => match storage {
+                Storage::Borrowed => {
+                    self.tlv_uses.insert("OctetStr");
+                    "OctetStr<'a>".to_owned()
+                }
+                Storage::Owned => "Vec<u8>".to_owned(),
+            },
+            DataType::CharStr => match storage {
+                Storage::Borrowed => {
+                    self.tlv_uses.insert("UtfStr");
+                    "UtfStr<'a>".to_owned()
+                }
+                Storage::Owned => "String".to_owned(),
+            },
+            DataType::Named(n) => camel_case(&n),
+            p => prim_name(&p).unwrap().to_owned(),
+        };
+        let mut out = base;
+        if ty.list {
+            out = match storage {
+                Storage::Borrowed => {
+                    self.tlv_uses.insert("TLVArray");
+                    format!("TLVArray<'a, {}>", out)
+                }
+                Storage::Owned => {
+                    self.tlv_uses.insert("TLVArrayOwned");
+                    format!("TLVArrayOwned<{}>", out)
+                }
+            };
+        }
+        if ty.nullable {
+            self.tlv_uses.insert("Nullable");
+            out = format!("Nullable<{}>", out);
+        }
+        if ty.optional {
+            out = format!("Option<{}>", out);
+        }
+        out
+    }
+
+    /// Enums and bitmaps are carried as their underlying integer type
+    fn resolve(&self, t: &DataType) -> DataType {
+        if let DataType::Named(n) = t {
+            if let Some(base) = self.find_enum_or_bitmap(n) {
+                return base;
+            }
+        }
+        t.clone()
+    }
+
+    fn find_enum_or_bitmap(&self, name: &str) -> Option<DataType> {
+        let mut scopes = vec![&self.spec.global];
+        if let Some(c) = self.cluster {
+            scopes.insert(0, &c.types);
+        }
+        for types in scopes {
+            if let Some(e) = types.enums.iter().find(|e| e.name == name) {
+                return Some(e.base.clone());
+            }
+            if let Some(b) = types.bitmaps.iter().find(|b| b.name == name) {
+                return Some(b.base.clone());
+            }
+        }
+        None
+    }
+}
+
+fn prim_name(t: &DataType) -> Option<&'static str> {
+    Some(match t {
+        DataType::Bool => "bool",
+        DataType::U8 => "u8",
+        DataType::U16 => "u16",
+        DataType::U32 => "u32",
+        DataType::U64 => "u64",
+        DataType::I8 => "i8",
+        DataType::I16 => "i16",
+        DataType::I32 => "i32",
+        DataType::I64 => "i64",
+        DataType::F32 => "f32",
+        DataType::F64 => "f64",
+        _ => return None,
+    })
+}
+
+/// "On/Off" -> "OnOff", "level control" -> "LevelControl"
+pub fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'V');
+    }
+    out
+}
+
+/// "OnOff" -> "on_off", "NOCResponse" -> "noc_response"
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = camel_case(name).chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i != 0 {
+            let prev = chars[i - 1];
+            let next_lower = matches!(chars.get(i + 1), Some(n) if n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+fn field_name(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else",
+        "enum", "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "ref", "return", "self", "static", "struct", "super", "trait", "type", "unsafe",
+        "use", "where", "while",
+    ];
+    let name = snake_case(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(camel_case("On/Off"), "OnOff");
+        assert_eq!(camel_case("general commissioning"), "GeneralCommissioning");
+        assert_eq!(snake_case("OnOff"), "on_off");
+        assert_eq!(snake_case("NOCResponse"), "noc_response");
+        assert_eq!(field_name("Type"), "type_");
+    }
+
+    #[test]
+    fn test_generate() {
+        let xml = r#"<configurator>
+            <cluster>
+              <name>Sample</name>
+              <code>0xFFF1FC00</code>
+              <attribute side="server" code="0x0000" type="int8u" default="0x05" writable="true">Level</attribute>
+              <attribute side="server" code="0x0001" type="char_string">Label</attribute>
+              <command source="client" code="0x00" name="SetLabel" response="SetLabelResponse">
+                <arg name="Label" type="char_string"/>
+                <arg name="Extra" type="int16u" fieldId="3" optional="true"/>
+              </command>
+              <command source="server" code="0x01" name="SetLabelResponse">
+                <arg name="Status" type="enum8"/>
+              </command>
+            </cluster>
+        </configurator>"#;
+        let mut parser = Parser::new();
+        parser.parse("sample.xml", xml).unwrap();
+        let modules = generate(&parser.finish());
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].name, "sample");
+        assert_eq!(modules[1].code, "pub mod sample;\n");
+
+        let code = &modules[0].code;
+        assert!(code.contains("pub const ID: u32 = 0xfff1fc00;"));
+        assert!(code.contains(
+            "#[attribute(default = \"AttrValue::Uint8(5)\", access = \"READ | WRITE | NEED_VIEW | NEED_OPERATE\")]"
+        ));
+        assert!(code.contains("read = \"read_custom\""));
+        assert!(code.contains("pub struct SetLabelReq<'a> {"));
+        assert!(code.contains(
+            "    pub label: UtfStr<'a>,\n    #[tagval(0x3)]\n    pub extra: Option<u16>,"
+        ));
+        assert!(code.contains("response = \"RespCommands::SetLabelResponse\""));
+        assert!(code.contains(") -> Result<SetLabelResponse, IMStatusCode> {"));
+    }
+}
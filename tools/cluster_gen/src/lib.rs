@@ -0,0 +1,12 @@
+//! Generates the Rust modules for Matter clusters from their XML definitions
+//!
+//! For each cluster this emits the attribute, command and event ID enums, the FromTLV/ToTLV
+//! structures for the command requests, responses and events, and a skeleton ClusterType that
+//! plugs into objects::Cluster through the ClusterType/ClusterAttributes derives.
+
+pub mod codegen;
+pub mod model;
+pub mod parser;
+
+pub use codegen::{generate, Module};
+pub use parser::Parser;
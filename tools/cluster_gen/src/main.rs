@@ -0,0 +1,68 @@
+extern crate clap;
+use clap::{App, Arg};
+use cluster_gen::{generate, Parser};
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+fn main() {
+    let m = App::new("cluster_gen")
+        .about("Generates Rust cluster modules from the Matter data-model XML")
+        .arg(
+            Arg::with_name("out")
+                .short("o")
+                .long("out")
+                .takes_value(true)
+                .help("The directory to write the modules to (Default: print to stdout)"),
+        )
+        .arg(
+            Arg::with_name("xml")
+                .help("The cluster XML files")
+                .required(true)
+                .multiple(true),
+        )
+        .get_matches();
+
+    let mut parser = Parser::new();
+    for file in m.values_of("xml").unwrap() {
+        let xml = fs::read_to_string(file).unwrap_or_else(|e| {
+            eprintln!("Couldn't read {}: {}", file, e);
+            process::exit(1);
+        });
+        let source = Path::new(file)
+            .file_name()
+            .map_or(file.to_owned(), |f| f.to_string_lossy().into_owned());
+        if let Err(e) = parser.parse(&source, &xml) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    let modules = generate(&parser.finish());
+
+    if let Some(out) = m.value_of("out") {
+        let out = Path::new(out);
+        if let Err(e) = fs::create_dir_all(out) {
+            eprintln!("Couldn't create {}: {}", out.display(), e);
+            process::exit(1);
+        }
+        let mut files = Vec::new();
+        for module in modules {
+            let path = out.join(format!("{}.rs", module.name));
+            if let Err(e) = fs::write(&path, module.code) {
+                eprintln!("Couldn't write {}: {}", path.display(), e);
+                process::exit(1);
+            }
+            println!("Generated {}", path.display());
+            files.push(path);
+        }
+        // Best effort, the generated code is readable even without this
+        let _ = Command::new("rustfmt")
+            .args(["--edition", "2018"])
+            .args(&files)
+            .status();
+    } else {
+        for module in modules {
+            println!("// ---- {}.rs ----\n{}", module.name, module.code);
+        }
+    }
+}
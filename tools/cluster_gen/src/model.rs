@@ -0,0 +1,148 @@
+/// The primitive data types of the Matter data model, as far as the generated code cares
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    OctetStr,
+    CharStr,
+    /// An enum, bitmap or struct defined in the XML, or elsewhere
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldType {
+    pub base: DataType,
+    pub list: bool,
+    pub nullable: bool,
+    pub optional: bool,
+}
+
+impl FieldType {
+    pub fn new(base: DataType) -> Self {
+        Self {
+            base,
+            list: false,
+            nullable: false,
+            optional: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub id: u32,
+    pub name: String,
+    pub ty: FieldType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Privilege {
+    View,
+    Operate,
+    Manage,
+    Administer,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: String,
+    pub base: DataType,
+    pub items: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitmapDef {
+    pub name: String,
+    pub base: DataType,
+    pub bits: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+/// The enums, bitmaps and structs defined either in a cluster, or globally
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    pub enums: Vec<EnumDef>,
+    pub bitmaps: Vec<BitmapDef>,
+    pub structs: Vec<StructDef>,
+}
+
+impl Types {
+    pub fn is_empty(&self) -> bool {
+        self.enums.is_empty() && self.bitmaps.is_empty() && self.structs.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeDef {
+    pub id: u32,
+    pub name: String,
+    pub ty: FieldType,
+    pub default: Option<String>,
+    pub read: Option<Privilege>,
+    pub write: Option<Privilege>,
+    pub fabric_scoped: bool,
+    pub fabric_sensitive: bool,
+    pub timed: bool,
+    pub persistent: bool,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandResponse {
+    /// The command is answered with a status code only
+    Status,
+    /// The command is answered with the response command of this name
+    Command(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandDef {
+    pub id: u32,
+    pub name: String,
+    /// Client to Server commands are handled by the cluster, the others are responses
+    pub to_server: bool,
+    pub fields: Vec<Field>,
+    pub response: CommandResponse,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventDef {
+    pub id: u32,
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterDef {
+    pub id: u32,
+    pub name: String,
+    pub revision: Option<u16>,
+    /// The XML file this cluster was read from
+    pub source: String,
+    pub types: Types,
+    pub attributes: Vec<AttributeDef>,
+    pub commands: Vec<CommandDef>,
+    pub events: Vec<EventDef>,
+}
+
+/// Everything read from a set of XML files
+#[derive(Debug, Default)]
+pub struct Spec {
+    pub clusters: Vec<ClusterDef>,
+    /// Types that aren't tied to any particular cluster
+    pub global: Types,
+}
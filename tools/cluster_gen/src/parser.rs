@@ -0,0 +1,546 @@
+use crate::model::*;
+use roxmltree::Node;
+
+/// Types that name the clusters they belong to, they are assigned once all the files are read
+struct PendingType {
+    clusters: Vec<u32>,
+    item: TypeItem,
+}
+
+enum TypeItem {
+    Enum(EnumDef),
+    Bitmap(BitmapDef),
+    Struct(StructDef),
+}
+
+/// Reads cluster definitions from XML files
+///
+/// Two flavours are supported:
+/// - The ZAP format (a <configurator> root), as used in the data-model directory of the SDK
+/// - The format generated from the specification (a <cluster> root)
+#[derive(Default)]
+pub struct Parser {
+    spec: Spec,
+    pending: Vec<PendingType>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn parse(&mut self, source: &str, xml: &str) -> Result<(), String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| format!("{}: {}", source, e))?;
+        let root = doc.root_element();
+        match root.tag_name().name() {
+            "configurator" => self.parse_zap(source, root),
+            "cluster" => self.parse_spec(source, root),
+            other => Err(format!("{}: Unknown root element <{}>", source, other)),
+        }
+    }
+
+    /// Returns everything read so far
+    pub fn finish(mut self) -> Spec {
+        for p in self.pending.drain(..) {
+            let mut assigned = false;
+            for c in self
+                .spec
+                .clusters
+                .iter_mut()
+                .filter(|c| p.clusters.contains(&c.id))
+            {
+                add_type(&mut c.types, &p.item);
+                assigned = true;
+            }
+            if !assigned {
+                add_type(&mut self.spec.global, &p.item);
+            }
+        }
+        self.spec
+    }
+
+    fn parse_zap(&mut self, source: &str, root: Node) -> Result<(), String> {
+        for node in elements(root) {
+            let clusters = elements(node)
+                .filter(|n| n.has_tag_name("cluster"))
+                .filter_map(|n| n.attribute("code").and_then(parse_int))
+                .map(|c| c as u32)
+                .collect();
+            let item = match node.tag_name().name() {
+                "enum" => TypeItem::Enum(EnumDef {
+                    name: required(node, "name")?.to_owned(),
+                    base: parse_type(node.attribute("type").unwrap_or("enum8")),
+                    items: elements(node)
+                        .filter(|n| n.has_tag_name("item"))
+                        .map(|n| Ok((required(n, "name")?.to_owned(), required_int(n, "value")?)))
+                        .collect::<Result<_, String>>()?,
+                }),
+                "bitmap" => TypeItem::Bitmap(BitmapDef {
+                    name: required(node, "name")?.to_owned(),
+                    base: parse_type(node.attribute("type").unwrap_or("bitmap8")),
+                    bits: elements(node)
+                        .filter(|n| n.has_tag_name("field"))
+                        .map(|n| Ok((required(n, "name")?.to_owned(), required_int(n, "mask")?)))
+                        .collect::<Result<_, String>>()?,
+                }),
+                "struct" => TypeItem::Struct(StructDef {
+                    name: required(node, "name")?.to_owned(),
+                    fields: zap_fields(node, "item")?,
+                }),
+                "cluster" => {
+                    let cluster = zap_cluster(source, node)?;
+                    self.spec.clusters.push(cluster);
+                    continue;
+                }
+                _ => continue,
+            };
+            self.pending.push(PendingType { clusters, item });
+        }
+        Ok(())
+    }
+
+    fn parse_spec(&mut self, source: &str, root: Node) -> Result<(), String> {
+        let mut types = Types::default();
+        if let Some(data_types) = child(root, "dataTypes") {
+            for node in elements(data_types) {
+                let item = match node.tag_name().name() {
+                    "enum" => {
+                        let items: Vec<(String, u64)> = elements(node)
+                            .filter(|n| n.has_tag_name("item"))
+                            .map(|n| {
+                                Ok((required(n, "name")?.to_owned(), required_int(n, "value")?))
+                            })
+                            .collect::<Result<_, String>>()?;
+                        let max = items.iter().map(|(_, v)| *v).max().unwrap_or(0);
+                        TypeItem::Enum(EnumDef {
+                            name: required(node, "name")?.to_owned(),
+                            base: if max > 0xff {
+                                DataType::U16
+                            } else {
+                                DataType::U8
+                            },
+                            items,
+                        })
+                    }
+                    "bitmap" => {
+                        let bits = elements(node)
+                            .filter(|n| n.has_tag_name("bitfield"))
+                            .map(|n| Ok((required(n, "name")?.to_owned(), spec_bitmask(n)?)))
+                            .collect::<Result<Vec<(String, u64)>, String>>()?;
+                        let all = bits.iter().fold(0, |acc, (_, m)| acc | *m);
+                        TypeItem::Bitmap(BitmapDef {
+                            name: required(node, "name")?.to_owned(),
+                            base: if all > 0xffff_ffff {
+                                DataType::U64
+                            } else if all > 0xffff {
+                                DataType::U32
+                            } else if all > 0xff {
+                                DataType::U16
+                            } else {
+                                DataType::U8
+                            },
+                            bits,
+                        })
+                    }
+                    "struct" => TypeItem::Struct(StructDef {
+                        name: required(node, "name")?.to_owned(),
+                        fields: spec_fields(node)?,
+                    }),
+                    _ => continue,
+                };
+                add_type(&mut types, &item);
+            }
+        }
+
+        let mut attributes = Vec::new();
+        if let Some(attrs) = child(root, "attributes") {
+            for node in elements(attrs).filter(|n| n.has_tag_name("attribute")) {
+                if !is_supported(node) {
+                    continue;
+                }
+                let mut attr = AttributeDef {
+                    id: required_int(node, "id")? as u32,
+                    name: required(node, "name")?.to_owned(),
+                    ty: spec_type(node),
+                    default: node.attribute("default").map(|d| d.to_owned()),
+                    read: Some(Privilege::View),
+                    write: None,
+                    fabric_scoped: false,
+                    fabric_sensitive: false,
+                    timed: false,
+                    persistent: false,
+                    fixed: false,
+                };
+                if let Some(access) = child(node, "access") {
+                    if access.attribute("read") == Some("false") {
+                        attr.read = None;
+                    } else if let Some(p) = access.attribute("readPrivilege") {
+                        attr.read = Some(parse_privilege(p));
+                    }
+                    if is_true(access.attribute("write")) {
+                        attr.write = Some(
+                            access
+                                .attribute("writePrivilege")
+                                .map_or(Privilege::Operate, parse_privilege),
+                        );
+                    }
+                    attr.fabric_scoped = is_true(access.attribute("fabricScoped"));
+                    attr.fabric_sensitive = is_true(access.attribute("fabricSensitive"));
+                    attr.timed = is_true(access.attribute("timed"));
+                }
+                if let Some(quality) = child(node, "quality") {
+                    match quality.attribute("persistence") {
+                        Some("fixed") => attr.fixed = true,
+                        Some("nonVolatile") => attr.persistent = true,
+                        _ => (),
+                    }
+                }
+                attributes.push(attr);
+            }
+        }
+
+        let mut commands = Vec::new();
+        if let Some(cmds) = child(root, "commands") {
+            for node in elements(cmds).filter(|n| n.has_tag_name("command")) {
+                if !is_supported(node) {
+                    continue;
+                }
+                let response = match node.attribute("response") {
+                    None | Some("Y") | Some("N") => CommandResponse::Status,
+                    Some(r) => CommandResponse::Command(r.to_owned()),
+                };
+                commands.push(CommandDef {
+                    id: required_int(node, "id")? as u32,
+                    name: required(node, "name")?.to_owned(),
+                    to_server: node.attribute("direction") != Some("responseFromServer")
+                        && node.attribute("direction") != Some("commandToClient"),
+                    fields: spec_fields(node)?,
+                    response,
+                });
+            }
+        }
+
+        let mut events = Vec::new();
+        if let Some(evts) = child(root, "events") {
+            for node in elements(evts).filter(|n| n.has_tag_name("event")) {
+                if !is_supported(node) {
+                    continue;
+                }
+                events.push(EventDef {
+                    id: required_int(node, "id")? as u32,
+                    name: required(node, "name")?.to_owned(),
+                    fields: spec_fields(node)?,
+                });
+            }
+        }
+
+        let revision = root
+            .attribute("revision")
+            .and_then(parse_int)
+            .map(|r| r as u16);
+        // Some files define a family of clusters sharing the same definition
+        let mut ids = Vec::new();
+        if let Some(id) = root.attribute("id").and_then(parse_int) {
+            ids.push((id as u32, required(root, "name")?.to_owned()));
+        } else if let Some(cluster_ids) = child(root, "clusterIds") {
+            for n in elements(cluster_ids).filter(|n| n.has_tag_name("clusterId")) {
+                if let Some(id) = n.attribute("id").and_then(parse_int) {
+                    ids.push((id as u32, required(n, "name")?.to_owned()));
+                }
+            }
+        }
+        if ids.is_empty() {
+            return Err(format!("{}: The cluster doesn't have an id", source));
+        }
+
+        for (id, name) in ids {
+            self.spec.clusters.push(ClusterDef {
+                id,
+                name,
+                revision,
+                source: source.to_owned(),
+                types: types.clone(),
+                attributes: attributes.clone(),
+                commands: commands.clone(),
+                events: events.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn add_type(types: &mut Types, item: &TypeItem) {
+    match item {
+        TypeItem::Enum(e) => types.enums.push(e.clone()),
+        TypeItem::Bitmap(b) => types.bitmaps.push(b.clone()),
+        TypeItem::Struct(s) => types.structs.push(s.clone()),
+    }
+}
+
+fn zap_cluster(source: &str, node: Node) -> Result<ClusterDef, String> {
+    let name = child(node, "name")
+        .and_then(|n| n.text())
+        .ok_or_else(|| format!("{}: Cluster without a name", source))?
+        .trim()
+        .to_owned();
+    let id = child(node, "code")
+        .and_then(|n| n.text())
+        .and_then(|t| parse_int(t.trim()))
+        .ok_or_else(|| format!("{}: Cluster {} without a code", source, name))? as u32;
+
+    let mut cluster = ClusterDef {
+        id,
+        name,
+        revision: None,
+        source: source.to_owned(),
+        types: Types::default(),
+        attributes: Vec::new(),
+        commands: Vec::new(),
+        events: Vec::new(),
+    };
+
+    for n in elements(node) {
+        match n.tag_name().name() {
+            // The ClusterRevision
+            "globalAttribute" if n.attribute("code").and_then(parse_int) == Some(0xfffd) => {
+                cluster.revision = n.attribute("value").and_then(parse_int).map(|r| r as u16);
+            }
+            "attribute" => {
+                if n.attribute("side") == Some("client") {
+                    continue;
+                }
+                let name = n
+                    .text()
+                    .map(|t| t.trim())
+                    .filter(|t| !t.is_empty())
+                    .or_else(|| child(n, "description").and_then(|d| d.text()))
+                    .ok_or_else(|| format!("{}: Attribute without a name", source))?
+                    .trim()
+                    .to_owned();
+                let mut attr = AttributeDef {
+                    id: required_int(n, "code")? as u32,
+                    name,
+                    ty: zap_type(n),
+                    default: n.attribute("default").map(|d| d.to_owned()),
+                    read: Some(Privilege::View),
+                    write: None,
+                    fabric_scoped: false,
+                    fabric_sensitive: is_true(n.attribute("isFabricSensitive")),
+                    timed: is_true(n.attribute("mustUseTimedWrite")),
+                    persistent: false,
+                    fixed: false,
+                };
+                if is_true(n.attribute("writable")) {
+                    attr.write = Some(Privilege::Operate);
+                }
+                for access in elements(n).filter(|a| a.has_tag_name("access")) {
+                    let privilege = access
+                        .attribute("privilege")
+                        .or_else(|| access.attribute("role"))
+                        .map_or(Privilege::View, parse_privilege);
+                    match access.attribute("op") {
+                        Some("read") => attr.read = Some(privilege),
+                        Some("write") => attr.write = Some(privilege),
+                        _ => (),
+                    }
+                }
+                cluster.attributes.push(attr);
+            }
+            "command" => {
+                let response = match n.attribute("response") {
+                    None => CommandResponse::Status,
+                    Some(r) => CommandResponse::Command(r.to_owned()),
+                };
+                cluster.commands.push(CommandDef {
+                    id: required_int(n, "code")? as u32,
+                    name: required(n, "name")?.to_owned(),
+                    to_server: n.attribute("source") != Some("server"),
+                    fields: zap_fields(n, "arg")?,
+                    response,
+                });
+            }
+            "event" => {
+                cluster.events.push(EventDef {
+                    id: required_int(n, "code")? as u32,
+                    name: required(n, "name")?.to_owned(),
+                    fields: zap_fields(n, "field")?,
+                });
+            }
+            _ => (),
+        }
+    }
+    Ok(cluster)
+}
+
+/// The type of a ZAP attribute/arg/item/field element
+fn zap_type(node: Node) -> FieldType {
+    let ty = node.attribute("type").unwrap_or("");
+    let mut field_type = if ty.eq_ignore_ascii_case("array") {
+        let mut f = FieldType::new(parse_type(node.attribute("entryType").unwrap_or("")));
+        f.list = true;
+        f
+    } else {
+        let mut f = FieldType::new(parse_type(ty));
+        f.list = is_true(node.attribute("array"));
+        f
+    };
+    field_type.nullable = is_true(node.attribute("isNullable"));
+    field_type.optional = is_true(node.attribute("optional"));
+    field_type
+}
+
+fn zap_fields(node: Node, tag: &str) -> Result<Vec<Field>, String> {
+    elements(node)
+        .filter(|n| n.has_tag_name(tag))
+        .enumerate()
+        .map(|(i, n)| {
+            let id = n
+                .attribute("fieldId")
+                .or_else(|| n.attribute("id"))
+                .and_then(parse_int)
+                .unwrap_or(i as u64) as u32;
+            Ok(Field {
+                id,
+                name: required(n, "name")?.to_owned(),
+                ty: zap_type(n),
+            })
+        })
+        .collect()
+}
+
+/// The type of a spec attribute/field element
+fn spec_type(node: Node) -> FieldType {
+    let ty = node.attribute("type").unwrap_or("");
+    let mut field_type = if ty == "list" {
+        let entry = child(node, "entry")
+            .and_then(|e| e.attribute("type"))
+            .unwrap_or("");
+        let mut f = FieldType::new(parse_type(entry));
+        f.list = true;
+        f
+    } else {
+        FieldType::new(parse_type(ty))
+    };
+    if let Some(quality) = child(node, "quality") {
+        field_type.nullable = is_true(quality.attribute("nullable"));
+    }
+    field_type.optional = !is_mandatory(node);
+    field_type
+}
+
+fn spec_fields(node: Node) -> Result<Vec<Field>, String> {
+    elements(node)
+        .filter(|n| n.has_tag_name("field") && is_supported(*n))
+        .map(|n| {
+            Ok(Field {
+                id: required_int(n, "id")? as u32,
+                name: required(n, "name")?.to_owned(),
+                ty: spec_type(n),
+            })
+        })
+        .collect()
+}
+
+fn spec_bitmask(node: Node) -> Result<u64, String> {
+    if let Some(bit) = node.attribute("bit").and_then(parse_int) {
+        Ok(1 << bit)
+    } else {
+        let from = required_int(node, "from")?;
+        let to = required_int(node, "to")?;
+        Ok((from..=to).fold(0, |acc, b| acc | (1 << b)))
+    }
+}
+
+/// Elements that are disallowed or deprecated in the specification aren't generated
+fn is_supported(node: Node) -> bool {
+    !elements(node).any(|n| n.has_tag_name("disallowConform") || n.has_tag_name("deprecateConform"))
+}
+
+/// Elements without any conformance are considered mandatory
+fn is_mandatory(node: Node) -> bool {
+    !elements(node).any(|n| {
+        n.has_tag_name("optionalConform")
+            || n.has_tag_name("otherwiseConform")
+            || n.has_tag_name("provisionalConform")
+    })
+}
+
+/// Map the type names of both the ZAP and the spec formats to the primitive types
+pub fn parse_type(name: &str) -> DataType {
+    let normalized: String = name
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .collect::<String>()
+        .to_lowercase();
+    match normalized.as_str() {
+        "boolean" | "bool" => DataType::Bool,
+        "int8u" | "uint8" | "enum8" | "bitmap8" | "fabricidx" | "percent" | "status"
+        | "actionid" | "priority" | "namespace" | "tag" => DataType::U8,
+        "int16u" | "uint16" | "enum16" | "bitmap16" | "vendorid" | "groupid" | "endpointno"
+        | "percent100ths" | "entryidx" => DataType::U16,
+        "int24u" | "uint24" | "int32u" | "uint32" | "bitmap32" | "epochs" | "elapseds" | "utc"
+        | "clusterid" | "attribid" | "attributeid" | "devtypeid" | "commandid" | "eventid"
+        | "fieldid" | "dataver" | "transid" => DataType::U32,
+        "int40u" | "int48u" | "int56u" | "int64u" | "uint40" | "uint48" | "uint56" | "uint64"
+        | "bitmap64" | "fabricid" | "nodeid" | "epochus" | "eventno" | "systimeus"
+        | "systimems" | "posixms" | "subjectid" => DataType::U64,
+        "int8s" | "int8" => DataType::I8,
+        "int16s" | "int16" | "temperature" => DataType::I16,
+        "int24s" | "int32s" | "int24" | "int32" => DataType::I32,
+        "int40s" | "int48s" | "int56s" | "int64s" | "int40" | "int48" | "int56" | "int64"
+        | "amperagema" | "voltagemv" | "energymwh" | "powermw" => DataType::I64,
+        "single" | "float" => DataType::F32,
+        "double" => DataType::F64,
+        "octetstring" | "longoctetstring" | "octstr" | "ipadr" | "ipv4adr" | "ipv6adr"
+        | "ipv6pre" | "hwadr" => DataType::OctetStr,
+        "charstring" | "longcharstring" | "string" => DataType::CharStr,
+        _ => DataType::Named(name.to_owned()),
+    }
+}
+
+fn parse_privilege(p: &str) -> Privilege {
+    match p {
+        "operate" => Privilege::Operate,
+        "manage" => Privilege::Manage,
+        "administer" | "admin" => Privilege::Administer,
+        _ => Privilege::View,
+    }
+}
+
+/// Parse integers in decimal or hexadecimal (0x) notation
+pub fn parse_int(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse::<u64>().ok()
+    }
+}
+
+fn is_true(v: Option<&str>) -> bool {
+    matches!(v, Some("true") | Some("True") | Some("1"))
+}
+
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(|n| n.is_element())
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    elements(node).find(|n| n.has_tag_name(name))
+}
+
+fn required<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, String> {
+    node.attribute(name).ok_or_else(|| {
+        format!(
+            "Missing attribute '{}' in <{}> at {:?}",
+            name,
+            node.tag_name().name(),
+            node.document().text_pos_at(node.range().start)
+        )
+    })
+}
+
+fn required_int(node: Node, name: &str) -> Result<u64, String> {
+    let v = required(node, name)?;
+    parse_int(v).ok_or_else(|| format!("Invalid integer '{}' for '{}'", v, name))
+}
@@ -0,0 +1,28 @@
+// This file is generated by cluster_gen from the global type definitions, do not edit it by hand
+
+use bitflags::bitflags;
+use crate::error::*;
+use num_derive::FromPrimitive;
+use crate::tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV};
+
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive)]
+#[repr(u8)]
+pub enum SampleMode {
+    Off = 0x0,
+    On = 0x1,
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct SampleFeature: u16 {
+        const FAST = 0x1;
+        const QUIET = 0x2;
+    }
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct SampleEntry {
+    pub index: u16,
+    pub label: String,
+    pub payload: Option<Vec<u8>>,
+}
@@ -0,0 +1,133 @@
+// This file is generated by cluster_gen from sample.xml, do not edit it by hand
+
+#[allow(unused_imports)]
+use super::global_types::*;
+use crate::data_model::objects::*;
+use crate::error::*;
+use crate::interaction_model::command::CommandReq;
+use crate::interaction_model::core::IMStatusCode;
+use crate::interaction_model::messages::ib;
+use log::error;
+use num_derive::FromPrimitive;
+use crate::tlv::{FromTLV, Nullable, OctetStr, TLVArray, TLVArrayOwned, TLVElement, TLVWriter, TagType, ToTLV, UtfStr};
+
+pub const ID: u32 = 0xfff1fc00;
+pub const CLUSTER_REVISION: u16 = 2;
+
+#[derive(FromPrimitive, ClusterAttributes)]
+pub enum Attributes {
+    #[attribute(default = "AttrValue::Bool(true)")]
+    Enabled = 0x0,
+    #[attribute(default = "AttrValue::Uint8(5)", access = "READ | WRITE | NEED_VIEW | NEED_OPERATE")]
+    Level = 0x1,
+    #[attribute(default = "AttrValue::Uint64(0)")]
+    Total = 0x4,
+    #[attribute(default = "AttrValue::Uint8(0)", access = "READ | WRITE | NEED_VIEW | NEED_OPERATE")]
+    Mode = 0x5,
+    #[attribute(access = "READ | WRITE | NEED_VIEW | NEED_OPERATE")]
+    Label = 0x6,
+    #[attribute]
+    Entries = 0x7,
+}
+
+#[derive(FromPrimitive)]
+pub enum Commands {
+    SetLabel = 0x0,
+    Reset = 0x1,
+}
+
+#[derive(FromPrimitive)]
+pub enum RespCommands {
+    SetLabelResponse = 0x2,
+}
+
+#[derive(FromPrimitive)]
+pub enum Events {
+    LabelChanged = 0x0,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive)]
+#[repr(u8)]
+pub enum LocalStatus {
+    Success = 0x0,
+    Busy = 0x1,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+pub struct SetLabelReq<'a> {
+    pub label: UtfStr<'a>,
+    pub data: Nullable<OctetStr<'a>>,
+    pub ids: TLVArray<'a, u16>,
+    pub offset: i8,
+    pub delta: i16,
+    pub scale: f32,
+    // SampleMode
+    pub mode: Option<u8>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct SetLabelResponse {
+    // LocalStatus
+    pub status: u8,
+    pub label: String,
+    pub entries: TLVArrayOwned<SampleEntry>,
+}
+
+#[derive(FromTLV, ToTLV)]
+pub struct LabelChangedEvent {
+    pub label: String,
+    // SampleFeature
+    pub features: u16,
+}
+
+#[derive(ClusterType)]
+#[cluster(id = "ID", attributes = "Attributes", commands, read = "read_custom", write = "write_custom")]
+pub struct SampleCluster {
+    base: Cluster,
+}
+
+impl SampleCluster {
+    pub fn new() -> Result<Box<Self>, Error> {
+        Ok(Box::new(Self {
+            base: Self::new_base()?,
+        }))
+    }
+
+    fn read_custom(&self, _encoder: &mut dyn Encoder, attr: &AttrDetails) {
+        match num::FromPrimitive::from_u16(attr.attr_id) {
+            Some(Attributes::Label) => {
+                // TODO: Encode the attribute
+            }
+            Some(Attributes::Entries) => {
+                // TODO: Encode the attribute
+            }
+            _ => {
+                error!("Unsupported Attribute: this shouldn't happen");
+            }
+        }
+    }
+
+    fn write_custom(&mut self, attr: &AttrDetails, data: &TLVElement) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr.attr_id) {
+            Some(Attributes::Label) => {
+                // TODO: Decode and store the attribute
+                Err(IMStatusCode::UnsupportedWrite)
+            }
+            _ => self.base.write_attribute_from_tlv(attr.attr_id, data),
+        }
+    }
+}
+
+#[cluster_commands]
+impl SampleCluster {
+    #[command(id = "Commands::SetLabel", response = "RespCommands::SetLabelResponse")]
+    fn handle_set_label(&mut self, _req: SetLabelReq, _cmd_req: &mut CommandReq) -> Result<SetLabelResponse, IMStatusCode> {
+        Err(IMStatusCode::UnsupportedCommand)
+    }
+
+    #[command(id = "Commands::Reset")]
+    fn handle_reset(&mut self, _cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        Err(IMStatusCode::UnsupportedCommand)
+    }
+}
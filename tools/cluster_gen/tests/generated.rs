@@ -0,0 +1,71 @@
+//! Builds the code generated from fixtures/sample.xml against the matter crate
+//!
+//! The generated code is checked in under fixtures/generated, as the generator prints it. The test
+//! below fails when it gets out of date, run it with UPDATE_FIXTURES=1 to update it.
+
+// The generated modules are meant to be a part of the matter crate, that they refer to as `crate`
+use matter::{data_model, error, interaction_model, tlv};
+
+#[allow(dead_code)]
+#[rustfmt::skip]
+#[path = "fixtures/generated/global_types.rs"]
+mod global_types;
+
+#[allow(dead_code)]
+#[rustfmt::skip]
+#[path = "fixtures/generated/sample.rs"]
+mod sample;
+
+use cluster_gen::{generate, Parser};
+use data_model::objects::ClusterType;
+use tlv::{FromTLV, TLVArrayOwned, TLVWriter, TagType, ToTLV};
+
+#[test]
+fn test_generated_is_up_to_date() {
+    let mut parser = Parser::new();
+    parser
+        .parse("sample.xml", include_str!("fixtures/sample.xml"))
+        .unwrap();
+    for module in generate(&parser.finish()) {
+        if module.name == "mod" {
+            continue;
+        }
+        let path = format!("tests/fixtures/generated/{}.rs", module.name);
+        if std::env::var_os("UPDATE_FIXTURES").is_some() {
+            std::fs::write(&path, &module.code).unwrap();
+        }
+        let code = std::fs::read_to_string(&path).unwrap();
+        assert!(code == module.code, "{} is out of date", path);
+    }
+}
+
+#[test]
+fn test_generated_cluster() {
+    let cluster = sample::SampleCluster::new().unwrap();
+    assert_eq!(cluster.base().id(), sample::ID);
+    assert!(cluster
+        .base()
+        .read_attribute_raw(sample::Attributes::Level as u16)
+        .is_ok());
+}
+
+#[test]
+fn test_generated_struct() {
+    let resp = sample::SetLabelResponse {
+        status: sample::LocalStatus::Busy as u8,
+        label: "kitchen".to_owned(),
+        entries: TLVArrayOwned::from_tlv(&tlv::get_root_node(&[0x16, 0x18]).unwrap()).unwrap(),
+    };
+    let mut buf = [0u8; 64];
+    let mut wb = matter::utils::writebuf::WriteBuf::new(&mut buf, 64);
+    let mut tw = TLVWriter::new(&mut wb);
+    resp.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+    let len = wb.as_borrow_slice().len();
+
+    let decoded =
+        sample::SetLabelResponse::from_tlv(&tlv::get_root_node_struct(&buf[..len]).unwrap())
+            .unwrap();
+    assert_eq!(decoded.status, sample::LocalStatus::Busy as u8);
+    assert_eq!(decoded.label, "kitchen");
+    assert_eq!(decoded.entries.iter().count(), 0);
+}
@@ -0,0 +1,490 @@
+//! Printing of Matter messages: the headers, and the payload according to its protocol
+
+use matter::tlv::{TLVValue, TagType};
+use matter::transport::plain_hdr::{MsgFlags, PlainHdr};
+use matter::transport::proto_hdr::ProtoHdr;
+use matter::utils::parsebuf::ParseBuf;
+use std::convert::TryInto;
+use std::io::{self, Write};
+
+const PROTO_ID_SECURE_CHANNEL: u16 = 0x00;
+const PROTO_ID_INTERACTION_MODEL: u16 = 0x01;
+
+const SC_STANDALONE_ACK: u8 = 0x10;
+const SC_STATUS_REPORT: u8 = 0x40;
+
+/// A session key, with the node ID of the sender that it decrypts the messages of
+pub struct Key {
+    pub key: Vec<u8>,
+    pub node_id: u64,
+}
+
+/// The name of a message, and the names of the tags of its fields
+type MsgInfo = (&'static str, &'static [(u8, &'static str)]);
+
+fn sc_msg_info(opcode: u8) -> Option<MsgInfo> {
+    let info: MsgInfo = match opcode {
+        0x00 => ("MsgCounterSyncReq", &[]),
+        0x01 => ("MsgCounterSyncResp", &[]),
+        SC_STANDALONE_ACK => ("MRPStandAloneAck", &[]),
+        0x20 => (
+            "PBKDFParamRequest",
+            &[
+                (1, "InitiatorRandom"),
+                (2, "InitiatorSessionId"),
+                (3, "PasscodeId"),
+                (4, "HasPBKDFParameters"),
+                (5, "InitiatorSEDParams"),
+            ],
+        ),
+        0x21 => (
+            "PBKDFParamResponse",
+            &[
+                (1, "InitiatorRandom"),
+                (2, "ResponderRandom"),
+                (3, "ResponderSessionId"),
+                (4, "PBKDFParameters"),
+                (5, "ResponderSEDParams"),
+            ],
+        ),
+        0x22 => ("PASEPake1", &[(1, "pA")]),
+        0x23 => ("PASEPake2", &[(1, "pB"), (2, "cB")]),
+        0x24 => ("PASEPake3", &[(1, "cA")]),
+        0x30 => (
+            "CASESigma1",
+            &[
+                (1, "InitiatorRandom"),
+                (2, "InitiatorSessionId"),
+                (3, "DestinationId"),
+                (4, "InitiatorEphPubKey"),
+                (5, "InitiatorSEDParams"),
+                (6, "ResumptionId"),
+                (7, "InitiatorResumeMIC"),
+            ],
+        ),
+        0x31 => (
+            "CASESigma2",
+            &[
+                (1, "ResponderRandom"),
+                (2, "ResponderSessionId"),
+                (3, "ResponderEphPubKey"),
+                (4, "Encrypted2"),
+                (5, "ResponderSEDParams"),
+            ],
+        ),
+        0x32 => ("CASESigma3", &[(1, "Encrypted3")]),
+        0x33 => (
+            "CASESigma2Resume",
+            &[
+                (1, "ResumptionId"),
+                (2, "Sigma2ResumeMIC"),
+                (3, "ResponderSessionId"),
+                (4, "ResponderSEDParams"),
+            ],
+        ),
+        SC_STATUS_REPORT => ("StatusReport", &[]),
+        _ => return None,
+    };
+    Some(info)
+}
+
+fn im_msg_info(opcode: u8) -> Option<MsgInfo> {
+    let info: MsgInfo = match opcode {
+        1 => ("StatusResponse", &[(0, "Status")]),
+        2 => (
+            "ReadRequest",
+            &[
+                (0, "AttributeRequests"),
+                (1, "EventRequests"),
+                (2, "EventFilters"),
+                (3, "FabricFiltered"),
+                (4, "DataVersionFilters"),
+            ],
+        ),
+        3 => (
+            "SubscribeRequest",
+            &[
+                (0, "KeepSubscriptions"),
+                (1, "MinIntervalFloor"),
+                (2, "MaxIntervalCeiling"),
+                (3, "AttributeRequests"),
+                (4, "EventRequests"),
+                (5, "EventFilters"),
+                (7, "FabricFiltered"),
+                (8, "DataVersionFilters"),
+            ],
+        ),
+        4 => (
+            "SubscribeResponse",
+            &[(0, "SubscriptionId"), (2, "MaxInterval")],
+        ),
+        5 => (
+            "ReportData",
+            &[
+                (0, "SubscriptionId"),
+                (1, "AttributeReports"),
+                (2, "EventReports"),
+                (3, "MoreChunkedMessages"),
+                (4, "SuppressResponse"),
+            ],
+        ),
+        6 => (
+            "WriteRequest",
+            &[
+                (0, "SuppressResponse"),
+                (1, "TimedRequest"),
+                (2, "WriteRequests"),
+                (3, "MoreChunkedMessages"),
+            ],
+        ),
+        7 => ("WriteResponse", &[(0, "WriteResponses")]),
+        8 => (
+            "InvokeRequest",
+            &[
+                (0, "SuppressResponse"),
+                (1, "TimedRequest"),
+                (2, "InvokeRequests"),
+            ],
+        ),
+        9 => (
+            "InvokeResponse",
+            &[(0, "SuppressResponse"), (1, "InvokeResponses")],
+        ),
+        10 => ("TimedRequest", &[(0, "Timeout")]),
+        _ => return None,
+    };
+    Some(info)
+}
+
+const GENERAL_CODES: [&str; 17] = [
+    "Success",
+    "Failure",
+    "BadPrecondition",
+    "OutOfRange",
+    "BadRequest",
+    "Unsupported",
+    "Unexpected",
+    "ResourceExhausted",
+    "Busy",
+    "Timeout",
+    "Continue",
+    "Aborted",
+    "InvalidArgument",
+    "NotFound",
+    "AlreadyExists",
+    "PermissionDenied",
+    "DataLoss",
+];
+
+const SC_STATUS_CODES: [&str; 6] = [
+    "SessionEstablishmentSuccess",
+    "NoSharedTrustRoots",
+    "InvalidParameter",
+    "CloseSession",
+    "Busy",
+    "SessionNotFound",
+];
+
+/// Print a Matter message: the headers, and the payload, which is decrypted with the first of the
+/// keys that works
+pub fn print_msg(msg: &[u8], keys: &[Key]) {
+    // Nothing is left to print to if stdout is gone
+    let _ = write_msg(&mut io::stdout().lock(), msg, keys);
+}
+
+/// Write a Matter message as [print_msg] prints it
+fn write_msg(out: &mut dyn Write, msg: &[u8], keys: &[Key]) -> io::Result<()> {
+    let mut buf = msg.to_vec();
+    let len = buf.len();
+    let mut parsebuf = ParseBuf::new(&mut buf, len);
+    let mut plain_hdr = PlainHdr::default();
+    if let Err(e) = plain_hdr.decode(&mut parsebuf) {
+        writeln!(out, "Invalid message header: {}", e)?;
+        return write_hex(out, "Message", msg);
+    }
+    write_plain_hdr(out, &plain_hdr, parsebuf.parsed_as_slice())?;
+
+    let mut proto_hdr = ProtoHdr::default();
+    let payload = if !plain_hdr.is_encrypted() {
+        if let Err(e) = proto_hdr.decrypt_and_decode(&plain_hdr, &mut parsebuf, 0, None) {
+            return writeln!(out, "  Invalid protocol header: {}", e);
+        }
+        parsebuf.as_slice().to_vec()
+    } else {
+        match decrypt(msg, keys, &mut proto_hdr) {
+            Some(payload) => payload,
+            None => {
+                if keys.is_empty() {
+                    writeln!(out, "  Encrypted, no session key")?;
+                } else {
+                    writeln!(out, "  Encrypted, none of the session keys decrypts it")?;
+                }
+                return write_hex(out, "  Encrypted payload", parsebuf.as_slice());
+            }
+        }
+    };
+
+    write_proto_hdr(out, &proto_hdr)?;
+    write_payload(out, &proto_hdr, &payload)
+}
+
+/// The payload of the message, decrypted with the first key that works
+fn decrypt(msg: &[u8], keys: &[Key], proto_hdr: &mut ProtoHdr) -> Option<Vec<u8>> {
+    for key in keys {
+        // Every try decrypts a fresh copy, in place
+        let mut buf = msg.to_vec();
+        let len = buf.len();
+        let mut parsebuf = ParseBuf::new(&mut buf, len);
+        let mut plain_hdr = PlainHdr::default();
+        if plain_hdr.decode(&mut parsebuf).is_err() {
+            return None;
+        }
+        if proto_hdr
+            .decrypt_and_decode(&plain_hdr, &mut parsebuf, key.node_id, Some(&key.key))
+            .is_ok()
+        {
+            return Some(parsebuf.as_slice().to_vec());
+        }
+    }
+    None
+}
+
+fn write_plain_hdr(out: &mut dyn Write, plain_hdr: &PlainHdr, hdr: &[u8]) -> io::Result<()> {
+    write!(
+        out,
+        "Message: session {}, counter {}",
+        plain_hdr.sess_id, plain_hdr.ctr
+    )?;
+    if let Some(src) = plain_hdr.get_src_u64() {
+        write!(out, ", source {:#018x}", src)?;
+    }
+    // The destination is at the end of the header
+    if plain_hdr.flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+        let dest = u64::from_le_bytes(hdr[hdr.len() - 8..].try_into().unwrap());
+        write!(out, ", destination {:#018x}", dest)?;
+    } else if plain_hdr.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+        let group = u16::from_le_bytes(hdr[hdr.len() - 2..].try_into().unwrap());
+        write!(out, ", group {:#06x}", group)?;
+    }
+    writeln!(out)
+}
+
+fn write_proto_hdr(out: &mut dyn Write, proto_hdr: &ProtoHdr) -> io::Result<()> {
+    write!(out, "  Exchange: {}", proto_hdr)?;
+    if let Some(vendor) = proto_hdr.proto_vendor_id {
+        write!(out, ", Vendor: {:#06x}", vendor)?;
+    }
+    if let Some(ack) = proto_hdr.ack_msg_ctr {
+        write!(out, ", Ack: {}", ack)?;
+    }
+    writeln!(out)
+}
+
+fn write_payload(out: &mut dyn Write, proto_hdr: &ProtoHdr, payload: &[u8]) -> io::Result<()> {
+    let opcode = proto_hdr.proto_opcode;
+    let info = match (proto_hdr.proto_vendor_id, proto_hdr.proto_id) {
+        (None, PROTO_ID_SECURE_CHANNEL) => sc_msg_info(opcode).map(|i| ("Secure Channel", i)),
+        (None, PROTO_ID_INTERACTION_MODEL) => im_msg_info(opcode).map(|i| ("Interaction Model", i)),
+        _ => None,
+    };
+    let (protocol, (name, tags)) = match info {
+        Some(info) => info,
+        None => {
+            writeln!(out, "  Unknown message {:#04x}", opcode)?;
+            return write_tlv_or_hex(out, payload, &[]);
+        }
+    };
+    writeln!(out, "  {} {}", protocol, name)?;
+
+    if proto_hdr.proto_id == PROTO_ID_SECURE_CHANNEL {
+        match opcode {
+            SC_STANDALONE_ACK => return Ok(()),
+            SC_STATUS_REPORT => return write_status_report(out, payload),
+            // The message counter sync messages aren't TLV
+            0x00 | 0x01 => return write_hex(out, "  Payload", payload),
+            _ => (),
+        }
+    }
+    write_tlv_or_hex(out, payload, tags)
+}
+
+fn write_status_report(out: &mut dyn Write, payload: &[u8]) -> io::Result<()> {
+    if payload.len() < 8 {
+        return write_hex(out, "  Invalid status report", payload);
+    }
+    let general_code = u16::from_le_bytes(payload[0..2].try_into().unwrap());
+    let proto_id = u32::from_le_bytes(payload[2..6].try_into().unwrap());
+    let proto_code = u16::from_le_bytes(payload[6..8].try_into().unwrap());
+
+    let general_name = GENERAL_CODES.get(general_code as usize).unwrap_or(&"?");
+    let proto_name = if proto_id == PROTO_ID_SECURE_CHANNEL as u32 {
+        SC_STATUS_CODES.get(proto_code as usize).unwrap_or(&"?")
+    } else {
+        "?"
+    };
+    writeln!(out, "  General code: {} ({})", general_code, general_name)?;
+    writeln!(out, "  Protocol: {:#010x}", proto_id)?;
+    writeln!(out, "  Protocol code: {} ({})", proto_code, proto_name)?;
+    if payload.len() > 8 {
+        write_hex(out, "  Protocol data", &payload[8..])?;
+    }
+    Ok(())
+}
+
+/// Write the payload as TLV, with the names of the members of the root struct
+fn write_tlv_or_hex(out: &mut dyn Write, payload: &[u8], tags: &[(u8, &str)]) -> io::Result<()> {
+    if payload.is_empty() {
+        return Ok(());
+    }
+    let value = match TLVValue::from_slice(payload) {
+        Ok(value) => value,
+        Err(_) => return write_hex(out, "  Payload", payload),
+    };
+    let members = match &value {
+        TLVValue::Struct(members) => members,
+        _ => return writeln!(out, "  {}", indent(&value.to_string())),
+    };
+
+    writeln!(out, "  {{")?;
+    for (tag, member) in members {
+        let name = match tag {
+            TagType::Context(t) => tags.iter().find(|(n, _)| n == t).map(|(_, name)| *name),
+            _ => None,
+        };
+        let name = match (tag, name) {
+            (TagType::Context(0xFF), None) => "InteractionModelRevision",
+            (_, Some(name)) => name,
+            (_, None) => "?",
+        };
+        writeln!(
+            out,
+            "      {} ({}): {},",
+            name,
+            tag_str(tag),
+            indent(&member.to_string())
+        )?;
+    }
+    writeln!(out, "  }}")
+}
+
+fn tag_str(tag: &TagType) -> String {
+    match tag {
+        TagType::Context(t) => t.to_string(),
+        _ => format!("{:?}", tag),
+    }
+}
+
+// The lines after the first one of a value, in the indentation of a member
+fn indent(value: &str) -> String {
+    value.replace('\n', "\n      ")
+}
+
+fn write_hex(out: &mut dyn Write, what: &str, b: &[u8]) -> io::Result<()> {
+    let hex: Vec<String> = b.iter().map(|b| format!("{:02x}", b)).collect();
+    writeln!(out, "{} ({} bytes): {}", what, b.len(), hex.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matter::transport::proto_hdr::encrypt_in_place;
+    use matter::utils::writebuf::WriteBuf;
+
+    // A plain header without a source, the security flags and a counter of 5
+    const PLAIN_HDR: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 5, 0, 0, 0];
+
+    fn dissect(msg: &[u8], keys: &[Key]) -> String {
+        let mut out = Vec::new();
+        write_msg(&mut out, msg, keys).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plain_msg() {
+        // An IM StatusResponse, with the status and the IM revision
+        let mut msg = PLAIN_HDR.to_vec();
+        msg.extend_from_slice(&[0x05, 0x01, 0x10, 0x00, 0x01, 0x00]);
+        msg.extend_from_slice(&[0x15, 0x24, 0x00, 0x00, 0x24, 0xFF, 0x01, 0x18]);
+        let out = dissect(&msg, &[]);
+        assert!(out.starts_with("Message: session 0, counter 5\n"));
+        assert!(out.contains("  Exchange: ExId: 16, Proto: 1, Opcode: 1"));
+        assert!(out.contains("  Interaction Model StatusResponse\n"));
+        assert!(out.contains("      Status (0): "));
+        assert!(out.contains("      InteractionModelRevision (255): "));
+
+        // A status report, and an unknown message of a known protocol
+        let mut msg = PLAIN_HDR.to_vec();
+        msg.extend_from_slice(&[0x05, SC_STATUS_REPORT, 0x10, 0x00, 0x00, 0x00]);
+        msg.extend_from_slice(&[1, 0, 0, 0, 0, 0, 5, 0, 0xaa]);
+        let out = dissect(&msg, &[]);
+        assert!(out.contains("  Secure Channel StatusReport\n"));
+        assert!(out.contains("  General code: 1 (Failure)\n"));
+        assert!(out.contains("  Protocol code: 5 (SessionNotFound)\n"));
+        assert!(out.contains("  Protocol data (1 bytes): aa\n"));
+
+        let mut msg = PLAIN_HDR.to_vec();
+        msg.extend_from_slice(&[0x05, 0x7f, 0x10, 0x00, 0x01, 0x00, 0xde, 0xad]);
+        let out = dissect(&msg, &[]);
+        assert!(out.contains("  Unknown message 0x7f\n"));
+        assert!(out.contains("  Payload (2 bytes): de ad\n"));
+    }
+
+    #[test]
+    fn test_invalid_msg() {
+        assert_eq!(
+            dissect(&[0x00, 0x01], &[]),
+            "Invalid message header: TruncatedPacket\nMessage (2 bytes): 00 01\n"
+        );
+        // The protocol header is cut short
+        let mut msg = PLAIN_HDR.to_vec();
+        msg.extend_from_slice(&[0x05, 0x01]);
+        let out = dissect(&msg, &[]);
+        assert!(out.contains("  Invalid protocol header: "));
+        // A status report that is too short
+        let mut msg = PLAIN_HDR.to_vec();
+        msg.extend_from_slice(&[0x05, SC_STATUS_REPORT, 0x10, 0x00, 0x00, 0x00, 0x01]);
+        let out = dissect(&msg, &[]);
+        assert!(out.contains("  Invalid status report (1 bytes): 01\n"));
+    }
+
+    #[test]
+    fn test_encrypted_msg() {
+        let key = [0x5a; 16];
+        let node_id = 0x1234;
+        // Session 1, so that the message is encrypted
+        let plain_hdr = [0x00, 0x01, 0x00, 0x00, 5, 0, 0, 0];
+        let mut buf = [0u8; 64];
+        let mut wb = WriteBuf::new(&mut buf, 64);
+        wb.append(&[0x05, 0x01, 0x10, 0x00, 0x01, 0x00]).unwrap();
+        wb.append(&[0x15, 0x24, 0x00, 0x00, 0x18]).unwrap();
+        encrypt_in_place(5, node_id, &plain_hdr, &mut wb, &key).unwrap();
+        let mut msg = plain_hdr.to_vec();
+        msg.extend_from_slice(wb.as_slice());
+
+        let out = dissect(&msg, &[]);
+        assert!(out.contains("  Encrypted, no session key\n"));
+        let wrong = Key {
+            key: vec![0; 16],
+            node_id,
+        };
+        let out = dissect(&msg, &[wrong]);
+        assert!(out.contains("  Encrypted, none of the session keys decrypts it\n"));
+        assert!(out.contains("  Encrypted payload (27 bytes): "));
+
+        // The first key that works is used
+        let keys = [
+            Key {
+                key: vec![0; 16],
+                node_id,
+            },
+            Key {
+                key: key.to_vec(),
+                node_id,
+            },
+        ];
+        let out = dissect(&msg, &keys);
+        assert!(out.starts_with("Message: session 1, counter 5\n"));
+        assert!(out.contains("  Interaction Model StatusResponse\n"));
+        assert!(out.contains("      Status (0): "));
+    }
+}
@@ -0,0 +1,468 @@
+//! The encoding of a textual description of TLV elements
+//!
+//! The description is the format that [TLVValue] is printed in, so that a message printed by the
+//! dissector can be edited and encoded back:
+//!
+//! ```text
+//! {
+//!     0: false,
+//!     1: 0x1234,
+//!     2: -3 (INT),
+//!     3: "hi",
+//!     4: len[3][01, 02, 03],
+//!     5: [1, 2, 3],
+//!     6: [[ 0: null, CommonPrf16(7): 0.5 (FLOAT) ]],
+//! }
+//! ```
+//!
+//! - Integers are unsigned unless they are negative, or followed by `(INT)`. Numbers with a
+//!   decimal point are doubles, unless they are followed by `(FLOAT)`.
+//! - Octet strings are `len[N][..]` with the bytes in hexadecimal, the length is checked. They may
+//!   also be written as `h"0102 03"`.
+//! - `{ }` is a struct, `[ ]` an array and `[[ ]]` a list. An array that starts with an array
+//!   needs a space between the brackets.
+//! - The members of structs and lists, and the top-level elements, may have a tag: a context tag
+//!   `N:`, or a [TagType] such as `ImplPrf32(N):`.
+
+use crate::{parse_hex, parse_u64};
+use matter::tlv::{TLVValue, TLVWriter, TagType, ToTLV};
+use matter::utils::writebuf::WriteBuf;
+use std::convert::{TryFrom, TryInto};
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+const MAX_ENCODED_LEN: usize = 65536;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// The tagged elements of the description
+pub fn parse(input: &str) -> Result<Vec<(TagType, TLVValue)>> {
+    let mut p = Parser { input, pos: 0 };
+    let mut elements = Vec::new();
+    loop {
+        p.skip_space();
+        if p.rest().is_empty() {
+            break;
+        }
+        elements.push(p.member()?);
+        p.skip_space();
+        p.eat(",");
+    }
+    Ok(elements)
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T> {
+        let line = self.input[..self.pos].matches('\n').count() + 1;
+        let found: String = self.rest().chars().take(16).collect();
+        Err(format!(
+            "Expected {} at line {}, found '{}'",
+            expected, line, found
+        ))
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        self.skip_space();
+        if self.eat(s) {
+            Ok(())
+        } else {
+            self.error(&format!("'{}'", s))
+        }
+    }
+
+    /// The characters of a number or of an identifier
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '_')))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// An element with an optional tag
+    fn member(&mut self) -> Result<(TagType, TLVValue)> {
+        let start = self.pos;
+        let tag = self.tag()?;
+        if tag.is_none() {
+            self.pos = start;
+        }
+        Ok((tag.unwrap_or(TagType::Anonymous), self.value()?))
+    }
+
+    /// The tag before a ':', if there is one
+    fn tag(&mut self) -> Result<Option<TagType>> {
+        self.skip_space();
+        let word = self.word();
+        let tag = if self.eat("(") {
+            let value = self.word();
+            if !self.eat(")") {
+                return Ok(None);
+            }
+            let value = parse_u64(value);
+            match word {
+                "CommonPrf16" => value.and_then(|v| Some(TagType::CommonPrf16(v.try_into().ok()?))),
+                "CommonPrf32" => value.and_then(|v| Some(TagType::CommonPrf32(v.try_into().ok()?))),
+                "ImplPrf16" => value.and_then(|v| Some(TagType::ImplPrf16(v.try_into().ok()?))),
+                "ImplPrf32" => value.and_then(|v| Some(TagType::ImplPrf32(v.try_into().ok()?))),
+                "FullQual48" => value.map(TagType::FullQual48),
+                "FullQual64" => value.map(TagType::FullQual64),
+                _ => return Ok(None),
+            }
+        } else {
+            parse_u64(word).and_then(|v| Some(TagType::Context(v.try_into().ok()?)))
+        };
+        self.skip_space();
+        if !self.eat(":") {
+            return Ok(None);
+        }
+        match tag {
+            Some(tag) => Ok(Some(tag)),
+            None => self.error("a tag"),
+        }
+    }
+
+    fn value(&mut self) -> Result<TLVValue> {
+        self.skip_space();
+        if self.eat("{") {
+            Ok(TLVValue::Struct(self.members("}")?))
+        } else if self.eat("[[") {
+            Ok(TLVValue::List(self.members("]]")?))
+        } else if self.eat("[") {
+            let mut elements = Vec::new();
+            loop {
+                self.skip_space();
+                if self.eat("]") {
+                    return Ok(TLVValue::Array(elements));
+                }
+                elements.push(self.value()?);
+                self.separator("]")?;
+            }
+        } else if self.rest().starts_with('"') {
+            Ok(TLVValue::from(self.string()?))
+        } else if self.eat("h\"") {
+            let end = match self.rest().find('"') {
+                Some(end) => end,
+                None => return self.error("'\"'"),
+            };
+            let hex: String = self.rest()[..end]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let bytes = match parse_hex(&hex) {
+                Some(bytes) => bytes,
+                None => return self.error("hexadecimal bytes"),
+            };
+            self.pos += end + 1;
+            Ok(TLVValue::from(bytes))
+        } else if self.eat("len[") {
+            let len = self.word().parse::<usize>();
+            self.expect("]")?;
+            self.expect("[")?;
+            let mut bytes = Vec::new();
+            loop {
+                self.skip_space();
+                if self.eat("]") {
+                    break;
+                }
+                match u8::from_str_radix(self.word(), 16) {
+                    Ok(b) => bytes.push(b),
+                    Err(_) => return self.error("a byte in hexadecimal"),
+                }
+                self.separator("]")?;
+            }
+            if len != Ok(bytes.len()) {
+                return self.error(&format!("a length of {}", bytes.len()));
+            }
+            Ok(TLVValue::from(bytes))
+        } else {
+            let start = self.pos;
+            let word = self.word();
+            let value = match word {
+                "true" => Some(TLVValue::Bool(true)),
+                "false" => Some(TLVValue::Bool(false)),
+                "null" => Some(TLVValue::Null),
+                _ => self.number(word)?,
+            };
+            match value {
+                Some(value) => Ok(value),
+                None => {
+                    self.pos = start;
+                    self.error("a value")
+                }
+            }
+        }
+    }
+
+    /// A number, with an optional type name after it
+    fn number(&mut self, word: &str) -> Result<Option<TLVValue>> {
+        let start = self.pos;
+        self.skip_space();
+        let type_name = if self.eat("(") {
+            let type_name = self.word();
+            self.expect(")")?;
+            type_name
+        } else {
+            self.pos = start;
+            ""
+        };
+        let (negative, digits) = match word.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, word),
+        };
+        let value = match type_name {
+            "FLOAT" => word.parse().ok().map(TLVValue::Float),
+            "DOUBLE" => word.parse().ok().map(TLVValue::Double),
+            "" if word.contains('.') => word.parse().ok().map(TLVValue::Double),
+            "INT" | "" if negative => parse_u64(digits)
+                .and_then(|v| 0i64.checked_sub_unsigned(v))
+                .map(TLVValue::from),
+            "INT" => parse_u64(digits)
+                .and_then(|v| i64::try_from(v).ok())
+                .map(TLVValue::from),
+            // A negative number doesn't fit
+            "UINT" if negative => None,
+            "UINT" | "" => parse_u64(digits).map(TLVValue::from),
+            _ => return self.error("INT, UINT, FLOAT or DOUBLE"),
+        };
+        Ok(value)
+    }
+
+    /// The tagged members of a struct or a list, up to the end
+    fn members(&mut self, end: &str) -> Result<Vec<(TagType, TLVValue)>> {
+        let mut members = Vec::new();
+        loop {
+            self.skip_space();
+            if self.eat(end) {
+                return Ok(members);
+            }
+            members.push(self.member()?);
+            self.separator(end)?;
+        }
+    }
+
+    /// A ',' between elements, or the end of the container
+    fn separator(&mut self, end: &str) -> Result<()> {
+        self.skip_space();
+        if self.eat(",") || self.rest().starts_with(end) {
+            Ok(())
+        } else {
+            self.error(&format!("',' or '{}'", end))
+        }
+    }
+
+    /// A string in double quotes, with the escapes of Rust strings
+    fn string(&mut self) -> Result<String> {
+        let mut s = String::new();
+        let mut chars = self.rest()[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            let c = match c {
+                '"' => {
+                    self.pos += i + 2;
+                    return Ok(s);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some('u') => {
+                        let code: String = chars
+                            .by_ref()
+                            .map(|(_, c)| c)
+                            .take_while(|c| *c != '}')
+                            .filter(|c| *c != '{')
+                            .collect();
+                        match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                            Some(c) => c,
+                            None => return self.error("a valid string escape"),
+                        }
+                    }
+                    Some(c) => c,
+                    None => break,
+                },
+                c => c,
+            };
+            s.push(c);
+        }
+        self.error("the end of the string")
+    }
+}
+
+/// The TLV encoding of the description, or of the JSON format of the Matter SDK
+pub fn encode(input: &str, json: bool) -> Result<Vec<u8>> {
+    let elements = if json {
+        let value = TLVValue::from_json(input).map_err(|e| format!("Invalid JSON: {:?}", e))?;
+        vec![(TagType::Anonymous, value)]
+    } else {
+        parse(input)?
+    };
+
+    let mut buf = vec![0; MAX_ENCODED_LEN];
+    let mut wb = WriteBuf::new(&mut buf, MAX_ENCODED_LEN);
+    let mut tw = TLVWriter::new(&mut wb);
+    for (tag, value) in elements {
+        value
+            .to_tlv(&mut tw, tag)
+            .map_err(|e| format!("Couldn't encode: {:?}", e))?;
+    }
+    let len = wb.get_tail();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = r#"{
+        0: false,
+        1: 0x1234,
+        2: -3 (INT),
+        3: "hi\n\u{e9}",
+        4: len[3][01, 02, 03],
+        5: [1, 2, 3],
+        6: [[ 0: null, CommonPrf16(7): 0.5 (FLOAT) ]],
+        7: h"0a0b 0c",
+        8: 1.25,
+        ImplPrf32(0x10000): { },
+        FullQual64(0x123456789): [ [true], [] ],
+    }"#;
+
+    fn decode(bytes: &[u8]) -> TLVValue {
+        TLVValue::from_slice(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = encode(DESCRIPTION, false).unwrap();
+        let value = decode(&bytes);
+        let members = match &value {
+            TLVValue::Struct(members) => members,
+            _ => panic!("Not a struct: {}", value),
+        };
+        assert_eq!(members.len(), 11);
+        assert_eq!(members[1], (TagType::Context(1), TLVValue::from(0x1234u16)));
+        assert_eq!(members[2], (TagType::Context(2), TLVValue::from(-3i8)));
+        assert_eq!(
+            members[3],
+            (TagType::Context(3), TLVValue::from("hi\n\u{e9}"))
+        );
+        assert_eq!(
+            members[4],
+            (TagType::Context(4), TLVValue::from(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            members[7],
+            (TagType::Context(7), TLVValue::from(vec![10, 11, 12]))
+        );
+        assert_eq!(members[8], (TagType::Context(8), TLVValue::Double(1.25)));
+        assert_eq!(members[9].0, TagType::ImplPrf32(0x10000));
+        assert_eq!(members[10].0, TagType::FullQual64(0x123456789));
+
+        // What the dissector prints is encoded back to the same bytes
+        assert_eq!(encode(&value.to_string(), false).unwrap(), bytes);
+
+        // Several top-level elements, with tags
+        let bytes = encode("1: 5, CommonPrf32(2): \"x\", null", false).unwrap();
+        assert_eq!(
+            bytes,
+            [0x24, 0x01, 0x05, 0x6c, 0x02, 0x00, 0x00, 0x00, 0x01, b'x', 0x14]
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = r#"{
+            "0:BOOL": true,
+            "1:UINT": 300,
+            "2:INT": -5,
+            "3:STRING": "hi",
+            "4:BYTES": "AQID",
+            "5:ARRAY-UINT": [1, 2],
+            "6:STRUCT": { "0:NULL": null },
+            "7:DOUBLE": 0.5
+        }"#;
+        let bytes = encode(json, true).unwrap();
+        let value = decode(&bytes);
+        assert_eq!(value, TLVValue::from_json(json).unwrap());
+        // Through the JSON and the text description, back to the same bytes
+        assert_eq!(encode(&value.to_json().unwrap(), true).unwrap(), bytes);
+        assert_eq!(encode(&value.to_string(), false).unwrap(), bytes);
+
+        // The text description of the same elements
+        let text = "{ 0: true, 1: 300, 2: -5, 3: \"hi\", 4: h\"010203\", \
+                    5: [1, 2], 6: { 0: null }, 7: 0.5 }";
+        assert_eq!(encode(text, false).unwrap(), bytes);
+    }
+
+    fn error(input: &str) -> String {
+        encode(input, false).unwrap_err()
+    }
+
+    #[test]
+    fn test_errors() {
+        // Bad types
+        assert_eq!(
+            error("0: 5 (CHAR)"),
+            "Expected INT, UINT, FLOAT or DOUBLE at line 1, found ''"
+        );
+        assert!(error("{ 0: maybe }").starts_with("Expected a value at line 1, found 'maybe }'"));
+        assert!(encode(r#"{ "0:CHAR": 5 }"#, true)
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+
+        // Numbers that don't fit
+        assert!(error("18446744073709551616").starts_with("Expected a value"));
+        assert!(error("-9223372036854775809").starts_with("Expected a value"));
+        assert!(error("9223372036854775808 (INT)").starts_with("Expected a value"));
+        assert!(encode(r#"{ "0:INT": 9223372036854775808 }"#, true).is_err());
+        assert!(error("-3 (UINT)").starts_with("Expected a value"));
+        assert!(encode(r#"{ "0:UINT": -3 }"#, true).is_err());
+        assert_eq!(
+            encode("18446744073709551615, -9223372036854775808", false).unwrap(),
+            [
+                0x07, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x03, 0, 0, 0, 0, 0, 0, 0,
+                0x80
+            ]
+        );
+
+        // Bad tags
+        assert_eq!(error("256: 1"), "Expected a tag at line 1, found ' 1'");
+        assert_eq!(
+            error("{\n  CommonPrf16(0x10000): 1 }"),
+            "Expected a tag at line 2, found ' 1 }'"
+        );
+        assert!(encode(r#"{ "256:UINT": 1 }"#, true).is_err());
+
+        // Malformed descriptions
+        assert_eq!(
+            error("len[2][01]"),
+            "Expected a length of 1 at line 1, found ''"
+        );
+        assert!(error("{ 0: 1 1: 2 }").starts_with("Expected ',' or '}'"));
+        assert!(error("\"open").starts_with("Expected the end of the string"));
+        assert!(error("h\"0g\"").starts_with("Expected hexadecimal bytes"));
+    }
+}
@@ -3,17 +3,64 @@ use clap::{App, Arg};
 use simple_logger::SimpleLogger;
 use matter::cert;
 use matter::tlv;
+use std::io::Read;
 use std::process;
-use std::u8;
 
-fn main() {
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Trace)
-        .with_colors(true)
-        .without_timestamps()
-        .init()
-        .unwrap();
+mod dissect;
+mod encode;
+mod pcap;
+
+fn parse_bytes(input: &str, base: u32) -> Vec<u8> {
+    let list: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut bytes = Vec::new();
+    for byte in list.split(',') {
+        let byte = byte.strip_prefix("0x").unwrap_or(byte);
+        if let Ok(b) = u8::from_str_radix(byte, base) {
+            bytes.push(b);
+        } else {
+            eprintln!("Skipping unknown byte: {}", byte);
+        }
+    }
+    bytes
+}
 
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    // An odd digit at the end has no byte
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// A key in hexadecimal, optionally followed by '@' and the node ID of the sender
+fn parse_key(s: &str) -> dissect::Key {
+    let (key, node_id) = match s.split_once('@') {
+        Some((key, node_id)) => (key, parse_u64(node_id)),
+        None => (s, Some(0)),
+    };
+    let key: String = key
+        .strip_prefix("0x")
+        .unwrap_or(key)
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .collect();
+    match (parse_hex(&key), node_id) {
+        (Some(key), Some(node_id)) if key.len() == 16 => dissect::Key { key, node_id },
+        _ => {
+            eprintln!("Invalid key: {}, expected 16 bytes in hexadecimal", s);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
     let m = App::new("tlv_tool")
         .arg(
             Arg::with_name("hex")
@@ -32,9 +79,104 @@ fn main() {
                 .long("cert")
                 .help("The input is a Matter-encoded Certificate"),
         )
-        .arg(Arg::with_name("tlvs").help("List of TLVs").required(true))
+        .arg(
+            Arg::with_name("msg")
+                .long("msg")
+                .help("The input is a Matter message, with its headers"),
+        )
+        .arg(
+            Arg::with_name("encode")
+                .short("e")
+                .long("encode")
+                .help(
+                    "Encode a textual description of TLVs, in the format they are printed in, \
+                     and print the bytes in Hexadecimal (Default) or Decimal. \
+                     The description is read from stdin if it is '-'",
+                ),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .requires("encode")
+                .help("The description to encode is in the JSON format of the Matter SDK"),
+        )
+        .arg(
+            Arg::with_name("pcap")
+                .long("pcap")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Print the Matter messages in a pcap or pcapng file"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .takes_value(true)
+                .default_value("5540")
+                .help("The UDP port of the Matter messages in the pcap file"),
+        )
+        .arg(
+            Arg::with_name("key")
+                .short("k")
+                .long("key")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY[@NODE]")
+                .help(
+                    "A session key to decrypt messages with, with the node ID of the sender \
+                     (Default: 0, as in PASE sessions). Can be repeated",
+                ),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Print the logs of the decoders"),
+        )
+        .arg(
+            Arg::with_name("tlvs")
+                .help("List of TLVs")
+                .required_unless("pcap"),
+        )
         .get_matches();
 
+    SimpleLogger::new()
+        .with_level(if m.is_present("verbose") {
+            log::LevelFilter::Trace
+        } else {
+            log::LevelFilter::Warn
+        })
+        .with_colors(true)
+        .without_timestamps()
+        .init()
+        .unwrap();
+
+    let keys: Vec<dissect::Key> = m
+        .values_of("key")
+        .map(|keys| keys.map(parse_key).collect())
+        .unwrap_or_default();
+
+    if let Some(file) = m.value_of("pcap") {
+        let port = m.value_of("port").unwrap().parse().unwrap_or_else(|_| {
+            eprintln!("Invalid port");
+            process::exit(1);
+        });
+        let capture = std::fs::read(file).unwrap_or_else(|e| {
+            eprintln!("Couldn't read {}: {}", file, e);
+            process::exit(1);
+        });
+        let datagrams = pcap::read_udp(&capture, port).unwrap_or_else(|e| {
+            eprintln!("Couldn't parse {}: {}", file, e);
+            process::exit(1);
+        });
+        for d in datagrams {
+            println!("#{} {} -> {}", d.number, d.src, d.dst);
+            dissect::print_msg(&d.payload, &keys);
+            println!();
+        }
+        return;
+    }
+
     // Assume hexadecimal by-default
     let base = if m.is_present("hex") {
         16
@@ -44,29 +186,46 @@ fn main() {
         16
     };
 
-    let list: String = m.value_of("tlvs").unwrap().chars().filter(|c|!c.is_whitespace()).collect();
-    let list = list.split(',');
-    let mut tlv_list: [u8; 1024] = [0; 1024];
-    let mut index = 0;
-    for byte in list {
-        let byte = byte.strip_prefix("0x").unwrap_or(byte);
-        if let Ok(b) = u8::from_str_radix(byte, base) {
-            tlv_list[index] = b;
-            index += 1;
-        } else {
-            eprintln!("Skipping unknown byte: {}", byte);
+    if m.is_present("encode") {
+        let mut input = m.value_of("tlvs").unwrap().to_owned();
+        if input == "-" {
+            input.clear();
+            if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+                eprintln!("Couldn't read stdin: {}", e);
+                process::exit(1);
+            }
         }
-        if index >= 1024 {
-            eprintln!("Input too long");
-            process::exit(1);
+        match encode::encode(&input, m.is_present("json")) {
+            Ok(bytes) => {
+                let bytes: Vec<String> = bytes
+                    .iter()
+                    .map(|b| {
+                        if base == 16 {
+                            format!("0x{:02x}", b)
+                        } else {
+                            b.to_string()
+                        }
+                    })
+                    .collect();
+                println!("{}", bytes.join(", "));
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
         }
+        return;
     }
 
-//    println!("Decoding: {:x?}", &tlv_list[..index]);
+    let tlv_list = parse_bytes(m.value_of("tlvs").unwrap(), base);
+
+//    println!("Decoding: {:x?}", &tlv_list);
     if m.is_present("cert") {
-	let cert = cert::Cert::new(&tlv_list[..index]).unwrap();
+	let cert = cert::Cert::new(&tlv_list).unwrap();
 	println!("{}", cert);
+    } else if m.is_present("msg") {
+        dissect::print_msg(&tlv_list, &keys);
     } else {
-        tlv::print_tlv_list(&tlv_list[..index]);
+        tlv::print_tlv_list(&tlv_list);
     }
 }
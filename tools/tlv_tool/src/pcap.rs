@@ -0,0 +1,467 @@
+//! A reader of the UDP datagrams in pcap and pcapng captures
+
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const PCAPNG_INTERFACE_DESC: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const IP_PROTO_UDP: u8 = 17;
+
+pub struct Datagram {
+    /// The number of the packet in the capture, starting from 1
+    pub number: usize,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+/// A slice that is read in either byte order
+struct Reader<'a> {
+    buf: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        let b = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        let b = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], String> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.buf.get(offset..end))
+            .ok_or_else(|| "Truncated capture file".to_string())
+    }
+}
+
+/// The UDP datagrams to or from the port, in a pcap or pcapng capture
+pub fn read_udp(capture: &[u8], port: u16) -> Result<Vec<Datagram>, String> {
+    let mut frames = Vec::new();
+    match capture.get(..4) {
+        Some([0xd4, 0xc3, 0xb2, 0xa1]) | Some([0x4d, 0x3c, 0xb2, 0xa1]) => {
+            read_pcap(capture, false, &mut frames)?
+        }
+        Some([0xa1, 0xb2, 0xc3, 0xd4]) | Some([0xa1, 0xb2, 0x3c, 0x4d]) => {
+            read_pcap(capture, true, &mut frames)?
+        }
+        Some([0x0a, 0x0d, 0x0d, 0x0a]) => read_pcapng(capture, &mut frames)?,
+        _ => return Err("Not a pcap or pcapng file".to_string()),
+    }
+
+    Ok(frames
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (linktype, frame))| {
+            let (src, dst, payload) = udp_from_frame(*linktype, frame)?;
+            if src.port() != port && dst.port() != port {
+                return None;
+            }
+            Some(Datagram {
+                number: i + 1,
+                src,
+                dst,
+                payload: payload.to_vec(),
+            })
+        })
+        .collect())
+}
+
+/// The frames of a pcap file, with their link type
+fn read_pcap<'a>(
+    capture: &'a [u8],
+    big_endian: bool,
+    frames: &mut Vec<(u32, &'a [u8])>,
+) -> Result<(), String> {
+    let r = Reader {
+        buf: capture,
+        big_endian,
+    };
+    let linktype = r.u32(20)?;
+    let mut offset = 24;
+    while offset < capture.len() {
+        let len = r.u32(offset + 8)? as usize;
+        frames.push((linktype, r.bytes(offset + 16, len)?));
+        offset += 16 + len;
+    }
+    Ok(())
+}
+
+/// The frames of a pcapng file, with the link type of their interface
+fn read_pcapng<'a>(capture: &'a [u8], frames: &mut Vec<(u32, &'a [u8])>) -> Result<(), String> {
+    let mut r = Reader {
+        buf: capture,
+        big_endian: false,
+    };
+    let mut interfaces = Vec::new();
+    let mut offset = 0;
+    while offset < capture.len() {
+        let block_type = r.u32(offset)?;
+        if block_type == PCAPNG_SECTION_HEADER {
+            // The byte order magic sets the byte order of this section
+            r.big_endian = r.bytes(offset + 8, 4)? == [0x1a, 0x2b, 0x3c, 0x4d];
+            interfaces.clear();
+        }
+        let block_len = r.u32(offset + 4)? as usize;
+        // The fixed fields of the block, before its data
+        let fixed_len = match block_type {
+            PCAPNG_INTERFACE_DESC => 8,
+            PCAPNG_ENHANCED_PACKET => 20,
+            PCAPNG_SIMPLE_PACKET => 4,
+            _ => 0,
+        };
+        // The type, the length before the body, and the length after it
+        if block_len < 12 + fixed_len {
+            return Err(format!("Invalid block length {}", block_len));
+        }
+        r.bytes(offset, block_len)?;
+        let body = offset + 8;
+        // What is left of the body for the data
+        let data_len = block_len - 12 - fixed_len;
+        match block_type {
+            PCAPNG_INTERFACE_DESC => interfaces.push(r.u16(body)? as u32),
+            PCAPNG_ENHANCED_PACKET => {
+                let interface = r.u32(body)? as usize;
+                let len = r.u32(body + 12)? as usize;
+                if len > data_len {
+                    return Err(format!("Packet length {} exceeds its block", len));
+                }
+                let linktype = *interfaces
+                    .get(interface)
+                    .ok_or_else(|| format!("Unknown interface {}", interface))?;
+                frames.push((linktype, r.bytes(body + 20, len)?));
+            }
+            PCAPNG_SIMPLE_PACKET => {
+                let linktype = *interfaces
+                    .first()
+                    .ok_or_else(|| "No interface".to_string())?;
+                // The captured length is what fits in the block
+                let len = (r.u32(body)? as usize).min(data_len);
+                frames.push((linktype, r.bytes(body + 4, len)?));
+            }
+            _ => (),
+        }
+        offset += block_len;
+    }
+    Ok(())
+}
+
+/// The addresses and the payload of a UDP datagram in the frame, if it is one
+fn udp_from_frame(linktype: u32, frame: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let (ethertype, packet) = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            // VLAN tags
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                offset += 4;
+                ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            }
+            (ethertype, frame.get(offset + 2..)?)
+        }
+        LINKTYPE_LINUX_SLL => (
+            u16::from_be_bytes(frame.get(14..16)?.try_into().ok()?),
+            frame.get(16..)?,
+        ),
+        LINKTYPE_LINUX_SLL2 => (
+            u16::from_be_bytes(frame.get(0..2)?.try_into().ok()?),
+            frame.get(20..)?,
+        ),
+        LINKTYPE_NULL => {
+            // The address family, in the byte order of the host that captured it
+            let family = frame.get(..4)?;
+            let family = if family[0] == 0 {
+                u32::from_be_bytes(family.try_into().ok()?)
+            } else {
+                u32::from_le_bytes(family.try_into().ok()?)
+            };
+            let ethertype = match family {
+                2 => 0x0800,
+                24 | 28 | 30 => 0x86dd,
+                _ => return None,
+            };
+            (ethertype, frame.get(4..)?)
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => match frame.first()? >> 4 {
+            4 => (0x0800, frame),
+            6 => (0x86dd, frame),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let (src, dst, udp) = match ethertype {
+        0x0800 => {
+            let header_len = ((packet.first()? & 0x0f) as usize) * 4;
+            let fragment = u16::from_be_bytes(packet.get(6..8)?.try_into().ok()?);
+            // Fragments aren't reassembled
+            if *packet.get(9)? != IP_PROTO_UDP || fragment & 0x3fff != 0 {
+                return None;
+            }
+            let src: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            (
+                IpAddr::V4(Ipv4Addr::from(src)),
+                IpAddr::V4(Ipv4Addr::from(dst)),
+                packet.get(header_len..)?,
+            )
+        }
+        0x86dd => {
+            // Extension headers aren't supported
+            if *packet.get(6)? != IP_PROTO_UDP {
+                return None;
+            }
+            let src: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            (
+                IpAddr::V6(Ipv6Addr::from(src)),
+                IpAddr::V6(Ipv6Addr::from(dst)),
+                packet.get(40..)?,
+            )
+        }
+        _ => return None,
+    };
+
+    let src_port = u16::from_be_bytes(udp.get(0..2)?.try_into().ok()?);
+    let dst_port = u16::from_be_bytes(udp.get(2..4)?.try_into().ok()?);
+    let len = u16::from_be_bytes(udp.get(4..6)?.try_into().ok()?) as usize;
+    Some((
+        SocketAddr::new(src, src_port),
+        SocketAddr::new(dst, dst_port),
+        udp.get(8..len.max(8))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: [u8; 3] = [1, 2, 3];
+
+    fn udp(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = Vec::new();
+        udp.extend_from_slice(&src_port.to_be_bytes());
+        udp.extend_from_slice(&dst_port.to_be_bytes());
+        udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(payload);
+        udp
+    }
+
+    // An Ethernet frame with an IPv4 packet from 10.0.0.1 to 10.0.0.2
+    fn ipv4_frame(src_port: u16, dst_port: u16) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0, 0, 64, IP_PROTO_UDP, 0, 0]);
+        frame.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(&udp(src_port, dst_port, &PAYLOAD));
+        frame
+    }
+
+    // A raw IPv6 packet from ::1 to ::2
+    fn ipv6_packet(src_port: u16, dst_port: u16) -> Vec<u8> {
+        let mut packet = vec![0x60, 0, 0, 0, 0, 0, IP_PROTO_UDP, 64];
+        packet.extend_from_slice(&Ipv6Addr::from(1).octets());
+        packet.extend_from_slice(&Ipv6Addr::from(2).octets());
+        packet.extend_from_slice(&udp(src_port, dst_port, &PAYLOAD));
+        packet
+    }
+
+    fn u32_bytes(v: u32, big_endian: bool) -> [u8; 4] {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    }
+
+    fn pcap(big_endian: bool, linktype: u32, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut capture = u32_bytes(0xa1b2c3d4, big_endian).to_vec();
+        capture.extend_from_slice(&[0; 16]);
+        capture.extend_from_slice(&u32_bytes(linktype, big_endian));
+        for frame in frames {
+            capture.extend_from_slice(&[0; 8]);
+            capture.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+            capture.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+            capture.extend_from_slice(frame);
+        }
+        capture
+    }
+
+    // A pcapng block, with its body padded to 32 bits
+    fn block(block_type: u32, body: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize((body.len() + 3) & !3, 0);
+        let len = u32_bytes(12 + body.len() as u32, big_endian);
+        let mut block = u32_bytes(block_type, big_endian).to_vec();
+        block.extend_from_slice(&len);
+        block.extend_from_slice(&body);
+        block.extend_from_slice(&len);
+        block
+    }
+
+    fn section(big_endian: bool) -> Vec<u8> {
+        let mut body = u32_bytes(0x1a2b3c4d, big_endian).to_vec();
+        body.extend_from_slice(&[0; 12]);
+        block(PCAPNG_SECTION_HEADER, &body, big_endian)
+    }
+
+    fn interface(linktype: u16, big_endian: bool) -> Vec<u8> {
+        let linktype = if big_endian {
+            linktype.to_be_bytes()
+        } else {
+            linktype.to_le_bytes()
+        };
+        let mut body = linktype.to_vec();
+        body.extend_from_slice(&[0; 6]);
+        block(PCAPNG_INTERFACE_DESC, &body, big_endian)
+    }
+
+    fn enhanced(interface: u32, frame: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut body = u32_bytes(interface, big_endian).to_vec();
+        body.extend_from_slice(&[0; 8]);
+        body.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+        body.extend_from_slice(frame);
+        block(PCAPNG_ENHANCED_PACKET, &body, big_endian)
+    }
+
+    fn simple(frame: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut body = u32_bytes(frame.len() as u32, big_endian).to_vec();
+        body.extend_from_slice(frame);
+        block(PCAPNG_SIMPLE_PACKET, &body, big_endian)
+    }
+
+    fn v4(last: u8, port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, last], port))
+    }
+
+    fn v6(addr: u128, port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::from(addr)), port)
+    }
+
+    #[test]
+    fn test_pcap() {
+        let frames = [
+            ipv4_frame(1000, 5540),
+            ipv4_frame(1000, 53),
+            ipv4_frame(5540, 1000),
+        ];
+        let capture = pcap(false, LINKTYPE_ETHERNET, &frames);
+        let datagrams = read_udp(&capture, 5540).unwrap();
+        assert_eq!(datagrams.len(), 2);
+        assert_eq!(datagrams[0].number, 1);
+        assert_eq!(datagrams[0].src, v4(1, 1000));
+        assert_eq!(datagrams[0].dst, v4(2, 5540));
+        assert_eq!(datagrams[0].payload, PAYLOAD);
+        // The numbers count the packets of the other ports too
+        assert_eq!(datagrams[1].number, 3);
+        assert_eq!(datagrams[1].src, v4(1, 5540));
+
+        let capture = pcap(true, LINKTYPE_RAW, &[ipv6_packet(5540, 2000)]);
+        let datagrams = read_udp(&capture, 5540).unwrap();
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(datagrams[0].src, v6(1, 5540));
+        assert_eq!(datagrams[0].dst, v6(2, 2000));
+        assert_eq!(datagrams[0].payload, PAYLOAD);
+    }
+
+    #[test]
+    fn test_pcapng() {
+        let mut capture = section(false);
+        capture.extend(interface(LINKTYPE_ETHERNET as u16, false));
+        capture.extend(interface(LINKTYPE_RAW as u16, false));
+        capture.extend(enhanced(1, &ipv6_packet(1000, 5540), false));
+        capture.extend(enhanced(0, &ipv4_frame(1000, 5540), false));
+        capture.extend(simple(&ipv4_frame(5540, 1000), false));
+        // An unknown block is skipped over
+        capture.extend(block(0x0BAD, &[1, 2, 3, 4], false));
+        // A new section, in the other byte order, has its own interfaces
+        capture.extend(section(true));
+        capture.extend(interface(LINKTYPE_RAW as u16, true));
+        capture.extend(enhanced(0, &ipv6_packet(5540, 1000), true));
+
+        let datagrams = read_udp(&capture, 5540).unwrap();
+        let addrs: Vec<_> = datagrams.iter().map(|d| (d.src, d.dst)).collect();
+        assert_eq!(
+            addrs,
+            [
+                (v6(1, 1000), v6(2, 5540)),
+                (v4(1, 1000), v4(2, 5540)),
+                (v4(1, 5540), v4(2, 1000)),
+                (v6(1, 5540), v6(2, 1000)),
+            ]
+        );
+        assert!(datagrams.iter().all(|d| d.payload == PAYLOAD));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(read_udp(&[], 5540).is_err());
+        assert!(read_udp(&[0; 32], 5540).is_err());
+
+        // A pcap record that is cut short
+        let mut capture = pcap(false, LINKTYPE_ETHERNET, &[ipv4_frame(1000, 5540)]);
+        capture.pop();
+        assert!(read_udp(&capture, 5540).is_err());
+        // A frame that isn't UDP is skipped over
+        let mut frame = ipv4_frame(1000, 5540);
+        frame[23] = 6;
+        let capture = pcap(false, LINKTYPE_ETHERNET, &[frame]);
+        assert!(read_udp(&capture, 5540).unwrap().is_empty());
+
+        let mut header = section(false);
+        header.extend(interface(LINKTYPE_ETHERNET as u16, false));
+        let with = |block: &[u8]| {
+            let mut capture = header.clone();
+            capture.extend_from_slice(block);
+            read_udp(&capture, 5540)
+        };
+        let frame = ipv4_frame(1000, 5540);
+        assert_eq!(with(&simple(&frame, false)).unwrap().len(), 1);
+
+        // Blocks too short for their fixed fields, or for their lengths
+        for len in 0..16u32 {
+            let mut block = simple(&frame, false);
+            block[4..8].copy_from_slice(&len.to_le_bytes());
+            assert!(with(&block).is_err());
+        }
+        let mut block = enhanced(0, &frame, false);
+        block[4..8].copy_from_slice(&28u32.to_le_bytes());
+        assert!(with(&block).is_err());
+        // A block that runs past the end of the file
+        let mut block = simple(&frame, false);
+        block.truncate(block.len() - 4);
+        assert!(with(&block).is_err());
+        // A packet that is longer than its block
+        let mut block = enhanced(0, &frame, false);
+        block[20..24].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(with(&block).is_err());
+        // A packet of an interface that isn't described
+        assert!(with(&enhanced(1, &frame, false)).is_err());
+    }
+}